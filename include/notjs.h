@@ -0,0 +1,47 @@
+/*
+ * C interface to the NotJS interpreter, built with `cargo build --release --features ffi`
+ * into libnotjs.so and libnotjs.a.
+ *
+ * A handle owns an interpreter. Strings given out by a handle stay valid until the next call
+ * with the same handle. The functions do not let panics through: a panic while running a
+ * program is reported as NOTJS_PANIC.
+ */
+#ifndef NOTJS_H
+#define NOTJS_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct NotJs NotJs;
+
+typedef enum NotJsStatus {
+    NOTJS_OK = 0,
+    NOTJS_PARSE_ERROR = 1,
+    NOTJS_RUNTIME_ERROR = 2,
+    /* The interpreter panicked, it may be left in an inconsistent state and should be freed. */
+    NOTJS_PANIC = 3,
+    /* A null handle or source. */
+    NOTJS_INVALID_ARGUMENT = 4,
+} NotJsStatus;
+
+/* Create an interpreter with the natives and the prelude, or null if that failed. */
+NotJs *notjs_new(void);
+
+/* Free an interpreter created by notjs_new. Freeing null does nothing. */
+void notjs_free(NotJs *handle);
+
+/* Run the null-terminated source. Globals stay defined for the next program. */
+NotJsStatus notjs_interpret(NotJs *handle, const char *source);
+
+/* The message of the error of the last notjs_interpret, or null if it succeeded. */
+const char *notjs_last_error(const NotJs *handle);
+
+/* Everything printed since the last call, or null for a null handle. Null bytes are dropped. */
+const char *notjs_get_output(NotJs *handle);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* NOTJS_H */
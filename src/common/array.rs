@@ -0,0 +1,89 @@
+use core::fmt;
+use std::{cmp::Ordering, ops::Deref, rc::Rc};
+
+use super::value::{drop_nested, Value};
+
+// Elements are read through `Deref` to a slice, changes go through `elements_mut` once the caller
+// has checked the array is not frozen.
+//
+// Clones share the elements, so reading an array out of a variable doesn't copy it. The elements
+// are copied the first time a shared array is changed, which keeps arrays values.
+#[derive(Clone, Default)]
+pub struct Array {
+    elements: Rc<Vec<Value>>,
+    frozen: bool,
+}
+
+impl Array {
+    pub fn into_vec(mut self) -> Vec<Value> {
+        Rc::try_unwrap(std::mem::take(&mut self.elements))
+            .unwrap_or_else(|elements| (*elements).clone())
+    }
+
+    pub fn elements_mut(&mut self) -> &mut Vec<Value> {
+        Rc::make_mut(&mut self.elements)
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    pub fn freeze(&mut self) {
+        self.frozen = true;
+    }
+
+    // The elements, leaving the array empty, if no other array shares them.
+    pub(super) fn take_unshared(&mut self) -> Vec<Value> {
+        Rc::get_mut(&mut self.elements)
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+}
+
+impl Drop for Array {
+    fn drop(&mut self) {
+        drop_nested(self.take_unshared());
+    }
+}
+
+impl Deref for Array {
+    type Target = [Value];
+
+    fn deref(&self) -> &Self::Target {
+        &self.elements
+    }
+}
+
+impl From<Vec<Value>> for Array {
+    fn from(elements: Vec<Value>) -> Self {
+        Array {
+            elements: Rc::new(elements),
+            frozen: false,
+        }
+    }
+}
+
+impl FromIterator<Value> for Array {
+    fn from_iter<T: IntoIterator<Item = Value>>(iter: T) -> Self {
+        Array::from(iter.into_iter().collect::<Vec<Value>>())
+    }
+}
+
+// Being frozen does not affect equality or ordering.
+impl PartialEq for Array {
+    fn eq(&self, other: &Self) -> bool {
+        self.elements == other.elements
+    }
+}
+
+impl PartialOrd for Array {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.elements.partial_cmp(&other.elements)
+    }
+}
+
+impl fmt::Debug for Array {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.elements)
+    }
+}
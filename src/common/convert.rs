@@ -0,0 +1,214 @@
+use std::fmt;
+
+use super::value::Value;
+
+// A value that did not have the type a conversion expected.
+#[derive(Clone, PartialEq)]
+pub struct ConversionError {
+    pub expected: &'static str,
+    pub found: &'static str,
+}
+
+impl ConversionError {
+    fn new(expected: &'static str, found: &Value) -> Self {
+        ConversionError {
+            expected,
+            found: found.type_name(),
+        }
+    }
+}
+
+impl fmt::Debug for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "expected {}, found {}", self.expected, self.found)
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+impl Value {
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            Value::Number(num) => Some(*num),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(str) => Some(str),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(arr) => Some(arr),
+            _ => None,
+        }
+    }
+}
+
+impl From<f64> for Value {
+    fn from(num: f64) -> Self {
+        Value::Number(num)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(bool: bool) -> Self {
+        Value::Boolean(bool)
+    }
+}
+
+impl From<String> for Value {
+    fn from(str: String) -> Self {
+        Value::String(str.into())
+    }
+}
+
+impl From<&str> for Value {
+    fn from(str: &str) -> Self {
+        Value::String(str.into())
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(arr: Vec<Value>) -> Self {
+        Value::Array(arr.into())
+    }
+}
+
+impl TryFrom<Value> for f64 {
+    type Error = ConversionError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        value
+            .as_number()
+            .ok_or_else(|| ConversionError::new("number", &value))
+    }
+}
+
+// Only numbers without a fractional part that fit in an i64 convert.
+impl TryFrom<Value> for i64 {
+    type Error = ConversionError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Number(num)
+                if num.fract() == 0.0 && num >= i64::MIN as f64 && num < i64::MAX as f64 =>
+            {
+                Ok(num as i64)
+            }
+            value => Err(ConversionError::new("integer", &value)),
+        }
+    }
+}
+
+impl TryFrom<Value> for bool {
+    type Error = ConversionError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Boolean(bool) => Ok(bool),
+            value => Err(ConversionError::new("boolean", &value)),
+        }
+    }
+}
+
+impl TryFrom<Value> for String {
+    type Error = ConversionError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::String(str) => Ok(str.to_string()),
+            value => Err(ConversionError::new("string", &value)),
+        }
+    }
+}
+
+impl TryFrom<Value> for Vec<Value> {
+    type Error = ConversionError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Array(arr) => Ok(arr.into_vec()),
+            value => Err(ConversionError::new("array", &value)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_rust() {
+        assert_eq!(Value::from(1.5), Value::Number(1.5));
+        assert_eq!(Value::from(true), Value::Boolean(true));
+        assert_eq!(Value::from("a"), Value::String("a".into()));
+        assert_eq!(Value::from(String::from("a")), Value::String("a".into()));
+        assert_eq!(
+            Value::from(vec![Value::from(1.0), Value::from("b")]),
+            Value::Array(vec![Value::Number(1.0), Value::String("b".into())].into())
+        );
+    }
+
+    #[test]
+    fn test_try_from_value() {
+        assert_eq!(f64::try_from(Value::from(2.5)), Ok(2.5));
+        assert_eq!(i64::try_from(Value::from(-3.0)), Ok(-3));
+        assert_eq!(bool::try_from(Value::from(false)), Ok(false));
+        assert_eq!(String::try_from(Value::from("x")), Ok("x".to_string()));
+        assert_eq!(
+            Vec::<Value>::try_from(Value::from(vec![Value::Null])),
+            Ok(vec![Value::Null])
+        );
+
+        let number: Result<f64, _> = Value::from("1").try_into();
+        assert_eq!(
+            number.unwrap_err().to_string(),
+            "expected number, found string"
+        );
+    }
+
+    #[test]
+    fn test_try_from_value_errors() {
+        fn error<T>(expected: &'static str, found: &'static str) -> Result<T, ConversionError> {
+            Err(ConversionError { expected, found })
+        }
+
+        assert_eq!(i64::try_from(Value::from(1.5)), error("integer", "number"));
+        assert_eq!(
+            i64::try_from(Value::from(1e300)),
+            error("integer", "number")
+        );
+        assert_eq!(i64::try_from(Value::Null), error("integer", "null"));
+        assert_eq!(bool::try_from(Value::from(1.0)), error("boolean", "number"));
+        assert_eq!(
+            String::try_from(Value::from(vec![])),
+            error("string", "array")
+        );
+        assert_eq!(
+            Vec::<Value>::try_from(Value::from("abc")),
+            error("array", "string")
+        );
+    }
+
+    #[test]
+    fn test_accessors() {
+        let array = Value::from(vec![Value::from(1.0)]);
+        assert_eq!(Value::from(3.0).as_number(), Some(3.0));
+        assert_eq!(Value::from("s").as_str(), Some("s"));
+        assert_eq!(array.as_array(), Some(&[Value::Number(1.0)][..]));
+        assert_eq!(Value::from(true).as_number(), None);
+        assert_eq!(Value::from(1.0).as_str(), None);
+        assert_eq!(Value::Null.as_array(), None);
+    }
+}
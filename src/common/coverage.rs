@@ -0,0 +1,209 @@
+use std::path::{Path, PathBuf};
+
+use super::statements::Statement;
+
+/// The lines of a file that statements start at, and which of them ran.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FileCoverage {
+    /// Canonical path of an imported file, `None` for the program itself.
+    pub path: Option<PathBuf>,
+    // Indexed by line.
+    statements: Vec<bool>,
+    executed: Vec<bool>,
+}
+
+impl FileCoverage {
+    fn new(path: Option<PathBuf>) -> Self {
+        Self {
+            path,
+            ..Self::default()
+        }
+    }
+
+    /// Lines with at least one statement, in order.
+    pub fn lines(&self) -> impl Iterator<Item = u32> + '_ {
+        (0..self.statements.len() as u32).filter(|&line| self.statements[line as usize])
+    }
+
+    /// Whether a statement starting at `line` ran.
+    pub fn is_covered(&self, line: u32) -> bool {
+        self.executed.get(line as usize).copied().unwrap_or(false)
+    }
+
+    pub fn total(&self) -> usize {
+        self.lines().count()
+    }
+
+    pub fn covered(&self) -> usize {
+        self.lines().filter(|&line| self.is_covered(line)).count()
+    }
+
+    /// Lines with statements that never ran.
+    pub fn uncovered(&self) -> Vec<u32> {
+        self.lines()
+            .filter(|&line| !self.is_covered(line))
+            .collect()
+    }
+
+    /// A line such as `main.notjs: 9/10 lines covered (90.0%)`.
+    pub fn summary(&self, name: &str) -> String {
+        let total = self.total();
+        let percent = if total == 0 {
+            100.0
+        } else {
+            self.covered() as f64 * 100.0 / total as f64
+        };
+
+        format!(
+            "{}: {}/{} lines covered ({:.1}%)",
+            name,
+            self.covered(),
+            total,
+            percent
+        )
+    }
+
+    /// The `source` of the file with each line numbered and marked: `+` if it ran, `-` if it has
+    /// statements that never ran, and nothing if it has no statements.
+    pub fn annotate(&self, source: &str) -> String {
+        let mut listing = String::new();
+
+        for (index, text) in source.lines().enumerate() {
+            let line = index as u32 + 1;
+            let mark = match (self.statements.get(line as usize), self.is_covered(line)) {
+                (_, true) => '+',
+                (Some(true), false) => '-',
+                _ => ' ',
+            };
+
+            let annotated = format!("{:>5} {} {}", line, mark, text);
+            listing += annotated.trim_end();
+            listing.push('\n');
+        }
+
+        listing
+    }
+
+    fn set(lines: &mut Vec<bool>, line: u32) {
+        let index = line as usize;
+        if index >= lines.len() {
+            lines.resize(index + 1, false);
+        }
+        lines[index] = true;
+    }
+}
+
+/// The lines that ran while coverage was enabled, as given by
+/// [`Interpreter::take_coverage`](crate::Interpreter::take_coverage). The program comes first,
+/// followed by the files it imported.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Coverage {
+    pub files: Vec<FileCoverage>,
+}
+
+impl Coverage {
+    /// The coverage of the imported file at `path`, or of the program itself with `None`.
+    pub fn file(&self, path: Option<&Path>) -> Option<&FileCoverage> {
+        self.files.iter().find(|file| file.path.as_deref() == path)
+    }
+
+    // Record the lines of a program about to run, including those of the files it imports and
+    // the bodies of its functions.
+    pub(crate) fn add_program(&mut self, statements: &[Statement]) {
+        if self.files.is_empty() {
+            self.files.push(FileCoverage::new(None));
+        }
+
+        for statement in statements {
+            self.add_statement(0, statement);
+        }
+    }
+
+    // Index of the file imported from `module`, added if it is new.
+    pub(crate) fn file_index(&mut self, module: &Path) -> usize {
+        match self
+            .files
+            .iter()
+            .position(|file| file.path.as_deref() == Some(module))
+        {
+            Some(index) => index,
+            None => {
+                self.files
+                    .push(FileCoverage::new(Some(module.to_path_buf())));
+                self.files.len() - 1
+            }
+        }
+    }
+
+    pub(crate) fn mark(&mut self, file: usize, line: u32) {
+        if let Some(file) = self.files.get_mut(file) {
+            FileCoverage::set(&mut file.executed, line);
+        }
+    }
+
+    fn add_statement(&mut self, file: usize, statement: &Statement) {
+        FileCoverage::set(&mut self.files[file].statements, statement.line());
+
+        match statement {
+            Statement::Block(block) => {
+                for statement in &block.statements {
+                    self.add_statement(file, statement);
+                }
+            }
+            Statement::If(statement) => {
+                self.add_statement(file, &statement.then_branch);
+                if let Some(ref else_branch) = statement.else_branch {
+                    self.add_statement(file, else_branch);
+                }
+            }
+            Statement::While(statement) => self.add_statement(file, &statement.body),
+            Statement::ForIn(statement) => self.add_statement(file, &statement.body),
+            Statement::Function(declaration) => self.add_statement(file, &declaration.body),
+            Statement::Test(declaration) => self.add_statement(file, &declaration.body),
+            Statement::Import(import) => {
+                let module = self.file_index(&import.module);
+                for statement in &import.statements {
+                    self.add_statement(module, statement);
+                }
+            }
+            Statement::Variable(_)
+            | Statement::Expression(_)
+            | Statement::Print(_)
+            | Statement::Return(_)
+            | Statement::Yield(_)
+            | Statement::Defer(_)
+            | Statement::Assert(_)
+            | Statement::Enum(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser;
+
+    #[test]
+    fn test_lines() {
+        let program = parser::parse(b"let x = 1\n\nif (x) {\n    print x\n} else print 2").unwrap();
+        let mut coverage = Coverage::default();
+        coverage.add_program(&program);
+        coverage.mark(0, 1);
+        coverage.mark(0, 3);
+
+        let file = coverage.file(None).unwrap();
+        assert_eq!(file.lines().collect::<Vec<u32>>(), [1, 3, 4, 5]);
+        assert_eq!((file.covered(), file.total()), (2, 4));
+        assert_eq!(file.uncovered(), [4, 5]);
+        assert_eq!(file.summary("main"), "main: 2/4 lines covered (50.0%)");
+        assert_eq!(
+            file.annotate("let x = 1\n\nif (x) {\n    print x\n} else print 2"),
+            "    1 + let x = 1
+    2
+    3 + if (x) {
+    4 -     print x
+    5 - } else print 2
+"
+        );
+    }
+}
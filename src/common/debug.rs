@@ -0,0 +1,225 @@
+use std::collections::BTreeSet;
+
+use crate::{
+    error::{runtime::TracedError, NotJsResult},
+    parser,
+};
+
+use super::{
+    environment::Environment, resolver::Resolver, source, statements::Statement, value::Value,
+};
+
+/// How the program goes on after a pause.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Resume {
+    /// Pause again before the next statement, including those of the functions called.
+    Step,
+    /// Run until a statement on a line with a breakpoint.
+    Continue,
+}
+
+/// The front-end of the debugger, attached with
+/// [`Interpreter::set_debugger`](crate::Interpreter::set_debugger). The interpreter pauses before
+/// the first statement, and then as the hook tells it to, and leaves the talking to the user to
+/// the hook.
+pub trait DebugHook {
+    /// Called before running the statement at `pause.line()`, giving how to go on from there.
+    fn paused(&mut self, pause: &mut Pause) -> Resume;
+}
+
+/// A program stopped before a statement, as seen by a [`DebugHook`].
+pub struct Pause<'a> {
+    environment: &'a mut Environment,
+    statement: &'a Statement,
+    breakpoints: &'a mut BTreeSet<u32>,
+}
+
+impl Pause<'_> {
+    /// Line of the statement about to run. Lines are those of the file the statement is in, so
+    /// a call to an imported function pauses at lines of the imported file.
+    pub fn line(&self) -> u32 {
+        self.statement.line()
+    }
+
+    /// The first line of the statement about to run, as printed by
+    /// [`to_source`](super::source::to_source).
+    pub fn source(&self) -> String {
+        source::first_line(self.statement)
+    }
+
+    /// Evaluate an expression in the scopes of the statement about to run, as with
+    /// [`Interpreter::eval_expression`](crate::Interpreter::eval_expression).
+    pub fn eval(&mut self, source: &str) -> NotJsResult<Value> {
+        let expression = parser::parse_expression_with_resolver(
+            source.as_bytes(),
+            Resolver::from_environment(self.environment),
+        )?;
+
+        // The program goes on after a failed evaluation, so only the trace is taken, the scopes
+        // and calls stay as they are.
+        expression.evaluate(self.environment).map_err(|error| {
+            TracedError {
+                error,
+                trace: self.environment.take_trace(),
+            }
+            .into()
+        })
+    }
+
+    /// The variables that have a value in each visible scope, outermost first and sorted by
+    /// name. The globals defined by the host and the prelude are left out.
+    pub fn scopes(&self) -> Vec<Vec<(String, Value)>> {
+        self.environment
+            .environment
+            .iter()
+            .enumerate()
+            .map(|(depth, scope)| {
+                let mut variables: Vec<(String, Value)> = scope
+                    .iter()
+                    .filter(|(name, _)| depth > 0 || !self.environment.builtins.contains_key(*name))
+                    .filter_map(|(name, variable)| Some((name.clone(), variable.value.clone()?)))
+                    .collect();
+                variables.sort_by(|a, b| a.0.cmp(&b.0));
+                variables
+            })
+            .collect()
+    }
+
+    /// Pause before the statements on `line` when continuing.
+    pub fn set_breakpoint(&mut self, line: u32) {
+        self.breakpoints.insert(line);
+    }
+
+    /// Remove the breakpoint on `line`, telling whether there was one.
+    pub fn clear_breakpoint(&mut self, line: u32) -> bool {
+        self.breakpoints.remove(&line)
+    }
+
+    /// The lines with a breakpoint, in order.
+    pub fn breakpoints(&self) -> impl Iterator<Item = u32> + '_ {
+        self.breakpoints.iter().copied()
+    }
+}
+
+// Decides where to pause, and hands the pauses to the hook.
+pub(crate) struct Debugger {
+    hook: Box<dyn DebugHook>,
+    stepping: bool,
+    breakpoints: BTreeSet<u32>,
+}
+
+impl Debugger {
+    pub fn new(hook: Box<dyn DebugHook>) -> Self {
+        Self {
+            hook,
+            stepping: true,
+            breakpoints: BTreeSet::new(),
+        }
+    }
+
+    // Pause before `statement` if stepping or if it is on a breakpoint. Blocks only hold other
+    // statements, so the pause is left to the first of them.
+    pub fn before(environment: &mut Environment, statement: &Statement) {
+        if matches!(statement, Statement::Block(_)) {
+            return;
+        }
+
+        // Taken out of the environment while paused, so that the code run by the hook, such as a
+        // function called from an evaluated expression, doesn't pause in turn.
+        let Some(mut debugger) = environment.debugger.take() else {
+            return;
+        };
+
+        if debugger.stepping || debugger.breakpoints.contains(&statement.line()) {
+            let mut pause = Pause {
+                environment,
+                statement,
+                breakpoints: &mut debugger.breakpoints,
+            };
+            debugger.stepping = debugger.hook.paused(&mut pause) == Resume::Step;
+        }
+
+        environment.debugger = Some(debugger);
+    }
+}
+
+// Closures can be attached directly, e.g. to script the pauses in tests.
+impl<F: FnMut(&mut Pause) -> Resume> DebugHook for F {
+    fn paused(&mut self, pause: &mut Pause) -> Resume {
+        self(pause)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+    use crate::Interpreter;
+
+    #[test]
+    fn test_debugger() {
+        let pauses = Rc::new(RefCell::new(Vec::new()));
+        let log = pauses.clone();
+
+        let mut interpreter = Interpreter::new();
+        interpreter.set_debugger(Some(Box::new(move |pause: &mut Pause| {
+            log.borrow_mut().push((pause.line(), pause.source()));
+
+            match (pause.line(), log.borrow().len()) {
+                (1, _) => {
+                    pause.set_breakpoint(3);
+                    Resume::Continue
+                }
+                (3, 2) => {
+                    let names: Vec<Vec<String>> = pause
+                        .scopes()
+                        .into_iter()
+                        .map(|scope| scope.into_iter().map(|(name, _)| name).collect())
+                        .collect();
+                    assert_eq!(names, [vec!["add", "total"], vec!["n"], vec![]]);
+                    assert_eq!(pause.eval("n * 10").unwrap(), Value::Number(10.0));
+                    // Failed evaluations leave the program running.
+                    assert!(pause.eval("missing").is_err());
+                    assert!(pause.eval("n.x").is_err());
+                    assert!(pause.eval("add(100)").is_ok());
+                    Resume::Continue
+                }
+                (3, _) => {
+                    assert_eq!(pause.breakpoints().collect::<Vec<u32>>(), [3]);
+                    assert!(pause.clear_breakpoint(3));
+                    Resume::Step
+                }
+                _ => Resume::Step,
+            }
+        })));
+
+        let (result, printed) = interpreter.interpret_capture(
+            b"let total = 0
+            function add(n) {
+                total += n
+            }
+            for (const i in [1, 2]) {
+                add(i)
+            }
+            print total",
+        );
+        result.unwrap();
+
+        // The call made while paused ran without pausing.
+        assert_eq!(printed, "103");
+        assert_eq!(
+            *pauses.borrow(),
+            [
+                (1, "let total = 0".to_string()),
+                (3, "total += n".to_string()),
+                (3, "total += n".to_string()),
+                (8, "print total".to_string()),
+            ]
+        );
+
+        interpreter.set_debugger(None);
+        interpreter.interpret(b"add(1)").unwrap();
+        assert_eq!(pauses.borrow().len(), 4);
+    }
+}
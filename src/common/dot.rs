@@ -0,0 +1,427 @@
+use std::fmt::Write;
+
+use super::{
+    expressions::{Expression, PostfixOperator},
+    source,
+    statements::Statement,
+    token::Token,
+};
+
+/// A Graphviz graph of the syntax tree of `statements`, to be drawn with e.g. `dot -Tpng`.
+///
+/// Each node is labeled with its kind and, below it, its operator, name or value. Edges go from a
+/// node to its children, labeled with the part they are when there is more than one kind of
+/// child, e.g. the `condition` of an if. Imports are shown without the statements of the
+/// imported file.
+pub fn to_dot(statements: &[Statement]) -> String {
+    let mut graph = Graph {
+        output: String::from("digraph ast {\n    node [shape=box];\n"),
+        nodes: 0,
+    };
+
+    let program = graph.node("Program", None);
+    for statement in statements {
+        let child = graph.statement(statement);
+        graph.edge(program, child, "");
+    }
+
+    graph.output.push_str("}\n");
+    graph.output
+}
+
+struct Graph {
+    output: String,
+    nodes: usize,
+}
+
+impl Graph {
+    fn node(&mut self, kind: &str, data: Option<String>) -> usize {
+        let id = self.nodes;
+        self.nodes += 1;
+
+        let label = match data {
+            Some(data) => format!("{}\\n{}", escape(kind), escape(&data)),
+            None => escape(kind),
+        };
+        // Writing to a string can't fail.
+        writeln!(self.output, "    n{} [label=\"{}\"];", id, label).unwrap();
+
+        id
+    }
+
+    fn edge(&mut self, from: usize, to: usize, label: &str) {
+        if label.is_empty() {
+            writeln!(self.output, "    n{} -> n{};", from, to).unwrap();
+        } else {
+            writeln!(
+                self.output,
+                "    n{} -> n{} [label=\"{}\"];",
+                from,
+                to,
+                escape(label)
+            )
+            .unwrap();
+        }
+    }
+
+    fn child_statement(&mut self, parent: usize, statement: &Statement, label: &str) {
+        let child = self.statement(statement);
+        self.edge(parent, child, label);
+    }
+
+    fn child(&mut self, parent: usize, expression: &Expression, label: &str) {
+        let child = self.expression(expression);
+        self.edge(parent, child, label);
+    }
+
+    fn statement(&mut self, statement: &Statement) -> usize {
+        match statement {
+            Statement::Block(block) => {
+                let id = self.node("Block", None);
+                for statement in &block.statements {
+                    self.child_statement(id, statement, "");
+                }
+                id
+            }
+            Statement::Variable(declaration) => {
+                let keyword = if declaration.mutable { "let" } else { "const" };
+                let mut data = format!("{} {}", keyword, declaration.identifier.name());
+                if let Some(annotation) = declaration.annotation {
+                    data += &format!(": {}", annotation);
+                }
+                let id = self.node("Variable", Some(data));
+                if let Some(ref initializer) = declaration.initializer {
+                    self.child(id, initializer, "");
+                }
+                id
+            }
+            Statement::Expression(statement) => {
+                let id = self.node("Expression", None);
+                self.child(id, &statement.expression, "");
+                id
+            }
+            Statement::Print(statement) => {
+                let keyword = if statement.new_line {
+                    "println"
+                } else {
+                    "print"
+                };
+                let id = self.node("Print", Some(keyword.to_string()));
+                self.child(id, &statement.expression, "");
+                id
+            }
+            Statement::If(statement) => {
+                let id = self.node("If", None);
+                self.child(id, &statement.condition, "condition");
+                self.child_statement(id, &statement.then_branch, "then");
+                if let Some(ref else_branch) = statement.else_branch {
+                    self.child_statement(id, else_branch, "else");
+                }
+                id
+            }
+            Statement::While(statement) => {
+                let id = self.node("While", None);
+                self.child(id, &statement.condition, "condition");
+                self.child_statement(id, &statement.body, "body");
+                id
+            }
+            Statement::ForIn(statement) => {
+                let keyword = if statement.mutable { "let" } else { "const" };
+                let id = self.node(
+                    "ForIn",
+                    Some(format!("{} {}", keyword, statement.identifier.name())),
+                );
+                self.child(id, &statement.iterable, "iterable");
+                self.child_statement(id, &statement.body, "body");
+                id
+            }
+            Statement::Return(statement) => {
+                let id = self.node("Return", None);
+                if let Some(ref value) = statement.value {
+                    self.child(id, value, "");
+                }
+                id
+            }
+            Statement::Yield(statement) => {
+                let id = self.node("Yield", None);
+                if let Some(ref value) = statement.value {
+                    self.child(id, value, "");
+                }
+                id
+            }
+            Statement::Defer(statement) => {
+                let id = self.node("Defer", None);
+                self.child(id, &statement.expression, "");
+                id
+            }
+            Statement::Assert(statement) => {
+                let id = self.node("Assert", None);
+                self.child(id, &statement.condition, "");
+                id
+            }
+            Statement::Function(declaration) => {
+                let parameters = declaration
+                    .parameters
+                    .iter()
+                    .map(|parameter| parameter.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                let mut data = format!(
+                    "{}{}({})",
+                    if declaration.generator { "*" } else { "" },
+                    declaration.name.name(),
+                    parameters
+                );
+                if let Some(return_type) = declaration.return_type {
+                    data += &format!(": {}", return_type);
+                }
+                let id = self.node("Function", Some(data));
+                self.child_statement(id, &declaration.body, "");
+                id
+            }
+            Statement::Enum(declaration) => {
+                let variants = declaration
+                    .variants
+                    .iter()
+                    .map(Token::name)
+                    .collect::<Vec<&str>>()
+                    .join(", ");
+                self.node(
+                    "Enum",
+                    Some(format!("{} {{{}}}", declaration.name.name(), variants)),
+                )
+            }
+            Statement::Test(declaration) => {
+                let id = self.node("Test", Some(format!("{:?}", declaration.name.value)));
+                self.child_statement(id, &declaration.body, "body");
+                id
+            }
+            Statement::Import(import) => {
+                let path = format!("{:?}", import.path.value);
+                let data = match import.namespace {
+                    Some(ref namespace) => format!("{} from {}", namespace.name(), path),
+                    None => path,
+                };
+                self.node("Import", Some(data))
+            }
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression) -> usize {
+        match expression {
+            Expression::Assignment(assignment) => {
+                let id = self.node(
+                    "Assignment",
+                    Some(format!(
+                        "{} {}",
+                        assignment.identifier.name(),
+                        assignment.operator
+                    )),
+                );
+                self.child(id, &assignment.value, "");
+                id
+            }
+            Expression::PropertyAssignment(assignment) => {
+                let target = target(&assignment.identifier, &assignment.path);
+                let id = self.node(
+                    "PropertyAssignment",
+                    Some(format!("{} {}", target, assignment.operator.token_type)),
+                );
+                for (operator, _) in &assignment.path {
+                    if let PostfixOperator::Index(index) = operator {
+                        self.child(id, index, "index");
+                    }
+                }
+                self.child(id, &assignment.value, "value");
+                id
+            }
+            Expression::Conditional(conditional) => {
+                let id = self.node("Conditional", None);
+                self.child(id, &conditional.condition, "condition");
+                self.child(id, &conditional.then_branch, "then");
+                self.child(id, &conditional.else_branch, "else");
+                id
+            }
+            Expression::Binary(binary) => {
+                // Chains are walked without recursing into the left side, see `chain`.
+                let (chain, left) = binary.chain();
+                let ids: Vec<usize> = chain
+                    .iter()
+                    .map(|binary| self.node("Binary", Some(binary.operator.token_type.to_string())))
+                    .collect();
+
+                for pair in ids.windows(2) {
+                    self.edge(pair[0], pair[1], "left");
+                }
+                self.child(ids[ids.len() - 1], left, "left");
+
+                for (&id, binary) in ids.iter().zip(chain).rev() {
+                    self.child(id, &binary.right, "right");
+                }
+
+                ids[0]
+            }
+            Expression::Unary(unary) => {
+                let id = self.node("Unary", Some(unary.operator.token_type.to_string()));
+                self.child(id, &unary.right, "");
+                id
+            }
+            Expression::Delete(delete) => {
+                let id = self.node("Delete", Some(target(&delete.identifier, &delete.path)));
+                for (operator, _) in &delete.path {
+                    if let PostfixOperator::Index(index) = operator {
+                        self.child(id, index, "index");
+                    }
+                }
+                id
+            }
+            Expression::Postfix(postfix) => match postfix.operator {
+                PostfixOperator::Index(ref index) => {
+                    let id = self.node("Index", None);
+                    self.child(id, &postfix.left, "object");
+                    self.child(id, index, "index");
+                    id
+                }
+                PostfixOperator::Dot(ref name) => {
+                    let id = self.node("Dot", Some(name.clone()));
+                    self.child(id, &postfix.left, "");
+                    id
+                }
+                PostfixOperator::Call(ref arguments) => {
+                    let id = self.node("Call", None);
+                    self.child(id, &postfix.left, "callee");
+                    for argument in arguments {
+                        self.child(id, argument, "argument");
+                    }
+                    id
+                }
+            },
+            Expression::Identifier(identifier) => {
+                self.node("Identifier", Some(identifier.identifier.name().to_string()))
+            }
+            Expression::Array(array) => {
+                let id = self.node("Array", None);
+                for element in &array.elements {
+                    self.child(id, element, "");
+                }
+                id
+            }
+            Expression::Match(expression) => {
+                let id = self.node("Match", None);
+                self.child(id, &expression.value, "value");
+                for arm in &expression.arms {
+                    self.child(id, &arm.body, &source::pattern(&arm.pattern));
+                }
+                id
+            }
+            Expression::Literal(value) => self.node("Literal", Some(format!("{:?}", value))),
+        }
+    }
+}
+
+// Escape text for a quoted DOT string. Line breaks become `\n`, which DOT draws as one.
+// The element or property held by a variable that is assigned or deleted, with the indexes
+// left out, e.g. `xs[].name`.
+fn target(identifier: &Token, path: &[(PostfixOperator, Token)]) -> String {
+    let mut target = identifier.name().to_string();
+    for (operator, _) in path {
+        match operator {
+            PostfixOperator::Dot(name) => target = format!("{}.{}", target, name),
+            _ => target += "[]",
+        }
+    }
+    target
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser;
+
+    #[test]
+    fn test_to_dot() {
+        let program = parser::parse(
+            b"let n = 3
+            while (n > 0) {
+                if (n * 2 == 4) println \"odd\" else print -n
+                n -= 1
+            }",
+        )
+        .unwrap();
+
+        let expected = r#"digraph ast {
+    node [shape=box];
+    n0 [label="Program"];
+    n1 [label="Variable\nlet n"];
+    n2 [label="Literal\n3"];
+    n1 -> n2;
+    n0 -> n1;
+    n3 [label="While"];
+    n4 [label="Binary\n>"];
+    n5 [label="Identifier\nn"];
+    n4 -> n5 [label="left"];
+    n6 [label="Literal\n0"];
+    n4 -> n6 [label="right"];
+    n3 -> n4 [label="condition"];
+    n7 [label="Block"];
+    n8 [label="If"];
+    n9 [label="Binary\n=="];
+    n10 [label="Binary\n*"];
+    n9 -> n10 [label="left"];
+    n11 [label="Identifier\nn"];
+    n10 -> n11 [label="left"];
+    n12 [label="Literal\n2"];
+    n10 -> n12 [label="right"];
+    n13 [label="Literal\n4"];
+    n9 -> n13 [label="right"];
+    n8 -> n9 [label="condition"];
+    n14 [label="Print\nprintln"];
+    n15 [label="Literal\n\"odd\""];
+    n14 -> n15;
+    n8 -> n14 [label="then"];
+    n16 [label="Print\nprint"];
+    n17 [label="Unary\n-"];
+    n18 [label="Identifier\nn"];
+    n17 -> n18;
+    n16 -> n17;
+    n8 -> n16 [label="else"];
+    n7 -> n8;
+    n19 [label="Expression"];
+    n20 [label="Assignment\nn -="];
+    n21 [label="Literal\n1"];
+    n20 -> n21;
+    n19 -> n20;
+    n7 -> n19;
+    n3 -> n7 [label="body"];
+    n0 -> n3;
+}
+"#;
+
+        assert_eq!(to_dot(&program), expected);
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("say \"hi\"\\n"), "say \\\"hi\\\"\\\\n");
+        assert_eq!(escape("a\r\nb"), "a\\nb");
+
+        let program = parser::parse(b"print \"a\\\"b\"").unwrap();
+        assert!(to_dot(&program).contains(r#"[label="Literal\n\"a\\\"b\""];"#));
+    }
+}
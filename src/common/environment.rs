@@ -1,47 +1,583 @@
-use std::collections::HashMap;
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    io::{self, Write},
+    path::PathBuf,
+    rc::Rc,
+    time::{Duration, Instant},
+};
 
 use crate::error::runtime::{RuntimeError, RuntimeResult};
 
-use super::{token::Token, value::Value};
+use super::{
+    coverage::Coverage,
+    debug::Debugger,
+    expressions::Expression,
+    function::UserFunction,
+    profile::Profiler,
+    statements::Statement,
+    token::Token,
+    value::{Value, ValueType},
+};
 
+pub(crate) const DEFAULT_MAX_CALL_DEPTH: usize = 1000;
+
+pub(crate) type Scope = HashMap<String, Variable>;
+
+// The global scope of the main program or of a module imported with a namespace. Only one of them
+// is in use at a time, as the first scope of the environment, the rest are parked in here.
+pub(crate) type Globals = Rc<RefCell<Scope>>;
+
+/// The state of a running program. Natives get it to write to the output of the program.
 pub struct Environment {
-    pub environment: Vec<HashMap<String, Variable>>,
+    pub(crate) environment: Vec<Scope>,
+    pub(crate) max_call_depth: usize,
+    // Maximum number of steps a program may take, unlimited when `None`.
+    pub(crate) max_steps: Option<usize>,
+    // Wall-clock time a program may run for, unlimited when `None`.
+    pub(crate) timeout: Option<Duration>,
+    // Where print statements write to.
+    pub(crate) output: Box<dyn Write>,
+    // Whether statements and calls are written to `trace_output` as they run.
+    pub(crate) tracing: bool,
+    // Where the execution trace is written to, apart from the output of the program.
+    pub(crate) trace_output: Box<dyn Write>,
+    // Times function calls while profiling is enabled.
+    pub(crate) profiler: Option<Profiler>,
+    // Lines that ran while coverage is enabled.
+    pub(crate) coverage: Option<Coverage>,
+    // Index in the coverage of the file whose code is running, `None` for code that isn't
+    // covered, such as the prelude.
+    pub(crate) file: Option<usize>,
+    // Pauses the program before statements while a debugger is attached.
+    pub(crate) debugger: Option<Debugger>,
+    // Canonical paths of the files that have already been imported.
+    pub(crate) modules: HashSet<PathBuf>,
+    // Namespaces of the files already imported with a name.
+    pub(crate) namespaces: HashMap<PathBuf, Value>,
+    // Globals defined by the host and the prelude, which modules start out with.
+    pub(crate) builtins: Scope,
+    // Whether the program runs in a sandboxed interpreter, where imports are refused.
+    pub(crate) sandboxed: bool,
+    // The globals currently in the first scope.
+    globals: Globals,
+    // Functions currently being called, innermost last.
+    frames: Vec<Frame>,
+    // Call stack at the point the last runtime error was raised, innermost last.
+    trace: Vec<Frame>,
+    steps: usize,
+    deadline: Option<Instant>,
+    returning: bool,
+    // Expressions deferred by the blocks being run, innermost block last.
+    pub(crate) deferred: Vec<DeferredBlock>,
+    // The user function whose body is running, while a return in it can be a tail call.
+    tail_position: Option<TailPosition>,
+    // A call returned in tail position, which the returning function runs in its own place.
+    tail_call: Option<TailCall>,
+    // The tests declared so far while they are collected to run, see `Interpreter::test`.
+    // Otherwise programs skip their tests.
+    pub(crate) tests: Option<Vec<Test>>,
+}
+
+pub(crate) struct Test {
+    pub name: String,
+    pub body: Rc<Statement>,
+    pub line: u32,
+}
+
+pub(crate) struct TailPosition {
+    function: Rc<UserFunction>,
+    // Deferred blocks of the caller, the ones past them belong to the body of the function.
+    deferred: usize,
+}
+
+pub(crate) struct TailCall {
+    pub function: Rc<UserFunction>,
+    pub arguments: Vec<Value>,
+    pub line: u32,
+}
+
+pub(crate) struct DeferredBlock {
+    // Index of the scope of the block.
+    depth: usize,
+    expressions: Vec<Deferred>,
+}
+
+// An expression to run when its block exits, along with copies of the scopes that were open
+// inside the block when it was deferred, such as those of loop variables.
+pub(crate) struct Deferred {
+    pub expression: Rc<Expression>,
+    pub scopes: Vec<Scope>,
+}
+
+/// The variables of an environment at some point, see [`Environment::snapshot`].
+pub struct EnvSnapshot {
+    scopes: Vec<Scope>,
+    builtins: Scope,
+    modules: HashSet<PathBuf>,
+    namespaces: HashMap<PathBuf, Value>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    pub name: String,
+    // Line the function was called from.
+    pub line: u32,
+    // What the function was doing when the error went through it, for natives that call back into
+    // the program, e.g. which elements sort was comparing.
+    pub note: Option<String>,
+    // Entered by a tail call, which the next tail call replaces instead of nesting in it.
+    pub(crate) tail: bool,
 }
 
 #[derive(Clone, Debug)]
-pub struct Variable {
+pub(crate) struct Variable {
     pub mutable: bool,
     pub value: Option<Value>,
+    // Line of the declaration, `None` for the globals defined by the host and the prelude.
+    pub line: Option<u32>,
+    // The type the variable is declared with, which every value assigned to it must have.
+    pub annotation: Option<ValueType>,
+}
+
+// Fail unless `value` has the type of `annotation`, if there is one. `target` describes what the
+// value is given to, e.g. "variable 'count'", and is only built when the check fails.
+pub(crate) fn check_annotation(
+    annotation: Option<ValueType>,
+    value: &Value,
+    target: impl FnOnce() -> String,
+    line: u32,
+) -> RuntimeResult<()> {
+    match annotation {
+        Some(expected) if value.value_type() != expected => Err(RuntimeError::new_mismatched_type(
+            target(),
+            expected,
+            value,
+            line,
+        )),
+        _ => Ok(()),
+    }
 }
 
 impl Environment {
-    pub fn new() -> Self {
+    pub(crate) fn new() -> Self {
         Self {
             environment: vec![HashMap::new()],
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            max_steps: None,
+            timeout: None,
+            output: Box::new(io::stdout()),
+            tracing: false,
+            trace_output: Box::new(io::stderr()),
+            profiler: None,
+            coverage: None,
+            file: None,
+            debugger: None,
+            modules: HashSet::new(),
+            namespaces: HashMap::new(),
+            builtins: HashMap::new(),
+            sandboxed: false,
+            globals: Globals::default(),
+            frames: Vec::new(),
+            trace: Vec::new(),
+            steps: 0,
+            deadline: None,
+            returning: false,
+            deferred: Vec::new(),
+            tail_position: None,
+            tail_call: None,
+            tests: None,
+        }
+    }
+
+    // Index of the innermost scope.
+    pub(crate) fn depth(&self) -> usize {
+        self.environment.len() - 1
+    }
+
+    // Count a statement or expression, failing once the instruction budget is used up.
+    pub(crate) fn step(&mut self) -> RuntimeResult<()> {
+        self.steps += 1;
+
+        match self.max_steps {
+            Some(limit) if self.steps > limit => Err(RuntimeError::Budget { limit }),
+            _ => Ok(()),
+        }
+    }
+
+    // Reset the instruction budget and start the timeout clock for a new run.
+    pub(crate) fn start_run(&mut self) {
+        self.steps = 0;
+        self.trace.clear();
+        self.deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+    }
+
+    // Fail if the run has gone past its deadline. This reads the clock, so it is only called at
+    // loop back-edges and call entries rather than on every step.
+    pub(crate) fn check_deadline(&self) -> RuntimeResult<()> {
+        match (self.deadline, self.timeout) {
+            (Some(deadline), Some(limit)) if Instant::now() > deadline => {
+                Err(RuntimeError::Timeout { limit })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Push a call frame, failing once the maximum call depth is reached.
+    pub(crate) fn enter_call(&mut self, name: &str, line: u32) -> RuntimeResult<()> {
+        self.check_deadline()?;
+
+        if self.frames.len() >= self.max_call_depth {
+            return Err(RuntimeError::new_call_depth_exceeded(
+                name,
+                self.max_call_depth,
+            ));
+        }
+
+        self.frames.push(Frame {
+            name: name.to_string(),
+            line,
+            note: None,
+            tail: false,
+        });
+
+        Ok(())
+    }
+
+    pub(crate) fn exit_call(&mut self) {
+        self.frames.pop();
+    }
+
+    // Enter a tail call of `name` from `line`, see `UserFunction::call`. Only the latest of the
+    // tail calls made in place of a call is kept in the call stack, so that tail recursion doesn't
+    // deepen it.
+    pub(crate) fn enter_tail_call(&mut self, name: &str, line: u32) -> RuntimeResult<()> {
+        self.check_deadline()?;
+
+        let frame = Frame {
+            name: name.to_string(),
+            line,
+            note: None,
+            tail: true,
+        };
+        match self.frames.last_mut() {
+            Some(last) if last.tail => *last = frame,
+            _ => self.frames.push(frame),
+        }
+
+        Ok(())
+    }
+
+    // Leave the tail calls made in place of the innermost call.
+    pub(crate) fn exit_tail_calls(&mut self) {
+        if self.frames.last().is_some_and(|frame| frame.tail) {
+            self.frames.pop();
+        }
+    }
+
+    // Mark `line` of the running file as covered.
+    pub(crate) fn cover(&mut self, line: u32) {
+        if let (Some(file), Some(ref mut coverage)) = (self.file, &mut self.coverage) {
+            coverage.mark(file, line);
+        }
+    }
+
+    pub(crate) fn enter_profile(&mut self, name: &str) {
+        if let Some(ref mut profiler) = self.profiler {
+            profiler.enter(name);
+        }
+    }
+
+    pub(crate) fn exit_profile(&mut self) {
+        if let Some(ref mut profiler) = self.profiler {
+            profiler.exit();
+        }
+    }
+
+    // Remember the current call stack for the error being raised. Only the innermost call
+    // records it, outer calls keep the deepest trace.
+    pub(crate) fn record_trace(&mut self) {
+        if self.trace.is_empty() {
+            self.trace = self.frames.clone();
+        }
+    }
+
+    pub(crate) fn take_trace(&mut self) -> Vec<Frame> {
+        std::mem::take(&mut self.trace)
+    }
+
+    // Tell in the trace of the error being raised what the innermost call, a native calling back
+    // into the program, was doing. The error itself is left as it is.
+    pub(crate) fn note_trace(&mut self, note: String) {
+        let Some(depth) = self.frames.len().checked_sub(1) else {
+            return;
+        };
+        if let Some(frame) = self.trace.get_mut(depth) {
+            frame.note.get_or_insert(note);
+        }
+    }
+
+    // Drop the scopes and calls left behind by a run that failed, keeping only the globals.
+    pub(crate) fn unwind(&mut self) {
+        self.environment.truncate(1);
+        self.frames.clear();
+        self.returning = false;
+        self.deferred.clear();
+        self.tail_position = None;
+        self.tail_call = None;
+    }
+
+    /// Capture the variables defined and the files imported so far, to go back to them with
+    /// [`restore`](Self::restore). Arrays and objects are kept as they are now, while generators
+    /// are shared and go on from wherever they are resumed to.
+    pub fn snapshot(&self) -> EnvSnapshot {
+        EnvSnapshot {
+            scopes: self.environment.clone(),
+            builtins: self.builtins.clone(),
+            modules: self.modules.clone(),
+            namespaces: self.namespaces.clone(),
+        }
+    }
+
+    /// Go back to the variables of `snapshot`, forgetting what was defined and imported since.
+    pub fn restore(&mut self, snapshot: EnvSnapshot) {
+        self.environment = snapshot.scopes;
+        self.builtins = snapshot.builtins;
+        self.modules = snapshot.modules;
+        self.namespaces = snapshot.namespaces;
+    }
+
+    /// Write `value` to the output. Without a new line the output is flushed, so prompts show up
+    /// before the program waits for input.
+    pub fn print(&mut self, value: &Value, new_line: bool) -> io::Result<()> {
+        if new_line {
+            writeln!(self.output, "{}", value)
+        } else {
+            write!(self.output, "{}", value)?;
+            self.output.flush()
+        }
+    }
+
+    // Write a line of the execution trace, with the line of the program it is about and the
+    // current nesting depth.
+    pub(crate) fn trace_event(&mut self, line: u32, event: &str) -> RuntimeResult<()> {
+        writeln!(
+            self.trace_output,
+            "[line {}, depth {}] {}",
+            line,
+            self.depth(),
+            event
+        )
+        .map_err(|err| RuntimeError::new(format!("Failed to write trace: {}", err)))
+    }
+
+    pub(crate) fn globals(&self) -> Globals {
+        self.globals.clone()
+    }
+
+    // Make `globals` the first scope, parking the current globals. Gives the globals to restore
+    // with `leave_globals`, or `None` if `globals` were already in use.
+    pub(crate) fn enter_globals(&mut self, globals: &Globals) -> Option<Globals> {
+        if Rc::ptr_eq(&self.globals, globals) {
+            return None;
+        }
+
+        let scope = std::mem::replace(&mut self.environment[0], globals.take());
+        *self.globals.borrow_mut() = scope;
+
+        Some(std::mem::replace(&mut self.globals, globals.clone()))
+    }
+
+    pub(crate) fn leave_globals(&mut self, previous: Option<Globals>) {
+        if let Some(previous) = previous {
+            let scope = std::mem::replace(&mut self.environment[0], previous.take());
+            *self.globals.borrow_mut() = scope;
+            self.globals = previous;
         }
     }
 
-    pub fn push(&mut self) {
+    // Hide the scopes above `depth` so a function body only sees the scopes it was declared in.
+    // Scopes are matched by position, so a function must be called while the scope it was
+    // declared in is still alive.
+    pub(crate) fn hide_scopes(&mut self, name: &Token, depth: usize) -> RuntimeResult<Vec<Scope>> {
+        if depth >= self.environment.len() {
+            return Err(RuntimeError::new_out_of_scope_call(name));
+        }
+
+        Ok(self.environment.split_off(depth + 1))
+    }
+
+    // Restore the scopes hidden by `hide_scopes`, dropping any left behind by the call.
+    pub(crate) fn restore_scopes(&mut self, depth: usize, hidden: Vec<Scope>) {
+        self.environment.truncate(depth + 1);
+        self.environment.extend(hidden);
+    }
+
+    // Mark that a return statement was executed, so enclosing blocks and loops stop early.
+    pub(crate) fn start_return(&mut self) {
+        self.returning = true;
+    }
+
+    pub(crate) fn is_returning(&self) -> bool {
+        self.returning
+    }
+
+    // Put aside a return in progress, giving whether there was one to restore with
+    // `resume_return`.
+    pub(crate) fn pause_return(&mut self) -> bool {
+        std::mem::take(&mut self.returning)
+    }
+
+    pub(crate) fn resume_return(&mut self, returning: bool) {
+        self.returning = returning;
+    }
+
+    // Let returns in tail position be tail calls while the body of `function` runs, or none with
+    // `None`, e.g. in natives. Gives the previous position to restore with `leave_tail_position`.
+    pub(crate) fn enter_tail_position(
+        &mut self,
+        function: Option<&Rc<UserFunction>>,
+    ) -> Option<TailPosition> {
+        let position = function.map(|function| TailPosition {
+            function: function.clone(),
+            deferred: self.deferred.len(),
+        });
+
+        std::mem::replace(&mut self.tail_position, position)
+    }
+
+    pub(crate) fn leave_tail_position(&mut self, previous: Option<TailPosition>) {
+        self.tail_position = previous;
+    }
+
+    pub(crate) fn in_tail_position(&self) -> bool {
+        self.tail_position.is_some()
+    }
+
+    // Whether a return can leave a call of `function` with `arguments` arguments for the function
+    // returning to run in its place. It can't when the result would differ from a plain call: when
+    // `function` is declared inside the returning function, whose scopes are gone by then, when
+    // the returning function has to check the value against its return type, when it has
+    // deferred expressions to run after the call, or when the call has to show in a trace or
+    // profile.
+    pub(crate) fn can_tail_call(&self, function: &Rc<UserFunction>, arguments: usize) -> bool {
+        let Some(ref position) = self.tail_position else {
+            return false;
+        };
+
+        !function.generator
+            && function.parameters.len() == arguments
+            && function.depth <= position.function.depth
+            && (position.function.return_type.is_none() || Rc::ptr_eq(&position.function, function))
+            && self.deferred[position.deferred..]
+                .iter()
+                .all(|block| block.expressions.is_empty())
+            && !self.tracing
+            && self.profiler.is_none()
+    }
+
+    // Leave `call` for the returning function to run, see `UserFunction::call`.
+    pub(crate) fn tail_call(&mut self, call: TailCall) {
+        self.tail_call = Some(call);
+    }
+
+    pub(crate) fn take_tail_call(&mut self) -> Option<TailCall> {
+        self.tail_call.take()
+    }
+
+    // Start collecting the expressions deferred by the block whose scope is the innermost one.
+    pub(crate) fn start_deferring(&mut self) {
+        self.deferred.push(DeferredBlock {
+            depth: self.depth(),
+            expressions: Vec::new(),
+        });
+    }
+
+    // Defer `expression` to when the innermost block exits. The scopes opened inside the block
+    // since are gone by then, so the values they hold now are kept.
+    pub(crate) fn defer(&mut self, expression: Rc<Expression>) {
+        if let Some(block) = self.deferred.last_mut() {
+            let scopes = self.environment[block.depth + 1..].to_vec();
+            block.expressions.push(Deferred { expression, scopes });
+        }
+    }
+
+    // The expressions deferred by the innermost block, in the order they were deferred.
+    pub(crate) fn take_deferred(&mut self) -> Vec<Deferred> {
+        self.deferred
+            .pop()
+            .map(|block| block.expressions)
+            .unwrap_or_default()
+    }
+
+    // Finish a return, giving the returned value or null if the body ended without one.
+    pub(crate) fn finish_return(&mut self, value: Value) -> Value {
+        if std::mem::take(&mut self.returning) {
+            value
+        } else {
+            Value::Null
+        }
+    }
+
+    pub(crate) fn push(&mut self) {
         self.environment.push(HashMap::new());
     }
 
-    pub fn pop(&mut self) {
+    pub(crate) fn pop(&mut self) {
         self.environment.pop();
     }
 
-    pub fn define(&mut self, identifier: Token, value: Option<Value>, mutable: bool) {
-        self.environment
-            .last_mut()
-            .unwrap()
-            .insert(identifier.value.to_string(), Variable { mutable, value });
+    pub(crate) fn define(&mut self, identifier: &Token, value: Option<Value>, mutable: bool) {
+        self.define_annotated(identifier, value, mutable, None);
+    }
+
+    // Define a variable with a type annotation, which the value is expected to have been checked
+    // against already.
+    pub(crate) fn define_annotated(
+        &mut self,
+        identifier: &Token,
+        value: Option<Value>,
+        mutable: bool,
+        annotation: Option<ValueType>,
+    ) {
+        self.environment.last_mut().unwrap().insert(
+            identifier.name().to_string(),
+            Variable {
+                mutable,
+                value,
+                line: Some(identifier.line),
+                annotation,
+            },
+        );
+    }
+
+    pub(crate) fn define_global(&mut self, name: &str, value: Value) {
+        let variable = Variable {
+            mutable: false,
+            value: Some(value),
+            line: None,
+            annotation: None,
+        };
+
+        self.builtins.insert(name.to_string(), variable.clone());
+        self.environment[0].insert(name.to_string(), variable);
     }
 
-    pub fn assign(&mut self, identifier: Token, value: Value) -> RuntimeResult<()> {
+    pub(crate) fn assign(&mut self, identifier: &Token, value: Value) -> RuntimeResult<()> {
         for scope in self.environment.iter_mut().rev() {
-            if let Some(variable) = scope.get_mut(identifier.value.to_string().as_str()) {
+            if let Some(variable) = scope.get_mut(identifier.name()) {
                 if !variable.mutable {
-                    return Err(RuntimeError::new_immutable_variable(identifier));
+                    return Err(RuntimeError::new_immutable_variable(
+                        identifier,
+                        variable.line,
+                    ));
                 }
+                check_annotation(
+                    variable.annotation,
+                    &value,
+                    || format!("variable '{}'", identifier.name()),
+                    identifier.line,
+                )?;
                 variable.value = Some(value);
                 return Ok(());
             }
@@ -49,9 +585,28 @@ impl Environment {
         Err(RuntimeError::new_undeclared_variable(identifier))
     }
 
-    pub fn get(&self, identifier: Token) -> RuntimeResult<&Value> {
+    // The value of a mutable variable, to change in place.
+    pub(crate) fn get_mut(&mut self, identifier: &Token) -> RuntimeResult<&mut Value> {
+        for scope in self.environment.iter_mut().rev() {
+            if let Some(variable) = scope.get_mut(identifier.name()) {
+                if !variable.mutable {
+                    return Err(RuntimeError::new_immutable_variable(
+                        identifier,
+                        variable.line,
+                    ));
+                }
+                return variable
+                    .value
+                    .as_mut()
+                    .ok_or_else(|| RuntimeError::new_undefined_variable(identifier));
+            }
+        }
+        Err(RuntimeError::new_undeclared_variable(identifier))
+    }
+
+    pub(crate) fn get(&self, identifier: &Token) -> RuntimeResult<&Value> {
         for scope in self.environment.iter().rev() {
-            if let Some(variable) = scope.get(identifier.value.to_string().as_str()) {
+            if let Some(variable) = scope.get(identifier.name()) {
                 if let Some(value) = &variable.value {
                     return Ok(value);
                 }
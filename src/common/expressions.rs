@@ -1,15 +1,16 @@
-use std::fmt;
+use std::{fmt, mem};
 
-use crate::error::runtime::RuntimeResult;
+use serde::{Deserialize, Serialize};
 
 use super::{
+    serialize,
     token::{Token, TokenType},
     value::Value,
 };
 
 /*
 expression = assignment_expression ;
-assignment_expression = conditional_expression , [ assignment_operator , assignment_expression ] ;
+assignment_expression = conditional_expression , [ assignment_operator , assignment_expression ] ; (* the target must be an identifier, element or property *)
 conditional_expression = logical_or_expression , [ "?" , expression , ":" , conditional_expression ] ;
 logical_or_expression = logical_and_expression , { "|" , logical_and_expression } ;
 logical_and_expression = equality_expression , { "&" , equality_expression } ;
@@ -17,68 +18,247 @@ equality_expression = relational_expression , { ( "==" | "!=" ) , relational_exp
 relational_expression = additive_expression , { ( "<" | "<=" | ">" | ">=" ) , additive_expression } ;
 additive_expression = multiplicative_expression , { ( "+" | "-" ) , multiplicative_expression } ;
 multiplicative_expression = unary_expression , { ( "*" | "/" ) , unary_expression } ;
-unary_expression = postfix_expression | ( (  "-" | "!" ) , unary_expression ) ;
+unary_expression = postfix_expression | ( (  "-" | "!" ) , unary_expression ) | "delete" , postfix_expression ; (* the target must be an element or property *)
 postfix_expression = primary_expression , { "[" , expression , "]" | "." , identifier | "(" , [ argument_list ] , ")" } ;
-primary_expression = identifier | literal | "(" , expression , ")" ;
+primary_expression = identifier | literal | "(" , expression , ")" | match_expression ;
+match_expression = "match" , expression , "{" , match_arm , { "," , match_arm } , [ "," ] , "}" ;
+match_arm = pattern , "=>" , expression ;
+pattern = single_pattern , { "|" , single_pattern } ;
+single_pattern = "_" | identifier , { "." , identifier } | [ "-" ] , literal | "[" , [ pattern , { "," , pattern } ] , "]" ;
 argument_list = expression , { "," , expression } ;
 assignment_operator = "=" | "+=" | "-=" | "*=" | "/=" ;
 identifier = letter , { letter | digit | "_" } ;
 literal = NUMBER | STRING | BOOLEAN | NULL ;
 */
 
-pub trait Expression {
-    fn evaluate(&self) -> RuntimeResult<Value>;
-    fn node_to_string(&self) -> String;
-    fn is_identifier(&self) -> Option<Token> {
-        None
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum Expression {
+    Assignment(AssignmentExpression),
+    PropertyAssignment(PropertyAssignment),
+    Conditional(ConditionalExpression),
+    Binary(BinaryExpression),
+    Unary(UnaryExpression),
+    Delete(DeleteExpression),
+    Postfix(PostfixExpression),
+    Identifier(Identifier),
+    Array(ArrayLiteral),
+    Match(MatchExpression),
+    Literal(#[serde(with = "serialize::literal")] Literal),
+}
+
+impl Expression {
+    pub fn is_identifier(&self) -> Option<Token> {
+        match self {
+            Expression::Identifier(identifier) => Some(identifier.identifier.clone()),
+            _ => None,
+        }
+    }
+
+    // Split an assignment target such as `xs[0].name` into its variable and the accesses into it.
+    pub fn into_target(self) -> Option<(Token, Vec<(PostfixOperator, Token)>)> {
+        match self {
+            Expression::Identifier(identifier) => Some((identifier.identifier, Vec::new())),
+            Expression::Postfix(PostfixExpression {
+                left,
+                operator,
+                token,
+            }) => {
+                if let PostfixOperator::Call(_) = operator {
+                    return None;
+                }
+
+                let (identifier, mut path) = left.into_target()?;
+                path.push((operator, token));
+
+                Some((identifier, path))
+            }
+            _ => None,
+        }
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct AssignmentExpression {
     pub identifier: Token,
     pub operator: TokenType,
-    pub value: Box<dyn Expression>,
+    pub value: Box<Expression>,
     pub scope: usize,
 }
 
+// Assignment to an element of an array or a property of an object held by a variable.
+#[derive(Serialize, Deserialize)]
+pub struct PropertyAssignment {
+    pub identifier: Token,
+    // Index and dot operators leading to the assigned element, each with its token.
+    pub path: Vec<(PostfixOperator, Token)>,
+    pub operator: Token,
+    pub value: Box<Expression>,
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct ConditionalExpression {
-    pub condition: Box<dyn Expression>,
-    pub then_branch: Box<dyn Expression>,
-    pub else_branch: Box<dyn Expression>,
+    pub condition: Box<Expression>,
+    pub then_branch: Box<Expression>,
+    pub else_branch: Box<Expression>,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct BinaryExpression {
-    pub left: Box<dyn Expression>,
+    pub left: Box<Expression>,
     pub operator: Token,
-    pub right: Box<dyn Expression>,
+    pub right: Box<Expression>,
+}
+
+// Chains such as `a + b + c + ...` nest to the left, one level per operator, and generated code
+// can make them thousands of levels deep. Code walking them uses `chain` and a loop instead of
+// recursing into `left`, so that the depth is not limited by the stack.
+impl BinaryExpression {
+    // The binary expressions along the left side, starting with this one, and the operand at the
+    // bottom of the chain.
+    pub fn chain(&self) -> (Vec<&BinaryExpression>, &Expression) {
+        let mut chain = vec![self];
+        let mut left = &*self.left;
+
+        while let Expression::Binary(binary) = left {
+            chain.push(binary);
+            left = &binary.left;
+        }
+
+        (chain, left)
+    }
+}
+
+// Dropping the chain level by level would recurse too, so the levels are detached first.
+impl Drop for BinaryExpression {
+    fn drop(&mut self) {
+        let mut left = mem::replace(&mut *self.left, Expression::Literal(Value::Null));
+
+        while let Expression::Binary(ref mut binary) = left {
+            left = mem::replace(&mut *binary.left, Expression::Literal(Value::Null));
+        }
+    }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct UnaryExpression {
     pub operator: Token,
-    pub right: Box<dyn Expression>,
+    pub right: Box<Expression>,
 }
 
+// Removal of an element of an array or a property of an object held by a variable.
+#[derive(Serialize, Deserialize)]
+pub struct DeleteExpression {
+    // The `delete` keyword.
+    pub token: Token,
+    pub identifier: Token,
+    // Index and dot operators leading to the removed element, each with its token. Never empty.
+    pub path: Vec<(PostfixOperator, Token)>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", content = "value")]
 pub enum PostfixOperator {
-    Index(Box<dyn Expression>),
+    Index(Box<Expression>),
     Dot(String),
-    Call(Vec<Box<dyn Expression>>),
+    Call(Vec<Expression>),
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct PostfixExpression {
-    pub left: Box<dyn Expression>,
+    pub left: Box<Expression>,
     pub operator: PostfixOperator,
+    // The `[`, `.` or `(` token of the operator.
+    pub token: Token,
 }
+#[derive(Serialize, Deserialize)]
 pub struct Identifier {
     pub identifier: Token,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct ArrayLiteral {
-    pub elements: Vec<Box<dyn Expression>>,
+    pub elements: Vec<Expression>,
+}
+
+// Gives the value of the first arm whose pattern matches the value, with the names the pattern
+// declares visible in that arm only.
+#[derive(Serialize, Deserialize)]
+pub struct MatchExpression {
+    // The `match` keyword.
+    pub token: Token,
+    pub value: Box<Expression>,
+    pub arms: Vec<MatchArm>,
+}
+
+impl MatchExpression {
+    // Whether some arm matches every value: one with a pattern that always matches, or arms for
+    // both `true` and `false`, which match every boolean.
+    pub fn is_exhaustive(&self) -> bool {
+        let patterns = || {
+            self.arms.iter().flat_map(|arm| match arm.pattern {
+                Pattern::Alternatives(ref alternatives) => alternatives.iter().collect(),
+                ref pattern => vec![pattern],
+            })
+        };
+        let covers = |boolean: bool| {
+            patterns().any(|pattern| {
+                matches!(pattern, Pattern::Literal(Value::Boolean(literal)) if *literal == boolean)
+            })
+        };
+
+        patterns().any(Pattern::is_irrefutable) || (covers(true) && covers(false))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub body: Expression,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", content = "value")]
+pub enum Pattern {
+    // `_`, which matches anything.
+    Wildcard,
+    // Matches values equal to the literal, as with `==`.
+    Literal(Literal),
+    // Matches anything, declaring the name as a constant holding the value.
+    Binding(Token),
+    // A property of a variable, such as the enum variant `State.Idle`, compared with `==`.
+    Constant(Box<Expression>),
+    // Matches arrays of the same length whose elements match the patterns, in order.
+    Array(Vec<Pattern>),
+    // `1 | 2`, which matches what any of the patterns matches. They can't declare names.
+    Alternatives(Vec<Pattern>),
+}
+
+impl Pattern {
+    // Whether the pattern matches every value.
+    pub fn is_irrefutable(&self) -> bool {
+        match self {
+            Pattern::Wildcard | Pattern::Binding(_) => true,
+            Pattern::Alternatives(alternatives) => alternatives.iter().any(Pattern::is_irrefutable),
+            Pattern::Literal(_) | Pattern::Constant(_) | Pattern::Array(_) => false,
+        }
+    }
+
+    // The names the pattern declares, in order.
+    pub fn bindings(&self) -> Vec<&Token> {
+        match self {
+            Pattern::Binding(name) => vec![name],
+            Pattern::Array(elements) | Pattern::Alternatives(elements) => {
+                elements.iter().flat_map(Pattern::bindings).collect()
+            }
+            Pattern::Wildcard | Pattern::Literal(_) | Pattern::Constant(_) => Vec::new(),
+        }
+    }
 }
 
 pub type Literal = Value;
 
-impl fmt::Debug for dyn Expression {
+impl fmt::Debug for Expression {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{:?}", self.node_to_string())
     }
@@ -0,0 +1,119 @@
+use std::fmt;
+
+use super::value::Value;
+
+pub struct FormatError {
+    pub message: String,
+}
+
+impl FormatError {
+    fn new(message: String) -> Self {
+        FormatError { message }
+    }
+}
+
+impl fmt::Debug for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+// Replace each `{}` in `template` with the next argument, in its display form. `{{` and `}}`
+// stand for literal braces. Every argument must be used, and there must be one for every
+// placeholder.
+pub fn format(template: &str, arguments: &[Value]) -> Result<String, FormatError> {
+    let mut result = String::with_capacity(template.len());
+    let mut chars = template.chars().enumerate().peekable();
+    let mut placeholders = 0;
+
+    while let Some((position, chr)) = chars.next() {
+        match chr {
+            '{' => match chars.next() {
+                Some((_, '{')) => result.push('{'),
+                Some((_, '}')) => {
+                    if let Some(argument) = arguments.get(placeholders) {
+                        result += &argument.to_string();
+                    }
+                    placeholders += 1;
+                }
+                _ => {
+                    return Err(FormatError::new(format!(
+                        "unmatched '{{' at position {}",
+                        position
+                    )))
+                }
+            },
+            '}' => match chars.next() {
+                Some((_, '}')) => result.push('}'),
+                _ => {
+                    return Err(FormatError::new(format!(
+                        "unmatched '}}' at position {}",
+                        position
+                    )))
+                }
+            },
+            chr => result.push(chr),
+        }
+    }
+
+    if placeholders != arguments.len() {
+        return Err(FormatError::new(format!(
+            "{} but {}",
+            counted(placeholders, "placeholder"),
+            counted(arguments.len(), "argument")
+        )));
+    }
+
+    Ok(result)
+}
+
+// `count` followed by `noun`, plural unless there is one, e.g. `2 arguments`.
+fn counted(count: usize, noun: &str) -> String {
+    match count {
+        1 => format!("1 {}", noun),
+        _ => format!("{} {}s", count, noun),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(template: &str, arguments: &[Value]) -> String {
+        format(template, arguments).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_format() {
+        let arguments = [Value::Number(1.5), Value::String("a".into())];
+        assert_eq!(format("({}, {})", &arguments).unwrap(), "(1.5, a)");
+        assert_eq!(format("no placeholders", &[]).unwrap(), "no placeholders");
+        assert_eq!(format("{{}} {{{}}}", &arguments[..1]).unwrap(), "{} {1.5}");
+        assert_eq!(
+            format("{}", &[Value::Array(vec![Value::Null].into())]).unwrap(),
+            "[Null]"
+        );
+    }
+
+    #[test]
+    fn test_format_errors() {
+        let one = [Value::Number(1.0)];
+        assert_eq!(error("{} {}", &one), "2 placeholders but 1 argument");
+        assert_eq!(
+            error("{}", &[one[0].clone(), one[0].clone()]),
+            "1 placeholder but 2 arguments"
+        );
+        assert_eq!(error("{} {}", &[]), "2 placeholders but 0 arguments");
+        assert_eq!(error("a {x}", &one), "unmatched '{' at position 2");
+        assert_eq!(error("a } {}", &one), "unmatched '}' at position 2");
+        assert_eq!(error("{", &[]), "unmatched '{' at position 0");
+    }
+}
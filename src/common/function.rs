@@ -0,0 +1,393 @@
+use core::fmt;
+use std::rc::Rc;
+
+use crate::error::runtime::{RuntimeError, RuntimeResult};
+
+use super::{
+    environment::{check_annotation, Environment, Globals},
+    generator::Generator,
+    statements::{Parameter, Statement},
+    token::Token,
+    value::{Value, ValueType},
+};
+
+pub type NativeBody = dyn Fn(&mut Environment, Vec<Value>) -> RuntimeResult<Value>;
+
+// Stack left below which a call runs on a newly allocated segment, and the size of the segment.
+const STACK_RED_ZONE: usize = 128 * 1024;
+const STACK_SEGMENT: usize = 4 * 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Arity {
+    Exact(usize),
+    Range(usize, usize),
+    AtLeast(usize),
+}
+
+impl Arity {
+    pub fn accepts(&self, count: usize) -> bool {
+        match *self {
+            Arity::Exact(expected) => count == expected,
+            Arity::Range(min, max) => (min..=max).contains(&count),
+            Arity::AtLeast(min) => count >= min,
+        }
+    }
+}
+
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Arity::Exact(expected) => write!(f, "{}", expected),
+            Arity::Range(min, max) => write!(f, "{} to {}", min, max),
+            Arity::AtLeast(min) => write!(f, "at least {}", min),
+        }
+    }
+}
+
+// The short form shown after the name of a native, e.g. `1..3` for `range/1..3`.
+fn short_arity(arity: Arity) -> String {
+    match arity {
+        Arity::Exact(expected) => expected.to_string(),
+        Arity::Range(min, max) => format!("{}..{}", min, max),
+        Arity::AtLeast(min) => format!("{}..", min),
+    }
+}
+
+pub struct NativeFunction {
+    pub name: String,
+    pub arity: Arity,
+    pub body: Box<NativeBody>,
+    /// One line describing the native, listed by `help()`.
+    pub doc: Option<String>,
+}
+
+impl NativeFunction {
+    pub fn new<F>(name: &str, arity: Arity, body: F) -> Self
+    where
+        F: Fn(&mut Environment, Vec<Value>) -> RuntimeResult<Value> + 'static,
+    {
+        NativeFunction {
+            name: name.to_string(),
+            arity,
+            body: Box::new(body),
+            doc: None,
+        }
+    }
+
+    /// Describe the native in one line, shown by `help()`.
+    pub fn with_doc(mut self, doc: &str) -> Self {
+        self.doc = Some(doc.to_string());
+        self
+    }
+
+    // The name followed by the arity, e.g. `range/1..3`.
+    pub(crate) fn signature(&self) -> String {
+        format!("{}/{}", self.name, short_arity(self.arity))
+    }
+
+    fn call(&self, environment: &mut Environment, arguments: Vec<Value>) -> RuntimeResult<Value> {
+        (self.body)(environment, arguments)
+    }
+}
+
+pub struct UserFunction {
+    pub name: Token,
+    pub parameters: Vec<Parameter>,
+    pub return_type: Option<ValueType>,
+    pub body: Rc<Statement>,
+    // Declared with `function*`, so that calls give a generator rather than running the body.
+    pub generator: bool,
+    // Index of the scope the function was declared in.
+    pub(crate) depth: usize,
+    // Globals of the program or module the function was declared in.
+    pub(crate) globals: Globals,
+    // File the function was declared in, for coverage.
+    pub(crate) file: Option<usize>,
+}
+
+#[derive(Clone)]
+pub enum Function {
+    Native(Rc<NativeFunction>),
+    User(Rc<UserFunction>),
+}
+
+impl Function {
+    pub fn name(&self) -> String {
+        match self {
+            Function::Native(native) => native.name.clone(),
+            Function::User(function) => function.name.value.to_string(),
+        }
+    }
+
+    // Number of parameters the function takes, the required ones for natives with optional
+    // parameters.
+    pub fn length(&self) -> usize {
+        match self {
+            Function::Native(native) => match native.arity {
+                Arity::Exact(count) | Arity::Range(count, _) | Arity::AtLeast(count) => count,
+            },
+            Function::User(function) => function.parameters.len(),
+        }
+    }
+
+    /// The name followed by the number of arguments the function takes, e.g. `add/2` or
+    /// `range/1..3`.
+    pub fn signature(&self) -> String {
+        format!("{}/{}", self.name(), short_arity(self.arity()))
+    }
+
+    fn arity(&self) -> Arity {
+        match self {
+            Function::Native(native) => native.arity,
+            Function::User(function) => Arity::Exact(function.parameters.len()),
+        }
+    }
+
+    // Call the function from `line`, which is recorded in the call stack.
+    pub fn call(
+        &self,
+        environment: &mut Environment,
+        arguments: Vec<Value>,
+        line: u32,
+    ) -> RuntimeResult<Value> {
+        // Checked before the call is entered, so that a failed call leaves no frame behind.
+        if !self.arity().accepts(arguments.len()) {
+            return Err(self.arity_mismatch(arguments.len(), line));
+        }
+
+        if environment.tracing {
+            let arguments: Vec<String> = arguments.iter().map(|arg| format!("{:?}", arg)).collect();
+            environment.trace_event(
+                line,
+                &format!("call {}({})", self.name(), arguments.join(", ")),
+            )?;
+        }
+
+        environment.enter_call(&self.name(), line)?;
+        environment.enter_profile(&self.name());
+
+        // Code run by natives, such as eval, is not part of any file.
+        let file = match self {
+            Function::Native(_) => None,
+            Function::User(function) => function.file,
+        };
+        let caller_file = std::mem::replace(&mut environment.file, file);
+
+        // Natives may run code of their own, which isn't in the body of the caller.
+        let position = environment.enter_tail_position(None);
+        // Each level of recursion takes several kilobytes of stack, far more in unoptimized
+        // builds, so the stack is extended when it runs low and the call depth limit is the only
+        // limit, whatever the stack of the thread running the interpreter.
+        let result = stacker::maybe_grow(STACK_RED_ZONE, STACK_SEGMENT, || match self {
+            Function::Native(native) => native.call(environment, arguments),
+            Function::User(function) => function.call(environment, arguments, line),
+        });
+        environment.leave_tail_position(position);
+
+        environment.file = caller_file;
+        environment.exit_profile();
+
+        let result = match result {
+            Ok(value) if environment.tracing => environment
+                .trace_event(line, &format!("{} returned {:?}", self.name(), value))
+                .map(|_| value),
+            result => result,
+        };
+
+        if result.is_err() {
+            environment.record_trace();
+        }
+
+        environment.exit_call();
+
+        result
+    }
+
+    fn arity_mismatch(&self, found: usize, line: u32) -> RuntimeError {
+        match self {
+            Function::Native(native) => RuntimeError::new_arity_mismatch(
+                native.name.clone(),
+                native.arity,
+                found,
+                line,
+                None,
+            ),
+            Function::User(function) => RuntimeError::new_arity_mismatch(
+                function.signature(),
+                self.arity(),
+                found,
+                line,
+                Some(function.name.line),
+            ),
+        }
+    }
+}
+
+impl UserFunction {
+    // The name followed by the parameters, e.g. `add(a, b)`.
+    fn signature(&self) -> String {
+        let parameters: Vec<String> = self
+            .parameters
+            .iter()
+            .map(|parameter| parameter.name.value.to_string())
+            .collect();
+
+        format!("{}({})", self.name.value, parameters.join(", "))
+    }
+
+    // Annotated parameters and return values are checked against the values they get, failing
+    // at `line`, the line of the call. The return value of a generator function is the generator,
+    // and the values its body returns are not checked.
+    //
+    // A return of a call in tail position, like `return loop(n - 1, acc)`, doesn't make the call
+    // but leaves it for this loop, which runs the called function in place of the one returning.
+    // Tail recursion runs in the same frame however deep it goes, while any other recursion
+    // still nests a call for each level, up to the maximum call depth.
+    fn call(
+        self: &Rc<Self>,
+        environment: &mut Environment,
+        arguments: Vec<Value>,
+        line: u32,
+    ) -> RuntimeResult<Value> {
+        self.check_arguments(&arguments, line)?;
+
+        if self.generator {
+            let generator = Value::Generator(Rc::new(Generator::new(self.clone(), arguments)));
+            check_annotation(
+                self.return_type,
+                &generator,
+                || format!("return value of '{}'", self.name.name()),
+                line,
+            )?;
+
+            return Ok(generator);
+        }
+
+        let result = self.run_tail_calls(environment, arguments, line);
+
+        // The trace is recorded while the frame of the last tail call is still there.
+        if result.is_err() {
+            environment.record_trace();
+        }
+        environment.exit_tail_calls();
+
+        result
+    }
+
+    // Run the body, and then the tail calls it returns one after another.
+    fn run_tail_calls(
+        self: &Rc<Self>,
+        environment: &mut Environment,
+        arguments: Vec<Value>,
+        line: u32,
+    ) -> RuntimeResult<Value> {
+        let mut function = self.clone();
+        let mut arguments = arguments;
+        let mut line = line;
+
+        loop {
+            let value = function.run(environment, arguments)?;
+
+            let Some(call) = environment.take_tail_call() else {
+                check_annotation(
+                    function.return_type,
+                    &value,
+                    || format!("return value of '{}'", function.name.name()),
+                    line,
+                )?;
+
+                return Ok(value);
+            };
+
+            call.function.check_arguments(&call.arguments, call.line)?;
+            environment.enter_tail_call(call.function.name.name(), call.line)?;
+            environment.file = call.function.file;
+
+            function = call.function;
+            arguments = call.arguments;
+            line = call.line;
+        }
+    }
+
+    fn check_arguments(&self, arguments: &[Value], line: u32) -> RuntimeResult<()> {
+        for (parameter, argument) in self.parameters.iter().zip(arguments) {
+            check_annotation(
+                parameter.annotation,
+                argument,
+                || {
+                    format!(
+                        "parameter '{}' of '{}'",
+                        parameter.name.name(),
+                        self.name.name()
+                    )
+                },
+                line,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    // Run the body with the parameters bound to `arguments`, giving the value it returned.
+    fn run(
+        self: &Rc<Self>,
+        environment: &mut Environment,
+        arguments: Vec<Value>,
+    ) -> RuntimeResult<Value> {
+        let hidden = environment.hide_scopes(&self.name, self.depth)?;
+        let globals = environment.enter_globals(&self.globals);
+
+        environment.push();
+        for (parameter, argument) in self.parameters.iter().zip(arguments) {
+            environment.define_annotated(
+                &parameter.name,
+                Some(argument),
+                true,
+                parameter.annotation,
+            );
+        }
+
+        let position = environment.enter_tail_position(Some(self));
+        let result = self.body.execute(environment);
+        environment.leave_tail_position(position);
+
+        environment.leave_globals(globals);
+        environment.restore_scopes(self.depth, hidden);
+
+        Ok(environment.finish_return(result?))
+    }
+}
+
+// Functions are equal only to themselves: two declarations with the same body are different
+// functions, while copies of a function value share the same declaration. Natives compare the
+// same way, so a native is equal to itself but not to a native of the same name defined again.
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Function::Native(val1), Function::Native(val2)) => Rc::ptr_eq(val1, val2),
+            (Function::User(val1), Function::User(val2)) => Rc::ptr_eq(val1, val2),
+            _ => false,
+        }
+    }
+}
+
+impl From<NativeFunction> for Function {
+    fn from(native: NativeFunction) -> Self {
+        Function::Native(Rc::new(native))
+    }
+}
+
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Function::Native(native) => write!(f, "<native fn {}>", native.signature()),
+            Function::User(function) => write!(f, "<fn {}>", function.signature()),
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use core::fmt;
+use std::{cell::RefCell, rc::Rc};
+
+use super::{
+    environment::{DeferredBlock, Scope},
+    function::UserFunction,
+    value::Value,
+};
+
+/// The value given by calling a `function*`. The body of the function runs a piece at a time:
+/// each time the generator is resumed, by its `next` method or by a for-in loop over it, the body
+/// continues from where it stopped until it reaches a `yield` or ends.
+///
+/// While paused, the generator keeps the scopes the body had open and the position of the
+/// `yield` it stopped at, so nothing runs twice. Like any function, it can only be resumed while
+/// the scope it was declared in is alive.
+pub struct Generator {
+    pub(crate) function: Rc<UserFunction>,
+    pub(crate) state: RefCell<State>,
+}
+
+pub(crate) enum State {
+    // Not started yet, holding the arguments of the call.
+    Created(Vec<Value>),
+    // Paused at a yield, with the scopes the body had open, the expressions its blocks deferred
+    // and the way back to the yield.
+    Suspended(Vec<Scope>, Vec<DeferredBlock>, Vec<Resume>),
+    Running,
+    // The body returned or failed, so there is nothing more to give.
+    Done,
+}
+
+// One statement the body was paused inside of. The innermost comes first, so that resuming pops
+// the outermost one first.
+pub(crate) enum Resume {
+    // At the statement with this index in a block.
+    Block(usize),
+    // In the branch of an if, the then branch if true.
+    If(bool),
+    // In the body of a while.
+    While,
+    // In the body of a for-in, with the elements still to come.
+    ForIn(Items),
+    // At the yield itself.
+    Yield,
+}
+
+// The elements a for-in loop goes through. Generators give theirs as they are resumed, the
+// elements of other values are taken when the loop starts.
+pub(crate) enum Items {
+    Values(std::vec::IntoIter<Value>),
+    Generator(Rc<Generator>),
+}
+
+impl Generator {
+    pub(crate) fn new(function: Rc<UserFunction>, arguments: Vec<Value>) -> Self {
+        Generator {
+            function,
+            state: RefCell::new(State::Created(arguments)),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        self.function.name.name()
+    }
+
+    // Whether the body has returned, after which the generator gives nothing more.
+    pub fn is_done(&self) -> bool {
+        matches!(*self.state.borrow(), State::Done)
+    }
+}
+
+impl fmt::Display for Generator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<generator {}>", self.name())
+    }
+}
@@ -0,0 +1,513 @@
+use std::{fmt, iter::Peekable, str::Chars};
+
+use super::{
+    object::Object,
+    value::{number_to_string, Value},
+};
+
+// Arrays and objects nested deeper than this are refused when reading and writing JSON, which
+// would otherwise recurse until the stack overflows.
+const MAX_DEPTH: usize = 512;
+
+pub struct JsonError {
+    pub message: String,
+}
+
+impl JsonError {
+    fn new(message: String) -> Self {
+        JsonError { message }
+    }
+}
+
+impl fmt::Debug for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+impl Value {
+    pub fn from_json(source: &str) -> Result<Value, JsonError> {
+        let mut reader = JsonReader::new(source);
+
+        reader.skip_whitespace();
+        let value = reader.value()?;
+        reader.skip_whitespace();
+
+        match reader.peek() {
+            None => Ok(value),
+            Some(chr) => Err(reader.error(format!("unexpected character '{}'", chr))),
+        }
+    }
+
+    // NaN and infinities have no JSON representation and are written as null.
+    pub fn to_json(&self, indent: usize) -> Result<String, JsonError> {
+        let mut result = String::new();
+        write_json(self, indent, 0, &mut result)?;
+        Ok(result)
+    }
+}
+
+struct JsonReader<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+    column: usize,
+    // Arrays and objects being read, one inside the other.
+    depth: usize,
+}
+
+impl<'a> JsonReader<'a> {
+    fn new(source: &'a str) -> Self {
+        JsonReader {
+            chars: source.chars().peekable(),
+            line: 1,
+            column: 1,
+            depth: 0,
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let chr = self.chars.next()?;
+
+        if chr == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+
+        Some(chr)
+    }
+
+    fn error(&self, message: String) -> JsonError {
+        JsonError::new(format!(
+            "{} at line {} column {}",
+            message, self.line, self.column
+        ))
+    }
+
+    fn unexpected(&mut self, expected: &str) -> JsonError {
+        match self.peek() {
+            Some(chr) => self.error(format!("expected {}, found '{}'", expected, chr)),
+            None => self.error(format!("expected {}, found end of input", expected)),
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), JsonError> {
+        if self.peek() == Some(expected) {
+            self.next();
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("'{}'", expected)))
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(' ' | '\t' | '\n' | '\r') = self.peek() {
+            self.next();
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, JsonError> {
+        match self.peek() {
+            Some('n') => self.keyword("null", Value::Null),
+            Some('t') => self.keyword("true", Value::Boolean(true)),
+            Some('f') => self.keyword("false", Value::Boolean(false)),
+            Some('"') => Ok(Value::String(self.string()?.into())),
+            Some('[') => self.nested(Self::array),
+            Some('{') => self.nested(Self::object),
+            Some('-' | '0'..='9') => self.number(),
+            _ => Err(self.unexpected("a value")),
+        }
+    }
+
+    // Read an array or object with `read`, refusing it past the maximum depth.
+    fn nested(
+        &mut self,
+        read: fn(&mut Self) -> Result<Value, JsonError>,
+    ) -> Result<Value, JsonError> {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error(format!("nesting deeper than {} levels", MAX_DEPTH)));
+        }
+
+        self.depth += 1;
+        let value = read(self);
+        self.depth -= 1;
+
+        value
+    }
+
+    fn keyword(&mut self, keyword: &str, value: Value) -> Result<Value, JsonError> {
+        for expected in keyword.chars() {
+            if self.peek() != Some(expected) {
+                return Err(self.unexpected(&format!("'{}'", keyword)));
+            }
+            self.next();
+        }
+
+        Ok(value)
+    }
+
+    fn digits(&mut self, number: &mut String) -> Result<(), JsonError> {
+        if !matches!(self.peek(), Some('0'..='9')) {
+            return Err(self.unexpected("a digit"));
+        }
+
+        while let Some(chr @ '0'..='9') = self.peek() {
+            number.push(chr);
+            self.next();
+        }
+
+        Ok(())
+    }
+
+    fn number(&mut self) -> Result<Value, JsonError> {
+        let mut number = String::new();
+
+        if self.peek() == Some('-') {
+            number.push('-');
+            self.next();
+        }
+
+        if self.peek() == Some('0') {
+            number.push('0');
+            self.next();
+        } else {
+            self.digits(&mut number)?;
+        }
+
+        if self.peek() == Some('.') {
+            number.push('.');
+            self.next();
+            self.digits(&mut number)?;
+        }
+
+        if let Some(chr @ ('e' | 'E')) = self.peek() {
+            number.push(chr);
+            self.next();
+
+            if let Some(sign @ ('+' | '-')) = self.peek() {
+                number.push(sign);
+                self.next();
+            }
+
+            self.digits(&mut number)?;
+        }
+
+        number
+            .parse()
+            .map(Value::Number)
+            .map_err(|_| self.error(format!("invalid number '{}'", number)))
+    }
+
+    fn hex_escape(&mut self) -> Result<u32, JsonError> {
+        let mut code = 0;
+
+        for _ in 0..4 {
+            match self.peek().and_then(|chr| chr.to_digit(16)) {
+                Some(digit) => {
+                    code = code * 16 + digit;
+                    self.next();
+                }
+                None => return Err(self.unexpected("a hexadecimal digit")),
+            }
+        }
+
+        Ok(code)
+    }
+
+    fn unicode_escape(&mut self) -> Result<char, JsonError> {
+        let code = self.hex_escape()?;
+
+        let code = if (0xD800..0xDC00).contains(&code) {
+            self.expect('\\')?;
+            self.expect('u')?;
+            let low = self.hex_escape()?;
+
+            if !(0xDC00..0xE000).contains(&low) {
+                return Err(self.error("invalid low surrogate".to_string()));
+            }
+
+            0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00)
+        } else {
+            code
+        };
+
+        char::from_u32(code).ok_or_else(|| self.error("invalid unicode escape".to_string()))
+    }
+
+    fn string(&mut self) -> Result<String, JsonError> {
+        self.expect('"')?;
+
+        let mut string = String::new();
+
+        loop {
+            match self.next() {
+                Some('"') => return Ok(string),
+                Some('\\') => match self.next() {
+                    Some('"') => string.push('"'),
+                    Some('\\') => string.push('\\'),
+                    Some('/') => string.push('/'),
+                    Some('b') => string.push('\u{8}'),
+                    Some('f') => string.push('\u{c}'),
+                    Some('n') => string.push('\n'),
+                    Some('r') => string.push('\r'),
+                    Some('t') => string.push('\t'),
+                    Some('u') => string.push(self.unicode_escape()?),
+                    Some(chr) => {
+                        return Err(self.error(format!("invalid escape character '{}'", chr)))
+                    }
+                    None => return Err(self.error("unterminated string".to_string())),
+                },
+                Some(chr) if (chr as u32) < 0x20 => {
+                    return Err(self.error("control character in string".to_string()))
+                }
+                Some(chr) => string.push(chr),
+                None => return Err(self.error("unterminated string".to_string())),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, JsonError> {
+        self.expect('[')?;
+        self.skip_whitespace();
+
+        let mut elements = Vec::new();
+
+        if self.peek() == Some(']') {
+            self.next();
+            return Ok(Value::Array(elements.into()));
+        }
+
+        loop {
+            self.skip_whitespace();
+            elements.push(self.value()?);
+            self.skip_whitespace();
+
+            match self.peek() {
+                Some(',') => {
+                    self.next();
+                }
+                Some(']') => {
+                    self.next();
+                    return Ok(Value::Array(elements.into()));
+                }
+                _ => return Err(self.unexpected("',' or ']'")),
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Value, JsonError> {
+        self.expect('{')?;
+        self.skip_whitespace();
+
+        let mut object = Object::new();
+
+        if self.peek() == Some('}') {
+            self.next();
+            return Ok(Value::Object(object));
+        }
+
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('"') {
+                return Err(self.unexpected("a string key"));
+            }
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            self.skip_whitespace();
+            object.insert(key.into(), self.value()?);
+            self.skip_whitespace();
+
+            match self.peek() {
+                Some(',') => {
+                    self.next();
+                }
+                Some('}') => {
+                    self.next();
+                    return Ok(Value::Object(object));
+                }
+                _ => return Err(self.unexpected("',' or '}'")),
+            }
+        }
+    }
+}
+
+fn write_string(string: &str, result: &mut String) {
+    result.push('"');
+    for chr in string.chars() {
+        match chr {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            '\u{8}' => result.push_str("\\b"),
+            '\u{c}' => result.push_str("\\f"),
+            chr if (chr as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", chr as u32)),
+            chr => result.push(chr),
+        }
+    }
+    result.push('"');
+}
+
+fn write_newline(indent: usize, depth: usize, result: &mut String) {
+    if indent > 0 {
+        result.push('\n');
+        result.push_str(&" ".repeat(indent * depth));
+    }
+}
+
+fn write_json(
+    value: &Value,
+    indent: usize,
+    depth: usize,
+    result: &mut String,
+) -> Result<(), JsonError> {
+    match value {
+        Value::Null => result.push_str("null"),
+        Value::Boolean(boolean) => result.push_str(&boolean.to_string()),
+        Value::Number(num) if num.is_finite() => result.push_str(&number_to_string(*num)),
+        Value::Number(_) => result.push_str("null"),
+        Value::String(string) => write_string(string, result),
+        Value::Array(_) | Value::Object(_) if depth == MAX_DEPTH => {
+            return Err(JsonError::new(format!(
+                "nesting deeper than {} levels",
+                MAX_DEPTH
+            )))
+        }
+        Value::Array(array) => {
+            result.push('[');
+            for (i, element) in array.iter().enumerate() {
+                if i != 0 {
+                    result.push(',');
+                }
+                write_newline(indent, depth + 1, result);
+                write_json(element, indent, depth + 1, result)?;
+            }
+            if !array.is_empty() {
+                write_newline(indent, depth, result);
+            }
+            result.push(']');
+        }
+        Value::Object(object) => {
+            result.push('{');
+            for (i, (key, element)) in object.iter().enumerate() {
+                if i != 0 {
+                    result.push(',');
+                }
+                write_newline(indent, depth + 1, result);
+                // Keys are always strings in JSON.
+                write_string(&key.to_string(), result);
+                result.push(':');
+                if indent > 0 {
+                    result.push(' ');
+                }
+                write_json(element, indent, depth + 1, result)?;
+            }
+            if !object.is_empty() {
+                write_newline(indent, depth, result);
+            }
+            result.push('}');
+        }
+        value @ (Value::Function(_) | Value::EnumVariant(_) | Value::Generator(_)) => {
+            return Err(JsonError::new(format!("cannot convert {} to JSON", value)))
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_round_trip() {
+        let source = r#"{"name":"notjs","tags":["a","b\n"],"nested":{"depth":2,"items":[1,2.5,-3e2,null,true,false,{}]}}"#;
+        let value = Value::from_json(source).unwrap();
+        assert_eq!(
+            value.to_json(0).unwrap(),
+            r#"{"name":"notjs","tags":["a","b\n"],"nested":{"depth":2,"items":[1,2.5,-300,null,true,false,{}]}}"#
+        );
+        assert_eq!(Value::from_json(&value.to_json(0).unwrap()).unwrap(), value);
+    }
+
+    #[test]
+    fn test_json_indent() {
+        let value = Value::from_json(r#"{"a":[1,2],"b":[],"c":{}}"#).unwrap();
+        assert_eq!(
+            value.to_json(2).unwrap(),
+            "{\n  \"a\": [\n    1,\n    2\n  ],\n  \"b\": [],\n  \"c\": {}\n}"
+        );
+    }
+
+    #[test]
+    fn test_json_string_escapes() {
+        let value = Value::from_json(r#""quote \" slash \/ é 😀""#).unwrap();
+        assert_eq!(
+            value,
+            Value::String("quote \" slash / \u{e9} \u{1f600}".into())
+        );
+    }
+
+    #[test]
+    fn test_json_malformed() {
+        let cases = [
+            (
+                "",
+                "expected a value, found end of input at line 1 column 1",
+            ),
+            (
+                "[1, 2",
+                "expected ',' or ']', found end of input at line 1 column 6",
+            ),
+            ("{\"a\" 1}", "expected ':', found '1' at line 1 column 6"),
+            (
+                "{\n  \"a\": tru\n}",
+                "expected 'true', found '\n' at line 2 column 11",
+            ),
+            ("[1] 2", "unexpected character '2' at line 1 column 5"),
+            (
+                "{a: 1}",
+                "expected a string key, found 'a' at line 1 column 2",
+            ),
+            ("01", "unexpected character '1' at line 1 column 2"),
+            ("\"abc", "unterminated string at line 1 column 5"),
+        ];
+
+        for (source, message) in cases {
+            assert_eq!(Value::from_json(source).unwrap_err().message, message);
+        }
+    }
+
+    #[test]
+    fn test_json_depth() {
+        let nested = |depth| "[".repeat(depth) + &"]".repeat(depth);
+        let value = Value::from_json(&nested(MAX_DEPTH)).unwrap();
+        assert_eq!(value.to_json(0).unwrap(), nested(MAX_DEPTH));
+
+        assert_eq!(
+            Value::from_json(&nested(100_000)).unwrap_err().message,
+            "nesting deeper than 512 levels at line 1 column 513"
+        );
+
+        let deeper = Value::Array(vec![value].into());
+        assert_eq!(
+            deeper.to_json(2).unwrap_err().message,
+            "nesting deeper than 512 levels"
+        );
+    }
+}
@@ -1,6 +1,21 @@
+pub mod array;
+pub mod convert;
+pub mod coverage;
+pub mod debug;
+pub mod dot;
 pub mod environment;
 pub mod expressions;
-pub mod resolver;
+pub mod format;
+pub mod function;
+pub mod generator;
+pub mod json;
+pub mod object;
+pub mod pretty;
+pub mod profile;
+pub(crate) mod resolver;
+pub(crate) mod serialize;
+pub mod source;
 pub mod statements;
 pub mod token;
+pub mod typecheck;
 pub mod value;
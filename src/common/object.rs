@@ -0,0 +1,223 @@
+use core::fmt;
+use std::{collections::HashMap, rc::Rc};
+
+use super::value::{drop_nested, number_to_string, EnumVariant, Value};
+
+/// The key of an object property. Strings, numbers, booleans and enum variants can be keys, and
+/// keys of different types are different, so `obj[1]` and `obj["1"]` are separate properties.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ValueKey {
+    String(Rc<str>),
+    // The bits of the number, which is never NaN and never `-0`, so that equal numbers give
+    // equal keys.
+    Number(u64),
+    Boolean(bool),
+    EnumVariant(Rc<EnumVariant>),
+}
+
+impl ValueKey {
+    /// The key for `value`. Only strings, numbers other than NaN, booleans and enum variants can
+    /// be keys, for other values the error describes the value, e.g. `an array`.
+    pub fn new(value: &Value) -> Result<Self, &'static str> {
+        match value {
+            Value::String(string) => Ok(ValueKey::String(string.clone())),
+            Value::Number(num) if num.is_nan() => Err("NaN"),
+            // `-0 + 0` is `0`, which gives `0` and `-0` the same key.
+            Value::Number(num) => Ok(ValueKey::Number((num + 0.0).to_bits())),
+            Value::Boolean(bool) => Ok(ValueKey::Boolean(*bool)),
+            Value::EnumVariant(variant) => Ok(ValueKey::EnumVariant(variant.clone())),
+            Value::Null => Err("null"),
+            Value::Array(_) => Err("an array"),
+            Value::Object(_) => Err("an object"),
+            Value::Function(_) => Err("a function"),
+            Value::Generator(_) => Err("a generator"),
+        }
+    }
+
+    pub fn to_value(&self) -> Value {
+        match self {
+            ValueKey::String(string) => Value::String(string.clone()),
+            ValueKey::Number(bits) => Value::Number(f64::from_bits(*bits)),
+            ValueKey::Boolean(bool) => Value::Boolean(*bool),
+            ValueKey::EnumVariant(variant) => Value::EnumVariant(variant.clone()),
+        }
+    }
+}
+
+impl From<&str> for ValueKey {
+    fn from(name: &str) -> Self {
+        ValueKey::String(name.into())
+    }
+}
+
+impl From<String> for ValueKey {
+    fn from(name: String) -> Self {
+        ValueKey::String(name.into())
+    }
+}
+
+// Strings are shown without quotes, as property names are.
+impl fmt::Display for ValueKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValueKey::String(string) => write!(f, "{}", string),
+            ValueKey::Number(bits) => write!(f, "{}", number_to_string(f64::from_bits(*bits))),
+            ValueKey::Boolean(bool) => write!(f, "{}", bool),
+            ValueKey::EnumVariant(variant) => write!(f, "{}", variant),
+        }
+    }
+}
+
+// Properties are kept in insertion order, which is also the order they are displayed in, and
+// found through the position of each key.
+//
+// As with `Array`, clones share the properties, which are copied the first time a shared object is
+// changed.
+#[derive(Clone, Default)]
+pub struct Object {
+    properties: Rc<Properties>,
+    frozen: bool,
+}
+
+#[derive(Clone, Default)]
+struct Properties {
+    entries: Vec<(ValueKey, Value)>,
+    positions: HashMap<ValueKey, usize>,
+}
+
+impl Object {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, key: &ValueKey) -> Option<&Value> {
+        let position = *self.properties.positions.get(key)?;
+        Some(&self.properties.entries[position].1)
+    }
+
+    // The property `name`, as read with a dot.
+    pub fn property(&self, name: &str) -> Option<&Value> {
+        self.get(&ValueKey::from(name))
+    }
+
+    // Replacing an existing property keeps its original position.
+    pub fn insert(&mut self, key: ValueKey, value: Value) {
+        let properties = Rc::make_mut(&mut self.properties);
+        match properties.positions.get(&key) {
+            Some(&position) => properties.entries[position].1 = value,
+            None => {
+                properties
+                    .positions
+                    .insert(key.clone(), properties.entries.len());
+                properties.entries.push((key, value));
+            }
+        }
+    }
+
+    pub fn get_mut(&mut self, key: &ValueKey) -> Option<&mut Value> {
+        let position = *self.properties.positions.get(key)?;
+        Some(&mut Rc::make_mut(&mut self.properties).entries[position].1)
+    }
+
+    // Remove a property, giving its value if there was one. Later properties keep their order.
+    pub fn remove(&mut self, key: &ValueKey) -> Option<Value> {
+        if !self.properties.positions.contains_key(key) {
+            return None;
+        }
+
+        let properties = Rc::make_mut(&mut self.properties);
+        let position = properties.positions.remove(key)?;
+        for (key, _) in &properties.entries[position + 1..] {
+            if let Some(later) = properties.positions.get_mut(key) {
+                *later -= 1;
+            }
+        }
+        Some(properties.entries.remove(position).1)
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    pub fn freeze(&mut self) {
+        self.frozen = true;
+    }
+
+    // The values of the properties, leaving the object empty, if no other object shares them.
+    pub(super) fn take_unshared(&mut self) -> Vec<Value> {
+        Rc::get_mut(&mut self.properties)
+            .map(|properties| {
+                properties.positions.clear();
+                properties
+                    .entries
+                    .drain(..)
+                    .map(|(_, value)| value)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    // The form given by `Debug`, or by `Display` without `debug`, of an object nested `depth`
+    // levels deep.
+    pub(super) fn write(&self, f: &mut fmt::Formatter, debug: bool, depth: usize) -> fmt::Result {
+        write!(f, "{{")?;
+        for (i, (key, value)) in self.iter().enumerate() {
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}: ", key)?;
+            value.write(f, debug, depth + 1)?;
+        }
+        write!(f, "}}")
+    }
+
+    pub fn len(&self) -> usize {
+        self.properties.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.properties.entries.is_empty()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&ValueKey, &mut Value)> {
+        Rc::make_mut(&mut self.properties)
+            .entries
+            .iter_mut()
+            .map(|(key, value)| (&*key, value))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&ValueKey, &Value)> {
+        self.properties
+            .entries
+            .iter()
+            .map(|(key, value)| (key, value))
+    }
+}
+
+// Being frozen does not affect equality.
+impl PartialEq for Object {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(key, value)| other.get(key) == Some(value))
+    }
+}
+
+impl Drop for Object {
+    fn drop(&mut self) {
+        drop_nested(self.take_unshared());
+    }
+}
+
+impl fmt::Debug for Object {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, true, 0)
+    }
+}
+
+impl fmt::Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write(f, false, 0)
+    }
+}
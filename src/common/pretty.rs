@@ -0,0 +1,117 @@
+use super::value::Value;
+
+// Width past which arrays and objects are broken over several lines.
+const WIDTH: usize = 80;
+// Arrays and objects nested deeper than this are shown as `[...]` or `{...}`.
+const MAX_DEPTH: usize = 6;
+const INDENT: usize = 2;
+
+impl Value {
+    // A multi-line form of the debug representation, for reading nested arrays and objects.
+    // Arrays and objects stay on one line while they fit.
+    pub fn pretty(&self) -> String {
+        pretty(self, 0, 0, 0)
+    }
+}
+
+// `indent` is the indentation of the line the value is on, and `column` where the value starts
+// on it.
+fn pretty(value: &Value, indent: usize, column: usize, depth: usize) -> String {
+    let (open, close, items) = match value {
+        Value::Array(arr) if depth >= MAX_DEPTH && !arr.is_empty() => return "[...]".to_string(),
+        Value::Object(obj) if depth >= MAX_DEPTH && !obj.is_empty() => return "{...}".to_string(),
+        Value::Array(arr) => (
+            "[",
+            "]",
+            arr.iter()
+                .map(|value| pretty(value, indent + INDENT, indent + INDENT, depth + 1))
+                .collect::<Vec<String>>(),
+        ),
+        Value::Object(obj) => (
+            "{",
+            "}",
+            obj.iter()
+                .map(|(key, value)| {
+                    let key = key.to_string();
+                    let column = indent + INDENT + key.len() + 2;
+                    format!(
+                        "{}: {}",
+                        key,
+                        pretty(value, indent + INDENT, column, depth + 1)
+                    )
+                })
+                .collect(),
+        ),
+        value => return format!("{:?}", value),
+    };
+
+    let inline = format!("{}{}{}", open, items.join(", "), close);
+
+    if column + inline.len() <= WIDTH && !inline.contains('\n') {
+        return inline;
+    }
+
+    let padding = " ".repeat(indent + INDENT);
+    let mut result = open.to_string() + "\n";
+
+    for item in items {
+        result += &padding;
+        result += &item;
+        result += ",\n";
+    }
+
+    result + &" ".repeat(indent) + close
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn json(source: &str) -> Value {
+        Value::from_json(source).unwrap()
+    }
+
+    #[test]
+    fn test_pretty_inline() {
+        assert_eq!(
+            json(r#"[1, "a\nb", true, null]"#).pretty(),
+            r#"[1, "a\nb", true, Null]"#
+        );
+        assert_eq!(json(r#"{"a": [], "b": {}}"#).pretty(), "{a: [], b: {}}");
+        assert_eq!(json(r#""text""#).pretty(), r#""text""#);
+        // The compact form is unchanged.
+        assert_eq!(json(r#"["a", ["b"]]"#).to_string(), "[a, [b]]");
+    }
+
+    #[test]
+    fn test_pretty_multi_line() {
+        let value = json(
+            r#"{"name": "a fairly long name for a value", "tags": ["first", "second", "third"],
+                "matrix": [[1, 2, 3], [4, 5, 6], [7, 8, 9], [10, 11, 12], [13, 14, 15], [16, 17, 18]]}"#,
+        );
+        assert_eq!(
+            value.pretty(),
+            r#"{
+  name: "a fairly long name for a value",
+  tags: ["first", "second", "third"],
+  matrix: [
+    [1, 2, 3],
+    [4, 5, 6],
+    [7, 8, 9],
+    [10, 11, 12],
+    [13, 14, 15],
+    [16, 17, 18],
+  ],
+}"#
+        );
+        assert_eq!(format!("{:#}", value), value.pretty());
+    }
+
+    #[test]
+    fn test_pretty_depth_limit() {
+        assert_eq!(
+            json("[[[[[[[1]]]]]], [[[[[[]]]]]], [[[[[{}]]]]], [[[[[{\"a\": 1}]]]]]]").pretty(),
+            "[[[[[[[...]]]]]], [[[[[[]]]]]], [[[[[{}]]]]], [[[[[{...}]]]]]]"
+        );
+    }
+}
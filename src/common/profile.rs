@@ -0,0 +1,181 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    time::{Duration, Instant},
+};
+
+/// Name of the entry for the code of a program outside of any function.
+pub const TOP_LEVEL: &str = "<top level>";
+
+/// How often a function was called and how long it ran.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FunctionProfile {
+    pub name: String,
+    pub calls: usize,
+    /// Time from entering the function until leaving it. Recursive calls run within an outer
+    /// call of the same function, so only the outermost one is counted.
+    pub total: Duration,
+    /// Time spent in the function itself, without the functions it called.
+    pub self_time: Duration,
+}
+
+/// Time spent in each function while profiling, as given by
+/// [`Interpreter::take_profile`](crate::Interpreter::take_profile). Functions are sorted by
+/// their self time, the hot spots first.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Profile {
+    pub functions: Vec<FunctionProfile>,
+}
+
+impl Profile {
+    /// The entry of the function called `name`, or of the top level with [`TOP_LEVEL`].
+    pub fn get(&self, name: &str) -> Option<&FunctionProfile> {
+        self.functions.iter().find(|function| function.name == name)
+    }
+}
+
+fn milliseconds(duration: Duration) -> String {
+    format!("{:.3}ms", duration.as_secs_f64() * 1000.0)
+}
+
+// A table with a row for each function.
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let width = self
+            .functions
+            .iter()
+            .map(|function| function.name.chars().count())
+            .chain(["function".len()])
+            .max()
+            .unwrap_or_default();
+
+        writeln!(
+            f,
+            "{:<width$}  {:>8}  {:>12}  {:>12}",
+            "function", "calls", "total", "self"
+        )?;
+        for function in &self.functions {
+            writeln!(
+                f,
+                "{:<width$}  {:>8}  {:>12}  {:>12}",
+                function.name,
+                function.calls,
+                milliseconds(function.total),
+                milliseconds(function.self_time)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+// Times calls as they are entered and left.
+#[derive(Default)]
+pub(crate) struct Profiler {
+    functions: HashMap<String, FunctionProfile>,
+    // Calls in progress, innermost last.
+    stack: Vec<Active>,
+}
+
+struct Active {
+    name: String,
+    start: Instant,
+    // Time spent in the calls made from this one.
+    children: Duration,
+}
+
+impl Profiler {
+    pub fn enter(&mut self, name: &str) {
+        self.stack.push(Active {
+            name: name.to_string(),
+            start: Instant::now(),
+            children: Duration::ZERO,
+        });
+    }
+
+    pub fn exit(&mut self) {
+        let Some(active) = self.stack.pop() else {
+            return;
+        };
+        let elapsed = active.start.elapsed();
+
+        if let Some(caller) = self.stack.last_mut() {
+            caller.children += elapsed;
+        }
+        let recursive = self.stack.iter().any(|caller| caller.name == active.name);
+
+        let function = self
+            .functions
+            .entry(active.name)
+            .or_insert_with_key(|name| FunctionProfile {
+                name: name.clone(),
+                ..FunctionProfile::default()
+            });
+        function.calls += 1;
+        function.self_time += elapsed.saturating_sub(active.children);
+        if !recursive {
+            function.total += elapsed;
+        }
+    }
+
+    // The profile gathered so far, starting over for the next one.
+    pub fn take(&mut self) -> Profile {
+        let mut functions: Vec<FunctionProfile> = self.functions.drain().map(|(_, f)| f).collect();
+        functions.sort_by(|a, b| {
+            b.self_time
+                .cmp(&a.self_time)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+
+        Profile { functions }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profiler() {
+        let mut profiler = Profiler::default();
+        profiler.enter(TOP_LEVEL);
+        profiler.enter("f");
+        profiler.enter("f");
+        profiler.enter("g");
+        profiler.exit();
+        profiler.exit();
+        profiler.exit();
+        profiler.exit();
+        // Unbalanced exits are ignored.
+        profiler.exit();
+
+        let profile = profiler.take();
+        let top = profile.get(TOP_LEVEL).unwrap();
+        let f = profile.get("f").unwrap();
+        let g = profile.get("g").unwrap();
+
+        assert_eq!((top.calls, f.calls, g.calls), (1, 2, 1));
+        assert!(f.total <= top.total);
+        assert!(g.total <= f.total);
+        assert_eq!(top.self_time + f.self_time + g.self_time, top.total);
+        assert_eq!(profiler.take(), Profile::default());
+    }
+
+    #[test]
+    fn test_display() {
+        let profile = Profile {
+            functions: vec![FunctionProfile {
+                name: "fib".to_string(),
+                calls: 3,
+                total: Duration::from_micros(1500),
+                self_time: Duration::from_micros(250),
+            }],
+        };
+
+        assert_eq!(
+            profile.to_string(),
+            "function     calls         total          self\n\
+             fib              3       1.500ms       0.250ms\n"
+        );
+    }
+}
@@ -1,18 +1,82 @@
 #![allow(dead_code)]
 use std::collections::HashMap;
 
-use crate::error::parse::{ParseError, ParseResult};
+use crate::error::parse::{ErrorCode, ParseError, ParseResult};
 
-use super::token::Token;
+use super::{
+    environment::{self, Environment, Scope},
+    expressions::{Expression, Pattern, PostfixOperator},
+    statements::Statement,
+    token::Token,
+};
 
+#[derive(Clone)]
 pub struct Resolver {
-    scopes: Vec<HashMap<String, bool>>,
+    scopes: Vec<HashMap<String, Variable>>,
+    // Whether the body of a generator function is being resolved, the only place yields can be.
+    generator: bool,
+}
+
+#[derive(Clone, Copy)]
+struct Variable {
+    mutable: bool,
+    // Line of the declaration, `None` for the globals defined by the host and the prelude.
+    line: Option<u32>,
 }
 
 impl Resolver {
     pub fn new() -> Self {
         Self {
             scopes: vec![HashMap::new()],
+            generator: false,
+        }
+    }
+
+    // Seed the scopes with the names already defined in a runtime environment, such as natives.
+    pub fn from_environment(environment: &Environment) -> Self {
+        Self {
+            scopes: environment
+                .environment
+                .iter()
+                .map(|scope| {
+                    scope
+                        .iter()
+                        .map(|(name, variable)| (name.clone(), Variable::from(variable)))
+                        .collect()
+                })
+                .collect(),
+            generator: false,
+        }
+    }
+
+    // Whether names are being declared in the outermost scope.
+    pub fn is_global(&self) -> bool {
+        self.scopes.len() == 1
+    }
+
+    // A resolver that only knows the names of the outermost scope.
+    pub fn globals(&self) -> Self {
+        Self {
+            scopes: vec![self.scopes[0].clone()],
+            generator: false,
+        }
+    }
+
+    // Declare in the outermost scope every global name known to `other`.
+    pub fn extend_globals(&mut self, other: Resolver) {
+        if let Some(globals) = other.scopes.into_iter().next() {
+            self.scopes[0].extend(globals);
+        }
+    }
+
+    // Only knows the names in `scope`, such as the builtins a module starts out with.
+    pub fn from_scope(scope: &Scope) -> Self {
+        Self {
+            scopes: vec![scope
+                .iter()
+                .map(|(name, variable)| (name.clone(), Variable::from(variable)))
+                .collect()],
+            generator: false,
         }
     }
 
@@ -25,10 +89,13 @@ impl Resolver {
     }
 
     pub fn declare(&mut self, identifier: Token, mutable: bool) -> usize {
-        self.scopes
-            .last_mut()
-            .unwrap()
-            .insert(identifier.value.to_string(), mutable);
+        self.scopes.last_mut().unwrap().insert(
+            identifier.name().to_string(),
+            Variable {
+                mutable,
+                line: Some(identifier.line),
+            },
+        );
 
         self.scopes.len() - 1
     }
@@ -36,37 +103,299 @@ impl Resolver {
     // Search for the identifier in the scopes, starting from the innermost scope and return the scope index.
     pub fn define(&mut self, identifier: Token) -> ParseResult<usize> {
         for (index, scope) in self.scopes.iter().enumerate().rev() {
-            if scope.contains_key(identifier.value.to_string().as_str()) {
-                if let Some(mutable) = scope.get(identifier.value.to_string().as_str()) {
-                    if *mutable {
-                        return Ok(index);
-                    } else {
-                        return Err(ParseError::new_single(format!(
-                            "Cannot reassign immutable variable '{}' at line {}.",
-                            identifier.value, identifier.line
-                        )));
-                    }
+            if let Some(variable) = scope.get(identifier.name()) {
+                if variable.mutable {
+                    return Ok(index);
                 }
+
+                let message = match variable.line {
+                    Some(line) => format!(
+                        "Cannot reassign '{}' (declared const at line {})",
+                        identifier.value, line
+                    ),
+                    None => format!("Cannot reassign global '{}'", identifier.value),
+                };
+                return Err(ParseError::new_single(
+                    ErrorCode::ImmutableVariable,
+                    Some(identifier.line),
+                    message,
+                )
+                .with_found(identifier.name()));
             }
         }
 
-        Err(ParseError::new_single(format!(
-            "Undeclared variable '{}' at line {}.",
-            identifier.value, identifier.line
-        )))
+        Err(undeclared(&identifier))
     }
 
     // Check if the identifier is in the scopes, starting from the innermost scope.
     pub fn resolve(&mut self, identifier: Token) -> ParseResult<()> {
         for scope in self.scopes.iter().rev() {
-            if scope.contains_key(identifier.value.to_string().as_str()) {
+            if scope.contains_key(identifier.name()) {
                 return Ok(());
             }
         }
 
-        Err(ParseError::new_single(format!(
-            "Undeclared variable '{}' at line {}.",
-            identifier.value, identifier.line
-        )))
+        Err(undeclared(&identifier))
+    }
+
+    // Whether `name` refers to a global defined by the host or the prelude, rather than to a
+    // variable of the program.
+    pub fn is_builtin(&self, name: &str) -> bool {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .is_some_and(|variable| variable.line.is_none())
+    }
+}
+
+impl From<&environment::Variable> for Variable {
+    fn from(variable: &environment::Variable) -> Self {
+        Self {
+            mutable: variable.mutable,
+            line: variable.line,
+        }
+    }
+}
+
+fn undeclared(identifier: &Token) -> ParseError {
+    ParseError::new_single(
+        ErrorCode::UndeclaredVariable,
+        Some(identifier.line),
+        format!("Undeclared variable '{}'", identifier.value),
+    )
+    .with_found(identifier.name())
+}
+
+// Resolution of a program that was not parsed from source, such as one read from JSON, following
+// the same rules as the parser. `builtins` are the names a module imported with a namespace
+// starts out with.
+impl Resolver {
+    pub fn resolve_statements(
+        &mut self,
+        statements: &[Statement],
+        builtins: &Resolver,
+    ) -> ParseResult<()> {
+        statements
+            .iter()
+            .try_for_each(|statement| self.resolve_statement(statement, builtins))
+    }
+
+    fn resolve_statement(&mut self, statement: &Statement, builtins: &Resolver) -> ParseResult<()> {
+        match statement {
+            Statement::Block(block) => {
+                self.push();
+                let result = self.resolve_statements(&block.statements, builtins);
+                self.pop();
+                result
+            }
+            Statement::Variable(declaration) => {
+                self.declare(declaration.identifier.clone(), declaration.mutable);
+                match declaration.initializer {
+                    Some(ref initializer) => self.resolve_expression(initializer),
+                    None => Ok(()),
+                }
+            }
+            Statement::Expression(statement) => self.resolve_expression(&statement.expression),
+            Statement::Print(statement) => self.resolve_expression(&statement.expression),
+            Statement::If(statement) => {
+                self.resolve_expression(&statement.condition)?;
+                self.resolve_statement(&statement.then_branch, builtins)?;
+                match statement.else_branch {
+                    Some(ref else_branch) => self.resolve_statement(else_branch, builtins),
+                    None => Ok(()),
+                }
+            }
+            Statement::While(statement) => {
+                self.resolve_expression(&statement.condition)?;
+                self.resolve_statement(&statement.body, builtins)
+            }
+            Statement::ForIn(statement) => {
+                self.resolve_expression(&statement.iterable)?;
+                self.push();
+                self.declare(statement.identifier.clone(), statement.mutable);
+                let result = self.resolve_statement(&statement.body, builtins);
+                self.pop();
+                result
+            }
+            Statement::Return(statement) => match statement.value {
+                Some(ref value) => self.resolve_expression(value),
+                None => Ok(()),
+            },
+            Statement::Yield(statement) => {
+                if !self.generator {
+                    return Err(ParseError::new_single(
+                        ErrorCode::MisplacedYield,
+                        Some(statement.line),
+                        "Yields are only allowed in generator functions, declared with function*"
+                            .to_string(),
+                    ));
+                }
+
+                match statement.value {
+                    Some(ref value) => self.resolve_expression(value),
+                    None => Ok(()),
+                }
+            }
+            Statement::Defer(statement) => self.resolve_expression(&statement.expression),
+            Statement::Assert(statement) => self.resolve_expression(&statement.condition),
+            Statement::Function(declaration) => {
+                self.declare(declaration.name.clone(), false);
+                self.push();
+                for parameter in &declaration.parameters {
+                    self.declare(parameter.name.clone(), true);
+                }
+                let enclosing = std::mem::replace(&mut self.generator, declaration.generator);
+                let result = self.resolve_statement(&declaration.body, builtins);
+                self.generator = enclosing;
+                self.pop();
+                result
+            }
+            Statement::Enum(declaration) => {
+                self.declare(declaration.name.clone(), false);
+                Ok(())
+            }
+            Statement::Test(declaration) => {
+                if !self.is_global() {
+                    return Err(ParseError::new_single(
+                        ErrorCode::MisplacedTest,
+                        Some(declaration.name.line),
+                        "Tests are only allowed at the top level, found one".to_string(),
+                    ));
+                }
+
+                self.resolve_statement(&declaration.body, builtins)
+            }
+            Statement::Import(import) => {
+                if !self.is_global() {
+                    return Err(ParseError::new_single(
+                        ErrorCode::MisplacedImport,
+                        Some(import.path.line),
+                        "Imports are only allowed at the top level, found one".to_string(),
+                    ));
+                }
+
+                match import.namespace {
+                    Some(ref namespace) => {
+                        builtins
+                            .clone()
+                            .resolve_statements(&import.statements, builtins)?;
+                        self.declare(namespace.clone(), false);
+                    }
+                    None => {
+                        let mut module = self.globals();
+                        module.resolve_statements(&import.statements, builtins)?;
+                        self.extend_globals(module);
+                    }
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    fn resolve_expression(&mut self, expression: &Expression) -> ParseResult<()> {
+        match expression {
+            Expression::Assignment(assignment) => {
+                self.define(assignment.identifier.clone())?;
+                self.resolve_expression(&assignment.value)
+            }
+            Expression::PropertyAssignment(assignment) => {
+                if assignment.path.is_empty() {
+                    return Err(ParseError::new_single(
+                        ErrorCode::InvalidAssignmentTarget,
+                        Some(assignment.operator.line),
+                        "Property assignment without a property".to_string(),
+                    ));
+                }
+
+                self.define(assignment.identifier.clone())?;
+                for (operator, _) in &assignment.path {
+                    if let PostfixOperator::Index(index) = operator {
+                        self.resolve_expression(index)?;
+                    }
+                }
+                self.resolve_expression(&assignment.value)
+            }
+            Expression::Conditional(conditional) => {
+                self.resolve_expression(&conditional.condition)?;
+                self.resolve_expression(&conditional.then_branch)?;
+                self.resolve_expression(&conditional.else_branch)
+            }
+            Expression::Binary(binary) => {
+                let (chain, left) = binary.chain();
+                self.resolve_expression(left)?;
+                chain
+                    .iter()
+                    .rev()
+                    .try_for_each(|binary| self.resolve_expression(&binary.right))
+            }
+            Expression::Unary(unary) => self.resolve_expression(&unary.right),
+            Expression::Delete(delete) => {
+                if delete.path.is_empty() {
+                    return Err(ParseError::new_single(
+                        ErrorCode::InvalidAssignmentTarget,
+                        Some(delete.token.line),
+                        "Delete without an element or property".to_string(),
+                    ));
+                }
+
+                self.define(delete.identifier.clone())?;
+                for (operator, _) in &delete.path {
+                    if let PostfixOperator::Index(index) = operator {
+                        self.resolve_expression(index)?;
+                    }
+                }
+                Ok(())
+            }
+            Expression::Postfix(postfix) => {
+                self.resolve_expression(&postfix.left)?;
+                match postfix.operator {
+                    PostfixOperator::Index(ref index) => self.resolve_expression(index),
+                    PostfixOperator::Call(ref arguments) => arguments
+                        .iter()
+                        .try_for_each(|argument| self.resolve_expression(argument)),
+                    PostfixOperator::Dot(_) => Ok(()),
+                }
+            }
+            Expression::Identifier(identifier) => self.resolve(identifier.identifier.clone()),
+            Expression::Array(array) => array
+                .elements
+                .iter()
+                .try_for_each(|element| self.resolve_expression(element)),
+            Expression::Match(expression) => {
+                self.resolve_expression(&expression.value)?;
+                for arm in &expression.arms {
+                    self.resolve_pattern(&arm.pattern)?;
+
+                    self.push();
+                    for name in arm.pattern.bindings() {
+                        self.declare(name.clone(), false);
+                    }
+                    let result = self.resolve_expression(&arm.body);
+                    self.pop();
+                    result?;
+                }
+                Ok(())
+            }
+            Expression::Literal(_) => Ok(()),
+        }
+    }
+
+    // Resolve the names read by the constants in `pattern`.
+    fn resolve_pattern(&mut self, pattern: &Pattern) -> ParseResult<()> {
+        match pattern {
+            Pattern::Constant(constant) => self.resolve_expression(constant),
+            Pattern::Array(elements) | Pattern::Alternatives(elements) => elements
+                .iter()
+                .try_for_each(|element| self.resolve_pattern(element)),
+            Pattern::Wildcard | Pattern::Literal(_) | Pattern::Binding(_) => Ok(()),
+        }
+    }
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self::new()
     }
 }
@@ -0,0 +1,206 @@
+use std::fmt;
+
+use serde::{
+    de::{self, MapAccess, SeqAccess, Visitor},
+    ser::{self, SerializeMap},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use super::{statements::Statement, value::Value};
+
+// Version of the format of serialized programs, to be raised with every change to the AST that
+// older programs can't be read with.
+pub const VERSION: u32 = 4;
+
+// A serialized program. The version comes first, so it can be checked before the statements are
+// read.
+#[derive(Serialize)]
+pub(crate) struct Program<'a> {
+    pub version: u32,
+    pub statements: &'a [Statement],
+}
+
+#[derive(Deserialize)]
+pub(crate) struct Header {
+    pub version: u32,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct OwnedProgram {
+    pub statements: Vec<Statement>,
+}
+
+// Values as they appear in literals and tokens of a serialized program. Numbers without a JSON
+// representation are written as `{ "number": "NaN" }`, `"Infinity"` or `"-Infinity"`, so they
+// can't be confused with strings. Objects and functions are never part of a program.
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Value::Number(num) if num.is_finite() => serializer.serialize_f64(*num),
+            Value::Number(num) => {
+                let name = if num.is_nan() {
+                    "NaN"
+                } else if *num > 0.0 {
+                    "Infinity"
+                } else {
+                    "-Infinity"
+                };
+
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry("number", name)?;
+                map.end()
+            }
+            Value::String(str) => serializer.serialize_str(str),
+            Value::Boolean(bool) => serializer.serialize_bool(*bool),
+            Value::Null => serializer.serialize_unit(),
+            Value::Array(arr) => serializer.collect_seq(arr.iter()),
+            value => Err(ser::Error::custom(format!(
+                "cannot serialize a value of type {}",
+                value.type_name()
+            ))),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a number, string, boolean, null or array")
+    }
+
+    fn visit_f64<E: de::Error>(self, num: f64) -> Result<Value, E> {
+        Ok(Value::Number(num))
+    }
+
+    fn visit_i64<E: de::Error>(self, num: i64) -> Result<Value, E> {
+        Ok(Value::Number(num as f64))
+    }
+
+    fn visit_u64<E: de::Error>(self, num: u64) -> Result<Value, E> {
+        Ok(Value::Number(num as f64))
+    }
+
+    fn visit_str<E: de::Error>(self, str: &str) -> Result<Value, E> {
+        Ok(Value::from(str))
+    }
+
+    fn visit_bool<E: de::Error>(self, bool: bool) -> Result<Value, E> {
+        Ok(Value::Boolean(bool))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut elements = Vec::new();
+        while let Some(element) = seq.next_element()? {
+            elements.push(element);
+        }
+        Ok(Value::from(elements))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let number = match map.next_entry::<String, String>()? {
+            Some((key, name)) if key == "number" => match name.as_str() {
+                "NaN" => f64::NAN,
+                "Infinity" => f64::INFINITY,
+                "-Infinity" => f64::NEG_INFINITY,
+                _ => return Err(de::Error::custom(format!("unknown number {}", name))),
+            },
+            _ => return Err(de::Error::custom("expected a number such as NaN")),
+        };
+
+        if map.next_key::<String>()?.is_some() {
+            return Err(de::Error::custom("expected only the number"));
+        }
+
+        Ok(Value::Number(number))
+    }
+}
+
+// A literal expression, `{ "kind": "Literal", "value": ... }`. The kind is added by the tag of
+// `Expression`, which needs the content of its variants to be a struct.
+pub(crate) mod literal {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::common::value::Value;
+
+    #[derive(Serialize)]
+    struct Literal<'a> {
+        value: &'a Value,
+    }
+
+    #[derive(Deserialize)]
+    struct OwnedLiteral {
+        value: Value,
+    }
+
+    pub fn serialize<S: Serializer>(value: &Value, serializer: S) -> Result<S::Ok, S::Error> {
+        Literal { value }.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Value, D::Error> {
+        Ok(OwnedLiteral::deserialize(deserializer)?.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn json(value: &Value) -> String {
+        serde_json::to_string(value).unwrap()
+    }
+
+    fn value(json: &str) -> Result<Value, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    #[test]
+    fn test_serialize_value() {
+        assert_eq!(json(&Value::from(1.5)), "1.5");
+        assert_eq!(json(&Value::from("a\"b")), r#""a\"b""#);
+        assert_eq!(json(&Value::from(true)), "true");
+        assert_eq!(json(&Value::Null), "null");
+        assert_eq!(
+            json(&Value::from(vec![Value::from(1.0), Value::Null])),
+            "[1.0,null]"
+        );
+        assert_eq!(json(&Value::Number(f64::NAN)), r#"{"number":"NaN"}"#);
+        assert_eq!(
+            json(&Value::Number(f64::NEG_INFINITY)),
+            r#"{"number":"-Infinity"}"#
+        );
+    }
+
+    #[test]
+    fn test_deserialize_value() {
+        for original in [
+            Value::from(1.5),
+            Value::from(-3.0),
+            Value::from("NaN"),
+            Value::from(false),
+            Value::Null,
+            Value::from(vec![Value::from(1.0), Value::from("x")]),
+            Value::Number(f64::INFINITY),
+        ] {
+            assert_eq!(value(&json(&original)).unwrap(), original);
+        }
+
+        assert!(matches!(value(r#"{"number":"NaN"}"#), Ok(Value::Number(num)) if num.is_nan()));
+        assert_eq!(value("7").unwrap(), Value::from(7.0));
+        assert!(value(r#"{"number":"one"}"#).is_err());
+        assert!(value(r#"{"name":"x"}"#).is_err());
+        assert!(value(r#"{"number":"NaN","other":1}"#).is_err());
+    }
+}
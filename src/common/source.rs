@@ -0,0 +1,730 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::lexer::Scanner;
+
+use super::{
+    expressions::{Expression, Pattern, PostfixOperator},
+    statements::Statement,
+    token::{Token, TokenType},
+    value::Value,
+};
+
+const INDENT: &str = "    ";
+
+// How tightly expressions bind, following the levels of the parser. An operand is put in
+// parentheses when its expression binds looser than the operand position allows.
+const ASSIGNMENT: u8 = 0;
+const CONDITIONAL: u8 = 1;
+const OR: u8 = 2;
+const AND: u8 = 3;
+const EQUALITY: u8 = 4;
+const RELATIONAL: u8 = 5;
+const ADDITIVE: u8 = 6;
+const MULTIPLICATIVE: u8 = 7;
+const UNARY: u8 = 8;
+const POSTFIX: u8 = 9;
+const PRIMARY: u8 = 10;
+
+/// Source code for `statements` that parses back into the same syntax tree, apart from line
+/// numbers. Parentheses are only written where the precedence of the operators needs them, and
+/// blocks are indented by four spaces.
+///
+/// Some programs can't be written back as they were: exported declarations come out without
+/// `export`, and negative number literals, which only the optimizer creates, come out as a minus
+/// applied to a number.
+pub fn to_source(statements: &[Statement]) -> String {
+    let mut printer = Printer::default();
+    printer.statements(statements, 0, true);
+    printer.source
+}
+
+/// The canonical form of `source`, the program `statements` were parsed from, as written by
+/// `notjs fmt`. The statements are written as by [`to_source`], along with the comments of the
+/// source, the `export` of declarations, and single blank lines where the source had any between
+/// statements.
+///
+/// Comments are kept in order, on their own line or after the statement they followed on its
+/// line. Comments inside an expression are moved after its statement.
+pub fn format(source: &[u8], statements: &[Statement]) -> String {
+    let mut printer = Printer {
+        lines: String::from_utf8_lossy(source)
+            .lines()
+            .map(|line| line.trim().is_empty())
+            .collect(),
+        ..Printer::default()
+    };
+
+    // The parser drops comments, and exports and the ends of blocks are only in the tokens.
+    let mut code_line = 0;
+    let mut braces = Vec::new();
+    let mut tokens = Scanner::with_comments(source);
+    while let Some(token) = tokens.next() {
+        match token.token_type {
+            TokenType::Comment => printer.comments.push_back(Comment {
+                text: token.value.to_string(),
+                line: token.line,
+                trailing: code_line == token.line,
+                block: braces.last().copied(),
+            }),
+            TokenType::LeftBrace => {
+                braces.push(printer.block_ends.len());
+                printer.block_ends.push(token.line);
+            }
+            TokenType::RightBrace => {
+                if let Some(index) = braces.pop() {
+                    printer.block_ends[index] = token.line;
+                }
+            }
+            TokenType::Export => {
+                // The keyword of the declaration, then its name.
+                if let Some(name) = tokens.find(|token| token.token_type == TokenType::Identifier) {
+                    printer.exports.insert(name.value.to_string());
+                }
+            }
+            _ => {}
+        }
+        if token.token_type != TokenType::Comment {
+            code_line = token.line;
+        }
+    }
+
+    printer.statements(statements, 0, true);
+    // None are left unless the blocks of `statements` don't match the braces of `source`, but
+    // comments are never dropped.
+    for comment in printer.comments.drain(..) {
+        printer.source.push_str(&comment.text);
+        printer.source.push('\n');
+    }
+    printer.source
+}
+
+// The first line of the source of `statement`, e.g. `while (i < 3) {` for a loop.
+pub(crate) fn first_line(statement: &Statement) -> String {
+    let mut printer = Printer::default();
+    printer.statement(statement, 0, false);
+    let mut source = printer.source;
+    source.truncate(source.find('\n').unwrap_or(source.len()));
+    source
+}
+
+struct Comment {
+    // With its delimiters, e.g. `// note`.
+    text: String,
+    line: u32,
+    // Whether it follows code on its line.
+    trailing: bool,
+    // Index of the innermost block it is in, counting blocks by their opening braces.
+    block: Option<usize>,
+}
+
+// Writes statements as source. When formatting, the comments of the source are written between
+// them as the lines of the statements go past.
+#[derive(Default)]
+struct Printer {
+    source: String,
+    // Comments not written yet, in order.
+    comments: VecDeque<Comment>,
+    // Line of the closing brace of each block, in the order of their opening braces, which is
+    // the order they are written in.
+    block_ends: Vec<u32>,
+    // Number of blocks started so far.
+    blocks: usize,
+    // Index of the block being written, `None` at the top level. Comments are only written in
+    // the block they were in.
+    block: Option<usize>,
+    // Names declared with `export`.
+    exports: HashSet<String>,
+    // Whether each line of the source, from the first, is blank.
+    lines: Vec<bool>,
+    // Line of the source last written.
+    line: u32,
+}
+
+impl Printer {
+    // Statements each on their own line, followed by the comments left in their block.
+    fn statements(&mut self, statements: &[Statement], indent: usize, top_level: bool) {
+        for statement in statements {
+            let line = statement.line();
+            self.comments_before(
+                |comment| comment.line < line || (comment.line == line && !comment.trailing),
+                indent,
+            );
+
+            self.blank_line(line);
+            self.statement(statement, indent, top_level);
+            self.trailing_comments(indent);
+            self.source.push('\n');
+        }
+
+        self.comments_before(|_| true, indent);
+    }
+
+    // Write the comments of the current block that come before a point of the source, each on
+    // its own line.
+    fn comments_before(&mut self, before: impl Fn(&Comment) -> bool, indent: usize) {
+        while let Some(comment) = self.comments.pop_front() {
+            if comment.block != self.block || !before(&comment) {
+                self.comments.push_front(comment);
+                break;
+            }
+
+            self.blank_line(comment.line);
+            self.source.push_str(&INDENT.repeat(indent));
+            self.source.push_str(&comment.text);
+            self.source.push('\n');
+            self.line = comment.line;
+        }
+    }
+
+    // Write the comments that followed the last statement written on its line. Nothing can
+    // follow a line comment, so the comments after one go on their own lines.
+    fn trailing_comments(&mut self, indent: usize) {
+        let mut line_comment = false;
+
+        while let Some(comment) = self.comments.front() {
+            if comment.block != self.block || !comment.trailing || comment.line > self.line {
+                break;
+            }
+
+            if line_comment {
+                self.source.push('\n');
+                self.source.push_str(&INDENT.repeat(indent));
+            } else {
+                self.source.push(' ');
+            }
+            self.source.push_str(&comment.text);
+            line_comment = comment.text.starts_with("//");
+            self.comments.pop_front();
+        }
+    }
+
+    // Keep a blank line of the source before `line`, unless at the start of a block or right
+    // after what was last written.
+    fn blank_line(&mut self, line: u32) {
+        let blank = line >= self.line + 2 && self.lines.get(line as usize - 2) == Some(&true);
+
+        if blank && !self.source.is_empty() && !self.source.ends_with("{\n") {
+            self.source.push('\n');
+        }
+    }
+
+    // Write a return or yield, with `keyword`.
+    fn optional_value(&mut self, keyword: &str, value: &Option<Expression>) {
+        match value {
+            Some(value) => {
+                let value = expression(value, ASSIGNMENT);
+                // A keyword followed by `null` has no value, so a value starting with null needs
+                // parentheses.
+                let starts_with_null = value.strip_prefix("null").is_some_and(|rest| {
+                    !rest.starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_')
+                });
+
+                if starts_with_null {
+                    self.source.push_str(&format!("{} ({})", keyword, value));
+                } else {
+                    self.source.push_str(&format!("{} {}", keyword, value));
+                }
+            }
+            // Written so that the statement after it isn't taken for the value.
+            None => self.source.push_str(&format!("{} null", keyword)),
+        }
+    }
+
+    // Write a statement. Declarations at the top level of a program can be exported.
+    fn statement(&mut self, statement: &Statement, indent: usize, top_level: bool) {
+        self.source.push_str(&INDENT.repeat(indent));
+        self.line = statement.line();
+
+        let exported = statement
+            .declared_name()
+            .is_some_and(|name| top_level && self.exports.contains(name.name()));
+        if exported {
+            self.source.push_str("export ");
+        }
+
+        match statement {
+            Statement::Block(block) => self.block(&block.statements, indent),
+            Statement::Variable(declaration) => {
+                self.source.push_str(if declaration.mutable {
+                    "let "
+                } else {
+                    "const "
+                });
+                self.source.push_str(declaration.identifier.name());
+                if let Some(annotation) = declaration.annotation {
+                    self.source.push_str(&format!(": {}", annotation));
+                }
+                if let Some(ref initializer) = declaration.initializer {
+                    self.source.push_str(" = ");
+                    self.source.push_str(&expression(initializer, ASSIGNMENT));
+                }
+            }
+            Statement::Expression(statement) => self
+                .source
+                .push_str(&expression(&statement.expression, ASSIGNMENT)),
+            Statement::Print(statement) => {
+                self.source.push_str(if statement.new_line {
+                    "println "
+                } else {
+                    "print "
+                });
+                self.source
+                    .push_str(&expression(&statement.expression, ASSIGNMENT));
+            }
+            Statement::If(statement) => {
+                self.source.push_str(&format!(
+                    "if ({}) ",
+                    expression(&statement.condition, ASSIGNMENT)
+                ));
+                self.body(&statement.then_branch, indent);
+                if let Some(ref else_branch) = statement.else_branch {
+                    self.source.push_str(" else ");
+                    self.body(else_branch, indent);
+                }
+            }
+            Statement::While(statement) => {
+                self.source.push_str(&format!(
+                    "while ({}) ",
+                    expression(&statement.condition, ASSIGNMENT)
+                ));
+                self.body(&statement.body, indent);
+            }
+            Statement::ForIn(statement) => {
+                self.source.push_str(&format!(
+                    "for ({} {} in {}) ",
+                    if statement.mutable { "let" } else { "const" },
+                    statement.identifier.name(),
+                    expression(&statement.iterable, ASSIGNMENT)
+                ));
+                self.body(&statement.body, indent);
+            }
+            Statement::Return(statement) => self.optional_value("return", &statement.value),
+            Statement::Yield(statement) => self.optional_value("yield", &statement.value),
+            Statement::Defer(statement) => {
+                self.source.push_str("defer ");
+                self.source
+                    .push_str(&expression(&statement.expression, ASSIGNMENT));
+            }
+            Statement::Function(declaration) => {
+                let parameters = declaration
+                    .parameters
+                    .iter()
+                    .map(|parameter| parameter.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                self.source.push_str(&format!(
+                    "function{} {}({})",
+                    if declaration.generator { "*" } else { "" },
+                    declaration.name.name(),
+                    parameters
+                ));
+                if let Some(return_type) = declaration.return_type {
+                    self.source.push_str(&format!(": {}", return_type));
+                }
+                self.source.push(' ');
+                self.body(&declaration.body, indent);
+            }
+            Statement::Assert(statement) => {
+                self.source.push_str("assert ");
+                self.source
+                    .push_str(&expression(&statement.condition, ASSIGNMENT));
+            }
+            Statement::Enum(declaration) => self.source.push_str(&declaration.to_string()),
+            Statement::Test(declaration) => {
+                self.source.push_str(&format!(
+                    "test {} ",
+                    string(&declaration.name.value.to_string())
+                ));
+                self.body(&declaration.body, indent);
+            }
+            Statement::Import(import) => {
+                let path = string(&import.path.value.to_string());
+                match import.namespace {
+                    Some(ref namespace) => {
+                        self.source
+                            .push_str(&format!("import {} from {}", namespace.name(), path))
+                    }
+                    None => self.source.push_str(&format!("import {}", path)),
+                }
+            }
+        }
+    }
+
+    // A statement that follows a keyword on the same line, such as the branch of an if.
+    fn body(&mut self, statement: &Statement, indent: usize) {
+        let start = self.source.len();
+        self.statement(statement, indent, false);
+        // Only the first line is indented by the caller.
+        self.source
+            .replace_range(start..start + INDENT.len() * indent, "");
+    }
+
+    fn block(&mut self, statements: &[Statement], indent: usize) {
+        let index = self.blocks;
+        self.blocks += 1;
+        let outer = self.block.replace(index);
+
+        let has_comments = self
+            .comments
+            .front()
+            .is_some_and(|comment| comment.block == Some(index));
+        if statements.is_empty() && !has_comments {
+            self.source.push_str("{}");
+        } else {
+            self.source.push_str("{\n");
+            self.statements(statements, indent + 1, false);
+            self.source.push_str(&INDENT.repeat(indent));
+            self.source.push('}');
+        }
+
+        self.block = outer;
+        if let Some(&end) = self.block_ends.get(index) {
+            self.line = end;
+        }
+    }
+}
+
+fn precedence(operator: TokenType) -> u8 {
+    match operator {
+        TokenType::Or => OR,
+        TokenType::And => AND,
+        TokenType::EqualEqual
+        | TokenType::BangEqual
+        | TokenType::EqualEqualEqual
+        | TokenType::BangEqualEqual => EQUALITY,
+        TokenType::Less | TokenType::LessEqual | TokenType::Greater | TokenType::GreaterEqual => {
+            RELATIONAL
+        }
+        TokenType::Plus | TokenType::Minus => ADDITIVE,
+        _ => MULTIPLICATIVE,
+    }
+}
+
+// The source of an expression in a position that takes expressions binding at least as tightly
+// as `min`.
+fn expression(expression: &Expression, min: u8) -> String {
+    let (source, binds) = unparenthesized(expression);
+
+    if binds < min {
+        format!("({})", source)
+    } else {
+        source
+    }
+}
+
+fn unparenthesized(expression: &Expression) -> (String, u8) {
+    match expression {
+        Expression::Assignment(assignment) => (
+            format!(
+                "{} {} {}",
+                assignment.identifier.name(),
+                assignment.operator,
+                self::expression(&assignment.value, ASSIGNMENT)
+            ),
+            ASSIGNMENT,
+        ),
+        Expression::PropertyAssignment(assignment) => {
+            let target = target(&assignment.identifier, &assignment.path);
+
+            (
+                format!(
+                    "{} {} {}",
+                    target,
+                    assignment.operator.token_type,
+                    self::expression(&assignment.value, ASSIGNMENT)
+                ),
+                ASSIGNMENT,
+            )
+        }
+        Expression::Conditional(conditional) => (
+            format!(
+                "{} ? {} : {}",
+                self::expression(&conditional.condition, OR),
+                self::expression(&conditional.then_branch, ASSIGNMENT),
+                self::expression(&conditional.else_branch, CONDITIONAL)
+            ),
+            CONDITIONAL,
+        ),
+        Expression::Binary(binary) => {
+            // Written from the bottom of the chain up, without recursing into the left side.
+            let (chain, bottom) = binary.chain();
+            let binds = |index: usize| precedence(chain[index].operator.token_type);
+
+            let mut source = self::expression(bottom, binds(chain.len() - 1));
+            for (index, binary) in chain.iter().enumerate().rev() {
+                if index + 1 < chain.len() && binds(index + 1) < binds(index) {
+                    source = format!("({})", source);
+                }
+
+                source.push_str(&format!(
+                    " {} {}",
+                    binary.operator.token_type,
+                    self::expression(&binary.right, binds(index) + 1)
+                ));
+            }
+
+            (source, binds(0))
+        }
+        Expression::Unary(unary) => {
+            let right = self::expression(&unary.right, UNARY);
+            // `- -x` rather than `--x`.
+            let space = if right.starts_with('-') { " " } else { "" };
+
+            (
+                format!("{}{}{}", unary.operator.token_type, space, right),
+                UNARY,
+            )
+        }
+        Expression::Delete(delete) => (
+            format!("delete {}", target(&delete.identifier, &delete.path)),
+            UNARY,
+        ),
+        Expression::Postfix(postfix) => (
+            operand(&postfix.left) + &self::postfix(&postfix.operator),
+            POSTFIX,
+        ),
+        Expression::Identifier(identifier) => (identifier.identifier.name().to_string(), PRIMARY),
+        Expression::Array(array) => {
+            let elements = array
+                .elements
+                .iter()
+                .map(|element| self::expression(element, ASSIGNMENT))
+                .collect::<Vec<String>>()
+                .join(", ");
+
+            (format!("[{}]", elements), PRIMARY)
+        }
+        Expression::Match(expression) => {
+            let arms = expression
+                .arms
+                .iter()
+                .map(|arm| {
+                    format!(
+                        "{} => {}",
+                        pattern(&arm.pattern),
+                        self::expression(&arm.body, ASSIGNMENT)
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join(", ");
+
+            (
+                format!(
+                    "match {} {{ {} }}",
+                    self::expression(&expression.value, ASSIGNMENT),
+                    arms
+                ),
+                PRIMARY,
+            )
+        }
+        Expression::Literal(value) => match value {
+            Value::Number(num) if num.is_sign_negative() && !num.is_nan() => {
+                (literal(value), UNARY)
+            }
+            _ => (literal(value), PRIMARY),
+        },
+    }
+}
+
+// The source of an element or property held by a variable, e.g. `xs[0].name`.
+fn target(identifier: &Token, path: &[(PostfixOperator, Token)]) -> String {
+    let mut target = identifier.name().to_string();
+    for (operator, _) in path {
+        target += &postfix(operator);
+    }
+    target
+}
+
+// The source of a whole expression, e.g. `add(1, 2) == 3` for a failed assertion.
+pub(crate) fn condition(expression: &Expression) -> String {
+    self::expression(expression, ASSIGNMENT)
+}
+
+// The source of the expression a postfix operator applies to, e.g. `(a + b)` in `(a + b)()`.
+pub(crate) fn operand(expression: &Expression) -> String {
+    match *expression {
+        // A dot after the number would be read as a decimal point.
+        Expression::Literal(ref value @ Value::Number(num)) if num.is_finite() => {
+            format!("({})", literal(value))
+        }
+        ref expression => self::expression(expression, POSTFIX),
+    }
+}
+
+// The source of a pattern of a match arm, e.g. `[a, 1 | 2]`.
+pub(crate) fn pattern(pattern: &Pattern) -> String {
+    match pattern {
+        Pattern::Wildcard => "_".to_string(),
+        Pattern::Literal(value) => literal(value),
+        Pattern::Binding(name) => name.name().to_string(),
+        Pattern::Constant(constant) => expression(constant, POSTFIX),
+        Pattern::Array(elements) => format!(
+            "[{}]",
+            elements
+                .iter()
+                .map(self::pattern)
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+        Pattern::Alternatives(alternatives) => alternatives
+            .iter()
+            .map(self::pattern)
+            .collect::<Vec<String>>()
+            .join(" | "),
+    }
+}
+
+fn postfix(operator: &PostfixOperator) -> String {
+    match operator {
+        PostfixOperator::Index(index) => format!("[{}]", expression(index, ASSIGNMENT)),
+        PostfixOperator::Dot(name) => format!(".{}", name),
+        PostfixOperator::Call(arguments) => {
+            let arguments = arguments
+                .iter()
+                .map(|argument| expression(argument, ASSIGNMENT))
+                .collect::<Vec<String>>()
+                .join(", ");
+
+            format!("({})", arguments)
+        }
+    }
+}
+
+fn literal(value: &Value) -> String {
+    match value {
+        Value::Number(num) if num.is_nan() => "NaN".to_string(),
+        Value::Number(num) if num.is_infinite() && *num > 0.0 => "Infinity".to_string(),
+        Value::Number(num) if num.is_infinite() => "-Infinity".to_string(),
+        Value::Number(num) => num.to_string(),
+        Value::String(str) => string(str),
+        Value::Array(array) => {
+            let elements = array
+                .iter()
+                .map(literal)
+                .collect::<Vec<String>>()
+                .join(", ");
+            format!("[{}]", elements)
+        }
+        Value::Null => "null".to_string(),
+        value => value.to_string(),
+    }
+}
+
+// A string literal with the escapes the lexer reads.
+fn string(text: &str) -> String {
+    let mut literal = String::with_capacity(text.len() + 2);
+    literal.push('"');
+
+    for c in text.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\t' => literal.push_str("\\t"),
+            '\0' => literal.push_str("\\0"),
+            c => literal.push(c),
+        }
+    }
+
+    literal.push('"');
+    literal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser;
+
+    fn print(source: &str) -> String {
+        to_source(&parser::parse(source.as_bytes()).unwrap())
+    }
+
+    #[test]
+    fn test_parentheses() {
+        let declarations =
+            "let a = 1 let b = 2 let c = 3 let xs = [1] function f(x) { return x }\n";
+        let cases = [
+            ("a - (b - c)", "a - (b - c)"),
+            ("(a - b) - c", "a - b - c"),
+            ("(a * b) + c * (a + b)", "a * b + c * (a + b)"),
+            ("(a | b) * c & a", "(a | b) * c & a"),
+            ("(a ? b : c) ? a : b", "(a ? b : c) ? a : b"),
+            ("a ? b : (c ? a : b)", "a ? b : c ? a : b"),
+            ("a ? (b = 1) : c", "a ? b = 1 : c"),
+            ("(a = b) + 1", "(a = b) + 1"),
+            ("a = b = c", "a = b = c"),
+            ("-(-a)", "- -a"),
+            ("!(a == b)", "!(a == b)"),
+            ("(-a)[0]", "(-a)[0]"),
+            ("(1).length + (1.5).length", "(1).length + (1.5).length"),
+            ("xs[0] += f((a))", "xs[0] += f(a)"),
+            (
+                "(delete xs[0]) + !(delete xs[a].b)",
+                "delete xs[0] + !delete xs[a].b",
+            ),
+            ("f(a)(b)[c].length", "f(a)(b)[c].length"),
+            ("\"a\\\"b\\n\" + 'c\\'d'", "\"a\\\"b\\n\" + \"c'd\""),
+            ("[NaN, Infinity, null, true]", "[NaN, Infinity, null, true]"),
+            (
+                "(match a { 1 | -2 => (b), [x, _] => x, _ => c }) + 1",
+                "match a { 1 | -2 => b, [x, _] => x, _ => c } + 1",
+            ),
+        ];
+
+        for (source, expected) in cases {
+            let printed = print(&(declarations.to_string() + source));
+            let last = printed.lines().last().unwrap();
+            assert_eq!(last, expected, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_statements() {
+        let source = "function f(x) { if (x) return null else { return } }
+            let i = 0
+            while (i < 2) i += 1
+            for (const x in [1]) { print x println f(x) }
+            function g() { return (null) }
+            function h(a: number, b, c: bool): function { return h }
+            const n: null = null
+            function* gen(x) { yield x yield null yield (null + 1) }
+            { defer i = 0 defer f(i) }
+            test \"f works\" { assert f(1) == null }";
+
+        assert_eq!(
+            print(source),
+            "function f(x) {
+    if (x) return null else {
+        return null
+    }
+}
+let i = 0
+while (i < 2) i += 1
+for (const x in [1]) {
+    print x
+    println f(x)
+}
+function g() {
+    return (null)
+}
+function h(a: number, b, c: boolean): function {
+    return h
+}
+const n: null = null
+function* gen(x) {
+    yield x
+    yield null
+    yield (null + 1)
+}
+{
+    defer i = 0
+    defer f(i)
+}
+test \"f works\" {
+    assert f(1) == null
+}
+"
+        );
+    }
+}
@@ -1,8 +1,8 @@
-use std::fmt;
+use std::{fmt, path::PathBuf, rc::Rc};
 
-use crate::error::runtime::RuntimeResult;
+use serde::{Deserialize, Serialize};
 
-use super::{expressions::Expression, token::Token, value::Value};
+use super::{expressions::Expression, token::Token, value::ValueType};
 
 /*
 statement = block
@@ -12,55 +12,225 @@ statement = block
             | if_statement
             | while_statement
             | for_statement
-            | return_statement ;
+            | return_statement
+            | yield_statement
+            | defer_statement
+            | assert_statement
+            | function_declaration
+            | enum_declaration
+            | test_declaration
+            | import_statement
+            | export_declaration ;
 */
-pub trait Statement {
-    fn execute(&self) -> RuntimeResult<Value>;
-    fn node_to_string(&self) -> String;
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum Statement {
+    Block(BlockStatement),
+    Variable(VariableDeclaration),
+    Expression(ExpressionStatement),
+    Print(PrintStatement),
+    If(IfStatement),
+    While(WhileStatement),
+    ForIn(ForInStatement),
+    Return(ReturnStatement),
+    Yield(YieldStatement),
+    Defer(DeferStatement),
+    Assert(AssertStatement),
+    Function(FunctionDeclaration),
+    Enum(EnumDeclaration),
+    Test(TestDeclaration),
+    Import(ImportStatement),
 }
 
+impl Statement {
+    /// Line the statement starts at.
+    pub fn line(&self) -> u32 {
+        match self {
+            Statement::Block(block) => block.line,
+            Statement::Variable(declaration) => declaration.identifier.line,
+            Statement::Expression(statement) => statement.line,
+            Statement::Print(statement) => statement.line,
+            Statement::If(statement) => statement.line,
+            Statement::While(statement) => statement.line,
+            Statement::ForIn(statement) => statement.identifier.line,
+            Statement::Return(statement) => statement.line,
+            Statement::Yield(statement) => statement.line,
+            Statement::Defer(statement) => statement.line,
+            Statement::Assert(statement) => statement.line,
+            Statement::Function(declaration) => declaration.name.line,
+            Statement::Enum(declaration) => declaration.name.line,
+            Statement::Test(declaration) => declaration.name.line,
+            Statement::Import(import) => import.path.line,
+        }
+    }
+
+    // The name the statement declares, for the statements that can be exported.
+    pub fn declared_name(&self) -> Option<&Token> {
+        match self {
+            Statement::Variable(declaration) => Some(&declaration.identifier),
+            Statement::Function(declaration) => Some(&declaration.name),
+            Statement::Enum(declaration) => Some(&declaration.name),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct BlockStatement {
-    pub statements: Vec<Box<dyn Statement>>,
+    pub statements: Vec<Statement>,
+    pub line: u32,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct VariableDeclaration {
     pub mutable: bool,
     pub identifier: Token,
-    pub initializer: Option<Box<dyn Expression>>,
+    // The type the variable is declared with, checked whenever it is given a value.
+    pub annotation: Option<ValueType>,
+    pub initializer: Option<Expression>,
     pub scope: usize,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct ExpressionStatement {
-    pub expression: Box<dyn Expression>,
+    pub expression: Expression,
+    pub line: u32,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct PrintStatement {
-    pub expression: Box<dyn Expression>,
+    pub expression: Expression,
     pub new_line: bool,
+    pub line: u32,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct IfStatement {
-    pub condition: Box<dyn Expression>,
-    pub then_branch: Box<dyn Statement>,
-    pub else_branch: Option<Box<dyn Statement>>,
+    pub condition: Expression,
+    pub then_branch: Box<Statement>,
+    pub else_branch: Option<Box<Statement>>,
+    pub line: u32,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct WhileStatement {
-    pub condition: Box<dyn Expression>,
-    pub body: Box<dyn Statement>,
+    pub condition: Expression,
+    pub body: Box<Statement>,
+    pub line: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ForInStatement {
+    pub mutable: bool,
+    pub identifier: Token,
+    pub iterable: Expression,
+    pub body: Box<Statement>,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct ReturnStatement {
-    pub value: Option<Box<dyn Expression>>,
+    pub value: Option<Expression>,
+    pub line: u32,
+}
+
+// Only found in the body of a generator function, where it pauses the body until the generator is
+// resumed.
+#[derive(Serialize, Deserialize)]
+pub struct YieldStatement {
+    pub value: Option<Expression>,
+    pub line: u32,
+}
+
+// Runs the expression when the enclosing block exits, after the statements of the block.
+#[derive(Serialize, Deserialize)]
+pub struct DeferStatement {
+    pub expression: Rc<Expression>,
+    pub line: u32,
+}
+
+// Fails with the source of the condition when it doesn't hold.
+#[derive(Serialize, Deserialize)]
+pub struct AssertStatement {
+    pub condition: Expression,
+    pub line: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FunctionDeclaration {
+    pub name: Token,
+    // Declared with `function*`, so that calls give a generator running the body.
+    pub generator: bool,
+    pub parameters: Vec<Parameter>,
+    // The type the function is declared to return, checked when it returns.
+    pub return_type: Option<ValueType>,
+    // Shared with every function value created from this declaration.
+    pub body: Rc<Statement>,
 }
 
-// pub struct FunctionStatement {
-//     pub name: String,
-//     pub parameters: Vec<String>,
-//     pub body: Box<dyn Statement>,
-// }
+// Declares `name` as a const holding a frozen object, whose properties are the variants.
+#[derive(Serialize, Deserialize)]
+pub struct EnumDeclaration {
+    pub name: Token,
+    pub variants: Vec<Token>,
+}
+
+// A test named by the string `name`, only found at the top level. Programs skip their tests, which
+// are run by `Interpreter::test` once the program ends.
+#[derive(Serialize, Deserialize)]
+pub struct TestDeclaration {
+    pub name: Token,
+    pub body: Rc<Statement>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Parameter {
+    pub name: Token,
+    pub annotation: Option<ValueType>,
+}
+
+// The imported file is parsed along with the importing one, so its declarations are known to the
+// resolver. It only runs the first time it is imported.
+#[derive(Serialize, Deserialize)]
+pub struct ImportStatement {
+    // The path as written in the source.
+    pub path: Token,
+    // Canonical path of the imported file, used to tell whether it already ran.
+    pub module: PathBuf,
+    pub statements: Vec<Statement>,
+    // With a namespace the file runs with its own globals, and only the exported names are
+    // available, as properties of the namespace. Without one, every global it declares is shared.
+    pub namespace: Option<Token>,
+    pub exports: Vec<String>,
+}
+
+// The name followed by its annotation, e.g. `x: number`, as written in the source.
+impl fmt::Display for Parameter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.annotation {
+            Some(annotation) => write!(f, "{}: {}", self.name.name(), annotation),
+            None => write!(f, "{}", self.name.name()),
+        }
+    }
+}
+
+// The declaration on one line, e.g. `enum State { Idle, Done }`, as written in the source.
+impl fmt::Display for EnumDeclaration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.variants.is_empty() {
+            return write!(f, "enum {} {{}}", self.name.name());
+        }
+
+        let variants = self
+            .variants
+            .iter()
+            .map(Token::name)
+            .collect::<Vec<&str>>()
+            .join(", ");
+        write!(f, "enum {} {{ {} }}", self.name.name(), variants)
+    }
+}
 
-impl fmt::Debug for dyn Statement {
+impl fmt::Debug for Statement {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.node_to_string())
     }
@@ -3,6 +3,7 @@ use std::fmt::Display;
 
 use super::value::Value;
 use phf::phf_map;
+use serde::{Deserialize, Serialize};
 
 pub const KEYWORDS: phf::Map<&str, TokenType> = phf_map! {
     "function" => TokenType::Function,
@@ -17,6 +18,7 @@ pub const KEYWORDS: phf::Map<&str, TokenType> = phf_map! {
     "null" => TokenType::Null,
     "while" => TokenType::While,
     "for" => TokenType::For,
+    "in" => TokenType::In,
     "return" => TokenType::Return,
     "break" => TokenType::Break,
     "continue" => TokenType::Continue,
@@ -25,10 +27,22 @@ pub const KEYWORDS: phf::Map<&str, TokenType> = phf_map! {
     "self" => TokenType::SelfTok,
     "let" => TokenType::Let,
     "const" => TokenType::Const,
+    "import" => TokenType::Import,
+    "export" => TokenType::Export,
+    "delete" => TokenType::Delete,
+    "enum" => TokenType::Enum,
+    "match" => TokenType::Match,
+    "yield" => TokenType::Yield,
+    "defer" => TokenType::Defer,
+    "test" => TokenType::Test,
+    "assert" => TokenType::Assert,
+    "NaN" => TokenType::Number,
+    "Infinity" => TokenType::Number,
 };
 
-#[derive(PartialEq, Clone, Debug)]
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub struct Token {
+    #[serde(rename = "type")]
     pub token_type: TokenType,
     pub value: Value,
     pub line: u32,
@@ -42,9 +56,14 @@ impl Token {
             line,
         }
     }
+
+    // The name of an identifier, which identifier tokens hold as a string value.
+    pub fn name(&self) -> &str {
+        self.value.as_str().unwrap_or_default()
+    }
 }
 
-#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum TokenType {
     LeftParentheses,
     RightParentheses,
@@ -67,8 +86,12 @@ pub enum TokenType {
     SlashEqual,
     Bang,
     BangEqual,
+    BangEqualEqual,
     Equal,
+    // `=>`, between the pattern and the value of a match arm.
+    Arrow,
     EqualEqual,
+    EqualEqualEqual,
     Greater,
     GreaterEqual,
     Less,
@@ -92,6 +115,7 @@ pub enum TokenType {
     Null,
     While,
     For,
+    In,
     Return,
     Break,
     Continue,
@@ -100,7 +124,18 @@ pub enum TokenType {
     SelfTok,
     Let,
     Const,
+    Import,
+    Export,
+    Delete,
+    Enum,
+    Match,
+    Yield,
+    Defer,
+    Test,
+    Assert,
     // Special tokens
+    // Only given by a scanner made with `Scanner::with_comments`.
+    Comment,
     Error,
 }
 
@@ -127,8 +162,11 @@ impl Display for TokenType {
             TokenType::SlashEqual => "/=",
             TokenType::Bang => "!",
             TokenType::BangEqual => "!=",
+            TokenType::BangEqualEqual => "!==",
             TokenType::Equal => "=",
+            TokenType::Arrow => "=>",
             TokenType::EqualEqual => "==",
+            TokenType::EqualEqualEqual => "===",
             TokenType::Greater => ">",
             TokenType::GreaterEqual => ">=",
             TokenType::Less => "<",
@@ -150,6 +188,7 @@ impl Display for TokenType {
             TokenType::Null => "Null",
             TokenType::While => "While",
             TokenType::For => "For",
+            TokenType::In => "In",
             TokenType::Return => "Return",
             TokenType::Break => "Break",
             TokenType::Continue => "Continue",
@@ -158,6 +197,16 @@ impl Display for TokenType {
             TokenType::SelfTok => "Self",
             TokenType::Let => "Let",
             TokenType::Const => "Const",
+            TokenType::Import => "Import",
+            TokenType::Export => "Export",
+            TokenType::Delete => "Delete",
+            TokenType::Enum => "Enum",
+            TokenType::Match => "Match",
+            TokenType::Yield => "Yield",
+            TokenType::Defer => "Defer",
+            TokenType::Test => "Test",
+            TokenType::Assert => "Assert",
+            TokenType::Comment => "Comment",
             TokenType::Error => "Error",
         };
 
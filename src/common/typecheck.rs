@@ -0,0 +1,624 @@
+use std::{collections::HashMap, rc::Rc};
+
+use crate::error::parse::{ErrorCode, ParseError, ParseResult};
+
+use super::{
+    expressions::{AssignmentExpression, Expression, PostfixOperator},
+    statements::{FunctionDeclaration, Statement},
+    token::{Token, TokenType},
+    value::ValueType,
+};
+
+/// Check the types of a program without running it. The mismatches found are given as errors
+/// with the code `E040`, whose [`diagnostics`](ParseError::diagnostics) are those reported by
+/// `notjs --typecheck`.
+///
+/// Types come from annotations and literals, and flow through constants, operators and calls to
+/// declared functions. Whatever can't be told for sure, such as the value of a `let` without an
+/// annotation or what a builtin returns, may be of any type and is never reported. The files
+/// imported by the program aren't checked.
+pub fn typecheck(program: &[Statement]) -> ParseResult<()> {
+    let mut checker = Checker {
+        scopes: vec![HashMap::new()],
+        functions: Vec::new(),
+        errors: Vec::new(),
+    };
+    checker.statements(program);
+
+    if checker.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ParseError::new_multiple(checker.errors))
+    }
+}
+
+// The parameters and return type of a declared function.
+struct Signature {
+    name: String,
+    parameters: Vec<(String, Option<ValueType>)>,
+    // The type calls give, a generator for generator functions.
+    return_type: Option<ValueType>,
+    // Declared with `function*`, whose body may return values of any type.
+    generator: bool,
+}
+
+// What is known about a name. A type of `None` is unknown, so the name may hold any value.
+#[derive(Clone, Default)]
+struct Binding {
+    value_type: Option<ValueType>,
+    // Whether the type is an annotation, which every value given to the name is checked against.
+    annotated: bool,
+    signature: Option<Rc<Signature>>,
+}
+
+struct Checker {
+    scopes: Vec<HashMap<String, Binding>>,
+    // The functions whose bodies are being checked, innermost last.
+    functions: Vec<Rc<Signature>>,
+    errors: Vec<ParseError>,
+}
+
+impl Checker {
+    fn declare(&mut self, name: &Token, binding: Binding) {
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .insert(name.name().to_string(), binding);
+    }
+
+    // Names that aren't declared in the program, such as builtins, are unknown.
+    fn lookup(&self, name: &Token) -> Binding {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name.name()))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn error(&mut self, line: u32, message: String) {
+        self.errors.push(ParseError::new_single(
+            ErrorCode::TypeMismatch,
+            Some(line),
+            message,
+        ));
+    }
+
+    // Report a value of type `found` given to something of type `expected`, when both are known
+    // and differ. `target` names what the value is given to, as in the runtime error.
+    fn expect(
+        &mut self,
+        expected: Option<ValueType>,
+        found: Option<ValueType>,
+        target: impl FnOnce() -> String,
+        line: u32,
+    ) {
+        if let (Some(expected), Some(found)) = (expected, found) {
+            if expected != found {
+                let message = format!("Expected {} for {}, found {}", expected, target(), found);
+                self.error(line, message);
+            }
+        }
+    }
+
+    fn statements(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Block(block) => {
+                self.scopes.push(HashMap::new());
+                self.statements(&block.statements);
+                self.scopes.pop();
+            }
+            Statement::Variable(declaration) => {
+                let found = declaration
+                    .initializer
+                    .as_ref()
+                    .and_then(|initializer| self.expression(initializer));
+                let identifier = &declaration.identifier;
+
+                let binding = match declaration.annotation {
+                    Some(annotation) => {
+                        self.expect(
+                            Some(annotation),
+                            found,
+                            || format!("variable '{}'", identifier.name()),
+                            identifier.line,
+                        );
+                        Binding {
+                            value_type: Some(annotation),
+                            annotated: true,
+                            signature: None,
+                        }
+                    }
+                    // Only a constant keeps the type of its initializer.
+                    None if !declaration.mutable => Binding {
+                        value_type: found,
+                        ..Binding::default()
+                    },
+                    None => Binding::default(),
+                };
+                self.declare(identifier, binding);
+            }
+            Statement::Expression(statement) => {
+                self.expression(&statement.expression);
+            }
+            Statement::Print(statement) => {
+                self.expression(&statement.expression);
+            }
+            Statement::If(statement) => {
+                self.expression(&statement.condition);
+                self.statement(&statement.then_branch);
+                if let Some(ref else_branch) = statement.else_branch {
+                    self.statement(else_branch);
+                }
+            }
+            Statement::While(statement) => {
+                self.expression(&statement.condition);
+                self.statement(&statement.body);
+            }
+            Statement::ForIn(statement) => {
+                let iterable = self.expression(&statement.iterable);
+
+                // Strings are iterated by character, the elements of arrays may be anything.
+                let value_type = match iterable {
+                    Some(ValueType::String) if !statement.mutable => Some(ValueType::String),
+                    _ => None,
+                };
+
+                self.scopes.push(HashMap::new());
+                self.declare(
+                    &statement.identifier,
+                    Binding {
+                        value_type,
+                        ..Binding::default()
+                    },
+                );
+                self.statement(&statement.body);
+                self.scopes.pop();
+            }
+            Statement::Return(statement) => {
+                let found = match statement.value {
+                    Some(ref value) => self.expression(value),
+                    None => Some(ValueType::Null),
+                };
+
+                let function = self.functions.last().cloned();
+                if let Some(function) = function.filter(|function| !function.generator) {
+                    self.expect(
+                        function.return_type,
+                        found,
+                        || format!("return value of '{}'", function.name),
+                        statement.line,
+                    );
+                }
+            }
+            Statement::Yield(statement) => {
+                if let Some(ref value) = statement.value {
+                    self.expression(value);
+                }
+            }
+            Statement::Defer(statement) => {
+                self.expression(&statement.expression);
+            }
+            Statement::Assert(statement) => {
+                self.expression(&statement.condition);
+            }
+            Statement::Test(declaration) => self.statement(&declaration.body),
+            Statement::Function(declaration) => self.function(declaration),
+            Statement::Enum(declaration) => self.declare(
+                &declaration.name,
+                Binding {
+                    value_type: Some(ValueType::Object),
+                    ..Binding::default()
+                },
+            ),
+            // Names declared by imported files stay unknown.
+            Statement::Import(_) => {}
+        }
+    }
+
+    fn function(&mut self, declaration: &FunctionDeclaration) {
+        let return_type = if declaration.generator {
+            self.expect(
+                declaration.return_type,
+                Some(ValueType::Generator),
+                || format!("return value of '{}'", declaration.name.name()),
+                declaration.name.line,
+            );
+            Some(ValueType::Generator)
+        } else {
+            declaration.return_type
+        };
+
+        let signature = Rc::new(Signature {
+            name: declaration.name.name().to_string(),
+            parameters: declaration
+                .parameters
+                .iter()
+                .map(|parameter| (parameter.name.name().to_string(), parameter.annotation))
+                .collect(),
+            return_type,
+            generator: declaration.generator,
+        });
+
+        // Declared before the body so the function can call itself.
+        self.declare(
+            &declaration.name,
+            Binding {
+                value_type: Some(ValueType::Function),
+                annotated: false,
+                signature: Some(signature.clone()),
+            },
+        );
+
+        self.scopes.push(HashMap::new());
+        for parameter in &declaration.parameters {
+            self.declare(
+                &parameter.name,
+                Binding {
+                    value_type: parameter.annotation,
+                    annotated: parameter.annotation.is_some(),
+                    signature: None,
+                },
+            );
+        }
+        self.functions.push(signature);
+        self.statement(&declaration.body);
+        self.functions.pop();
+        self.scopes.pop();
+    }
+
+    // The type of the values of `expression`, `None` if it can't be told.
+    fn expression(&mut self, expression: &Expression) -> Option<ValueType> {
+        match expression {
+            Expression::Assignment(assignment) => self.assignment(assignment),
+            Expression::PropertyAssignment(assignment) => {
+                self.path(&assignment.path);
+                let value = self.expression(&assignment.value);
+                match assignment.operator.token_type {
+                    TokenType::Equal => value,
+                    _ => None,
+                }
+            }
+            Expression::Conditional(conditional) => {
+                self.expression(&conditional.condition);
+                let then_branch = self.expression(&conditional.then_branch);
+                let else_branch = self.expression(&conditional.else_branch);
+                same(then_branch, else_branch)
+            }
+            Expression::Binary(binary) => {
+                let (chain, left) = binary.chain();
+                let mut result = self.expression(left);
+
+                for binary in chain.into_iter().rev() {
+                    let right = self.expression(&binary.right);
+                    result = self.operator(
+                        binary.operator.token_type,
+                        result,
+                        right,
+                        binary.operator.line,
+                    );
+                }
+
+                result
+            }
+            Expression::Unary(unary) => {
+                let right = self.expression(&unary.right);
+                match unary.operator.token_type {
+                    TokenType::Minus => match right {
+                        Some(ValueType::Number) | None => right,
+                        Some(right) => {
+                            let message = format!("Cannot apply '-' to {}", right);
+                            self.error(unary.operator.line, message);
+                            None
+                        }
+                    },
+                    _ => Some(ValueType::Boolean),
+                }
+            }
+            Expression::Delete(delete) => {
+                self.path(&delete.path);
+                Some(ValueType::Boolean)
+            }
+            Expression::Postfix(postfix) => {
+                let left = self.expression(&postfix.left);
+
+                match postfix.operator {
+                    PostfixOperator::Index(ref index) => {
+                        self.expression(index);
+                        // Strings are indexed by character.
+                        left.filter(|left| *left == ValueType::String)
+                    }
+                    PostfixOperator::Dot(ref name) => match (left, name.as_str()) {
+                        (
+                            Some(ValueType::String | ValueType::Array | ValueType::Function),
+                            "length",
+                        ) => Some(ValueType::Number),
+                        (Some(ValueType::Function), "name") => Some(ValueType::String),
+                        _ => None,
+                    },
+                    PostfixOperator::Call(ref arguments) => {
+                        let arguments: Vec<Option<ValueType>> = arguments
+                            .iter()
+                            .map(|argument| self.expression(argument))
+                            .collect();
+
+                        let signature = match *postfix.left {
+                            Expression::Identifier(ref callee) => {
+                                self.lookup(&callee.identifier).signature
+                            }
+                            _ => None,
+                        };
+                        let signature = signature?;
+
+                        for ((parameter, expected), found) in
+                            signature.parameters.iter().zip(arguments)
+                        {
+                            self.expect(
+                                *expected,
+                                found,
+                                || format!("parameter '{}' of '{}'", parameter, signature.name),
+                                postfix.token.line,
+                            );
+                        }
+
+                        signature.return_type
+                    }
+                }
+            }
+            Expression::Identifier(identifier) => self.lookup(&identifier.identifier).value_type,
+            Expression::Array(array) => {
+                for element in &array.elements {
+                    self.expression(element);
+                }
+                Some(ValueType::Array)
+            }
+            Expression::Match(expression) => {
+                self.expression(&expression.value);
+
+                let mut result = None;
+                for (index, arm) in expression.arms.iter().enumerate() {
+                    // The values the pattern declares may be anything.
+                    self.scopes.push(HashMap::new());
+                    for name in arm.pattern.bindings() {
+                        self.declare(name, Binding::default());
+                    }
+                    let body = self.expression(&arm.body);
+                    self.scopes.pop();
+
+                    result = if index == 0 { body } else { same(result, body) };
+                }
+                result
+            }
+            Expression::Literal(literal) => Some(literal.value_type()),
+        }
+    }
+
+    fn assignment(&mut self, assignment: &AssignmentExpression) -> Option<ValueType> {
+        let value = self.expression(&assignment.value);
+        let target = self.lookup(&assignment.identifier);
+
+        let operator = match assignment.operator {
+            TokenType::PlusEqual => TokenType::Plus,
+            TokenType::MinusEqual => TokenType::Minus,
+            TokenType::StarEqual => TokenType::Star,
+            TokenType::SlashEqual => TokenType::Slash,
+            _ => TokenType::Equal,
+        };
+        let result = match operator {
+            TokenType::Equal => value,
+            operator => self.operator(
+                operator,
+                target.value_type,
+                value,
+                assignment.identifier.line,
+            ),
+        };
+
+        if target.annotated {
+            self.expect(
+                target.value_type,
+                result,
+                || format!("variable '{}'", assignment.identifier.name()),
+                assignment.identifier.line,
+            );
+        }
+
+        result
+    }
+
+    fn path(&mut self, path: &[(PostfixOperator, Token)]) {
+        for (operator, _) in path {
+            if let PostfixOperator::Index(index) = operator {
+                self.expression(index);
+            }
+        }
+    }
+
+    // The type of a binary operator applied to values of `left` and `right`, reporting the
+    // operands it fails on at runtime. Only operands of known types are reported.
+    fn operator(
+        &mut self,
+        operator: TokenType,
+        left: Option<ValueType>,
+        right: Option<ValueType>,
+        line: u32,
+    ) -> Option<ValueType> {
+        use ValueType::{Number, String};
+
+        let result = match operator {
+            TokenType::Plus => match (left, right) {
+                (Some(Number), Some(Number)) => Ok(Some(Number)),
+                (Some(String), Some(String | Number)) | (Some(Number), Some(String)) => {
+                    Ok(Some(String))
+                }
+                (Some(String), None) | (None, Some(String)) => Ok(Some(String)),
+                (Some(_), Some(_)) => Err("+"),
+                _ => Ok(None),
+            },
+            TokenType::Minus | TokenType::Star | TokenType::Slash => match (left, right) {
+                (Some(left), Some(right)) if left != Number || right != Number => {
+                    Err(match operator {
+                        TokenType::Minus => "-",
+                        TokenType::Star => "*",
+                        _ => "/",
+                    })
+                }
+                _ => Ok(Some(Number)),
+            },
+            TokenType::And | TokenType::Or => Ok(same(left, right)),
+            _ => Ok(Some(ValueType::Boolean)),
+        };
+
+        match (result, left, right) {
+            (Ok(result), _, _) => result,
+            (Err(operator), Some(left), Some(right)) => {
+                let message = format!("Cannot apply '{}' to {} and {}", operator, left, right);
+                self.error(line, message);
+                None
+            }
+            (Err(_), _, _) => None,
+        }
+    }
+}
+
+// The type of a value that is one of two, known only when both are the same.
+fn same(a: Option<ValueType>, b: Option<ValueType>) -> Option<ValueType> {
+    if a == b {
+        a
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interpreter::Interpreter;
+
+    fn check(source: &str) -> Vec<String> {
+        match Interpreter::new().typecheck(source.as_bytes()) {
+            Ok(()) => Vec::new(),
+            Err(error) => error
+                .errors()
+                .into_iter()
+                .map(|error| format!("{}: {}", error.line().unwrap(), error.message()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_accepted() {
+        for source in [
+            "let count: number = 0 count += 1 count = count * 2",
+            "let name: string = \"a\" name += 1 name = name + name",
+            "let later: bool later = 1 < 2",
+            "function f(x: number): string { return \"\" + x } let s: string = f(1)",
+            "function g(): null { return } function h(): null {}",
+            // Unknown types are never reported.
+            "let n = \"1\" let m: number = n",
+            "let xs = [1] let first: number = xs[0]",
+            "function f(x: number) { return x } let s: string = f(1)",
+            "let x: number = len(\"abc\") let y: string = jsonParse(\"1\")",
+            "function f(x: array) { } f(range(3))",
+            "let x = 1 x = \"x\" print x - 1",
+            // Branches of different types give any type.
+            "let c = 1 let x: string = c ? \"a\" : 1",
+            "let a: number = 1 let b: string = \"1\" let t: string = a & b",
+            "const s = \"abc\" let c: string = s[0] let n: number = s.length",
+            "for (const c in \"ab\") { let d: string = c }",
+            "function g() {} const f = g let x: number = f()",
+            "function* g(): generator { yield 1 return \"done\" } let gen: generator = g()",
+            "function* g() { for (const x in g()) { let n: number = x } }",
+            // Arms of different types give any type.
+            "let n: number = match 1 { 1 => 2, _ => \"a\" }",
+            "let s: string = match [] { [x] => x, _ => \"\" }",
+        ] {
+            assert_eq!(check(source), Vec::<String>::new(), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_rejected() {
+        for (source, expected) in [
+            (
+                "let count: number = \"0\"",
+                "1: Expected number for variable 'count', found string",
+            ),
+            (
+                "let count: number = 0\ncount = null",
+                "2: Expected number for variable 'count', found null",
+            ),
+            (
+                "let count: number = 0\ncount += \"!\"",
+                "2: Expected number for variable 'count', found string",
+            ),
+            (
+                "const name = \"a\"\nlet n: number = name",
+                "2: Expected number for variable 'n', found string",
+            ),
+            (
+                "function f(x: number, s: string) {}\nf(1, 2)",
+                "2: Expected string for parameter 's' of 'f', found number",
+            ),
+            (
+                "function f(x: number) { x = [] }",
+                "1: Expected number for variable 'x', found array",
+            ),
+            (
+                "function f(): string {\n    return 1 == 1\n}",
+                "2: Expected string for return value of 'f', found boolean",
+            ),
+            (
+                "function f(): array { return }",
+                "1: Expected array for return value of 'f', found null",
+            ),
+            (
+                "function f(): number { return 1 }\nlet s: string = f()",
+                "2: Expected string for variable 's', found number",
+            ),
+            (
+                "let a: number = 1\nlet b: bool = true\nprint a + b",
+                "3: Cannot apply '+' to number and boolean",
+            ),
+            ("print [1] * 2", "1: Cannot apply '*' to array and number"),
+            ("print -\"a\"", "1: Cannot apply '-' to string"),
+            (
+                "let n: number = match 1 {\n    1 => \"a\",\n    _ => \"b\",\n}",
+                "1: Expected number for variable 'n', found string",
+            ),
+            (
+                "function* g() {}\nlet n: number = g()",
+                "2: Expected number for variable 'n', found generator",
+            ),
+            (
+                "function* g(): array {}",
+                "1: Expected array for return value of 'g', found generator",
+            ),
+            (
+                "function* g() {\n    yield 1 + null\n}",
+                "2: Cannot apply '+' to number and null",
+            ),
+            // Code that never runs is checked too.
+            (
+                "if (false) { let x: object = 1 }",
+                "1: Expected object for variable 'x', found number",
+            ),
+        ] {
+            assert_eq!(check(source), [expected], "{}", source);
+        }
+
+        // Every mismatch is reported, but not those caused by another.
+        assert_eq!(
+            check("let a: number = \"a\"\nlet b: string = ([] + 1) + 2"),
+            [
+                "1: Expected number for variable 'a', found string",
+                "2: Cannot apply '+' to array and number",
+            ]
+        );
+    }
+}
@@ -1,16 +1,158 @@
 use core::fmt;
 use std::{
-    io::{Error, Result},
+    cmp::Ordering,
     ops::{Add, Div, Mul, Neg, Not, Sub},
+    rc::Rc,
 };
 
-#[derive(PartialEq, Clone, PartialOrd)]
+use serde::{Deserialize, Serialize};
+
+use super::{array::Array, function::Function, generator::Generator, object::Object};
+
+// Arrays and objects nested deeper than this are shown as `[...]` or `{...}` by `Display` and
+// `Debug`, which show a level per call.
+const MAX_DISPLAY_DEPTH: usize = 256;
+
+// Numbers are shown as JavaScript shows them:
+// - With the fewest digits that read back as the same number, so `0.1 + 0.2` is
+//   `0.30000000000000004` but `0.1` is `0.1`.
+// - `-0` is `0`.
+// - In scientific notation from `1e+21` up and from `1e-7` down, e.g. `1.5e-7`.
+// - Infinities are `Infinity` and `-Infinity` rather than Rust's `inf`, NaN is `NaN`.
+pub fn number_to_string(num: f64) -> String {
+    if num.is_nan() {
+        return "NaN".to_string();
+    }
+    if num.is_infinite() {
+        return if num > 0.0 { "Infinity" } else { "-Infinity" }.to_string();
+    }
+    if num == 0.0 {
+        return "0".to_string();
+    }
+
+    // Rust's scientific notation has the shortest digits, e.g. `1.5e-7`.
+    let scientific = format!("{:e}", num.abs());
+    let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+    let digits = mantissa.replace('.', "");
+    // The number is `0.digits` times 10 to the power of `point`.
+    let point = exponent.parse::<i32>().unwrap_or(0) + 1;
+    let count = digits.len() as i32;
+
+    let unsigned = if count <= point && point <= 21 {
+        digits + &"0".repeat((point - count) as usize)
+    } else if 0 < point && point <= 21 {
+        format!(
+            "{}.{}",
+            &digits[..point as usize],
+            &digits[point as usize..]
+        )
+    } else if -6 < point && point <= 0 {
+        format!("0.{}{}", "0".repeat(-point as usize), digits)
+    } else {
+        let sign = if point > 0 { '+' } else { '-' };
+        let (first, rest) = digits.split_at(1);
+        let fraction = if rest.is_empty() {
+            String::new()
+        } else {
+            format!(".{}", rest)
+        };
+        format!("{}{}e{}{}", first, fraction, sign, (point - 1).abs())
+    };
+
+    if num < 0.0 {
+        format!("-{}", unsigned)
+    } else {
+        unsigned
+    }
+}
+
+/// The type of a value, as named in type annotations such as `let count: number = 0`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValueType {
+    Null,
+    Number,
+    String,
+    Boolean,
+    Array,
+    Object,
+    Function,
+    Enum,
+    Generator,
+}
+
+impl ValueType {
+    pub const ALL: [ValueType; 9] = [
+        ValueType::Null,
+        ValueType::Number,
+        ValueType::String,
+        ValueType::Boolean,
+        ValueType::Array,
+        ValueType::Object,
+        ValueType::Function,
+        ValueType::Enum,
+        ValueType::Generator,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ValueType::Null => "null",
+            ValueType::Number => "number",
+            ValueType::String => "string",
+            ValueType::Boolean => "boolean",
+            ValueType::Array => "array",
+            ValueType::Object => "object",
+            ValueType::Function => "function",
+            ValueType::Enum => "enum",
+            ValueType::Generator => "generator",
+        }
+    }
+
+    /// The type with the given name, where `bool` is short for `boolean`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "bool" => Some(ValueType::Boolean),
+            name => Self::ALL
+                .into_iter()
+                .find(|value_type| value_type.name() == name),
+        }
+    }
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Clone)]
 pub enum Value {
     Null,
     Number(f64),
-    String(String),
+    // Strings are immutable, so clones share the same buffer.
+    String(Rc<str>),
     Boolean(bool),
-    Array(Vec<Value>),
+    Array(Array),
+    Object(Object),
+    Function(Function),
+    // Shared, so that values stay small.
+    EnumVariant(Rc<EnumVariant>),
+    // Shared by its copies, which all resume the same body.
+    Generator(Rc<Generator>),
+}
+
+/// A variant of an `enum` declaration, e.g. `State.Idle`. It is equal only to the same variant of
+/// an enum with the same name.
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct EnumVariant {
+    pub enum_name: Rc<str>,
+    pub variant: Rc<str>,
+}
+
+impl fmt::Display for EnumVariant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}", self.enum_name, self.variant)
+    }
 }
 
 impl Value {
@@ -21,36 +163,109 @@ impl Value {
             Value::String(str) => !str.is_empty(),
             Value::Boolean(bool) => *bool,
             Value::Array(arr) => !arr.is_empty(),
+            Value::Object(obj) => !obj.is_empty(),
+            Value::Function(_) | Value::EnumVariant(_) | Value::Generator(_) => true,
         }
     }
-}
 
-impl fmt::Debug for Value {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    pub fn value_type(&self) -> ValueType {
         match self {
-            Value::Null => write!(f, "Null"),
-            Value::Number(num) => write!(f, "{}", num),
-            Value::String(str) => write!(f, "\"{}\"", str),
-            Value::Boolean(bool) => write!(f, "{}", bool),
-            Value::Array(arr) => {
-                write!(f, "[")?;
-                for (i, val) in arr.iter().enumerate() {
-                    if i != 0 {
-                        write!(f, ", ")?;
+            Value::Null => ValueType::Null,
+            Value::Number(_) => ValueType::Number,
+            Value::String(_) => ValueType::String,
+            Value::Boolean(_) => ValueType::Boolean,
+            Value::Array(_) => ValueType::Array,
+            Value::Object(_) => ValueType::Object,
+            Value::Function(_) => ValueType::Function,
+            Value::EnumVariant(_) => ValueType::Enum,
+            Value::Generator(_) => ValueType::Generator,
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        self.value_type().name()
+    }
+
+    // Iterate over the elements of an array, the characters of a string or the keys of an object.
+    pub fn iter(&self) -> Option<Box<dyn Iterator<Item = Value> + '_>> {
+        match self {
+            Value::Array(arr) => Some(Box::new(arr.iter().cloned())),
+            Value::String(str) => Some(Box::new(
+                str.chars().map(|chr| Value::String(chr.to_string().into())),
+            )),
+            Value::Object(obj) => Some(Box::new(obj.iter().map(|(key, _)| key.to_value()))),
+            _ => None,
+        }
+    }
+
+    // Copy nested arrays and objects, sharing functions. Gives `None` if arrays and objects are
+    // nested more than `max_depth` levels deep.
+    pub fn deep_copy(&self, max_depth: usize) -> Option<Value> {
+        match self {
+            Value::Array(_) | Value::Object(_) if max_depth == 0 => None,
+            Value::Array(arr) => arr
+                .iter()
+                .map(|value| value.deep_copy(max_depth - 1))
+                .collect::<Option<Array>>()
+                .map(Value::Array),
+            Value::Object(obj) => {
+                let mut copy = Object::new();
+                for (key, value) in obj.iter() {
+                    copy.insert(key.clone(), value.deep_copy(max_depth - 1)?);
+                }
+                Some(Value::Object(copy))
+            }
+            value => Some(value.clone()),
+        }
+    }
+
+    // Whether the value can't be modified. Arrays and objects can be frozen, other values never
+    // change anyway.
+    pub fn is_frozen(&self) -> bool {
+        match self {
+            Value::Array(arr) => arr.is_frozen(),
+            Value::Object(obj) => obj.is_frozen(),
+            _ => true,
+        }
+    }
+
+    // Freeze an array or object, and with `deep` the arrays and objects nested in it, which are
+    // taken from a stack rather than recursively.
+    pub fn freeze(&mut self, deep: bool) {
+        let mut values = vec![self];
+        while let Some(value) = values.pop() {
+            match value {
+                Value::Array(arr) => {
+                    arr.freeze();
+                    if deep {
+                        values.extend(arr.elements_mut().iter_mut());
                     }
-                    write!(f, "{:?}", val)?;
                 }
-                write!(f, "]")
+                Value::Object(obj) => {
+                    obj.freeze();
+                    if deep {
+                        values.extend(obj.iter_mut().map(|(_, value)| value));
+                    }
+                }
+                _ => {}
             }
         }
     }
-}
 
-impl fmt::Display for Value {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    // The form given by `Debug`, or by `Display` without `debug`, of a value nested `depth`
+    // levels deep.
+    pub(super) fn write(&self, f: &mut fmt::Formatter, debug: bool, depth: usize) -> fmt::Result {
         match self {
+            Value::Array(arr) if depth >= MAX_DISPLAY_DEPTH && !arr.is_empty() => {
+                write!(f, "[...]")
+            }
+            Value::Object(obj) if depth >= MAX_DISPLAY_DEPTH && !obj.is_empty() => {
+                write!(f, "{{...}}")
+            }
             Value::Null => write!(f, "Null"),
-            Value::Number(num) => write!(f, "{}", num),
+            Value::Number(num) => write!(f, "{}", number_to_string(*num)),
+            // Quoted, with quotes and control characters escaped.
+            Value::String(str) if debug => write!(f, "{:?}", str),
             Value::String(str) => write!(f, "{}", str),
             Value::Boolean(bool) => write!(f, "{}", bool),
             Value::Array(arr) => {
@@ -59,72 +274,246 @@ impl fmt::Display for Value {
                     if i != 0 {
                         write!(f, ", ")?;
                     }
-                    write!(f, "{}", val)?;
+                    val.write(f, debug, depth + 1)?;
                 }
                 write!(f, "]")
             }
+            Value::Object(obj) => obj.write(f, debug, depth),
+            Value::Function(function) => write!(f, "{}", function),
+            Value::EnumVariant(variant) => write!(f, "{}", variant),
+            Value::Generator(generator) => write!(f, "{}", generator),
+        }
+    }
+
+    // `===`: values are equal only if they are of the same type and `==` holds. Arrays and
+    // objects are compared element by element, functions by identity. `==` currently requires
+    // the same type as well, but unlike `===` it is allowed to grow coercions.
+    pub fn strict_eq(&self, other: &Value) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other) && self == other
+    }
+
+    // Position of the variant in the declaration, used to order values of different types.
+    fn rank(&self) -> u8 {
+        match self {
+            Value::Null => 0,
+            Value::Number(_) => 1,
+            Value::String(_) => 2,
+            Value::Boolean(_) => 3,
+            Value::Array(_) => 4,
+            Value::Object(_) => 5,
+            Value::Function(_) => 6,
+            Value::EnumVariant(_) => 7,
+            Value::Generator(_) => 8,
+        }
+    }
+}
+
+// Equality as given by `==` in scripts, for every pair of types:
+// - Values of different types are never equal, e.g. `1 == "1"` and `null == false` are false.
+// - `null` is equal to itself.
+// - Numbers compare as IEEE 754 floats, so NaN is not equal to anything, itself included. Use
+//   `isNaN` to test for it. `0` and `-0` are equal.
+// - Strings and booleans compare by value.
+// - Arrays are equal if they have the same length and their elements are equal in order.
+// - Objects are equal if they have the same keys, in any order, with equal values.
+// - Functions are equal only to themselves, see `Function`.
+// - Enum variants are equal if they have the same name and belong to enums with the same name.
+// - Generators are equal only to themselves and their copies.
+// Arrays and objects are compared however deeply they nest, and being frozen doesn't matter.
+// Nested arrays and objects are compared from a stack of pairs rather than recursively.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        let mut pairs = vec![(self, other)];
+        while let Some(pair) = pairs.pop() {
+            let equal = match pair {
+                (Value::Null, Value::Null) => true,
+                (Value::Number(val1), Value::Number(val2)) => val1 == val2,
+                (Value::String(val1), Value::String(val2)) => val1 == val2,
+                (Value::Boolean(val1), Value::Boolean(val2)) => val1 == val2,
+                (Value::Array(val1), Value::Array(val2)) => {
+                    pairs.extend(val1.iter().zip(val2.iter()));
+                    val1.len() == val2.len()
+                }
+                (Value::Object(val1), Value::Object(val2)) => {
+                    val1.len() == val2.len()
+                        && val1.iter().all(|(key, value)| match val2.get(key) {
+                            Some(other) => {
+                                pairs.push((value, other));
+                                true
+                            }
+                            None => false,
+                        })
+                }
+                (Value::Function(val1), Value::Function(val2)) => val1 == val2,
+                (Value::EnumVariant(val1), Value::EnumVariant(val2)) => val1 == val2,
+                (Value::Generator(val1), Value::Generator(val2)) => Rc::ptr_eq(val1, val2),
+                _ => false,
+            };
+            if !equal {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Null, Value::Null) => Some(Ordering::Equal),
+            (Value::Number(val1), Value::Number(val2)) => val1.partial_cmp(val2),
+            (Value::String(val1), Value::String(val2)) => val1.partial_cmp(val2),
+            (Value::Boolean(val1), Value::Boolean(val2)) => val1.partial_cmp(val2),
+            (Value::Array(val1), Value::Array(val2)) => compare_arrays(val1, val2),
+            (Value::Object(_), _) | (_, Value::Object(_)) => None,
+            (Value::Function(_), _) | (_, Value::Function(_)) => None,
+            (Value::EnumVariant(_), _) | (_, Value::EnumVariant(_)) => None,
+            (Value::Generator(_), _) | (_, Value::Generator(_)) => None,
+            _ => self.rank().partial_cmp(&other.rank()),
+        }
+    }
+}
+
+// Arrays are ordered by their first elements that differ, and by length when one starts with the
+// other. Nested arrays are compared from a stack rather than recursively.
+fn compare_arrays(arr1: &Array, arr2: &Array) -> Option<Ordering> {
+    let mut stack = vec![(arr1.iter(), arr2.iter())];
+    while let Some((iter1, iter2)) = stack.last_mut() {
+        match (iter1.next(), iter2.next()) {
+            (None, None) => {
+                stack.pop();
+            }
+            (None, Some(_)) => return Some(Ordering::Less),
+            (Some(_), None) => return Some(Ordering::Greater),
+            (Some(Value::Array(val1)), Some(Value::Array(val2))) => {
+                stack.push((val1.iter(), val2.iter()))
+            }
+            (Some(val1), Some(val2)) => match val1.partial_cmp(val2) {
+                Some(Ordering::Equal) => {}
+                ordering => return ordering,
+            },
+        }
+    }
+
+    Some(Ordering::Equal)
+}
+
+// Drop `values` and the arrays and objects nested in them, taking the nested ones apart one at a
+// time so that dropping a deeply nested value doesn't recurse. Arrays that share their elements
+// with another one are only released.
+pub(super) fn drop_nested(mut values: Vec<Value>) {
+    while let Some(value) = values.pop() {
+        match value {
+            Value::Array(mut arr) => values.append(&mut arr.take_unshared()),
+            Value::Object(mut obj) => values.append(&mut obj.take_unshared()),
+            _ => {}
+        }
+    }
+}
+
+impl fmt::Debug for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, true, 0)
+    }
+}
+
+// `{:#}` gives the pretty form.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if f.alternate() {
+            return write!(f, "{}", self.pretty());
+        }
+
+        self.write(f, false, 0)
+    }
+}
+
+// An operator applied to operands of types it does not support, e.g. `true + 1`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OperandError {
+    pub operator: &'static str,
+    pub left: &'static str,
+    // `None` for unary operators.
+    pub right: Option<&'static str>,
+}
+
+impl OperandError {
+    fn binary(operator: &'static str, left: &Value, right: &Value) -> Self {
+        OperandError {
+            operator,
+            left: left.type_name(),
+            right: Some(right.type_name()),
         }
     }
 }
 
+pub type OperatorResult = Result<Value, OperandError>;
+
 impl Add for Value {
-    type Output = Result<Self>;
+    type Output = OperatorResult;
 
-    fn add(self, other: Self) -> Result<Self> {
+    fn add(self, other: Self) -> OperatorResult {
         match (self, other) {
             (Value::Number(val1), Value::Number(val2)) => Ok(Value::Number(val1 + val2)),
-            (Value::String(val1), Value::String(val2)) => Ok(Value::String(val1 + &val2)),
-            (Value::Number(val1), Value::String(val2)) => {
-                Ok(Value::String(val2 + &val1.to_string()))
+            (Value::String(val1), Value::String(val2)) => {
+                Ok(Value::String([&*val1, &*val2].concat().into()))
             }
-            (Value::String(val1), Value::Number(val2)) => {
-                Ok(Value::String(val1 + &val2.to_string()))
+            (Value::Number(val1), Value::String(val2)) => {
+                Ok(Value::String((number_to_string(val1) + &val2).into()))
             }
-            _ => Err(Error::new(std::io::ErrorKind::Other, "Invalid Operands")),
+            (Value::String(val1), Value::Number(val2)) => Ok(Value::String(
+                [&*val1, &number_to_string(val2)].concat().into(),
+            )),
+            (val1, val2) => Err(OperandError::binary("+", &val1, &val2)),
         }
     }
 }
 
 impl Sub for Value {
-    type Output = Result<Self>;
+    type Output = OperatorResult;
 
-    fn sub(self, other: Self) -> Result<Self> {
+    fn sub(self, other: Self) -> OperatorResult {
         match (self, other) {
             (Value::Number(val1), Value::Number(val2)) => Ok(Value::Number(val1 - val2)),
-            _ => Err(Error::new(std::io::ErrorKind::Other, "Invalid Operands")),
+            (val1, val2) => Err(OperandError::binary("-", &val1, &val2)),
         }
     }
 }
 
 impl Mul for Value {
-    type Output = Result<Self>;
+    type Output = OperatorResult;
 
-    fn mul(self, other: Self) -> Result<Self> {
+    fn mul(self, other: Self) -> OperatorResult {
         match (self, other) {
             (Value::Number(val1), Value::Number(val2)) => Ok(Value::Number(val1 * val2)),
-            _ => Err(Error::new(std::io::ErrorKind::Other, "Invalid Operands")),
+            (val1, val2) => Err(OperandError::binary("*", &val1, &val2)),
         }
     }
 }
 
 impl Div for Value {
-    type Output = Result<Self>;
+    type Output = OperatorResult;
 
-    fn div(self, other: Self) -> Result<Self> {
+    fn div(self, other: Self) -> OperatorResult {
         match (self, other) {
             (Value::Number(val1), Value::Number(val2)) => Ok(Value::Number(val1 / val2)),
-            _ => Err(Error::new(std::io::ErrorKind::Other, "Invalid Operands")),
+            (val1, val2) => Err(OperandError::binary("/", &val1, &val2)),
         }
     }
 }
 
 impl Neg for Value {
-    type Output = Result<Self>;
+    type Output = OperatorResult;
 
-    fn neg(self) -> Result<Self> {
+    fn neg(self) -> OperatorResult {
         match self {
             Value::Number(val1) => Ok(Value::Number(-val1)),
-            _ => Err(Error::new(std::io::ErrorKind::Other, "Invalid Operands")),
+            val1 => Err(OperandError {
+                operator: "-",
+                left: val1.type_name(),
+                right: None,
+            }),
         }
     }
 }
@@ -136,3 +525,161 @@ impl Not for Value {
         Value::Boolean(!self.is_truthy())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Interpreter;
+
+    #[test]
+    fn test_number_to_string() {
+        let cases = [
+            (0.0, "0"),
+            (-0.0, "0"),
+            (1.0, "1"),
+            (-5.0, "-5"),
+            (123.456, "123.456"),
+            (0.1 + 0.2, "0.30000000000000004"),
+            (1.0 / 3.0, "0.3333333333333333"),
+            (1e20, "100000000000000000000"),
+            (1e21, "1e+21"),
+            (-1.5e21, "-1.5e+21"),
+            (2f64.powi(70), "1.1805916207174113e+21"),
+            (1e-6, "0.000001"),
+            (1e-7, "1e-7"),
+            (1.5e-7, "1.5e-7"),
+            (f64::MAX, "1.7976931348623157e+308"),
+            (5e-324, "5e-324"),
+            (f64::NAN, "NaN"),
+            (f64::INFINITY, "Infinity"),
+            (f64::NEG_INFINITY, "-Infinity"),
+        ];
+
+        for (num, expected) in cases {
+            assert_eq!(number_to_string(num), expected, "{:?}", num);
+            // The digits read back as the same number.
+            if num.is_finite() {
+                assert_eq!(expected.parse::<f64>().unwrap(), num);
+            }
+        }
+    }
+
+    #[test]
+    fn test_number_display() {
+        let mut interpreter = Interpreter::new();
+        let (result, printed) = interpreter.interpret_capture(
+            b"println 0.1 + 0.2
+            println -0
+            println 1000000000000000000000
+            println \"x\" + 0.0000001
+            print jsonStringify([-0, 0.0000001])",
+        );
+        result.unwrap();
+        assert_eq!(printed, "0.30000000000000004\n0\n1e+21\nx1e-7\n[0,1e-7]");
+
+        // As echoed by the REPL.
+        let value = interpreter
+            .interpret(b"[-0, 1000000000000000000000]")
+            .unwrap();
+        assert_eq!(format!("{:#}", value), "[0, 1e+21]");
+    }
+
+    #[test]
+    fn test_equality() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .interpret(
+                br#"function f() {}
+                function g() {}
+                const fs = [f]
+                let o = jsonParse("{\"a\": 1, \"b\": [2]}")
+                let p = jsonParse("{\"b\": [2], \"a\": 1}")
+                let q = jsonParse("{\"a\": 1}")"#,
+            )
+            .unwrap();
+
+        let cases = [
+            ("null", "null", true),
+            ("null", "false", false),
+            ("null", "0", false),
+            ("1", "1", true),
+            ("0", "-0", true),
+            ("1", "\"1\"", false),
+            ("NaN", "NaN", false),
+            ("\"a\"", "\"a\"", true),
+            ("\"a\"", "\"b\"", false),
+            ("true", "true", true),
+            ("true", "1", false),
+            ("[]", "[]", true),
+            ("[1, [2, 3]]", "[1, [2, 3]]", true),
+            ("[1, [2, 3]]", "[1, [2, 4]]", false),
+            ("[1, 2]", "[1, 2, 3]", false),
+            ("[NaN]", "[NaN]", false),
+            ("[1]", "freeze([1])", true),
+            ("o", "p", true),
+            ("o", "q", false),
+            ("[o]", "[deepFreeze(p)]", true),
+            ("o", "[1, [2]]", false),
+            ("f", "f", true),
+            ("f", "g", false),
+            ("[f]", "fs", true),
+            ("[f, [f]]", "[f, [f]]", true),
+            ("[f]", "[g]", false),
+            ("len", "len", true),
+            ("len", "f", false),
+            ("f", "null", false),
+        ];
+
+        for (left, right, equal) in cases {
+            for (left, right) in [(left, right), (right, left)] {
+                let source = format!("[{} == {}, {} != {}]", left, right, left, right);
+                assert_eq!(
+                    interpreter
+                        .interpret(source.as_bytes())
+                        .unwrap()
+                        .to_string(),
+                    format!("[{}, {}]", equal, !equal),
+                    "{}",
+                    source
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_deep_nesting() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .interpret(
+                b"let a = []
+                let b = []
+                let i = 0
+                while (i < 200000) {
+                    a = [a]
+                    b = [b]
+                    i = i + 1
+                }
+                b = deepFreeze(b)",
+            )
+            .unwrap();
+
+        let eval = |interpreter: &mut Interpreter, source: &str| {
+            interpreter
+                .interpret(source.as_bytes())
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(
+            eval(&mut interpreter, "[a == b, a < b, [a] < [b, 1]]"),
+            "[true, false, true]"
+        );
+        assert_eq!(eval(&mut interpreter, "len(a.flat(Infinity))"), "0");
+
+        let shown = eval(&mut interpreter, "a");
+        assert_eq!(shown, "[".repeat(256) + "[...]" + &"]".repeat(256));
+        assert_eq!(eval(&mut interpreter, "repr(a)"), shown);
+        assert!(interpreter.interpret(b"jsonStringify(a)").is_err());
+
+        // Dropping the interpreter drops the nested arrays.
+    }
+}
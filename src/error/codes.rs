@@ -0,0 +1,386 @@
+use super::{parse::ErrorCode, runtime::RuntimeError};
+
+/// A stable error code along with what it means, as printed by `notjs --explain`. Codes starting
+/// with `E` are found while parsing, those starting with `R` while running, and those starting
+/// with `I` when reading the program. A code is never given to another kind of error, so
+/// documentation and editors can rely on it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Code {
+    pub code: &'static str,
+    pub name: &'static str,
+    pub explanation: &'static str,
+}
+
+/// Every error code, in order.
+pub const CODES: &[Code] = &[
+    Code {
+        code: "E001",
+        name: "unexpected-token",
+        explanation: "A token appeared where the grammar doesn't allow it, such as `to` where \
+                      an import expects `from`.",
+    },
+    Code {
+        code: "E002",
+        name: "missing-token",
+        explanation: "A token the statement needs is missing, such as the name after `let` or \
+                      the closing `)` of a call. The message names the token that was expected.",
+    },
+    Code {
+        code: "E003",
+        name: "unexpected-eof",
+        explanation: "The file ended in the middle of a statement, usually because of an \
+                      unclosed `(`, `[` or `{`.",
+    },
+    Code {
+        code: "E004",
+        name: "expected-expression",
+        explanation: "A value was expected, e.g. after an operator or `=`, but the next token \
+                      can't start one. Strings and comments that are never closed, and strings \
+                      with an unknown escape, are reported this way too.",
+    },
+    Code {
+        code: "E005",
+        name: "invalid-assignment-target",
+        explanation: "Only variables, array elements and object properties can be assigned to, \
+                      and only elements and properties deleted, so `1 = 2`, `f() = 3` and \
+                      `delete x` are rejected.",
+    },
+    Code {
+        code: "E006",
+        name: "too-deeply-nested",
+        explanation: "Expressions and statements are nested deeper than the parser allows. Split \
+                      the expression with intermediate variables.",
+    },
+    Code {
+        code: "E007",
+        name: "unknown-type",
+        explanation: "A type annotation names a type that doesn't exist. The types are `number`, \
+                      `string`, `boolean` (or `bool`), `null`, `array`, `object`, `function` \
+                      and `enum`.",
+    },
+    Code {
+        code: "E008",
+        name: "duplicate-variant",
+        explanation: "An `enum` declaration lists the same variant twice. Each variant must have \
+                      its own name.",
+    },
+    Code {
+        code: "E010",
+        name: "undeclared-variable",
+        explanation: "A name is used that no `let`, `const`, function, import or builtin \
+                      declares in a visible scope. Check the spelling, or declare it before use.",
+    },
+    Code {
+        code: "E011",
+        name: "immutable-variable",
+        explanation: "A variable declared with `const` is assigned after its declaration. \
+                      Declare it with `let` if it has to change.",
+    },
+    Code {
+        code: "E020",
+        name: "misplaced-import",
+        explanation: "`import` is only allowed at the top level of a file, not inside blocks or \
+                      functions.",
+    },
+    Code {
+        code: "E021",
+        name: "misplaced-export",
+        explanation: "`export` is only allowed before declarations at the top level of a file.",
+    },
+    Code {
+        code: "E022",
+        name: "import-failed",
+        explanation: "An imported file couldn't be read or didn't parse. The message gives the \
+                      path that was tried, relative to the importing file.",
+    },
+    Code {
+        code: "E023",
+        name: "import-cycle",
+        explanation: "Files import each other in a cycle. Move what they share to a third file \
+                      that both import.",
+    },
+    Code {
+        code: "E024",
+        name: "misplaced-yield",
+        explanation: "A `yield` was found outside of a generator function. Declare the function \
+                      with `function*` to make it a generator, see R023.",
+    },
+    Code {
+        code: "E025",
+        name: "misplaced-test",
+        explanation:
+            "A test block was found inside a block or function. Tests are declared at the \
+                      top level of a file, and run with `notjs test FILE`.",
+    },
+    Code {
+        code: "E030",
+        name: "invalid-ast",
+        explanation: "A syntax tree given as JSON doesn't describe a valid program.",
+    },
+    Code {
+        code: "E040",
+        name: "type-mismatch",
+        explanation: "With `--typecheck`, a value was found to have a different type than the \
+                      annotation of the variable, parameter or return value it is given to, or \
+                      an operator was applied to types it doesn't work on. Only types known \
+                      before running are checked, the rest are checked as the program runs.",
+    },
+    Code {
+        code: "E050",
+        name: "invalid-pattern",
+        explanation: "A pattern of a `match` arm is malformed, declares the same name twice, or \
+                      declares names in alternatives joined with `|`.",
+    },
+    Code {
+        code: "E051",
+        name: "non-exhaustive-match",
+        explanation: "No arm of a `match` matches every value. Add a last arm with the pattern \
+                      `_`, or a name, for the values the other arms don't match. Arms for both \
+                      `true` and `false` are enough, see R008.",
+    },
+    Code {
+        code: "E060",
+        name: "unused-result",
+        explanation: "`freeze` or `deepFreeze` is called as a statement of its own. Values are \
+                      copied when passed, so the call freezes a copy that is thrown away and \
+                      the variable can still be changed. Assign the result instead, as in \
+                      `xs = freeze(xs)`.",
+    },
+    Code {
+        code: "R001",
+        name: "type-error",
+        explanation: "A value of the wrong type was used, such as looping over a number, \
+                      indexing an array with a string, or using an array or NaN as an object \
+                      key. Values that don't match the type annotation of a variable, parameter \
+                      or return value are reported this way too.",
+    },
+    Code {
+        code: "R002",
+        name: "index-out-of-bounds",
+        explanation: "An array or string was indexed past its end, or with a negative or \
+                      fractional index. Valid indices go from 0 to the length minus one.",
+    },
+    Code {
+        code: "R003",
+        name: "invalid-operands",
+        explanation: "An operator was applied to values it doesn't work on, such as subtracting \
+                      an array or negating a string.",
+    },
+    Code {
+        code: "R004",
+        name: "invalid-operator",
+        explanation: "An operation was applied to a value that doesn't support it, such as \
+                      calling a number or indexing null. The message gives the expression and \
+                      the type of its value.",
+    },
+    Code {
+        code: "R005",
+        name: "undefined-property",
+        explanation: "An object has no property with the name read, e.g. a key missing from \
+                      the JSON given to `jsonParse`.",
+    },
+    Code {
+        code: "R006",
+        name: "unknown-property",
+        explanation: "Values of this type have no property with that name. The message lists \
+                      the properties they do have.",
+    },
+    Code {
+        code: "R007",
+        name: "frozen",
+        explanation: "An element or property of an array or object frozen with `freeze` or \
+                      `deepFreeze` was assigned. Frozen values can't be changed.",
+    },
+    Code {
+        code: "R008",
+        name: "no-match",
+        explanation: "No arm of a `match` matched its value. This happens when the arms only \
+                      cover `true` and `false` and the value isn't a boolean.",
+    },
+    Code {
+        code: "R010",
+        name: "undeclared-variable",
+        explanation: "A name was read that isn't declared where it runs, which the parser \
+                      usually catches first as E010.",
+    },
+    Code {
+        code: "R011",
+        name: "undefined-variable",
+        explanation: "A variable was read before it was given a value, e.g. after `let x` \
+                      without an initializer.",
+    },
+    Code {
+        code: "R012",
+        name: "immutable-variable",
+        explanation: "A constant was assigned while running, which the parser usually catches \
+                      first as E011.",
+    },
+    Code {
+        code: "R020",
+        name: "arity-mismatch",
+        explanation: "A function was called with a different number of arguments than it \
+                      declares.",
+    },
+    Code {
+        code: "R021",
+        name: "out-of-scope-call",
+        explanation: "A function was called after the scope it was declared in ended, e.g. a \
+                      nested function returned from its parent.",
+    },
+    Code {
+        code: "R022",
+        name: "call-depth-exceeded",
+        explanation: "Calls nested deeper than the interpreter allows, usually because of \
+                      recursion without an end.",
+    },
+    Code {
+        code: "R023",
+        name: "generator-running",
+        explanation: "A generator was resumed by its own body, e.g. by calling its `next` method \
+                      or looping over it while it runs. A generator runs one step at a time, so \
+                      its body has to yield before it can be resumed again.",
+    },
+    Code {
+        code: "R024",
+        name: "assertion-failed",
+        explanation: "The condition of an `assert` statement was false. The message gives the \
+                      condition as written, and inside a test the test fails with it.",
+    },
+    Code {
+        code: "R030",
+        name: "budget",
+        explanation: "The program ran more steps than the budget given to the interpreter.",
+    },
+    Code {
+        code: "R031",
+        name: "timeout",
+        explanation: "The program ran for longer than the timeout given with `--timeout` or by \
+                      the host.",
+    },
+    Code {
+        code: "R090",
+        name: "native-error",
+        explanation: "A builtin function rejected its arguments or failed, such as `chr` given \
+                      a string. The message starts with the name of the function.",
+    },
+    Code {
+        code: "I001",
+        name: "io-error",
+        explanation: "The file of the program couldn't be read, e.g. because it doesn't exist \
+                      or is a directory. The message gives the reason from the system.",
+    },
+];
+
+/// The code with the given name, e.g. `E010`, in either case.
+pub fn explain(code: &str) -> Option<&'static Code> {
+    CODES
+        .iter()
+        .find(|known| known.code.eq_ignore_ascii_case(code))
+}
+
+impl ErrorCode {
+    /// The stable code of the error, e.g. `E010`, see [`CODES`].
+    pub fn id(&self) -> &'static str {
+        match self {
+            ErrorCode::UnexpectedToken => "E001",
+            ErrorCode::MissingToken => "E002",
+            ErrorCode::UnexpectedEof => "E003",
+            ErrorCode::ExpectedExpression => "E004",
+            ErrorCode::InvalidAssignmentTarget => "E005",
+            ErrorCode::TooDeeplyNested => "E006",
+            ErrorCode::UnknownType => "E007",
+            ErrorCode::DuplicateVariant => "E008",
+            ErrorCode::UndeclaredVariable => "E010",
+            ErrorCode::ImmutableVariable => "E011",
+            ErrorCode::MisplacedImport => "E020",
+            ErrorCode::MisplacedExport => "E021",
+            ErrorCode::MisplacedYield => "E024",
+            ErrorCode::MisplacedTest => "E025",
+            ErrorCode::ImportFailed => "E022",
+            ErrorCode::ImportCycle => "E023",
+            ErrorCode::InvalidAst => "E030",
+            ErrorCode::TypeMismatch => "E040",
+            ErrorCode::InvalidPattern => "E050",
+            ErrorCode::NonExhaustiveMatch => "E051",
+            ErrorCode::UnusedResult => "E060",
+        }
+    }
+}
+
+impl RuntimeError {
+    /// The stable code of the error, e.g. `R002`, see [`CODES`].
+    pub fn code(&self) -> &'static str {
+        match self {
+            RuntimeError::TypeError { .. }
+            | RuntimeError::MismatchedType { .. }
+            | RuntimeError::InvalidKey { .. } => "R001",
+            RuntimeError::IndexOutOfBounds { .. } => "R002",
+            RuntimeError::InvalidOperands { .. } => "R003",
+            RuntimeError::InvalidOperator { .. } | RuntimeError::Unsupported { .. } => "R004",
+            RuntimeError::UndefinedProperty { .. } => "R005",
+            RuntimeError::UnknownProperty { .. } => "R006",
+            RuntimeError::Frozen { .. } => "R007",
+            RuntimeError::NoMatch { .. } => "R008",
+            RuntimeError::UndeclaredVariable { .. } => "R010",
+            RuntimeError::UndefinedVariable { .. } => "R011",
+            RuntimeError::ImmutableVariable { .. } => "R012",
+            RuntimeError::ArityMismatch { .. } => "R020",
+            RuntimeError::OutOfScopeCall { .. } => "R021",
+            RuntimeError::CallDepthExceeded { .. } => "R022",
+            RuntimeError::GeneratorRunning { .. } => "R023",
+            RuntimeError::AssertionFailed { .. } => "R024",
+            RuntimeError::Budget { .. } => "R030",
+            RuntimeError::Timeout { .. } => "R031",
+            RuntimeError::Custom(_) => "R090",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn test_codes() {
+        let codes: HashSet<&str> = CODES.iter().map(|code| code.code).collect();
+        assert_eq!(codes.len(), CODES.len());
+
+        // Parse codes keep the names used in diagnostics.
+        for code in [
+            ErrorCode::UnexpectedToken,
+            ErrorCode::MissingToken,
+            ErrorCode::UnexpectedEof,
+            ErrorCode::ExpectedExpression,
+            ErrorCode::InvalidAssignmentTarget,
+            ErrorCode::TooDeeplyNested,
+            ErrorCode::UnknownType,
+            ErrorCode::DuplicateVariant,
+            ErrorCode::UndeclaredVariable,
+            ErrorCode::ImmutableVariable,
+            ErrorCode::MisplacedImport,
+            ErrorCode::MisplacedExport,
+            ErrorCode::MisplacedYield,
+            ErrorCode::MisplacedTest,
+            ErrorCode::ImportFailed,
+            ErrorCode::ImportCycle,
+            ErrorCode::InvalidAst,
+            ErrorCode::TypeMismatch,
+            ErrorCode::InvalidPattern,
+            ErrorCode::NonExhaustiveMatch,
+            ErrorCode::UnusedResult,
+        ] {
+            assert_eq!(explain(code.id()).unwrap().name, code.as_str());
+        }
+
+        assert_eq!(explain("r002").unwrap().name, "index-out-of-bounds");
+        assert_eq!(
+            explain(RuntimeError::new("chr: expected number".to_string()).code())
+                .unwrap()
+                .name,
+            "native-error"
+        );
+        assert_eq!(explain("E999"), None);
+    }
+}
@@ -0,0 +1,24 @@
+use serde::Serialize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem found in a program without running it, as written by `notjs check --json`. The
+/// fields are read by editors, so they are kept as they are: `line` and `column` are null when
+/// the problem has no place, e.g. `column` for type errors, and `column` counts characters
+/// from 1. An unexpected end of file is placed right after the last token.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    /// The kind of problem, such as `undeclared-variable`.
+    pub code: &'static str,
+    /// The stable code of the problem, such as `E010`, explained by `notjs --explain`.
+    pub id: &'static str,
+}
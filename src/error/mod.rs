@@ -1,3 +1,87 @@
+use std::{
+    error::Error,
+    fmt::{self, Debug, Display},
+    io,
+};
+
+use self::{parse::ParseError, runtime::TracedError};
+
+pub mod codes;
+pub mod diagnostic;
 pub mod generic;
 pub mod parse;
 pub mod runtime;
+
+/// The result of running a program with the [`Interpreter`](crate::Interpreter).
+pub type NotJsResult<T> = Result<T, NotJsError>;
+
+/// Why a program could not be run to the end. Strings and comments that are never closed are
+/// found while parsing, so they are parse errors too.
+pub enum NotJsError {
+    /// The program did not parse, nothing was run.
+    Parse(ParseError),
+    /// The program failed while running, along with the calls the error was raised in.
+    Runtime(TracedError),
+    /// The file of the program couldn't be read, see
+    /// [`Interpreter::run_file`](crate::Interpreter::run_file). Files that fail to import are
+    /// parse errors instead.
+    Io(io::Error),
+}
+
+impl NotJsError {
+    /// The stable code of the error, e.g. `E010` or `R002`, see [`codes::CODES`]. With several
+    /// parse errors it is the code of the first.
+    pub fn code(&self) -> &'static str {
+        match self {
+            NotJsError::Parse(error) => error.code(),
+            NotJsError::Runtime(error) => error.error.code(),
+            NotJsError::Io(_) => "I001",
+        }
+    }
+}
+
+impl Debug for NotJsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+/// The message of the error as plain text. Programs showing it on a terminal can color it
+/// themselves, as the `notjs` binary does.
+impl Display for NotJsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NotJsError::Parse(error) => Display::fmt(error, f),
+            NotJsError::Runtime(error) => Display::fmt(error, f),
+            NotJsError::Io(error) => Display::fmt(error, f),
+        }
+    }
+}
+
+impl Error for NotJsError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            NotJsError::Parse(error) => Some(error),
+            NotJsError::Runtime(error) => Some(error),
+            NotJsError::Io(error) => Some(error),
+        }
+    }
+}
+
+impl From<ParseError> for NotJsError {
+    fn from(error: ParseError) -> Self {
+        NotJsError::Parse(error)
+    }
+}
+
+impl From<TracedError> for NotJsError {
+    fn from(error: TracedError) -> Self {
+        NotJsError::Runtime(error)
+    }
+}
+
+impl From<io::Error> for NotJsError {
+    fn from(error: io::Error) -> Self {
+        NotJsError::Io(error)
+    }
+}
@@ -1,80 +1,307 @@
-use std::{error::Error, fmt::{Debug, self, Display}};
+use std::{
+    error::Error,
+    fmt::{self, Debug, Display},
+    ops::Range,
+};
 
 use crate::common::token::{Token, TokenType};
 
+use super::diagnostic::{Diagnostic, Severity};
+
 pub type ParseResult<T> = Result<T, ParseError>;
 
+/// The kind of a parse error, for tools that handle some of them apart.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorCode {
+    UnexpectedToken,
+    MissingToken,
+    UnexpectedEof,
+    ExpectedExpression,
+    InvalidAssignmentTarget,
+    TooDeeplyNested,
+    UnknownType,
+    DuplicateVariant,
+    UndeclaredVariable,
+    ImmutableVariable,
+    MisplacedImport,
+    MisplacedExport,
+    MisplacedYield,
+    MisplacedTest,
+    ImportFailed,
+    ImportCycle,
+    InvalidAst,
+    TypeMismatch,
+    InvalidPattern,
+    NonExhaustiveMatch,
+    UnusedResult,
+}
+
+impl ErrorCode {
+    /// The name of the code in diagnostics, e.g. `undeclared-variable`. Names are kept stable.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::UnexpectedToken => "unexpected-token",
+            ErrorCode::MissingToken => "missing-token",
+            ErrorCode::UnexpectedEof => "unexpected-eof",
+            ErrorCode::ExpectedExpression => "expected-expression",
+            ErrorCode::InvalidAssignmentTarget => "invalid-assignment-target",
+            ErrorCode::TooDeeplyNested => "too-deeply-nested",
+            ErrorCode::UnknownType => "unknown-type",
+            ErrorCode::DuplicateVariant => "duplicate-variant",
+            ErrorCode::UndeclaredVariable => "undeclared-variable",
+            ErrorCode::ImmutableVariable => "immutable-variable",
+            ErrorCode::MisplacedImport => "misplaced-import",
+            ErrorCode::MisplacedExport => "misplaced-export",
+            ErrorCode::MisplacedYield => "misplaced-yield",
+            ErrorCode::MisplacedTest => "misplaced-test",
+            ErrorCode::ImportFailed => "import-failed",
+            ErrorCode::ImportCycle => "import-cycle",
+            ErrorCode::InvalidAst => "invalid-ast",
+            ErrorCode::TypeMismatch => "type-mismatch",
+            ErrorCode::InvalidPattern => "invalid-pattern",
+            ErrorCode::NonExhaustiveMatch => "non-exhaustive-match",
+            ErrorCode::UnusedResult => "unused-result",
+        }
+    }
+}
+
 #[derive(Clone)]
 pub enum ParseError {
-    Single(Single),
+    // Boxed so that parse results stay small, as the parser nests them deeply on the stack.
+    Single(Box<Single>),
     Multiple(Multiple),
 }
 
 impl ParseError {
-    pub fn new_single(message: String) -> Self {
-        ParseError::Single(Single { message })
+    /// An error with `message`, which shouldn't say where it was found: the line is added when
+    /// the error is shown.
+    pub fn new_single(code: ErrorCode, line: Option<u32>, message: String) -> Self {
+        ParseError::Single(Box::new(Single {
+            code,
+            message,
+            line,
+            column: None,
+            span: None,
+            expected: Vec::new(),
+            found: None,
+            cause: None,
+        }))
     }
 
     pub fn new_multiple(errors: Vec<ParseError>) -> Self {
-        ParseError::Multiple(Multiple { errors })
+        Self::new_truncated(errors, 0)
+    }
+
+    // `errors` followed by `hidden` more that are left out of the report.
+    pub(crate) fn new_truncated(errors: Vec<ParseError>, hidden: usize) -> Self {
+        ParseError::Multiple(Multiple { errors, hidden })
     }
 
     pub fn new_unexpected_token(token: Token) -> Self {
-        ParseError::Single(Single {
-            message: format!("Unexpected token: {} at line {}", token.value, token.line),
-        })
+        Self::new_single(
+            ErrorCode::UnexpectedToken,
+            Some(token.line),
+            format!("Unexpected token: {}", token.value),
+        )
     }
 
     pub fn new_missing_token(missing_token_type: TokenType, after_token: Token) -> Self {
-        ParseError::Single(Single {
-            message: format!(
-                "Expected: {} after {} at line {}",
-                missing_token_type, after_token.value, after_token.line
+        Self::new_single(
+            ErrorCode::MissingToken,
+            Some(after_token.line),
+            format!(
+                "Expected: {} after {}",
+                missing_token_type, after_token.value
             ),
-        })
+        )
+        .with_expected(vec![missing_token_type])
+    }
+
+    pub fn new_unexpected_eof(line: u32) -> Self {
+        Self::new_single(
+            ErrorCode::UnexpectedEof,
+            Some(line),
+            "Unexpected end of file".to_string(),
+        )
+    }
+
+    // Set the fields of a single error, leaving those of several as they are.
+    fn with(mut self, set: impl FnOnce(&mut Single)) -> Self {
+        if let ParseError::Single(ref mut error) = self {
+            set(error);
+        }
+        self
     }
 
-    pub fn new_unexpected_eof() -> Self {
-        ParseError::Single(Single {
-            message: format!("Unexpected end of file"),
+    // Place the error at `span` of the source, starting at `column` of its line, unless it
+    // already has a place, e.g. given where it was raised deeper in the parser.
+    pub(crate) fn with_span(self, column: u32, span: Range<usize>) -> Self {
+        self.with(|error| {
+            if error.span.is_none() {
+                error.column = Some(column);
+                error.span = Some(span);
+            }
         })
     }
+
+    pub(crate) fn with_expected(self, expected: Vec<TokenType>) -> Self {
+        self.with(|error| error.expected = expected)
+    }
+
+    pub(crate) fn with_found(self, found: impl Into<String>) -> Self {
+        self.with(|error| error.found = Some(found.into()))
+    }
+
+    pub(crate) fn with_cause(self, cause: impl Display) -> Self {
+        self.with(|error| error.cause = Some(cause.to_string()))
+    }
+
+    /// The stable code of the first error, e.g. `E010`.
+    pub fn code(&self) -> &'static str {
+        self.errors()
+            .first()
+            .map_or(ErrorCode::InvalidAst.id(), |error| error.code.id())
+    }
+
+    /// How many more errors were found past those reported, see [`errors`](Self::errors).
+    pub fn hidden(&self) -> usize {
+        match self {
+            ParseError::Single(_) => 0,
+            ParseError::Multiple(multiple) => multiple.hidden,
+        }
+    }
+
+    /// Each of the errors, in the order they were found.
+    pub fn errors(&self) -> Vec<&Single> {
+        match self {
+            ParseError::Single(error) => vec![&**error],
+            ParseError::Multiple(multiple) => multiple.iter().collect(),
+        }
+    }
+
+    /// A diagnostic for each of the errors, as reported by `notjs check`.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.errors()
+            .into_iter()
+            .map(|error| Diagnostic {
+                severity: Severity::Error,
+                message: match error.cause {
+                    Some(ref cause) => format!("{}: {}", error.message, cause),
+                    None => error.message.clone(),
+                },
+                line: error.line,
+                column: error.column,
+                code: error.code.as_str(),
+                id: error.code.id(),
+            })
+            .collect()
+    }
 }
 
+// Debug output is shown by `expect` and `unwrap`, so it gives the message itself.
 impl Debug for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        match self {
-            ParseError::Single(err) => write!(f, "{}", err),
-            ParseError::Multiple(err) => write!(f, "{}", err),
-        }
+        write!(f, "{}", self)
     }
 }
 
+// Plain text, colors are left to the program showing the error.
 impl Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         match self {
-            ParseError::Single(err) => write!(f, "{}", err),
-            ParseError::Multiple(err) => write!(f, "{}", err),
+            ParseError::Single(err) => Display::fmt(err, f),
+            ParseError::Multiple(err) => Display::fmt(err, f),
         }
     }
 }
 
 impl std::error::Error for ParseError {}
 
+/// One error, with where it was found kept apart from its message for tools such as editors.
 #[derive(Clone)]
 pub struct Single {
+    code: ErrorCode,
     message: String,
+    line: Option<u32>,
+    column: Option<u32>,
+    span: Option<Range<usize>>,
+    expected: Vec<TokenType>,
+    found: Option<String>,
+    cause: Option<String>,
+}
+
+impl Single {
+    pub fn code(&self) -> ErrorCode {
+        self.code
+    }
+
+    /// What went wrong, without where, e.g. `Undeclared variable 'x'`.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Line the error was found at, unknown for errors found without the source at hand.
+    pub fn line(&self) -> Option<u32> {
+        self.line
+    }
+
+    /// Column of the start of [`span`](Self::span) in its line, counting characters from 1.
+    pub fn column(&self) -> Option<u32> {
+        self.column
+    }
+
+    /// The bytes of the source the error is about, usually a single token. Errors found
+    /// without the source at hand, such as those in a program read from JSON, have none.
+    pub fn span(&self) -> Option<Range<usize>> {
+        self.span.clone()
+    }
+
+    /// The tokens that would have been accepted where the error was found, if the parser was
+    /// looking for specific ones.
+    pub fn expected(&self) -> &[TokenType] {
+        &self.expected
+    }
+
+    /// The text of the token that caused the error, as written in the source.
+    pub fn found(&self) -> Option<&str> {
+        self.found.as_deref()
+    }
+
+    /// The error underneath this one, e.g. why an imported file couldn't be read.
+    pub fn cause(&self) -> Option<&str> {
+        self.cause.as_deref()
+    }
+
+    // Whether this is the same error as `previous`, found again on the line after it.
+    pub(crate) fn repeats(&self, previous: &Single) -> bool {
+        match (previous.line, self.line) {
+            (Some(before), Some(line)) => {
+                line == before + 1
+                    && self.code == previous.code
+                    && self.message == previous.message
+                    && self.cause == previous.cause
+            }
+            _ => false,
+        }
+    }
 }
 
 impl Debug for Single {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        write!(f, "\x1b[31mParse error:\x1b[0m {} ", self.message)
+        write!(f, "{}", self)
     }
 }
 
 impl Display for Single {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        write!(f, "\x1b[31mParse error:\x1b[0m {} ", self.message)
+        write!(f, "Parse error: {}", self.message)?;
+        if let Some(line) = self.line {
+            write!(f, " at line {}", line)?;
+        }
+        if let Some(ref cause) = self.cause {
+            write!(f, ": {}", cause)?;
+        }
+        Ok(())
     }
 }
 
@@ -83,29 +310,35 @@ impl Error for Single {}
 #[derive(Clone)]
 pub struct Multiple {
     pub errors: Vec<ParseError>,
+    /// Errors found past those in `errors`, which are only counted.
+    pub hidden: usize,
+}
+
+impl Multiple {
+    /// Each of the errors, in the order they were found.
+    pub fn iter(&self) -> impl Iterator<Item = &Single> + '_ {
+        self.errors.iter().flat_map(ParseError::errors)
+    }
 }
 
 impl Debug for Multiple {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        let err_str = self
-            .errors
-            .iter()
-            .map(|x| format!("{}", x))
-            .fold(String::new(), |ac, x| ac + &x + "\n");
-
-        write!(f, "Failed to parse, errors:\n{}", err_str)
+        write!(f, "{}", self)
     }
 }
 
 impl Display for Multiple {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        let err_str = self
-            .errors
-            .iter()
-            .map(|x| format!("{}", x))
-            .fold(String::new() + "\n", |ac, x| ac + &x + "\n");
-
-        write!(f, "Failed to parse, errors:\n{}", err_str)
+        writeln!(f, "Failed to parse, errors:")?;
+        for error in &self.errors {
+            writeln!(f)?;
+            Display::fmt(error, f)?;
+        }
+        match self.hidden {
+            0 => Ok(()),
+            1 => write!(f, "\nand 1 more error"),
+            hidden => write!(f, "\nand {} more errors", hidden),
+        }
     }
 }
 
@@ -1,59 +1,553 @@
 use std::{
     error::Error,
     fmt::{self, Debug, Display},
+    time::Duration,
 };
 
-use crate::common::token::Token;
+use crate::common::{
+    environment::Frame,
+    function::Arity,
+    token::Token,
+    value::{number_to_string, OperandError, Value, ValueType},
+};
 
 pub type RuntimeResult<T> = Result<T, RuntimeError>;
 
-pub struct RuntimeError {
-    pub message: String,
+#[derive(Clone, PartialEq)]
+pub enum RuntimeError {
+    UndeclaredVariable {
+        name: String,
+        line: u32,
+    },
+    UndefinedVariable {
+        name: String,
+        line: u32,
+    },
+    // `declared` is the line of the declaration, globals of the host have none.
+    ImmutableVariable {
+        name: String,
+        line: u32,
+        declared: Option<u32>,
+    },
+    TypeError {
+        expected: String,
+        found: String,
+        line: u32,
+    },
+    // A value didn't match the type annotation of what it was given to. `target` names it, e.g.
+    // "variable 'count'" or "return value of 'f'".
+    MismatchedType {
+        target: String,
+        expected: String,
+        found: String,
+        line: u32,
+    },
+    IndexOutOfBounds {
+        index: f64,
+        len: usize,
+        line: u32,
+    },
+    UndefinedProperty {
+        name: String,
+        line: u32,
+    },
+    // A property that values of some type do not have.
+    UnknownProperty {
+        name: String,
+        available: Vec<String>,
+        line: u32,
+    },
+    // A value that can't be the key of an object property was used as one. `found` describes
+    // it, e.g. "an array".
+    InvalidKey {
+        found: String,
+        line: u32,
+    },
+    // An element or property of a frozen array or object was assigned.
+    Frozen {
+        target: String,
+        type_name: String,
+        line: u32,
+    },
+    // No arm of a match matched `value`, as shown by `repr`.
+    NoMatch {
+        value: String,
+        line: u32,
+    },
+    InvalidOperands {
+        operator: String,
+        left: String,
+        // `None` for unary operators.
+        right: Option<String>,
+        line: u32,
+    },
+    // `operator` names the kind of operator, e.g. "binary" or "assignment".
+    InvalidOperator {
+        operator: String,
+        line: u32,
+    },
+    // A value was called, indexed or had a property read, which its type doesn't allow.
+    // `operation` says which, e.g. "call", and `target` is the source of the expression that
+    // gave the value.
+    Unsupported {
+        operation: String,
+        target: String,
+        type_name: String,
+        line: u32,
+    },
+    // `callee` is the name of the function, followed by its parameters for user functions, and
+    // `line` is that of the call. `declared` is the line of the declaration, natives have none.
+    ArityMismatch {
+        callee: String,
+        expected: Arity,
+        found: usize,
+        line: u32,
+        declared: Option<u32>,
+    },
+    // A function was called after the scope it was declared in ended.
+    OutOfScopeCall {
+        name: String,
+        line: u32,
+    },
+    CallDepthExceeded {
+        name: String,
+        max_depth: usize,
+    },
+    // A generator was resumed while its body was running.
+    GeneratorRunning {
+        name: String,
+        line: u32,
+    },
+    // The condition of an assert statement, given as its source, didn't hold.
+    AssertionFailed {
+        condition: String,
+        line: u32,
+    },
+    // The instruction budget given to the interpreter ran out.
+    Budget {
+        limit: usize,
+    },
+    // The program ran for longer than the timeout given to the interpreter.
+    Timeout {
+        limit: Duration,
+    },
+    Custom(String),
 }
 
 impl RuntimeError {
     pub fn new(message: String) -> Self {
-        RuntimeError { message }
+        RuntimeError::Custom(message)
     }
 
-    pub fn new_undeclared_variable(token: Token) -> Self {
-        RuntimeError {
-            message: format!(
-                "Undeclared variable: {} at line {}\n",
-                token.value, token.line
-            ),
+    pub fn new_undeclared_variable(token: &Token) -> Self {
+        RuntimeError::UndeclaredVariable {
+            name: token.name().to_string(),
+            line: token.line,
         }
     }
 
-    pub fn new_undefined_variable(token: Token) -> Self {
-        RuntimeError {
-            message: format!(
-                "Undefined variable: {} at line {}\n",
-                token.value, token.line
-            ),
+    pub fn new_undefined_variable(token: &Token) -> Self {
+        RuntimeError::UndefinedVariable {
+            name: token.name().to_string(),
+            line: token.line,
         }
     }
 
-    pub fn new_immutable_variable(token: Token) -> Self {
-        RuntimeError {
-            message: format!(
-                "Immutable variable assignment: {} at line {}\n",
-                token.value, token.line
-            ),
+    pub fn new_immutable_variable(token: &Token, declared: Option<u32>) -> Self {
+        RuntimeError::ImmutableVariable {
+            name: token.name().to_string(),
+            line: token.line,
+            declared,
+        }
+    }
+
+    pub fn new_type_error(expected: &str, found: &Value, token: &Token) -> Self {
+        RuntimeError::TypeError {
+            expected: expected.to_string(),
+            found: found.type_name().to_string(),
+            line: token.line,
+        }
+    }
+
+    pub fn new_mismatched_type(
+        target: String,
+        expected: ValueType,
+        found: &Value,
+        line: u32,
+    ) -> Self {
+        RuntimeError::MismatchedType {
+            target,
+            expected: expected.to_string(),
+            found: found.type_name().to_string(),
+            line,
+        }
+    }
+
+    pub fn new_index_out_of_bounds(index: f64, len: usize, token: &Token) -> Self {
+        RuntimeError::IndexOutOfBounds {
+            index,
+            len,
+            line: token.line,
+        }
+    }
+
+    pub fn new_unknown_property(name: &str, available: &[&str], token: &Token) -> Self {
+        RuntimeError::UnknownProperty {
+            name: name.to_string(),
+            available: available.iter().map(|name| name.to_string()).collect(),
+            line: token.line,
+        }
+    }
+
+    pub fn new_invalid_key(found: &str, token: &Token) -> Self {
+        RuntimeError::InvalidKey {
+            found: found.to_string(),
+            line: token.line,
+        }
+    }
+
+    pub fn new_frozen(target: String, frozen: &Value, token: &Token) -> Self {
+        RuntimeError::Frozen {
+            target,
+            type_name: frozen.type_name().to_string(),
+            line: token.line,
+        }
+    }
+
+    pub fn new_no_match(value: &Value, token: &Token) -> Self {
+        RuntimeError::NoMatch {
+            value: format!("{:?}", value),
+            line: token.line,
+        }
+    }
+
+    pub fn new_invalid_operands(error: OperandError, token: &Token) -> Self {
+        RuntimeError::InvalidOperands {
+            operator: error.operator.to_string(),
+            left: error.left.to_string(),
+            right: error.right.map(str::to_string),
+            line: token.line,
+        }
+    }
+
+    pub fn new_invalid_operator(operator: &str, token: &Token) -> Self {
+        RuntimeError::InvalidOperator {
+            operator: operator.to_string(),
+            line: token.line,
+        }
+    }
+
+    pub fn new_unsupported(operation: &str, target: String, value: &Value, token: &Token) -> Self {
+        RuntimeError::Unsupported {
+            operation: operation.to_string(),
+            target,
+            type_name: value.type_name().to_string(),
+            line: token.line,
+        }
+    }
+
+    pub fn new_undefined_property(name: &str, token: &Token) -> Self {
+        RuntimeError::UndefinedProperty {
+            name: name.to_string(),
+            line: token.line,
+        }
+    }
+
+    pub fn new_out_of_scope_call(token: &Token) -> Self {
+        RuntimeError::OutOfScopeCall {
+            name: token.name().to_string(),
+            line: token.line,
+        }
+    }
+
+    pub fn new_call_depth_exceeded(name: &str, max_depth: usize) -> Self {
+        RuntimeError::CallDepthExceeded {
+            name: name.to_string(),
+            max_depth,
+        }
+    }
+
+    pub fn new_generator_running(name: &Token, line: u32) -> Self {
+        RuntimeError::GeneratorRunning {
+            name: name.name().to_string(),
+            line,
+        }
+    }
+
+    pub fn new_assertion_failed(condition: String, line: u32) -> Self {
+        RuntimeError::AssertionFailed { condition, line }
+    }
+
+    pub fn new_arity_mismatch(
+        callee: String,
+        expected: Arity,
+        found: usize,
+        line: u32,
+        declared: Option<u32>,
+    ) -> Self {
+        RuntimeError::ArityMismatch {
+            callee,
+            expected,
+            found,
+            line,
+            declared,
+        }
+    }
+
+    // Line of the program the error was raised at, if it is tied to one.
+    pub fn line(&self) -> Option<u32> {
+        match self {
+            RuntimeError::UndeclaredVariable { line, .. }
+            | RuntimeError::UndefinedVariable { line, .. }
+            | RuntimeError::ImmutableVariable { line, .. }
+            | RuntimeError::TypeError { line, .. }
+            | RuntimeError::MismatchedType { line, .. }
+            | RuntimeError::IndexOutOfBounds { line, .. }
+            | RuntimeError::UndefinedProperty { line, .. }
+            | RuntimeError::UnknownProperty { line, .. }
+            | RuntimeError::InvalidKey { line, .. }
+            | RuntimeError::Frozen { line, .. }
+            | RuntimeError::NoMatch { line, .. }
+            | RuntimeError::InvalidOperands { line, .. }
+            | RuntimeError::InvalidOperator { line, .. }
+            | RuntimeError::Unsupported { line, .. }
+            | RuntimeError::ArityMismatch { line, .. }
+            | RuntimeError::OutOfScopeCall { line, .. }
+            | RuntimeError::GeneratorRunning { line, .. }
+            | RuntimeError::AssertionFailed { line, .. } => Some(*line),
+            RuntimeError::CallDepthExceeded { .. }
+            | RuntimeError::Budget { .. }
+            | RuntimeError::Timeout { .. }
+            | RuntimeError::Custom(_) => None,
         }
     }
 }
 
 impl Debug for RuntimeError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "RuntimeError: {}\n", self.message)
+        write!(f, "{}", self)
     }
 }
 
 impl Display for RuntimeError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "RuntimeError: {}\n", self.message)
+        write!(f, "RuntimeError: ")?;
+        match self {
+            RuntimeError::UndeclaredVariable { name, line } => {
+                writeln!(f, "Undeclared variable: {} at line {}", name, line)
+            }
+            RuntimeError::UndefinedVariable { name, line } => {
+                writeln!(f, "Undefined variable: {} at line {}", name, line)
+            }
+            RuntimeError::ImmutableVariable {
+                name,
+                line,
+                declared: Some(declared),
+            } => writeln!(
+                f,
+                "Cannot reassign '{}' (declared const at line {}) at line {}",
+                name, declared, line
+            ),
+            RuntimeError::ImmutableVariable {
+                name,
+                line,
+                declared: None,
+            } => writeln!(f, "Cannot reassign global '{}' at line {}", name, line),
+            RuntimeError::TypeError {
+                expected,
+                found,
+                line,
+            } => writeln!(f, "Expected {}, found {} at line {}", expected, found, line),
+            RuntimeError::MismatchedType {
+                target,
+                expected,
+                found,
+                line,
+            } => writeln!(
+                f,
+                "Expected {} for {}, found {} at line {}",
+                expected, target, found, line
+            ),
+            RuntimeError::IndexOutOfBounds { index, len, line } => writeln!(
+                f,
+                "Index {} out of bounds for length {} at line {}",
+                number_to_string(*index),
+                len,
+                line
+            ),
+            RuntimeError::UndefinedProperty { name, line } => {
+                writeln!(f, "Undefined property: {} at line {}", name, line)
+            }
+            RuntimeError::UnknownProperty {
+                name,
+                available,
+                line,
+            } => writeln!(
+                f,
+                "Unknown property: {} at line {}, available properties: {}",
+                name,
+                line,
+                available.join(", ")
+            ),
+            RuntimeError::InvalidKey { found, line } => {
+                writeln!(f, "Cannot use {} as an object key at line {}", found, line)
+            }
+            RuntimeError::Frozen {
+                target,
+                type_name,
+                line,
+            } => writeln!(
+                f,
+                "Cannot modify frozen {} {} at line {}",
+                type_name, target, line
+            ),
+            RuntimeError::NoMatch { value, line } => {
+                writeln!(f, "No arm of the match matches {} at line {}", value, line)
+            }
+            RuntimeError::InvalidOperands {
+                operator,
+                left,
+                right: Some(right),
+                line,
+            } => writeln!(
+                f,
+                "Cannot apply '{}' to {} and {} at line {}",
+                operator, left, right, line
+            ),
+            RuntimeError::InvalidOperands {
+                operator,
+                left,
+                right: None,
+                line,
+            } => {
+                writeln!(
+                    f,
+                    "Cannot apply '{}' to {} at line {}",
+                    operator, left, line
+                )
+            }
+            RuntimeError::InvalidOperator { operator, line } => {
+                writeln!(f, "Invalid {} operator at line {}", operator, line)
+            }
+            RuntimeError::Unsupported {
+                operation,
+                target,
+                type_name,
+                line,
+            } => {
+                // `null` is a value rather than a kind of them.
+                let article = match type_name.as_str() {
+                    "null" => "",
+                    "array" | "object" => "an ",
+                    _ => "a ",
+                };
+                writeln!(
+                    f,
+                    "Cannot {} '{}' ({}{}) at line {}",
+                    operation, target, article, type_name, line
+                )
+            }
+            RuntimeError::ArityMismatch {
+                callee,
+                expected,
+                found,
+                line,
+                declared,
+            } => {
+                let arguments = match expected {
+                    Arity::Exact(1) | Arity::AtLeast(1) => "argument",
+                    _ => "arguments",
+                };
+                write!(
+                    f,
+                    "Function '{}' expects {} {} but was called with {} at line {}",
+                    callee, expected, arguments, found, line
+                )?;
+                match declared {
+                    Some(declared) => writeln!(f, " (declared at line {})", declared),
+                    None => writeln!(f),
+                }
+            }
+            RuntimeError::OutOfScopeCall { name, line } => writeln!(
+                f,
+                "Function {} called outside of the scope it was declared in at line {}",
+                name, line
+            ),
+            RuntimeError::CallDepthExceeded { name, max_depth } => writeln!(
+                f,
+                "Maximum call depth of {} exceeded calling {}",
+                max_depth, name
+            ),
+            RuntimeError::GeneratorRunning { name, line } => writeln!(
+                f,
+                "Generator {} resumed while it is running at line {}",
+                name, line
+            ),
+            RuntimeError::AssertionFailed { condition, line } => {
+                writeln!(f, "Assertion failed: {} at line {}", condition, line)
+            }
+            RuntimeError::Budget { limit } => {
+                writeln!(f, "Instruction budget of {} steps exceeded", limit)
+            }
+            RuntimeError::Timeout { limit } => {
+                writeln!(f, "Execution timed out after {:?}", limit)
+            }
+            RuntimeError::Custom(message) => writeln!(f, "{}", message),
+        }?;
+        writeln!(f)
     }
 }
 
 impl Error for RuntimeError {}
+
+// A runtime error that escaped the program, with the calls it was raised in.
+pub struct TracedError {
+    pub error: RuntimeError,
+    // Innermost call last.
+    pub trace: Vec<Frame>,
+}
+
+impl Debug for TracedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl Display for TracedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.error)?;
+
+        // Runs of the same call, as left by recursion, are shown once with how often they repeat.
+        let mut frames = self.trace.iter().rev().peekable();
+        while let Some(frame) = frames.next() {
+            match frame.note {
+                Some(ref note) => {
+                    writeln!(f, "    at {} (line {}), {}", frame.name, frame.line, note)?
+                }
+                None => writeln!(f, "    at {} (line {})", frame.name, frame.line)?,
+            }
+
+            let mut repeated = 0;
+            while frames
+                .next_if(|next| {
+                    next.name == frame.name && next.line == frame.line && next.note == frame.note
+                })
+                .is_some()
+            {
+                repeated += 1;
+            }
+            match repeated {
+                0 => {}
+                1 => writeln!(f, "    ... previous frame repeated 1 more time")?,
+                _ => writeln!(f, "    ... previous frame repeated {} more times", repeated)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Error for TracedError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
@@ -0,0 +1,194 @@
+// The C interface declared in include/notjs.h. A handle owns an interpreter along with the output
+// and the last error of its programs, and every string it gives out stays valid until the next
+// call with the same handle. Panics are caught before they reach the caller.
+
+use std::{
+    ffi::{c_char, CStr, CString},
+    panic::{self, AssertUnwindSafe},
+    ptr,
+};
+
+use crate::{interpreter::SharedBuffer, Interpreter, NotJsError};
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NotJsStatus {
+    Ok = 0,
+    ParseError = 1,
+    RuntimeError = 2,
+    // The interpreter panicked, it may be left in an inconsistent state and should be freed.
+    Panic = 3,
+    // A null handle or source.
+    InvalidArgument = 4,
+}
+
+pub struct NotJs {
+    interpreter: Interpreter,
+    output: SharedBuffer,
+    // Output handed out by the last `notjs_get_output`.
+    taken_output: CString,
+    last_error: Option<CString>,
+}
+
+// A string that C can read up to its end. Interior null bytes would cut it short, so they are
+// dropped.
+fn c_string(text: impl Into<Vec<u8>>) -> CString {
+    let mut bytes = text.into();
+    bytes.retain(|&byte| byte != 0);
+    CString::new(bytes).unwrap_or_default()
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_default()
+}
+
+/// Create an interpreter with the natives and the prelude. Free it with `notjs_free`.
+#[no_mangle]
+pub extern "C" fn notjs_new() -> *mut NotJs {
+    let result = panic::catch_unwind(|| {
+        let output = SharedBuffer::default();
+        let interpreter = Interpreter::new().with_output(output.clone());
+
+        Box::new(NotJs {
+            interpreter,
+            output,
+            taken_output: CString::default(),
+            last_error: None,
+        })
+    });
+
+    result.map_or(ptr::null_mut(), Box::into_raw)
+}
+
+/// Free an interpreter created by `notjs_new`. Freeing null does nothing.
+///
+/// # Safety
+///
+/// `handle` must be null or a pointer given by `notjs_new` that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn notjs_free(handle: *mut NotJs) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
+
+/// Run the null-terminated `source`. Globals stay defined for the next program.
+///
+/// # Safety
+///
+/// `handle` must be null or a live pointer given by `notjs_new`, and `source` must be null or
+/// point to a null-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn notjs_interpret(handle: *mut NotJs, source: *const c_char) -> NotJsStatus {
+    let Some(notjs) = handle.as_mut() else {
+        return NotJsStatus::InvalidArgument;
+    };
+    if source.is_null() {
+        notjs.last_error = Some(c_string("notjs_interpret: source is null"));
+        return NotJsStatus::InvalidArgument;
+    }
+    let source = CStr::from_ptr(source).to_bytes();
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| notjs.interpreter.interpret(source)));
+
+    let (status, error) = match result {
+        Ok(Ok(_)) => (NotJsStatus::Ok, None),
+        Ok(Err(err @ NotJsError::Parse(_))) => (NotJsStatus::ParseError, Some(err.to_string())),
+        // Running a source reads no file, so the other errors are raised while running.
+        Ok(Err(err)) => (NotJsStatus::RuntimeError, Some(err.to_string())),
+        Err(payload) => (
+            NotJsStatus::Panic,
+            Some(format!("Internal error: {}", panic_message(&*payload))),
+        ),
+    };
+
+    notjs.last_error = error.map(c_string);
+    status
+}
+
+/// The message of the error of the last `notjs_interpret`, or null if it succeeded.
+///
+/// # Safety
+///
+/// `handle` must be null or a live pointer given by `notjs_new`.
+#[no_mangle]
+pub unsafe extern "C" fn notjs_last_error(handle: *const NotJs) -> *const c_char {
+    handle
+        .as_ref()
+        .and_then(|notjs| notjs.last_error.as_ref())
+        .map_or(ptr::null(), |error| error.as_ptr())
+}
+
+/// Everything printed since the last call, or null for a null handle.
+///
+/// # Safety
+///
+/// `handle` must be null or a live pointer given by `notjs_new`.
+#[no_mangle]
+pub unsafe extern "C" fn notjs_get_output(handle: *mut NotJs) -> *const c_char {
+    let Some(notjs) = handle.as_mut() else {
+        return ptr::null();
+    };
+
+    let printed = std::mem::take(&mut *notjs.output.0.borrow_mut());
+    notjs.taken_output = c_string(printed);
+    notjs.taken_output.as_ptr()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    unsafe fn text(pointer: *const c_char) -> Option<String> {
+        (!pointer.is_null()).then(|| CStr::from_ptr(pointer).to_str().unwrap().to_string())
+    }
+
+    #[test]
+    fn test_ffi() {
+        unsafe {
+            let handle = notjs_new();
+            assert!(!handle.is_null());
+
+            let status = notjs_interpret(handle, c"let x = 20 println x + 1".as_ptr());
+            assert_eq!(status, NotJsStatus::Ok);
+            assert_eq!(text(notjs_last_error(handle)), None);
+            assert_eq!(text(notjs_get_output(handle)).unwrap(), "21\n");
+            assert_eq!(text(notjs_get_output(handle)).unwrap(), "");
+
+            let status = notjs_interpret(handle, c"print x\nx()".as_ptr());
+            assert_eq!(status, NotJsStatus::RuntimeError);
+            assert!(text(notjs_last_error(handle)).unwrap().contains("line 2"));
+            assert_eq!(text(notjs_get_output(handle)).unwrap(), "20");
+
+            let status = notjs_interpret(handle, c"let = 1".as_ptr());
+            assert_eq!(status, NotJsStatus::ParseError);
+            assert!(text(notjs_last_error(handle))
+                .unwrap()
+                .contains("Parse error"));
+
+            assert_eq!(
+                notjs_interpret(handle, ptr::null()),
+                NotJsStatus::InvalidArgument
+            );
+            assert_eq!(
+                notjs_interpret(ptr::null_mut(), c"1".as_ptr()),
+                NotJsStatus::InvalidArgument
+            );
+            assert!(notjs_last_error(ptr::null()).is_null());
+            assert!(notjs_get_output(ptr::null_mut()).is_null());
+
+            notjs_free(handle);
+            notjs_free(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn test_c_string() {
+        assert_eq!(c_string("ab").as_bytes(), b"ab");
+        assert_eq!(c_string(b"a\0b".to_vec()).as_bytes(), b"ab");
+    }
+}
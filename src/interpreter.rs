@@ -1,43 +1,129 @@
-use lazy_static::lazy_static;
-use std::sync::RwLock;
+use std::{
+    cell::RefCell,
+    io::{self, Write},
+    path::Path,
+    rc::Rc,
+    time::Duration,
+};
 
+use crate::common::coverage::Coverage;
+use crate::common::debug::{DebugHook, Debugger};
 use crate::common::expressions::{ArrayLiteral, Identifier};
+use crate::common::function::{Function, NativeFunction, UserFunction};
+use crate::common::generator::Items;
+use crate::common::object::{Object, ValueKey};
+use crate::common::profile::{self, Profile, Profiler};
+use crate::common::resolver::Resolver;
+use crate::common::source;
 use crate::common::token::Token;
-use crate::error::generic::GenericResult;
-use crate::error::runtime::{RuntimeError, RuntimeResult};
+use crate::common::typecheck;
+use crate::error::parse::ParseResult;
+use crate::error::runtime::{RuntimeError, RuntimeResult, TracedError};
+use crate::error::NotJsResult;
 use crate::{
     common::{
-        environment::Environment,
+        environment::{check_annotation, Deferred, EnvSnapshot, Environment, TailCall, Test},
         expressions::{
-            AssignmentExpression, BinaryExpression, ConditionalExpression, Expression, Literal,
-            PostfixExpression, PostfixOperator, UnaryExpression,
+            AssignmentExpression, BinaryExpression, ConditionalExpression, DeleteExpression,
+            Expression, Literal, MatchExpression, Pattern, PostfixExpression, PostfixOperator,
+            PropertyAssignment, UnaryExpression,
         },
         statements::{
-            BlockStatement, ExpressionStatement, IfStatement, PrintStatement, ReturnStatement,
-            Statement, VariableDeclaration, WhileStatement,
+            AssertStatement, BlockStatement, DeferStatement, EnumDeclaration, ExpressionStatement,
+            ForInStatement, FunctionDeclaration, IfStatement, ImportStatement, PrintStatement,
+            ReturnStatement, Statement, TestDeclaration, VariableDeclaration, WhileStatement,
+            YieldStatement,
         },
         token::TokenType,
-        value::Value,
+        value::{number_to_string, EnumVariant, Value},
     },
-    parser,
+    optimizer,
+    parser::{self, Imports},
 };
 
-lazy_static! {
-    static ref ENVIRONMENT: RwLock<Environment> = RwLock::new(Environment::new());
-}
+mod bind;
+mod generator;
+mod natives;
+
+pub use bind::Bind;
 
 // ## Statements
-impl Statement for BlockStatement {
-    fn execute(&self) -> RuntimeResult<Value> {
+impl Statement {
+    pub fn execute(&self, environment: &mut Environment) -> RuntimeResult<Value> {
+        self.prepare(environment)?;
+
+        match self {
+            Statement::Block(statement) => statement.execute(environment),
+            Statement::Variable(statement) => statement.execute(environment),
+            Statement::Expression(statement) => statement.execute(environment),
+            Statement::Print(statement) => statement.execute(environment),
+            Statement::If(statement) => statement.execute(environment),
+            Statement::While(statement) => statement.execute(environment),
+            Statement::ForIn(statement) => statement.execute(environment),
+            Statement::Return(statement) => statement.execute(environment),
+            Statement::Yield(statement) => statement.execute(environment),
+            Statement::Defer(statement) => statement.execute(environment),
+            Statement::Assert(statement) => statement.execute(environment),
+            Statement::Function(statement) => statement.execute(environment),
+            Statement::Enum(statement) => statement.execute(environment),
+            Statement::Test(statement) => statement.execute(environment),
+            Statement::Import(statement) => statement.execute(environment),
+        }
+    }
+
+    // Trace, cover and let the debugger pause before the statement runs.
+    pub(crate) fn prepare(&self, environment: &mut Environment) -> RuntimeResult<()> {
+        if environment.tracing {
+            environment.trace_event(self.line(), &source::first_line(self))?;
+        }
+        environment.cover(self.line());
+        if environment.debugger.is_some() {
+            Debugger::before(environment, self);
+        }
+
+        Ok(())
+    }
+
+    pub fn node_to_string(&self) -> String {
+        match self {
+            Statement::Block(statement) => statement.node_to_string(),
+            Statement::Variable(statement) => statement.node_to_string(),
+            Statement::Expression(statement) => statement.node_to_string(),
+            Statement::Print(statement) => statement.node_to_string(),
+            Statement::If(statement) => statement.node_to_string(),
+            Statement::While(statement) => statement.node_to_string(),
+            Statement::ForIn(statement) => statement.node_to_string(),
+            Statement::Return(statement) => statement.node_to_string(),
+            Statement::Yield(statement) => statement.node_to_string(),
+            Statement::Defer(statement) => statement.node_to_string(),
+            Statement::Assert(statement) => statement.node_to_string(),
+            Statement::Function(statement) => statement.node_to_string(),
+            Statement::Enum(statement) => statement.to_string(),
+            Statement::Test(statement) => statement.node_to_string(),
+            Statement::Import(statement) => statement.node_to_string(),
+        }
+    }
+}
+
+impl BlockStatement {
+    fn execute(&self, environment: &mut Environment) -> RuntimeResult<Value> {
+        environment.step()?;
+
         let mut result = Value::Null;
 
-        ENVIRONMENT.write().unwrap().push();
+        environment.push();
+        environment.start_deferring();
 
         for statement in &self.statements {
-            result = statement.execute()?;
+            result = statement.execute(environment)?;
+
+            if environment.is_returning() {
+                break;
+            }
         }
 
-        ENVIRONMENT.write().unwrap().pop();
+        run_deferred(environment)?;
+        environment.pop();
 
         Ok(result)
     }
@@ -53,66 +139,78 @@ impl Statement for BlockStatement {
     }
 }
 
-impl Statement for VariableDeclaration {
-    fn execute(&self) -> RuntimeResult<Value> {
+impl VariableDeclaration {
+    fn execute(&self, environment: &mut Environment) -> RuntimeResult<Value> {
+        environment.step()?;
+
         match self.initializer {
             Some(ref initializer) => {
-                let value = initializer.evaluate()?;
-                ENVIRONMENT.write().unwrap().define(
-                    self.identifier.clone(),
+                let value = initializer.evaluate(environment)?;
+                check_annotation(
+                    self.annotation,
+                    &value,
+                    || format!("variable '{}'", self.identifier.name()),
+                    self.identifier.line,
+                )?;
+                environment.define_annotated(
+                    &self.identifier,
                     Some(value),
                     self.mutable,
+                    self.annotation,
                 );
                 Ok(Value::Null)
             }
+            // The type is checked once the variable is given a value.
             None => {
-                ENVIRONMENT
-                    .write()
-                    .unwrap()
-                    .define(self.identifier.clone(), None, self.mutable);
+                environment.define_annotated(&self.identifier, None, self.mutable, self.annotation);
                 Ok(Value::Null)
             }
         }
     }
 
     fn node_to_string(&self) -> String {
+        let declaration = format!(
+            "{} {}",
+            if self.mutable { "let" } else { "const" },
+            self.identifier.value
+        );
+        let declaration = match self.annotation {
+            Some(annotation) => format!("{}: {}", declaration, annotation),
+            None => declaration,
+        };
+
         match self.initializer {
-            Some(ref initializer) => format!(
-                "{} {} = {}",
-                if self.mutable { "let" } else { "const" },
-                self.identifier.value,
-                initializer.node_to_string()
-            ),
-            None => format!(
-                "{} {}",
-                if self.mutable { "let" } else { "const" },
-                self.identifier.value
-            ),
+            Some(ref initializer) => {
+                format!("{} = {}", declaration, initializer.node_to_string())
+            }
+            None => declaration,
         }
     }
 }
 
-impl Statement for ExpressionStatement {
-    fn execute(&self) -> RuntimeResult<Value> {
-        self.expression.evaluate()
+impl ExpressionStatement {
+    fn execute(&self, environment: &mut Environment) -> RuntimeResult<Value> {
+        environment.step()?;
+
+        self.expression.evaluate(environment)
     }
 
     fn node_to_string(&self) -> String {
-        format!("{}", self.expression.node_to_string())
+        self.expression.node_to_string()
     }
 }
 
-impl Statement for PrintStatement {
-    fn execute(&self) -> RuntimeResult<Value> {
-        let value = self.expression.evaluate()?;
+impl PrintStatement {
+    fn execute(&self, environment: &mut Environment) -> RuntimeResult<Value> {
+        environment.step()?;
 
-        if self.new_line {
-            println!("{}", value);
-        } else {
-            print!("{}", value);
-        }
+        let value = self.expression.evaluate(environment)?;
 
-        Ok(value)
+        environment
+            .print(&value, self.new_line)
+            .map_err(|err| RuntimeError::new(format!("Failed to write output: {}", err)))?;
+
+        Ok(Value::Null)
     }
 
     fn node_to_string(&self) -> String {
@@ -120,14 +218,16 @@ impl Statement for PrintStatement {
     }
 }
 
-impl Statement for IfStatement {
-    fn execute(&self) -> RuntimeResult<Value> {
-        let condition = self.condition.evaluate()?;
+impl IfStatement {
+    fn execute(&self, environment: &mut Environment) -> RuntimeResult<Value> {
+        environment.step()?;
+
+        let condition = self.condition.evaluate(environment)?;
 
         if condition.is_truthy() {
-            self.then_branch.execute()
+            self.then_branch.execute(environment)
         } else if let Some(ref else_branch) = self.else_branch {
-            else_branch.execute()
+            else_branch.execute(environment)
         } else {
             Ok(Value::Null)
         }
@@ -151,12 +251,20 @@ impl Statement for IfStatement {
     }
 }
 
-impl Statement for WhileStatement {
-    fn execute(&self) -> RuntimeResult<Value> {
+impl WhileStatement {
+    fn execute(&self, environment: &mut Environment) -> RuntimeResult<Value> {
+        environment.step()?;
+
         let mut result = Value::Null;
 
-        while self.condition.evaluate()?.is_truthy() {
-            result = self.body.execute()?;
+        while self.condition.evaluate(environment)?.is_truthy() {
+            result = self.body.execute(environment)?;
+
+            if environment.is_returning() {
+                break;
+            }
+
+            environment.check_deadline()?;
         }
 
         Ok(result)
@@ -171,13 +279,61 @@ impl Statement for WhileStatement {
     }
 }
 
-impl Statement for ReturnStatement {
-    fn execute(&self) -> RuntimeResult<Value> {
-        if let Some(ref value) = self.value {
-            value.evaluate()
-        } else {
-            Ok(Value::Null)
+impl ForInStatement {
+    fn execute(&self, environment: &mut Environment) -> RuntimeResult<Value> {
+        environment.step()?;
+
+        let iterable = self.iterable.evaluate(environment)?;
+        let mut result = Value::Null;
+
+        let mut items = Items::new(iterable, &self.identifier)?;
+
+        while let Some(item) = items.next(environment, self.identifier.line)? {
+            environment.push();
+            environment.define(&self.identifier, Some(item), self.mutable);
+            let body = self.body.execute(environment);
+            environment.pop();
+            result = body?;
+
+            if environment.is_returning() {
+                break;
+            }
+
+            environment.check_deadline()?;
         }
+
+        Ok(result)
+    }
+
+    fn node_to_string(&self) -> String {
+        format!(
+            "for {} {} in {} {}",
+            if self.mutable { "let" } else { "const" },
+            self.identifier.value,
+            self.iterable.node_to_string(),
+            self.body.node_to_string()
+        )
+    }
+}
+
+impl ReturnStatement {
+    fn execute(&self, environment: &mut Environment) -> RuntimeResult<Value> {
+        environment.step()?;
+
+        let value = match self.value {
+            Some(Expression::Postfix(ref call))
+                if matches!(call.operator, PostfixOperator::Call(_))
+                    && environment.in_tail_position() =>
+            {
+                call.evaluate_tail_call(environment)?
+            }
+            Some(ref value) => value.evaluate(environment)?,
+            None => Value::Null,
+        };
+
+        environment.start_return();
+
+        Ok(value)
     }
 
     fn node_to_string(&self) -> String {
@@ -189,351 +345,4407 @@ impl Statement for ReturnStatement {
     }
 }
 
-// ## Expressions
-impl Expression for AssignmentExpression {
-    fn evaluate(&self) -> RuntimeResult<Value> {
-        let value = self.value.evaluate()?;
-
-        match self.operator {
-            TokenType::Equal => {
-                ENVIRONMENT
-                    .write()
-                    .unwrap()
-                    .assign(self.identifier.clone(), value.clone())?;
-                Ok(value)
-            }
-            TokenType::PlusEqual => {
-                let left = ENVIRONMENT
-                    .read()
-                    .unwrap()
-                    .get(self.identifier.clone())
-                    .cloned()?;
-                let left = (left + value).unwrap();
-                ENVIRONMENT
-                    .write()
-                    .unwrap()
-                    .assign(self.identifier.clone(), left.clone())?;
-                Ok(left)
-            }
-            TokenType::MinusEqual => {
-                let left = ENVIRONMENT
-                    .read()
-                    .unwrap()
-                    .get(self.identifier.clone())
-                    .cloned()?;
-                let left = (left - value).unwrap();
-                ENVIRONMENT
-                    .write()
-                    .unwrap()
-                    .assign(self.identifier.clone(), left.clone())?;
-                Ok(left)
-            }
-            TokenType::StarEqual => {
-                let left = ENVIRONMENT
-                    .read()
-                    .unwrap()
-                    .get(self.identifier.clone())
-                    .cloned()?;
-                let left = (left * value).unwrap();
-                ENVIRONMENT
-                    .write()
-                    .unwrap()
-                    .assign(self.identifier.clone(), left.clone())?;
-                Ok(left)
-            }
-            TokenType::SlashEqual => {
-                let left = ENVIRONMENT
-                    .read()
-                    .unwrap()
-                    .get(self.identifier.clone())
-                    .cloned()?;
-                let left = (left / value).unwrap();
-                ENVIRONMENT
-                    .write()
-                    .unwrap()
-                    .assign(self.identifier.clone(), left.clone())?;
-                Ok(left)
-            }
-            _ => Err(RuntimeError::new("Invalid assignment operator".to_string())),
-        }
+// Generators run their yields themselves, see `Generator::resume`, so the parser only allows them
+// in the body of a generator function.
+impl YieldStatement {
+    fn execute(&self, _: &mut Environment) -> RuntimeResult<Value> {
+        Err(RuntimeError::new(format!(
+            "Cannot yield outside of a generator at line {}",
+            self.line
+        )))
     }
 
     fn node_to_string(&self) -> String {
-        format!(
-            "{} {} {}",
-            self.identifier.value,
-            self.operator,
-            self.value.node_to_string()
-        )
+        match self.value {
+            Some(ref value) => format!("yield {}", value.node_to_string()),
+            None => "yield".to_string(),
+        }
     }
 }
 
-impl Expression for ConditionalExpression {
-    fn evaluate(&self) -> RuntimeResult<Value> {
-        let condition = self.condition.evaluate()?;
+impl DeferStatement {
+    fn execute(&self, environment: &mut Environment) -> RuntimeResult<Value> {
+        environment.step()?;
+        environment.defer(self.expression.clone());
 
-        if condition.is_truthy() {
-            self.then_branch.evaluate()
-        } else {
-            self.else_branch.evaluate()
-        }
+        Ok(Value::Null)
     }
 
     fn node_to_string(&self) -> String {
-        format!(
-            "{} ? {} : {}",
-            self.condition.node_to_string(),
-            self.then_branch.node_to_string(),
-            self.else_branch.node_to_string()
-        )
+        format!("defer {}", self.expression.node_to_string())
     }
 }
 
-impl Expression for BinaryExpression {
-    fn evaluate(&self) -> RuntimeResult<Value> {
-        let left = self.left.evaluate()?;
-        let right = self.right.evaluate()?;
+impl AssertStatement {
+    fn execute(&self, environment: &mut Environment) -> RuntimeResult<Value> {
+        environment.step()?;
 
-        match self.operator.token_type {
-            TokenType::Plus => Ok((left + right).unwrap()),
-            TokenType::Minus => Ok((left - right).unwrap()),
-            TokenType::Star => Ok((left * right).unwrap()),
-            TokenType::Slash => Ok((left / right).unwrap()),
-            TokenType::EqualEqual => Ok(Value::Boolean(left == right)),
-            TokenType::BangEqual => Ok(Value::Boolean(left != right)),
-            TokenType::Greater => Ok(Value::Boolean(left > right)),
-            TokenType::GreaterEqual => Ok(Value::Boolean(left >= right)),
-            TokenType::Less => Ok(Value::Boolean(left < right)),
-            TokenType::LessEqual => Ok(Value::Boolean(left <= right)),
-            TokenType::And => {
-                if left.is_truthy() {
-                    Ok(right)
-                } else {
-                    Ok(left)
-                }
-            }
-            TokenType::Or => {
-                if left.is_truthy() {
-                    Ok(left)
-                } else {
-                    Ok(right)
-                }
-            }
-            _ => Err(RuntimeError::new("Invalid binary operator".to_string())),
+        if !self.condition.evaluate(environment)?.is_truthy() {
+            return Err(RuntimeError::new_assertion_failed(
+                source::condition(&self.condition),
+                self.line,
+            ));
         }
+
+        Ok(Value::Null)
     }
 
     fn node_to_string(&self) -> String {
-        format!(
-            "{} {} {}",
-            self.left.node_to_string(),
-            self.operator.value,
-            self.right.node_to_string()
-        )
+        format!("assert {}", self.condition.node_to_string())
     }
 }
 
-impl Expression for UnaryExpression {
-    fn evaluate(&self) -> RuntimeResult<Value> {
-        let right = self.right.evaluate()?;
+impl TestDeclaration {
+    fn execute(&self, environment: &mut Environment) -> RuntimeResult<Value> {
+        environment.step()?;
 
-        match self.operator.token_type {
-            TokenType::Minus => Ok((-right).unwrap()),
-            TokenType::Bang => Ok(!right),
-            _ => Err(RuntimeError::new("Invalid unary operator".to_string())),
+        if let Some(ref mut tests) = environment.tests {
+            tests.push(Test {
+                name: self.name.value.to_string(),
+                body: self.body.clone(),
+                line: self.name.line,
+            });
         }
+
+        Ok(Value::Null)
     }
 
     fn node_to_string(&self) -> String {
-        format!("{}{}", self.operator.value, self.right.node_to_string())
+        format!("test {:?} {}", self.name.value, self.body.node_to_string())
     }
 }
 
-impl Expression for PostfixExpression {
-    fn evaluate(&self) -> RuntimeResult<Value> {
-        let left = self.left.evaluate()?;
+// Run the expressions deferred by the block that is exiting, the last deferred first. They run in
+// the scope of the block, which is still the innermost one, and a return in progress is put aside
+// so that the functions they call run in full.
+fn run_deferred(environment: &mut Environment) -> RuntimeResult<()> {
+    let deferred = environment.take_deferred();
+    if deferred.is_empty() {
+        return Ok(());
+    }
 
-        match self.operator {
-            PostfixOperator::Index(ref index) => {
-                let index = index.evaluate()?;
-                match left {
-                    Value::String(string) => {
-                        if let Value::Number(num) = index {
-                            let index = num;
-                            // if the number its negative, we start from the end of the string
-                            let index = if index < 0.0 {
-                                string.len() - index.abs() as usize
-                            } else {
-                                index as usize
-                            };
-                            Ok(Value::String(string[index..index + 1].to_string()))
-                        } else {
-                            return Err(RuntimeError::new("Invalid index operator".to_string()));
-                        }
-                    }
-                    Value::Array(array) => {
-                        if let Value::Number(num) = index {
-                            let index = num;
-                            // if the number its negative, we start from the end of the array
-                            let index = if index < 0.0 {
-                                array.len() - index.abs() as usize
-                            } else {
-                                index as usize
-                            };
-                            Ok(array[index].clone())
-                        } else {
-                            return Err(RuntimeError::new("Invalid index operator".to_string()));
-                        }
-                    }
-                    _ => Err(RuntimeError::new("Invalid index operator".to_string())),
-                }
+    let returning = environment.pause_return();
+    let depth = environment.depth();
+
+    for Deferred { expression, scopes } in deferred.into_iter().rev() {
+        environment.environment.extend(scopes);
+        let result = expression.evaluate(environment);
+        environment.environment.truncate(depth + 1);
+        result?;
+    }
+
+    environment.resume_return(returning);
+
+    Ok(())
+}
+
+impl ImportStatement {
+    fn run(&self, environment: &mut Environment) -> RuntimeResult<()> {
+        let file = match (environment.file, environment.coverage.as_mut()) {
+            (Some(_), Some(coverage)) => Some(coverage.file_index(&self.module)),
+            _ => None,
+        };
+        let importing_file = std::mem::replace(&mut environment.file, file);
+
+        let result = self.run_statements(environment);
+
+        environment.file = importing_file;
+        result
+    }
+
+    fn run_statements(&self, environment: &mut Environment) -> RuntimeResult<()> {
+        environment.start_deferring();
+
+        for statement in &self.statements {
+            statement.execute(environment)?;
+
+            // A return outside of a function ends the imported file.
+            if environment.is_returning() {
+                environment.finish_return(Value::Null);
+                break;
             }
-            PostfixOperator::Dot(ref name) => match left {
-                // Value::Object(object) => Ok(object.get(name).unwrap().clone()),
-                Value::String(string) => match name.as_str() {
-                    "length" => Ok(Value::Number(string.len() as f64)),
-                    _ => Err(RuntimeError::new("Invalid dot operator".to_string())),
-                },
-                Value::Array(array) => match name.as_str() {
-                    "length" => Ok(Value::Number(array.len() as f64)),
-                    _ => Err(RuntimeError::new("Invalid dot operator".to_string())),
-                },
-                _ => Err(RuntimeError::new("Invalid dot operator".to_string())),
-            },
-            PostfixOperator::Call(ref arguments) => match left {
-                // Value::Function(function) => {
-                //     let mut arguments = arguments
-                //         .arguments
-                //         .iter()
-                //         .map(|argument| argument.evaluate())
-                //         .collect::<RuntimeResult<Vec<Value>>>()?;
-                //     function.call(&mut arguments)
-                // }
-                _ => Err(RuntimeError::new("Invalid call operator".to_string())),
-            },
         }
+
+        run_deferred(environment)
     }
 
-    fn node_to_string(&self) -> String {
-        match self.operator {
-            PostfixOperator::Index(ref index) => {
-                format!("{}[{}]", self.left.node_to_string(), index.node_to_string())
+    // Run the file with its own globals, collecting the exported ones.
+    fn run_namespace(&self, environment: &mut Environment) -> RuntimeResult<Value> {
+        let globals = Rc::new(RefCell::new(environment.builtins.clone()));
+
+        let previous = environment.enter_globals(&globals);
+        let result = self.run(environment);
+        environment.leave_globals(previous);
+        result?;
+
+        let globals = globals.borrow();
+        let mut namespace = Object::new();
+
+        for name in &self.exports {
+            if let Some(variable) = globals.get(name) {
+                namespace.insert(
+                    name.as_str().into(),
+                    variable.value.clone().unwrap_or(Value::Null),
+                );
             }
-            PostfixOperator::Dot(ref name) => {
-                format!("{}.{}", self.left.node_to_string(), name)
+        }
+
+        Ok(Value::Object(namespace))
+    }
+}
+
+impl ImportStatement {
+    fn execute(&self, environment: &mut Environment) -> RuntimeResult<Value> {
+        environment.step()?;
+
+        let Some(ref name) = self.namespace else {
+            if environment.modules.insert(self.module.clone()) {
+                self.run(environment)?;
             }
-            PostfixOperator::Call(ref arguments) => {
-                format!("{}({:?})", self.left.node_to_string(), arguments)
+
+            return Ok(Value::Null);
+        };
+
+        let namespace = match environment.namespaces.get(&self.module) {
+            Some(namespace) => namespace.clone(),
+            None => {
+                let namespace = self.run_namespace(environment)?;
+                environment
+                    .namespaces
+                    .insert(self.module.clone(), namespace.clone());
+                namespace
             }
+        };
+
+        environment.define(name, Some(namespace), false);
+
+        Ok(Value::Null)
+    }
+
+    fn node_to_string(&self) -> String {
+        match self.namespace {
+            Some(ref name) => format!("import {} from \"{}\"", name.value, self.path.value),
+            None => format!("import \"{}\"", self.path.value),
         }
     }
 }
 
-impl Expression for Identifier {
-    fn evaluate(&self) -> RuntimeResult<Value> {
-        match ENVIRONMENT.read().unwrap().get(self.identifier.clone()) {
-            Ok(value) => Ok(value.clone()),
-            Err(err) => Err(err),
-        }
+impl FunctionDeclaration {
+    fn execute(&self, environment: &mut Environment) -> RuntimeResult<Value> {
+        environment.step()?;
+
+        let function = UserFunction {
+            name: self.name.clone(),
+            parameters: self.parameters.clone(),
+            return_type: self.return_type,
+            body: self.body.clone(),
+            generator: self.generator,
+            depth: environment.depth(),
+            globals: environment.globals(),
+            file: environment.file,
+        };
+
+        environment.define(
+            &self.name,
+            Some(Value::Function(Function::User(Rc::new(function)))),
+            false,
+        );
+
+        Ok(Value::Null)
     }
 
     fn node_to_string(&self) -> String {
-        self.identifier.value.to_string()
-    }
+        let parameters = self
+            .parameters
+            .iter()
+            .map(|parameter| parameter.to_string())
+            .collect::<Vec<String>>()
+            .join(", ");
+        let return_type = match self.return_type {
+            Some(return_type) => format!(": {}", return_type),
+            None => String::new(),
+        };
 
-    fn is_identifier(&self) -> Option<Token> {
-        Some(self.identifier.clone())
+        format!(
+            "function{} {}({}){} {}",
+            if self.generator { "*" } else { "" },
+            self.name.value,
+            parameters,
+            return_type,
+            self.body.node_to_string()
+        )
     }
 }
 
-impl Expression for ArrayLiteral {
-    fn evaluate(&self) -> RuntimeResult<Value> {
-        let mut result = Vec::new();
+impl EnumDeclaration {
+    fn execute(&self, environment: &mut Environment) -> RuntimeResult<Value> {
+        environment.step()?;
 
-        for element in &self.elements {
-            result.push(element.evaluate()?);
+        let enum_name: Rc<str> = self.name.name().into();
+        let mut namespace = Object::new();
+        for variant in &self.variants {
+            namespace.insert(
+                variant.name().into(),
+                Value::EnumVariant(Rc::new(EnumVariant {
+                    enum_name: enum_name.clone(),
+                    variant: variant.name().into(),
+                })),
+            );
         }
+        namespace.freeze();
+
+        environment.define(&self.name, Some(Value::Object(namespace)), false);
 
-        Ok(Value::Array(result))
+        Ok(Value::Null)
     }
+}
 
-    fn node_to_string(&self) -> String {
-        let mut result = "[".to_string();
+// ## Expressions
+impl Expression {
+    pub fn evaluate(&self, environment: &mut Environment) -> RuntimeResult<Value> {
+        match self {
+            Expression::Assignment(expression) => expression.evaluate(environment),
+            Expression::PropertyAssignment(expression) => expression.evaluate(environment),
+            Expression::Conditional(expression) => expression.evaluate(environment),
+            Expression::Binary(expression) => expression.evaluate(environment),
+            Expression::Unary(expression) => expression.evaluate(environment),
+            Expression::Delete(expression) => expression.evaluate(environment),
+            Expression::Postfix(expression) => expression.evaluate(environment),
+            Expression::Identifier(expression) => expression.evaluate(environment),
+            Expression::Array(expression) => expression.evaluate(environment),
+            Expression::Match(expression) => expression.evaluate(environment),
+            Expression::Literal(literal) => {
+                environment.step()?;
 
-        for (i, element) in self.elements.iter().enumerate() {
-            if i != 0 {
-                result += ", ";
+                Ok(literal.clone())
             }
-            result += &element.node_to_string();
         }
+    }
 
-        result += "]";
-
-        result
+    pub fn node_to_string(&self) -> String {
+        match self {
+            Expression::Assignment(expression) => expression.node_to_string(),
+            Expression::PropertyAssignment(expression) => expression.node_to_string(),
+            Expression::Conditional(expression) => expression.node_to_string(),
+            Expression::Binary(expression) => expression.node_to_string(),
+            Expression::Unary(expression) => expression.node_to_string(),
+            Expression::Delete(expression) => expression.node_to_string(),
+            Expression::Postfix(expression) => expression.node_to_string(),
+            Expression::Identifier(expression) => expression.node_to_string(),
+            Expression::Array(expression) => expression.node_to_string(),
+            Expression::Match(expression) => expression.node_to_string(),
+            Expression::Literal(literal) => literal_to_string(literal),
+        }
     }
 }
 
-impl Expression for Literal {
-    fn evaluate(&self) -> RuntimeResult<Value> {
-        Ok(self.clone())
+impl AssignmentExpression {
+    fn evaluate(&self, environment: &mut Environment) -> RuntimeResult<Value> {
+        environment.step()?;
+
+        let value = self.value.evaluate(environment)?;
+
+        match self.operator {
+            TokenType::Equal => {
+                environment.assign(&self.identifier, value.clone())?;
+                Ok(value)
+            }
+            TokenType::PlusEqual => {
+                let left = environment.get(&self.identifier).cloned()?;
+                let left = (left + value)
+                    .map_err(|err| RuntimeError::new_invalid_operands(err, &self.identifier))?;
+                environment.assign(&self.identifier, left.clone())?;
+                Ok(left)
+            }
+            TokenType::MinusEqual => {
+                let left = environment.get(&self.identifier).cloned()?;
+                let left = (left - value)
+                    .map_err(|err| RuntimeError::new_invalid_operands(err, &self.identifier))?;
+                environment.assign(&self.identifier, left.clone())?;
+                Ok(left)
+            }
+            TokenType::StarEqual => {
+                let left = environment.get(&self.identifier).cloned()?;
+                let left = (left * value)
+                    .map_err(|err| RuntimeError::new_invalid_operands(err, &self.identifier))?;
+                environment.assign(&self.identifier, left.clone())?;
+                Ok(left)
+            }
+            TokenType::SlashEqual => {
+                let left = environment.get(&self.identifier).cloned()?;
+                let left = (left / value)
+                    .map_err(|err| RuntimeError::new_invalid_operands(err, &self.identifier))?;
+                environment.assign(&self.identifier, left.clone())?;
+                Ok(left)
+            }
+            _ => Err(RuntimeError::new_invalid_operator(
+                "assignment",
+                &self.identifier,
+            )),
+        }
     }
 
     fn node_to_string(&self) -> String {
-        match self {
-            Value::Number(num) => num.to_string(),
-            Value::String(ref string) => "\"".to_string() + string + "\"",
-            Value::Boolean(boolean) => boolean.to_string(),
-            Value::Null => "null".to_string(),
-            Value::Array(ref array) => {
-                let mut result = "[".to_string();
-                for (i, value) in array.iter().enumerate() {
-                    if i != 0 {
-                        result += ", ";
+        format!(
+            "{} {} {}",
+            self.identifier.value,
+            self.operator,
+            self.value.node_to_string()
+        )
+    }
+}
+
+impl PropertyAssignment {
+    // Assign to the element `keys[depth]` of `target`, or to an element nested inside it.
+    fn assign(
+        &self,
+        target: &mut Value,
+        depth: usize,
+        keys: &[Value],
+        value: Value,
+    ) -> RuntimeResult<Value> {
+        let (Some((_, token)), Some(key)) = (self.path.get(depth), keys.get(depth)) else {
+            return Err(RuntimeError::new_invalid_operator(
+                "assignment",
+                &self.operator,
+            ));
+        };
+        let last = depth + 1 == keys.len();
+
+        if last && target.is_frozen() {
+            return Err(RuntimeError::new_frozen(
+                self.target_to_string(depth),
+                target,
+                token,
+            ));
+        }
+
+        let element = match target {
+            Value::Array(array) => {
+                let index = position(key, array.len(), token)?;
+                // `position` only gives indexes inside the array.
+                &mut array.elements_mut()[index]
+            }
+            Value::Object(object) => {
+                let key = ValueKey::new(key)
+                    .map_err(|found| RuntimeError::new_invalid_key(found, token))?;
+
+                // Only the last property may be new, the ones leading to it must exist.
+                if object.get(&key).is_none() {
+                    if !last {
+                        return Err(RuntimeError::new_undefined_property(
+                            &key.to_string(),
+                            token,
+                        ));
+                    }
+
+                    let value = self.combine(&Value::Null, value)?;
+                    object.insert(key, value.clone());
+                    return Ok(value);
+                }
+
+                match object.get_mut(&key) {
+                    Some(element) => element,
+                    None => {
+                        return Err(RuntimeError::new_undefined_property(
+                            &key.to_string(),
+                            token,
+                        ))
                     }
-                    result += &value.node_to_string();
                 }
-                result += "]";
-                result
             }
+            target => {
+                return Err(RuntimeError::new_type_error(
+                    "array or object",
+                    target,
+                    token,
+                ))
+            }
+        };
+
+        if !last {
+            return self.assign(element, depth + 1, keys, value);
         }
-    }
-}
 
-pub fn interpret(source: &[u8]) -> GenericResult<()> {
-    let statements = parser::parse(source)?;
+        let value = self.combine(element, value)?;
+        *element = value.clone();
+
+        Ok(value)
+    }
 
-    for statement in statements {
-        statement.execute()?;
+    // The value an element holding `current` gets from the operator, e.g. the sum for `+=`.
+    fn combine(&self, current: &Value, value: Value) -> RuntimeResult<Value> {
+        match self.operator.token_type {
+            TokenType::Equal => Ok(value),
+            TokenType::PlusEqual => current.clone() + value,
+            TokenType::MinusEqual => current.clone() - value,
+            TokenType::StarEqual => current.clone() * value,
+            TokenType::SlashEqual => current.clone() / value,
+            _ => {
+                return Err(RuntimeError::new_invalid_operator(
+                    "assignment",
+                    &self.operator,
+                ))
+            }
+        }
+        .map_err(|err| RuntimeError::new_invalid_operands(err, &self.operator))
     }
 
-    Ok(())
+    // The source of the target up to the array or object modified at `depth`, e.g. `xs[0]`.
+    fn target_to_string(&self, depth: usize) -> String {
+        target_to_string(&self.identifier, &self.path[..depth])
+    }
 }
 
-#[cfg(test)]
+// The source of a target such as `xs[0].name`, given the accesses leading to it.
+fn target_to_string(identifier: &Token, path: &[(PostfixOperator, Token)]) -> String {
+    let mut result = identifier.value.to_string();
+
+    for (operator, _) in path {
+        match operator {
+            PostfixOperator::Index(index) => result += &format!("[{}]", index.node_to_string()),
+            PostfixOperator::Dot(name) => result += &format!(".{}", name),
+            PostfixOperator::Call(_) => {}
+        }
+    }
+
+    result
+}
+
+impl PropertyAssignment {
+    fn evaluate(&self, environment: &mut Environment) -> RuntimeResult<Value> {
+        environment.step()?;
+
+        let mut keys = Vec::with_capacity(self.path.len());
+
+        for (operator, token) in &self.path {
+            keys.push(match operator {
+                PostfixOperator::Index(index) => index.evaluate(environment)?,
+                PostfixOperator::Dot(name) => Value::String(name.as_str().into()),
+                PostfixOperator::Call(_) => {
+                    return Err(RuntimeError::new_invalid_operator("assignment", token))
+                }
+            });
+        }
+
+        let value = self.value.evaluate(environment)?;
+
+        // The value is changed in place, so an array or object only held by the variable isn't
+        // copied. One shared with other values is copied when changed, as they are values. Nothing
+        // is changed until the assignment is known to succeed.
+        let root = environment.get_mut(&self.identifier)?;
+        self.assign(root, 0, &keys, value)
+    }
+
+    fn node_to_string(&self) -> String {
+        format!(
+            "{} {} {}",
+            self.target_to_string(self.path.len()),
+            self.operator.value,
+            self.value.node_to_string()
+        )
+    }
+}
+
+impl ConditionalExpression {
+    fn evaluate(&self, environment: &mut Environment) -> RuntimeResult<Value> {
+        environment.step()?;
+
+        let condition = self.condition.evaluate(environment)?;
+
+        if condition.is_truthy() {
+            self.then_branch.evaluate(environment)
+        } else {
+            self.else_branch.evaluate(environment)
+        }
+    }
+
+    fn node_to_string(&self) -> String {
+        format!(
+            "{} ? {} : {}",
+            self.condition.node_to_string(),
+            self.then_branch.node_to_string(),
+            self.else_branch.node_to_string()
+        )
+    }
+}
+
+impl BinaryExpression {
+    fn evaluate(&self, environment: &mut Environment) -> RuntimeResult<Value> {
+        if !matches!(*self.left, Expression::Binary(_)) {
+            environment.step()?;
+
+            let left = self.left.evaluate(environment)?;
+
+            if Self::short_circuits(&self.operator, &left) {
+                return Ok(left);
+            }
+
+            let right = self.right.evaluate(environment)?;
+
+            return Self::apply(&self.operator, left, right);
+        }
+
+        // A chain of operators, evaluated from the innermost one out.
+        let (chain, left) = self.chain();
+
+        for _ in &chain {
+            environment.step()?;
+        }
+
+        let mut result = left.evaluate(environment)?;
+
+        for binary in chain.into_iter().rev() {
+            if Self::short_circuits(&binary.operator, &result) {
+                continue;
+            }
+
+            let right = binary.right.evaluate(environment)?;
+            result = Self::apply(&binary.operator, result, right)?;
+        }
+
+        Ok(result)
+    }
+
+    // `&` and `|` short-circuit, the right side is only evaluated if the left one does not
+    // decide the result.
+    pub fn short_circuits(operator: &Token, left: &Value) -> bool {
+        match operator.token_type {
+            TokenType::And => !left.is_truthy(),
+            TokenType::Or => left.is_truthy(),
+            _ => false,
+        }
+    }
+
+    // Apply the operator to both operands, also used to fold operators on literals.
+    pub fn apply(operator: &Token, left: Value, right: Value) -> RuntimeResult<Value> {
+        if Self::short_circuits(operator, &left) {
+            return Ok(left);
+        }
+
+        match operator.token_type {
+            TokenType::Plus => {
+                (left + right).map_err(|err| RuntimeError::new_invalid_operands(err, operator))
+            }
+            TokenType::Minus => {
+                (left - right).map_err(|err| RuntimeError::new_invalid_operands(err, operator))
+            }
+            TokenType::Star => {
+                (left * right).map_err(|err| RuntimeError::new_invalid_operands(err, operator))
+            }
+            TokenType::Slash => {
+                (left / right).map_err(|err| RuntimeError::new_invalid_operands(err, operator))
+            }
+            TokenType::EqualEqual => Ok(Value::Boolean(left == right)),
+            TokenType::BangEqual => Ok(Value::Boolean(left != right)),
+            TokenType::EqualEqualEqual => Ok(Value::Boolean(left.strict_eq(&right))),
+            TokenType::BangEqualEqual => Ok(Value::Boolean(!left.strict_eq(&right))),
+            TokenType::Greater => Ok(Value::Boolean(left > right)),
+            TokenType::GreaterEqual => Ok(Value::Boolean(left >= right)),
+            TokenType::Less => Ok(Value::Boolean(left < right)),
+            TokenType::LessEqual => Ok(Value::Boolean(left <= right)),
+            TokenType::And | TokenType::Or => Ok(right),
+            _ => Err(RuntimeError::new_invalid_operator("binary", operator)),
+        }
+    }
+
+    fn node_to_string(&self) -> String {
+        let (chain, left) = self.chain();
+        let mut result = left.node_to_string();
+
+        for binary in chain.into_iter().rev() {
+            result += &format!(
+                " {} {}",
+                binary.operator.value,
+                binary.right.node_to_string()
+            );
+        }
+
+        result
+    }
+}
+
+impl UnaryExpression {
+    fn evaluate(&self, environment: &mut Environment) -> RuntimeResult<Value> {
+        environment.step()?;
+
+        let right = self.right.evaluate(environment)?;
+
+        Self::apply(&self.operator, right)
+    }
+
+    // Apply the operator to its operand, also used to fold operators on literals.
+    pub fn apply(operator: &Token, right: Value) -> RuntimeResult<Value> {
+        match operator.token_type {
+            TokenType::Minus => {
+                (-right).map_err(|err| RuntimeError::new_invalid_operands(err, operator))
+            }
+            TokenType::Bang => Ok(!right),
+            _ => Err(RuntimeError::new_invalid_operator("unary", operator)),
+        }
+    }
+
+    fn node_to_string(&self) -> String {
+        format!("{}{}", self.operator.value, self.right.node_to_string())
+    }
+}
+
+impl DeleteExpression {
+    fn evaluate(&self, environment: &mut Environment) -> RuntimeResult<Value> {
+        environment.step()?;
+
+        let mut keys = Vec::with_capacity(self.path.len());
+
+        for (operator, token) in &self.path {
+            keys.push(match operator {
+                PostfixOperator::Index(index) => index.evaluate(environment)?,
+                PostfixOperator::Dot(name) => Value::String(name.as_str().into()),
+                PostfixOperator::Call(_) => {
+                    return Err(RuntimeError::new_invalid_operator("delete", token))
+                }
+            });
+        }
+
+        // The variable gets a changed copy of its value, only when something was removed.
+        let mut root = environment.get(&self.identifier)?.clone();
+        let removed = self.remove(&mut root, 0, &keys)?;
+        if removed {
+            environment.assign(&self.identifier, root)?;
+        }
+
+        Ok(Value::Boolean(removed))
+    }
+
+    // Remove the element `keys[depth]` of `target`, or an element nested inside it, telling
+    // whether it was there. Elements of arrays after the removed one move down by one.
+    fn remove(&self, target: &mut Value, depth: usize, keys: &[Value]) -> RuntimeResult<bool> {
+        let (Some((_, token)), Some(key)) = (self.path.get(depth), keys.get(depth)) else {
+            return Err(RuntimeError::new_invalid_operator("delete", &self.token));
+        };
+        let last = depth + 1 == keys.len();
+
+        if last && target.is_frozen() {
+            return Err(RuntimeError::new_frozen(
+                target_to_string(&self.identifier, &self.path[..depth]),
+                target,
+                token,
+            ));
+        }
+
+        let element = match target {
+            Value::Array(array) if last => {
+                if !matches!(key, Value::Number(_)) {
+                    return Err(RuntimeError::new_type_error("number", key, token));
+                }
+
+                // An index past either end is not an element, so there is nothing to remove.
+                return Ok(match position(key, array.len(), token) {
+                    Ok(index) => {
+                        array.elements_mut().remove(index);
+                        true
+                    }
+                    Err(_) => false,
+                });
+            }
+            Value::Array(array) => {
+                let index = position(key, array.len(), token)?;
+                &mut array.elements_mut()[index]
+            }
+            Value::Object(object) => {
+                let key = ValueKey::new(key)
+                    .map_err(|found| RuntimeError::new_invalid_key(found, token))?;
+
+                if last {
+                    return Ok(object.remove(&key).is_some());
+                }
+
+                match object.get_mut(&key) {
+                    Some(element) => element,
+                    None => {
+                        return Err(RuntimeError::new_undefined_property(
+                            &key.to_string(),
+                            token,
+                        ))
+                    }
+                }
+            }
+            target => {
+                return Err(RuntimeError::new_type_error(
+                    "array or object",
+                    target,
+                    token,
+                ))
+            }
+        };
+
+        self.remove(element, depth + 1, keys)
+    }
+
+    fn node_to_string(&self) -> String {
+        format!("delete {}", target_to_string(&self.identifier, &self.path))
+    }
+}
+
+// Turn an index into a position in a sequence of length `len`. Negative indexes count from the
+// end.
+fn position(index: &Value, len: usize, token: &Token) -> RuntimeResult<usize> {
+    let Value::Number(number) = *index else {
+        return Err(RuntimeError::new_type_error("number", index, token));
+    };
+
+    let position = if number < 0.0 {
+        len as f64 + number.trunc()
+    } else {
+        number.trunc()
+    };
+
+    // NaN fails both comparisons, so it is checked on its own.
+    if position.is_nan() || position < 0.0 || position >= len as f64 {
+        return Err(RuntimeError::new_index_out_of_bounds(number, len, token));
+    }
+
+    Ok(position as usize)
+}
+
+impl PostfixExpression {
+    fn evaluate(&self, environment: &mut Environment) -> RuntimeResult<Value> {
+        environment.step()?;
+
+        let left = self.left.evaluate(environment)?;
+
+        match self.operator {
+            PostfixOperator::Index(ref index) => {
+                let index = index.evaluate(environment)?;
+                match left {
+                    // Strings are indexed by character, as their length counts them.
+                    Value::String(string) => {
+                        let index = position(&index, string.chars().count(), &self.token)?;
+                        let chr = string.chars().nth(index).unwrap_or_default();
+                        Ok(Value::String(chr.to_string().into()))
+                    }
+                    Value::Array(array) => {
+                        let index = position(&index, array.len(), &self.token)?;
+                        Ok(array.get(index).cloned().unwrap_or(Value::Null))
+                    }
+                    Value::Object(object) => {
+                        let key = ValueKey::new(&index)
+                            .map_err(|found| RuntimeError::new_invalid_key(found, &self.token))?;
+                        object.get(&key).cloned().ok_or_else(|| {
+                            RuntimeError::new_undefined_property(&key.to_string(), &self.token)
+                        })
+                    }
+                    left => Err(self.unsupported("index", &left)),
+                }
+            }
+            PostfixOperator::Dot(ref name) => match left {
+                // Properties of the object take precedence over its methods.
+                Value::Object(object) => match object.property(name) {
+                    Some(value) => Ok(value.clone()),
+                    None if name == "clone" => Ok(Value::Function(
+                        natives::clone_method(Value::Object(object)).into(),
+                    )),
+                    None => match natives::object_method(object, name) {
+                        Some(method) => Ok(Value::Function(method.into())),
+                        None => Err(RuntimeError::new_undefined_property(name, &self.token)),
+                    },
+                },
+                Value::String(string) => match name.as_str() {
+                    "length" => Ok(Value::Number(string.chars().count() as f64)),
+                    _ => match natives::string_method(string, name) {
+                        Some(method) => Ok(Value::Function(method.into())),
+                        None => Err(RuntimeError::new_unknown_property(
+                            name,
+                            &["length", "padStart", "padEnd"],
+                            &self.token,
+                        )),
+                    },
+                },
+                Value::Array(array) => match name.as_str() {
+                    "length" => Ok(Value::Number(array.len() as f64)),
+                    "clone" => Ok(Value::Function(
+                        natives::clone_method(Value::Array(array)).into(),
+                    )),
+                    _ => match natives::array_method(array, name, self.token.line) {
+                        Some(method) => Ok(Value::Function(method.into())),
+                        None => Err(RuntimeError::new_unknown_property(
+                            name,
+                            &["length", "clone", "flat", "concat", "sort"],
+                            &self.token,
+                        )),
+                    },
+                },
+                Value::Generator(generator) => match name.as_str() {
+                    "next" => Ok(Value::Function(
+                        natives::next_method(generator, self.token.line).into(),
+                    )),
+                    _ => Err(RuntimeError::new_unknown_property(
+                        name,
+                        &["next"],
+                        &self.token,
+                    )),
+                },
+                Value::Function(function) => match name.as_str() {
+                    "name" => Ok(Value::String(function.name().into())),
+                    "length" => Ok(Value::Number(function.length() as f64)),
+                    _ => Err(RuntimeError::new_unknown_property(
+                        name,
+                        &["name", "length"],
+                        &self.token,
+                    )),
+                },
+                left => Err(self.unsupported(&format!("read property '{}' of", name), &left)),
+            },
+            PostfixOperator::Call(ref arguments) => self.call(left, arguments, environment, false),
+        }
+    }
+
+    // Evaluate a call returned from the body of a function, leaving it for the function to make
+    // in its place when it can, see `UserFunction::call`. The value is then null, and the returning
+    // function gives the value of the call.
+    fn evaluate_tail_call(&self, environment: &mut Environment) -> RuntimeResult<Value> {
+        environment.step()?;
+
+        let left = self.left.evaluate(environment)?;
+
+        match self.operator {
+            PostfixOperator::Call(ref arguments) => self.call(left, arguments, environment, true),
+            _ => unreachable!("only calls are tail calls"),
+        }
+    }
+
+    fn call(
+        &self,
+        left: Value,
+        arguments: &[Expression],
+        environment: &mut Environment,
+        tail: bool,
+    ) -> RuntimeResult<Value> {
+        let Value::Function(function) = left else {
+            return Err(self.unsupported("call", &left));
+        };
+
+        let arguments = arguments
+            .iter()
+            .map(|argument| argument.evaluate(environment))
+            .collect::<RuntimeResult<Vec<Value>>>()?;
+
+        match function {
+            Function::User(function)
+                if tail && environment.can_tail_call(&function, arguments.len()) =>
+            {
+                environment.tail_call(TailCall {
+                    function,
+                    arguments,
+                    line: self.token.line,
+                });
+                Ok(Value::Null)
+            }
+            function => function.call(environment, arguments, self.token.line),
+        }
+    }
+
+    // `left`, the value of the operand, doesn't support `operation`.
+    fn unsupported(&self, operation: &str, left: &Value) -> RuntimeError {
+        RuntimeError::new_unsupported(operation, source::operand(&self.left), left, &self.token)
+    }
+
+    fn node_to_string(&self) -> String {
+        match self.operator {
+            PostfixOperator::Index(ref index) => {
+                format!("{}[{}]", self.left.node_to_string(), index.node_to_string())
+            }
+            PostfixOperator::Dot(ref name) => {
+                format!("{}.{}", self.left.node_to_string(), name)
+            }
+            PostfixOperator::Call(ref arguments) => {
+                format!("{}({:?})", self.left.node_to_string(), arguments)
+            }
+        }
+    }
+}
+
+impl Identifier {
+    fn evaluate(&self, environment: &mut Environment) -> RuntimeResult<Value> {
+        environment.step()?;
+
+        environment.get(&self.identifier).cloned()
+    }
+
+    fn node_to_string(&self) -> String {
+        self.identifier.value.to_string()
+    }
+}
+
+impl ArrayLiteral {
+    fn evaluate(&self, environment: &mut Environment) -> RuntimeResult<Value> {
+        environment.step()?;
+
+        let mut result = Vec::new();
+
+        for element in &self.elements {
+            result.push(element.evaluate(environment)?);
+        }
+
+        Ok(Value::Array(result.into()))
+    }
+
+    fn node_to_string(&self) -> String {
+        let mut result = "[".to_string();
+
+        for (i, element) in self.elements.iter().enumerate() {
+            if i != 0 {
+                result += ", ";
+            }
+            result += &element.node_to_string();
+        }
+
+        result += "]";
+
+        result
+    }
+}
+
+impl MatchExpression {
+    fn evaluate(&self, environment: &mut Environment) -> RuntimeResult<Value> {
+        environment.step()?;
+
+        let value = self.value.evaluate(environment)?;
+
+        for arm in &self.arms {
+            let mut bindings = Vec::new();
+            if !matches_pattern(&arm.pattern, &value, environment, &mut bindings)? {
+                continue;
+            }
+
+            environment.push();
+            for (name, value) in bindings {
+                environment.define(name, Some(value), false);
+            }
+            let result = arm.body.evaluate(environment);
+            environment.pop();
+
+            return result;
+        }
+
+        Err(RuntimeError::new_no_match(&value, &self.token))
+    }
+
+    fn node_to_string(&self) -> String {
+        let arms = self
+            .arms
+            .iter()
+            .map(|arm| {
+                format!(
+                    "{} => {}",
+                    source::pattern(&arm.pattern),
+                    arm.body.node_to_string()
+                )
+            })
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        format!("match {} {{ {} }}", self.value.node_to_string(), arms)
+    }
+}
+
+// Whether `value` matches `pattern`, adding the names the pattern declares to `bindings` along
+// with their values. Constants in the pattern are read as they are reached.
+fn matches_pattern<'a>(
+    pattern: &'a Pattern,
+    value: &Value,
+    environment: &mut Environment,
+    bindings: &mut Vec<(&'a Token, Value)>,
+) -> RuntimeResult<bool> {
+    match pattern {
+        Pattern::Wildcard => Ok(true),
+        Pattern::Literal(literal) => Ok(value == literal),
+        Pattern::Binding(name) => {
+            bindings.push((name, value.clone()));
+            Ok(true)
+        }
+        Pattern::Constant(constant) => Ok(*value == constant.evaluate(environment)?),
+        Pattern::Array(elements) => {
+            let Value::Array(array) = value else {
+                return Ok(false);
+            };
+            if array.len() != elements.len() {
+                return Ok(false);
+            }
+
+            for (pattern, element) in elements.iter().zip(array.iter()) {
+                if !matches_pattern(pattern, element, environment, bindings)? {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        }
+        Pattern::Alternatives(alternatives) => {
+            for alternative in alternatives {
+                if matches_pattern(alternative, value, environment, bindings)? {
+                    return Ok(true);
+                }
+            }
+            Ok(false)
+        }
+    }
+}
+
+fn literal_to_string(literal: &Literal) -> String {
+    match literal {
+        Value::Number(num) => number_to_string(*num),
+        Value::String(ref string) => "\"".to_string() + string + "\"",
+        Value::Boolean(boolean) => boolean.to_string(),
+        Value::Null => "null".to_string(),
+        Value::Array(ref array) => {
+            let mut result = "[".to_string();
+            for (i, value) in array.iter().enumerate() {
+                if i != 0 {
+                    result += ", ";
+                }
+                result += &literal_to_string(value);
+            }
+            result += "]";
+            result
+        }
+        Value::Object(ref object) => format!("{:?}", object),
+        Value::Function(ref function) => function.to_string(),
+        Value::EnumVariant(ref variant) => variant.to_string(),
+        Value::Generator(ref generator) => generator.to_string(),
+    }
+}
+
+// Helpers written in NotJS, defined as globals before any user code runs.
+const PRELUDE: &str = include_str!("interpreter/prelude.notjs");
+
+/// Runs NotJS programs. Globals defined by a program stay defined for the next one, so an
+/// interpreter can back a REPL or run a library before the script that uses it.
+pub struct Interpreter {
+    environment: Environment,
+    imports: Imports,
+    // Whether parsed programs go through the optimizer before running.
+    optimize: bool,
+}
+
+/// How a test block run by [`Interpreter::test`] went.
+#[derive(Debug)]
+pub struct TestResult {
+    pub name: String,
+    /// Line the test is declared at.
+    pub line: u32,
+    /// Why the test failed, `None` if it passed.
+    pub error: Option<TracedError>,
+}
+
+/// The kind of top-level statement that gave the value of a run, see
+/// [`Interpreter::interpret_with_kind`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ResultKind {
+    /// A declaration of a variable, function, enum or test, or an import.
+    Declaration,
+    Print,
+    /// An expression statement, telling whether it is the `null` literal itself.
+    Expression {
+        null_literal: bool,
+    },
+    /// Any other statement, such as a loop or a block.
+    Other,
+}
+
+impl ResultKind {
+    fn of(statement: &Statement) -> Self {
+        match statement {
+            Statement::Variable(_)
+            | Statement::Function(_)
+            | Statement::Enum(_)
+            | Statement::Test(_)
+            | Statement::Import(_) => ResultKind::Declaration,
+            Statement::Print(_) => ResultKind::Print,
+            Statement::Expression(statement) => ResultKind::Expression {
+                null_literal: matches!(statement.expression, Expression::Literal(Value::Null)),
+            },
+            _ => ResultKind::Other,
+        }
+    }
+}
+
+impl Interpreter {
+    /// An interpreter with every native and the prelude, as used by the `notjs` binary.
+    pub fn new() -> Self {
+        let mut interpreter = Self::bare();
+        interpreter.load_prelude();
+        interpreter
+    }
+
+    /// An interpreter with the natives but without the prelude.
+    pub fn bare() -> Self {
+        let mut interpreter = Interpreter {
+            environment: Environment::new(),
+            imports: Imports::default(),
+            optimize: false,
+        };
+
+        natives::register(&mut interpreter);
+        #[cfg(feature = "host")]
+        natives::register_host(&mut interpreter);
+
+        interpreter
+    }
+
+    /// An interpreter without the natives that reach outside of the program, such as `env`.
+    /// Imports fail without reading any file, also those run through `eval`.
+    pub fn sandboxed() -> Self {
+        let mut interpreter = Interpreter {
+            environment: Environment::new(),
+            imports: Imports::denied(),
+            optimize: false,
+        };
+        interpreter.environment.sandboxed = true;
+
+        natives::register(&mut interpreter);
+        interpreter.load_prelude();
+
+        interpreter
+    }
+
+    fn load_prelude(&mut self) {
+        self.interpret(PRELUDE.as_bytes())
+            .expect("the prelude should run without errors");
+
+        // Modules imported with a namespace get the prelude too.
+        self.environment.builtins = self.environment.environment[0].clone();
+    }
+
+    /// Resolve the imports of the programs run by the interpreter relative to the directory of
+    /// the script at `path`.
+    pub fn with_script(mut self, path: impl AsRef<Path>) -> Self {
+        self.set_script(Some(path.as_ref()));
+        self
+    }
+
+    /// Resolve the imports of the programs run from now on relative to the directory of the
+    /// script at `path`, or to the working directory with `None`. Running several files in turn
+    /// lets each import from its own directory.
+    pub fn set_script(&mut self, path: Option<&Path>) {
+        self.imports = match path {
+            _ if self.environment.sandboxed => Imports::denied(),
+            Some(path) => Imports::for_file(path),
+            None => Imports::default(),
+        };
+    }
+
+    /// Optimize programs before running them: operators on literals are computed once, and
+    /// branches that can never run are removed.
+    pub fn with_optimizer(mut self) -> Self {
+        self.optimize = true;
+        self
+    }
+
+    /// Make the arguments given after the script path available through `args()`.
+    pub fn with_args(mut self, args: Vec<String>) -> Self {
+        self.define_native(natives::args(args));
+        self
+    }
+
+    /// Limit how deep function calls can nest before failing with an error.
+    pub fn with_max_call_depth(mut self, max_call_depth: usize) -> Self {
+        self.environment.max_call_depth = max_call_depth;
+        self
+    }
+
+    /// Limit the length of the arrays built by `range()`.
+    pub fn with_range_limit(mut self, limit: usize) -> Self {
+        self.define_native(natives::range(limit));
+        self
+    }
+
+    /// Limit how many statements and expressions each call to `interpret` may run, failing with
+    /// `RuntimeError::Budget` once the limit is reached.
+    pub fn with_budget(mut self, steps: usize) -> Self {
+        self.environment.max_steps = Some(steps);
+        self
+    }
+
+    /// Abort each call to `interpret` with `RuntimeError::Timeout` once it has run for longer
+    /// than `timeout`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.environment.timeout = Some(timeout);
+        self
+    }
+
+    /// Send the output of print statements to `output` instead of stdout.
+    pub fn with_output(mut self, output: impl Write + 'static) -> Self {
+        self.environment.output = Box::new(output);
+        self
+    }
+
+    /// Write each statement to the trace output before running it, with its line and nesting
+    /// depth, along with each function call, its arguments and the value it returns.
+    pub fn set_trace(&mut self, enabled: bool) {
+        self.environment.tracing = enabled;
+    }
+
+    /// Send the trace enabled with [`set_trace`](Self::set_trace) to `output` instead of stderr.
+    pub fn with_trace_output(mut self, output: impl Write + 'static) -> Self {
+        self.environment.trace_output = Box::new(output);
+        self
+    }
+
+    /// Time every function call, and the code outside of functions, until profiling is turned
+    /// off. The times are given by [`take_profile`](Self::take_profile).
+    pub fn set_profiling(&mut self, enabled: bool) {
+        match (enabled, self.environment.profiler.is_some()) {
+            (true, false) => self.environment.profiler = Some(Profiler::default()),
+            (false, true) => self.environment.profiler = None,
+            _ => {}
+        }
+    }
+
+    /// The time spent in each function since profiling was turned on or the profile was last
+    /// taken. Empty when profiling is off.
+    pub fn take_profile(&mut self) -> Profile {
+        self.environment
+            .profiler
+            .as_mut()
+            .map(Profiler::take)
+            .unwrap_or_default()
+    }
+
+    /// Record which lines of the programs run, and of the files they import, execute at least
+    /// one statement, until coverage is turned off. The lines are given by
+    /// [`take_coverage`](Self::take_coverage).
+    pub fn set_coverage(&mut self, enabled: bool) {
+        match (enabled, self.environment.coverage.is_some()) {
+            (true, false) => self.environment.coverage = Some(Coverage::default()),
+            (false, true) => self.environment.coverage = None,
+            _ => {}
+        }
+    }
+
+    /// The lines covered since coverage was turned on or was last taken. Empty when coverage is
+    /// off.
+    pub fn take_coverage(&mut self) -> Coverage {
+        self.environment
+            .coverage
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Pause the programs run before each statement and hand the pauses to `hook`, which decides
+    /// when to pause next, or run without pausing again with `None`.
+    pub fn set_debugger(&mut self, hook: Option<Box<dyn DebugHook>>) {
+        self.environment.debugger = hook.map(Debugger::new);
+    }
+
+    /// Define a global function implemented in Rust.
+    pub fn define_native(&mut self, native: NativeFunction) {
+        let name = native.name.clone();
+        self.define(&name, Value::Function(native.into()));
+    }
+
+    /// Define a native backed by a Rust closure with typed arguments, e.g.
+    /// `interpreter.bind("hypot", |a: f64, b: f64| (a * a + b * b).sqrt())`.
+    pub fn bind<Args>(&mut self, name: &str, function: impl Bind<Args>) {
+        self.define_native(function.into_native(name));
+    }
+
+    /// Define a global constant visible to every program run by the interpreter.
+    pub fn define(&mut self, name: &str, value: Value) {
+        self.environment.define_global(name, value);
+    }
+
+    /// Parse a program with the interpreter globals already known to the resolver.
+    pub fn parse(&self, source: &[u8]) -> ParseResult<Vec<Statement>> {
+        let statements = self.parse_unoptimized(source)?;
+
+        Ok(if self.optimize {
+            optimizer::optimize(statements)
+        } else {
+            statements
+        })
+    }
+
+    fn parse_unoptimized(&self, source: &[u8]) -> ParseResult<Vec<Statement>> {
+        let builtins = Resolver::from_scope(&self.environment.builtins);
+
+        parser::parse_with_imports(
+            source,
+            Resolver::from_environment(&self.environment),
+            self.imports.clone().with_builtins(builtins),
+        )
+    }
+
+    /// Parse `source` as [`interpret`](Self::interpret) would and check its types without
+    /// running it, see [`typecheck`](typecheck::typecheck). Code the optimizer would drop is
+    /// checked too.
+    pub fn typecheck(&self, source: &[u8]) -> ParseResult<()> {
+        typecheck::typecheck(&self.parse_unoptimized(source)?)
+    }
+
+    /// Run `source`, giving the value of the last statement executed, or null for an empty
+    /// program.
+    pub fn interpret(&mut self, source: &[u8]) -> NotJsResult<Value> {
+        let statements = self.parse(source)?;
+        self.run(statements)
+    }
+
+    /// Interpret `source` like [`interpret`](Self::interpret), also telling what kind of
+    /// statement the result comes from, which is the last one of the program. A prompt can tell
+    /// from it whether a null result is worth showing.
+    pub fn interpret_with_kind(&mut self, source: &[u8]) -> NotJsResult<(Value, ResultKind)> {
+        let statements = self.parse(source)?;
+        let kind = statements.last().map_or(ResultKind::Other, ResultKind::of);
+
+        Ok((self.run(statements)?, kind))
+    }
+
+    /// Run the program in the file at `path`, resolving its imports next to it as with
+    /// [`set_script`](Self::set_script). A file that can't be read gives
+    /// [`NotJsError::Io`](crate::NotJsError::Io).
+    #[cfg(feature = "host")]
+    pub fn run_file(&mut self, path: impl AsRef<Path>) -> NotJsResult<Value> {
+        let path = path.as_ref();
+        let source = std::fs::read(path)?;
+
+        self.set_script(Some(path));
+        self.interpret(&source)
+    }
+
+    /// Run a program that was already parsed, such as one read with
+    /// [`parser::program_from_json`]. Its names are resolved again against the interpreter
+    /// globals, since nothing in a serialized program can be trusted to be declared.
+    pub fn run_ast(&mut self, program: Vec<Statement>) -> NotJsResult<Value> {
+        let builtins = Resolver::from_scope(&self.environment.builtins);
+        Resolver::from_environment(&self.environment).resolve_statements(&program, &builtins)?;
+
+        let statements = if self.optimize {
+            optimizer::optimize(program)
+        } else {
+            program
+        };
+
+        self.run(statements)
+    }
+
+    fn run(&mut self, statements: Vec<Statement>) -> NotJsResult<Value> {
+        self.environment.start_run();
+
+        if let Some(ref mut coverage) = self.environment.coverage {
+            coverage.add_program(&statements);
+            self.environment.file = Some(0);
+        }
+
+        self.environment.enter_profile(profile::TOP_LEVEL);
+        let result = self.run_statements(statements);
+        self.environment.exit_profile();
+        self.environment.file = None;
+
+        result.map_err(|error| self.traced(error).into())
+    }
+
+    fn run_statements(&mut self, statements: Vec<Statement>) -> RuntimeResult<Value> {
+        let mut result = Value::Null;
+
+        self.environment.start_deferring();
+
+        for statement in statements {
+            result = statement.execute(&mut self.environment)?;
+
+            // A return outside of a function ends the program.
+            if self.environment.is_returning() {
+                self.environment.finish_return(Value::Null);
+                break;
+            }
+        }
+
+        run_deferred(&mut self.environment)?;
+
+        Ok(result)
+    }
+
+    /// Capture the globals defined so far, so that [`rollback`](Self::rollback) can undo the
+    /// programs run after it. Names are resolved against the globals each time a program is
+    /// parsed, so rolling back also forgets the names declared since.
+    pub fn checkpoint(&self) -> EnvSnapshot {
+        self.environment.snapshot()
+    }
+
+    /// Go back to the globals of `checkpoint`.
+    pub fn rollback(&mut self, checkpoint: EnvSnapshot) {
+        self.environment.restore(checkpoint);
+    }
+
+    /// Evaluate a single expression against the interpreter's environment. Statements are
+    /// rejected.
+    pub fn eval_expression(&mut self, source: &[u8]) -> NotJsResult<Value> {
+        let expression = parser::parse_expression_with_resolver(
+            source,
+            Resolver::from_environment(&self.environment),
+        )?;
+
+        self.environment.start_run();
+
+        expression
+            .evaluate(&mut self.environment)
+            .map_err(|error| self.traced(error).into())
+    }
+
+    // Wrap an error that escaped a run, resetting the environment for the next one.
+    fn traced(&mut self, error: RuntimeError) -> TracedError {
+        let trace = self.environment.take_trace();
+        self.environment.unwind();
+
+        TracedError { error, trace }
+    }
+
+    /// Interpret `source`, returning everything it printed along with the result.
+    pub fn interpret_capture(&mut self, source: &[u8]) -> (NotJsResult<Value>, String) {
+        let buffer = SharedBuffer::default();
+        let output = std::mem::replace(&mut self.environment.output, Box::new(buffer.clone()));
+
+        let result = self.interpret(source);
+
+        self.environment.output = output;
+        let printed = String::from_utf8_lossy(&buffer.0.borrow()).into_owned();
+
+        (result, printed)
+    }
+
+    /// Run `source` with its test blocks collected instead of skipped, then run each test in the
+    /// order they were declared. Tests run in the globals the program left, and see what the tests
+    /// before them changed. The program failing is an error, while a test failing only fails its
+    /// result.
+    pub fn test(&mut self, source: &[u8]) -> NotJsResult<Vec<TestResult>> {
+        self.environment.tests = Some(Vec::new());
+        let result = self.interpret(source);
+        let tests = self.environment.tests.take().unwrap_or_default();
+        result?;
+
+        Ok(tests.into_iter().map(|test| self.run_test(test)).collect())
+    }
+
+    fn run_test(&mut self, test: Test) -> TestResult {
+        self.environment.start_run();
+
+        let result = test.body.execute(&mut self.environment);
+        // A return ends the test.
+        self.environment.finish_return(Value::Null);
+
+        TestResult {
+            name: test.name,
+            line: test.line,
+            error: result.err().map(|error| self.traced(error)),
+        }
+    }
+}
+
+// A buffer that can be handed out as an output while still being readable afterwards.
+#[derive(Clone, Default)]
+pub(crate) struct SharedBuffer(pub(crate) Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Run `source` in a fresh [`Interpreter`].
+pub fn interpret(source: &[u8]) -> NotJsResult<()> {
+    Interpreter::new().interpret(source)?;
+    Ok(())
+}
+
+/// Run `source` in a fresh [`Interpreter`], giving the value of the last statement executed.
+pub fn interpret_value(source: &[u8]) -> NotJsResult<Value> {
+    Interpreter::new().interpret(source)
+}
+
+#[cfg(test)]
 mod tests {
-    use crate::parser::parse;
+    use crate::{common::function::Arity, error::NotJsError};
+
+    use super::*;
+
+    fn get(interpreter: &Interpreter, name: &str) -> Value {
+        interpreter
+            .environment
+            .get(&Token::new(
+                TokenType::Identifier,
+                Value::String(name.into()),
+                0,
+            ))
+            .cloned()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_interpret_value() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(interpreter.interpret(b"").unwrap(), Value::Null);
+        assert_eq!(interpreter.interpret(b"1 + 2").unwrap(), Value::Number(3.0));
+        assert_eq!(
+            interpreter.interpret(b"let x = 4 x * 2").unwrap(),
+            Value::Number(8.0)
+        );
+        assert_eq!(interpreter.interpret(b"let y = 1").unwrap(), Value::Null);
+        assert_eq!(
+            interpreter.interpret(b"return 5 6").unwrap(),
+            Value::Number(5.0)
+        );
+        assert_eq!(
+            interpret_value(b"\"a\" + \"b\"").unwrap(),
+            Value::String("ab".into())
+        );
+        assert!(interpret(b"1").is_ok());
+    }
+
+    #[test]
+    fn test_interpret_print() {
+        let source = br#"
+            println "hello"
+            print 1 + 2
+            print [1, "a"]
+        "#;
+        let (result, printed) = Interpreter::new().interpret_capture(source);
+        result.unwrap();
+        assert_eq!(printed, "hello\n3[1, a]");
+    }
+
+    #[test]
+    fn test_interpret_with_output() {
+        let buffer = SharedBuffer::default();
+        let mut interpreter = Interpreter::new().with_output(buffer.clone());
+        interpreter.interpret(b"println 1").unwrap();
+        let (_, printed) = interpreter.interpret_capture(b"println 2");
+        assert_eq!(printed, "2\n");
+        // The configured output is restored after capturing.
+        interpreter.interpret(b"println 3").unwrap();
+        assert_eq!(*buffer.0.borrow(), b"1\n3\n");
+    }
+
+    #[test]
+    fn test_interpret_string_index() {
+        let source = br#"
+            let str = "hello";
+            let char = str[1];
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source).unwrap();
+        assert_eq!(get(&interpreter, "char"), Value::String("e".into()));
+
+        // Characters, not bytes, as with the length.
+        let value = interpret_value("let str = \"d\u{e9}j\u{e0}\" str[1] + str[-1]".as_bytes());
+        assert_eq!(value.unwrap(), Value::from("\u{e9}\u{e0}"));
+        assert!(interpret_value("\"\u{e9}\"[1]".as_bytes()).is_err());
+        assert!(interpret_value(b"[1, 2][NaN]").is_err());
+    }
+
+    #[test]
+    fn test_interpret_string_length() {
+        let source = br#"
+            let str = "hello";
+            let length = str.length;
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source).unwrap();
+        assert_eq!(get(&interpreter, "length"), Value::Number(5.0));
+    }
+
+    #[test]
+    fn test_interpret_args() {
+        let source = br#"
+            print args().length
+            let count = args().length
+            let first = args()[0]
+        "#;
+        let mut interpreter =
+            Interpreter::new().with_args(vec!["foo".to_string(), "bar".to_string()]);
+        let (result, printed) = interpreter.interpret_capture(source);
+        result.unwrap();
+        assert_eq!(printed, "2");
+        assert_eq!(get(&interpreter, "count"), Value::Number(2.0));
+        assert_eq!(get(&interpreter, "first"), Value::String("foo".into()));
+    }
+
+    #[test]
+    fn test_interpret_args_empty_by_default() {
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(b"let count = args().length").unwrap();
+        assert_eq!(get(&interpreter, "count"), Value::Number(0.0));
+    }
+
+    #[cfg(feature = "host")]
+    #[test]
+    fn test_interpret_env() {
+        std::env::set_var("NOTJS_TEST_ENV", "hello");
+        std::env::remove_var("NOTJS_TEST_ENV_UNSET");
+        let source = br#"
+            let value = env("NOTJS_TEST_ENV")
+            let unset = env("NOTJS_TEST_ENV_UNSET")
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source).unwrap();
+        assert_eq!(get(&interpreter, "value"), Value::String("hello".into()));
+        assert_eq!(get(&interpreter, "unset"), Value::Null);
+    }
+
+    #[cfg(feature = "host")]
+    #[test]
+    fn test_interpret_set_env() {
+        let source = br#"
+            setEnv("NOTJS_TEST_SET_ENV", "world")
+            let value = env("NOTJS_TEST_SET_ENV")
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source).unwrap();
+        assert_eq!(get(&interpreter, "value"), Value::String("world".into()));
+        assert_eq!(std::env::var("NOTJS_TEST_SET_ENV").unwrap(), "world");
+    }
+
+    #[cfg(feature = "host")]
+    #[test]
+    fn test_interpret_env_invalid_name() {
+        let mut interpreter = Interpreter::new();
+        assert!(interpreter.interpret(b"env(1)").is_err());
+        assert!(interpreter.interpret(b"setEnv(\"A=B\", \"x\")").is_err());
+    }
+
+    #[test]
+    fn test_interpret_sandboxed_has_no_env() {
+        let mut interpreter = Interpreter::sandboxed();
+        assert!(interpreter.interpret(b"env(\"HOME\")").is_err());
+        assert!(interpreter.interpret(b"let count = args().length").is_ok());
+    }
+
+    #[test]
+    fn test_interpret_eval() {
+        let source = br#"
+            let x = 2
+            let sum = eval("1 + x")
+            let last = eval("let a = 1 a + 41")
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source).unwrap();
+        assert_eq!(get(&interpreter, "sum"), Value::Number(3.0));
+        assert_eq!(get(&interpreter, "last"), Value::Number(42.0));
+    }
+
+    #[test]
+    fn test_interpret_eval_defines_for_later_evals() {
+        let source = br#"
+            eval("let y = 3")
+            let z = eval("y * 2")
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source).unwrap();
+        assert_eq!(get(&interpreter, "y"), Value::Number(3.0));
+        assert_eq!(get(&interpreter, "z"), Value::Number(6.0));
+    }
+
+    #[test]
+    fn test_interpret_eval_definitions_not_visible_to_caller() {
+        let mut interpreter = Interpreter::new();
+        assert!(interpreter
+            .interpret(b"eval(\"let w = 1\") let v = w")
+            .is_err());
+    }
+
+    #[test]
+    fn test_interpret_eval_parse_error() {
+        let mut interpreter = Interpreter::new();
+        let err = interpreter.interpret(b"eval(\"let = 1\")").unwrap_err();
+        assert!(matches!(err, NotJsError::Runtime(_)));
+        assert!(err.to_string().contains("eval:"));
+    }
+
+    #[test]
+    fn test_interpret_json() {
+        let source = br#"
+            let data = jsonParse("{\"name\": \"notjs\", \"tags\": [1, [2, 3]]}")
+            let name = data.name
+            let inner = data["tags"][1][0]
+            let text = jsonStringify(data)
+            let pretty = jsonStringify([1], 2)
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source).unwrap();
+        assert_eq!(get(&interpreter, "name"), Value::String("notjs".into()));
+        assert_eq!(get(&interpreter, "inner"), Value::Number(2.0));
+        assert_eq!(
+            get(&interpreter, "text"),
+            Value::String(r#"{"name":"notjs","tags":[1,[2,3]]}"#.into())
+        );
+        assert_eq!(
+            get(&interpreter, "pretty"),
+            Value::String("[\n  1\n]".into())
+        );
+    }
+
+    #[test]
+    fn test_interpret_json_errors() {
+        let mut interpreter = Interpreter::new();
+        let err = interpreter
+            .interpret(br#"jsonParse("[1,")"#)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("jsonParse: expected a value, found end of input at line 1 column 4"));
+        let err = interpreter
+            .interpret(b"jsonStringify([args])")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("jsonStringify: cannot convert <native fn args/0> to JSON"));
+
+        let source = format!("jsonParse(\"{}\")", "[".repeat(100_000));
+        let err = runtime_error(&mut interpreter, source.as_bytes());
+        assert!(err
+            .to_string()
+            .contains("jsonParse: nesting deeper than 512 levels at line 1 column 513"));
+    }
+
+    #[test]
+    fn test_interpret_ord_chr() {
+        let source = r#"
+            let a = ord("A")
+            let euro = ord("€")
+            let emoji = ord("😀")
+            let letter = chr(65)
+            let symbol = chr(8364)
+            let round_trip = chr(ord("ñ"))
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source.as_bytes()).unwrap();
+        assert_eq!(get(&interpreter, "a"), Value::Number(65.0));
+        assert_eq!(get(&interpreter, "euro"), Value::Number(8364.0));
+        assert_eq!(get(&interpreter, "emoji"), Value::Number(128512.0));
+        assert_eq!(get(&interpreter, "letter"), Value::String("A".into()));
+        assert_eq!(get(&interpreter, "symbol"), Value::String("€".into()));
+        assert_eq!(get(&interpreter, "round_trip"), Value::String("ñ".into()));
+    }
+
+    #[test]
+    fn test_interpret_ord_chr_errors() {
+        let mut interpreter = Interpreter::new();
+        let mut error = |source: &str| runtime_error(&mut interpreter, source.as_bytes());
+
+        assert_eq!(
+            error(r#"ord("ab")"#),
+            RuntimeError::new("ord: expected a single character, found \"ab\"".to_string())
+        );
+        assert_eq!(
+            error(r#"ord("")"#),
+            RuntimeError::new("ord: expected a single character, found \"\"".to_string())
+        );
+        assert_eq!(
+            error("ord(65)"),
+            RuntimeError::new("ord: expected string, found number".to_string())
+        );
+        assert_eq!(
+            error("chr(65.5)"),
+            RuntimeError::new("chr: expected a non-negative integer, found 65.5".to_string())
+        );
+        assert_eq!(
+            error("chr(-1)"),
+            RuntimeError::new("chr: expected a non-negative integer, found -1".to_string())
+        );
+        assert_eq!(
+            error("chr(Infinity)"),
+            RuntimeError::new("chr: expected a non-negative integer, found Infinity".to_string())
+        );
+        assert_eq!(
+            error("chr(1000000000000000000000)"),
+            RuntimeError::new("chr: expected a non-negative integer, found 1e+21".to_string())
+        );
+        assert_eq!(
+            error("chr(55296)"),
+            RuntimeError::new("chr: 55296 is not a valid code point".to_string())
+        );
+        assert_eq!(
+            error("chr(1114112)"),
+            RuntimeError::new("chr: 1114112 is not a valid code point".to_string())
+        );
+        assert_eq!(
+            error(r#"chr("A")"#),
+            RuntimeError::new("chr: expected number, found string".to_string())
+        );
+    }
+
+    #[test]
+    fn test_interpret_format() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter
+                .interpret(br#"format("({}, {}) {{ok}}", 1, [true, "b"])"#)
+                .unwrap(),
+            Value::String("(1, [true, b]) {ok}".into())
+        );
+
+        let mut error = |source: &str| runtime_error(&mut interpreter, source.as_bytes());
+        assert_eq!(
+            error(r#"format("{} {}", 1)"#),
+            RuntimeError::new("format: 2 placeholders but 1 argument".to_string())
+        );
+        assert_eq!(
+            error(r#"format("{}", 1, 2)"#),
+            RuntimeError::new("format: 1 placeholder but 2 arguments".to_string())
+        );
+        assert_eq!(
+            error("format(1)"),
+            RuntimeError::new("format: expected string, found number".to_string())
+        );
+        assert_eq!(
+            error("format()").to_string(),
+            "RuntimeError: Function 'format' expects at least 1 argument but was called with 0 at line 1\n\n"
+        );
+    }
+
+    #[test]
+    fn test_interpret_pprint() {
+        let mut interpreter = Interpreter::new();
+        let (result, printed) = interpreter.interpret_capture(
+            b"pprint([\"a\", range(20), range(20)]) pprint(\"b\") print [\"c\"]",
+        );
+        let row = "[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19]";
+        assert_eq!(result.unwrap(), Value::Null);
+        assert_eq!(
+            printed,
+            format!("[\n  \"a\",\n  {},\n  {},\n]\n\"b\"\n[c]", row, row)
+        );
+    }
+
+    #[test]
+    fn test_interpret_delete() {
+        let source = br#"
+            let o = jsonParse("{\"a\": 1, \"b\": {\"c\": 2}, \"d\": 3}")
+            let xs = [1, 2, 3, 4]
+            let copy = xs
+            let results = [delete o.a, delete o.a, delete o["b"].c, delete o.missing]
+            let removed = [delete xs[0], delete xs[-1], delete xs[5], delete xs[-9]]
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source).unwrap();
+
+        assert_eq!(
+            get(&interpreter, "results").to_string(),
+            "[true, false, true, false]"
+        );
+        assert_eq!(get(&interpreter, "o").to_string(), "{b: {}, d: 3}");
+        // Later elements move down, and other copies of the array are left as they were.
+        assert_eq!(
+            get(&interpreter, "removed").to_string(),
+            "[true, true, false, false]"
+        );
+        assert_eq!(get(&interpreter, "xs").to_string(), "[2, 3]");
+        assert_eq!(get(&interpreter, "copy").to_string(), "[1, 2, 3, 4]");
+
+        // Only the last access may be missing.
+        assert!(matches!(
+            runtime_error(&mut interpreter, b"delete o.missing.c"),
+            RuntimeError::UndefinedProperty { .. }
+        ));
+        assert!(matches!(
+            runtime_error(&mut interpreter, b"delete xs[\"0\"]"),
+            RuntimeError::TypeError { .. }
+        ));
+        assert_eq!(
+            interpreter
+                .interpret(b"let frozen = deepFreeze([[1]])\ndelete frozen[0][0]")
+                .unwrap_err()
+                .to_string(),
+            "RuntimeError: Cannot modify frozen array frozen[0] at line 2\n\n"
+        );
+        assert_eq!(get(&interpreter, "frozen").to_string(), "[[1]]");
+    }
+
+    #[test]
+    fn test_interpret_enum() {
+        let source = br#"
+            enum State { Idle, Running, Done }
+            enum Other { Idle }
+            let state = State.Idle
+            let same = [state == State.Idle, state === State.Idle, state != State.Idle]
+            let different = [state == State.Running, state == Other.Idle, state == "State.Idle"]
+            let handlers = jsonParse("{}")
+            handlers[State.Running] = "run"
+            handlers["State.Running"] = "string"
+            let annotated: enum = State.Done
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source).unwrap();
+
+        assert_eq!(get(&interpreter, "state").to_string(), "State.Idle");
+        assert_eq!(format!("{:?}", get(&interpreter, "state")), "State.Idle");
+        assert_eq!(
+            get(&interpreter, "State").to_string(),
+            "{Idle: State.Idle, Running: State.Running, Done: State.Done}"
+        );
+        assert_eq!(get(&interpreter, "same").to_string(), "[true, true, false]");
+        assert_eq!(
+            get(&interpreter, "different").to_string(),
+            "[false, false, false]"
+        );
+        // Variants are keys of their own, distinct from the string they print as.
+        assert_eq!(
+            interpreter
+                .interpret(b"[handlers[State.Running], handlers.keys()[0] == State.Running]")
+                .unwrap()
+                .to_string(),
+            "[run, true]"
+        );
+        assert_eq!(get(&interpreter, "annotated").type_name(), "enum");
+
+        assert_eq!(
+            interpreter
+                .interpret(b"let states = State\nstates.Idle = 1")
+                .unwrap_err()
+                .to_string(),
+            "RuntimeError: Cannot modify frozen object states at line 2\n\n"
+        );
+        assert!(matches!(
+            runtime_error(&mut interpreter, b"State.Paused"),
+            RuntimeError::UndefinedProperty { .. }
+        ));
+        // Like objects, variants have no order.
+        assert_eq!(
+            interpreter
+                .interpret(b"[State.Idle < State.Done, State.Idle >= State.Done]")
+                .unwrap()
+                .to_string(),
+            "[false, false]"
+        );
+        assert!(matches!(
+            runtime_error(&mut interpreter, b"let n: number = State.Idle"),
+            RuntimeError::MismatchedType { .. }
+        ));
+    }
+
+    #[test]
+    fn test_interpret_match() {
+        let source = br#"
+            enum State { Idle, Done }
+            const limit = 10
+            function describe(x) {
+                return match x {
+                    0 => "zero",
+                    1 | 2 | 3 => "small",
+                    -1 => "minus one",
+                    "" | null => "empty",
+                    State.Idle => "idle",
+                    [] => "no elements",
+                    [first] => "one: " + repr(first),
+                    [_, [inner]] => "nested: " + repr(inner),
+                    [true, second] => "flagged: " + repr(second),
+                    other => "other: " + repr(other),
+                }
+            }
+            let results = ""
+            for (const value in [0, 2, -1, "", null, State.Idle, State.Done, [], [4], [4, [5]],
+                                 [true, 6], [false, 6], "s"]) {
+                results += describe(value) + ";"
+            }
+            let x = "outer"
+            let shadowed = match [1] { [x] => x + 1, _ => 0 }
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source).unwrap();
+
+        let results = get(&interpreter, "results").to_string();
+        let results: Vec<&str> = results.trim_end_matches(';').split(';').collect();
+        assert_eq!(
+            results,
+            [
+                "zero",
+                "small",
+                "minus one",
+                "empty",
+                "empty",
+                "idle",
+                "other: State.Done",
+                "no elements",
+                "one: 4",
+                "nested: 5",
+                "flagged: 6",
+                "other: [false, 6]",
+                "other: \"s\"",
+            ]
+        );
+        // Names declared by a pattern only exist in the arm.
+        assert_eq!(get(&interpreter, "shadowed").to_string(), "2");
+        assert_eq!(get(&interpreter, "x").to_string(), "outer");
+
+        // The first arm that matches is taken, and the others are not evaluated.
+        let (result, printed) = interpreter.interpret_capture(
+            b"function note(s) { print s return s }\nlet picked = match 2 { 2 => note(\"first\"), _ => note(\"second\") }",
+        );
+        result.unwrap();
+        assert_eq!(printed, "first");
+
+        // Arms for both booleans are exhaustive for booleans only.
+        assert_eq!(
+            interpreter
+                .interpret(b"match 1 < 2 { true => \"yes\", false => \"no\" }")
+                .unwrap()
+                .to_string(),
+            "yes"
+        );
+        assert_eq!(
+            interpreter
+                .interpret(b"let flag = [1]\nmatch flag {\n    true => 1,\n    false => 0,\n}")
+                .unwrap_err()
+                .to_string(),
+            "RuntimeError: No arm of the match matches [1] at line 2\n\n"
+        );
+        // Errors in arms and constants are raised as they are reached.
+        assert!(matches!(
+            runtime_error(&mut interpreter, b"match 1 { State.Paused => 1, _ => 0 }"),
+            RuntimeError::UndefinedProperty { .. }
+        ));
+        assert!(matches!(
+            runtime_error(&mut interpreter, b"match [] { [] => [] - 1, _ => 0 }"),
+            RuntimeError::InvalidOperands { .. }
+        ));
+    }
+
+    #[test]
+    fn test_interpret_defer() {
+        let source = br#"
+            let log = ""
+            function note(text) {
+                log += text + " "
+            }
+            defer note("exit")
+
+            function ordered() {
+                defer note("first")
+                defer note("second")
+                note("body")
+            }
+            ordered()
+
+            // The loop variable no longer exists when the function body exits, so each defer
+            // keeps the value it had when the defer ran.
+            function loop() {
+                for (const i in [1, 2, 3]) defer note("i" + i)
+                note("loop")
+            }
+            loop()
+
+            function early(x) {
+                defer note("cleanup")
+                if (x) return "early"
+                note("late")
+                return "late"
+            }
+            const result = early(true)
+
+            // Variables of the block itself are read when it exits.
+            {
+                let count = 0
+                defer note("count" + count)
+                count = 5
+            }
+            for (const i in [1, 2]) {
+                defer note("end" + i)
+                note("start" + i)
+            }
+
+            function* guarded() {
+                defer note("closed")
+                yield 1
+                yield 2
+            }
+            for (const v in guarded()) note("got" + v)
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source).unwrap();
+
+        assert_eq!(
+            get(&interpreter, "log").to_string(),
+            "body second first loop i3 i2 i1 cleanup count5 start1 end1 start2 end2 got1 got2 \
+             closed exit "
+        );
+        assert_eq!(get(&interpreter, "result").to_string(), "early");
+    }
+
+    #[test]
+    fn test_interpret_tests() {
+        let source = br#"
+            let runs = 0
+            test "first" {
+                runs += 1
+                assert runs == 1
+            }
+            test "second" {
+                runs += 1
+                defer runs += 10
+                if (runs == 2) return null
+                assert false
+            }
+            test "third" {
+                assert runs == 1
+            }
+            runs
+        "#;
+
+        // Programs skip their tests.
+        let mut interpreter = Interpreter::new();
+        assert_eq!(interpreter.interpret(source).unwrap(), Value::Number(0.0));
+
+        let mut interpreter = Interpreter::new();
+        let results = interpreter.test(source).unwrap();
+        let outcomes: Vec<(&str, u32, bool)> = results
+            .iter()
+            .map(|result| (result.name.as_str(), result.line, result.error.is_none()))
+            .collect();
+        assert_eq!(
+            outcomes,
+            [
+                ("first", 3, true),
+                ("second", 7, true),
+                ("third", 13, false)
+            ]
+        );
+        assert_eq!(
+            results[2].error.as_ref().unwrap().error,
+            RuntimeError::AssertionFailed {
+                condition: "runs == 1".to_string(),
+                line: 14,
+            }
+        );
+        // Tests see what the tests before them changed.
+        assert_eq!(get(&interpreter, "runs"), Value::Number(12.0));
+
+        // Assertions also work outside of tests.
+        assert_eq!(
+            runtime_error(&mut interpreter, b"assert 1 + 1 == 3"),
+            RuntimeError::AssertionFailed {
+                condition: "1 + 1 == 3".to_string(),
+                line: 1,
+            }
+        );
+        assert!(interpreter.interpret(b"assert [1]").is_ok());
+    }
+
+    #[test]
+    fn test_interpret_generator() {
+        let source = br#"
+            let log = ""
+            function* letters(name) {
+                log += name + "0 "
+                yield name + "1"
+                for (const i in [2, 3]) {
+                    let label = name + i
+                    log += label + " "
+                    if (i == 3) {
+                        yield label
+                    }
+                }
+                return name + " done"
+            }
+            const a = letters("a")
+            const b = letters("b")
+            let steps = ""
+            for (const g in [a, b, a, b, a, b, a]) {
+                const step = g.next()
+                steps += repr(step.value) + " " + repr(step.done) + ", "
+            }
+
+            function* naturals() {
+                let n = 0
+                while (true) {
+                    yield n
+                    n += 1
+                }
+            }
+            function firstSquareOver(limit) {
+                for (const n in naturals()) {
+                    if (n * n > limit) return n
+                }
+            }
+            const root = firstSquareOver(50)
+
+            function* flatten(xs) {
+                for (const x in xs) {
+                    if (repr(x)[0] == "[") for (const y in flatten(x)) yield y
+                    else yield x
+                }
+            }
+            let flat = ""
+            for (const x in flatten([1, [2, [3, 4]], [], 5])) flat += x
+        "#;
+        let mut interpreter = Interpreter::new();
+        let (result, printed) = interpreter.interpret_capture(source);
+        result.unwrap();
+        assert_eq!(printed, "");
+
+        // Each generator only runs up to its next yield, so their steps interleave.
+        assert_eq!(get(&interpreter, "log").to_string(), "a0 b0 a2 a3 b2 b3 ");
+        assert_eq!(
+            get(&interpreter, "steps").to_string(),
+            "\"a1\" false, \"b1\" false, \"a3\" false, \"b3\" false, \"a done\" true, \
+             \"b done\" true, Null true, "
+        );
+        assert_eq!(get(&interpreter, "root"), Value::Number(8.0));
+        assert_eq!(get(&interpreter, "flat").to_string(), "12345");
+
+        // Generators are values of their own, equal only to themselves.
+        assert_eq!(
+            interpreter
+                .interpret(b"[repr(a), a == a, a == b, a == letters(\"a\")]")
+                .unwrap()
+                .to_string(),
+            "[<generator letters>, true, false, false]"
+        );
+
+        // The parameters are checked when the generator is made, and the annotation of the
+        // function is for the generator.
+        assert!(interpreter
+            .interpret(b"function* typed(n: number): generator { yield n } typed(1).next()")
+            .is_ok());
+        assert!(matches!(
+            runtime_error(&mut interpreter, b"typed(\"1\")"),
+            RuntimeError::MismatchedType { .. }
+        ));
+        assert!(matches!(
+            runtime_error(
+                &mut interpreter,
+                b"function* wrong(): number { yield 1 } wrong()"
+            ),
+            RuntimeError::MismatchedType { .. }
+        ));
+
+        // A generator can't resume itself, and one whose body failed is done.
+        let error = runtime_error(
+            &mut interpreter,
+            b"let own = null\nfunction* selfish() { own.next() }\nown = selfish()\nown.next()",
+        );
+        assert!(matches!(
+            error,
+            RuntimeError::GeneratorRunning { ref name, line: 2 } if name == "selfish"
+        ));
+        assert_eq!(
+            interpreter.interpret(b"own.next()").unwrap().to_string(),
+            "{value: Null, done: true}"
+        );
+
+        assert!(matches!(
+            runtime_error(&mut interpreter, b"a.send(1)"),
+            RuntimeError::UnknownProperty { .. }
+        ));
+    }
+
+    #[test]
+    fn test_interpret_object_keys() {
+        let source = br#"
+            let scores = jsonParse("{}")
+            scores[42] = "ok"
+            scores[1.0] = "one"
+            scores[1] += "!"
+            scores["1"] = "string"
+            scores[true] = "yes"
+            scores[-0] = "zero"
+            scores[0] += "?"
+            let keys = ""
+            for (const key in scores) keys += repr(key) + " "
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source).unwrap();
+
+        // Keys keep their type.
+        assert_eq!(get(&interpreter, "keys").to_string(), "42 1 \"1\" true 0 ");
+        assert_eq!(
+            interpreter
+                .interpret(b"[scores[42], scores[1], scores[\"1\"], scores[1 == 1], scores[0]]")
+                .unwrap()
+                .to_string(),
+            "[ok, one!, string, yes, zero?]"
+        );
+        // JSON only has string keys.
+        assert_eq!(
+            interpreter
+                .interpret(b"let o = jsonParse(\"{}\") o[1] = 2 o[false] = 3 jsonStringify(o)")
+                .unwrap()
+                .to_string(),
+            r#"{"1":2,"false":3}"#
+        );
+
+        for (source, found) in [
+            ("scores[[1]]", "an array"),
+            ("scores[[1]] = 1", "an array"),
+            ("scores[0 / 0] = 1", "NaN"),
+            ("scores[null]", "null"),
+            ("scores[scores] = 1", "an object"),
+            ("scores[len]", "a function"),
+        ] {
+            assert_eq!(
+                runtime_error(&mut interpreter, source.as_bytes()),
+                RuntimeError::InvalidKey {
+                    found: found.to_string(),
+                    line: 1,
+                },
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn test_interpret_object_methods() {
+        let source = br#"
+            let o = jsonParse("{\"b\": 1, \"a\": [2]}")
+            o[3] = true
+            o.b = 4
+            let keys = o.keys()
+            let values = o.values()
+            let entries = o.entries()
+            let pairs = ""
+            for (const entry in entries) pairs += repr(entry[0]) + "=" + repr(entry[1]) + " "
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source).unwrap();
+
+        // Properties come in insertion order, and numbers stay numbers.
+        assert_eq!(get(&interpreter, "keys").to_string(), "[b, a, 3]");
+        assert_eq!(get(&interpreter, "values").to_string(), "[4, [2], true]");
+        assert_eq!(
+            get(&interpreter, "pairs").to_string(),
+            "\"b\"=4 \"a\"=[2] 3=true "
+        );
+
+        // The arrays are taken when the method is read, and are not frozen with the object.
+        interpreter
+            .interpret(b"let method = o.keys o.c = 5 o = deepFreeze(o) let later = method()")
+            .unwrap();
+        assert_eq!(get(&interpreter, "later").to_string(), "[b, a, 3]");
+        interpreter.interpret(b"later[0] = 1").unwrap();
+
+        // Properties with the same name come first.
+        interpreter
+            .interpret(b"let p = jsonParse(\"{\\\"keys\\\": 1}\") let shadowed = p.keys")
+            .unwrap();
+        assert_eq!(get(&interpreter, "shadowed"), Value::Number(1.0));
+        assert!(matches!(
+            runtime_error(&mut interpreter, b"p.items()"),
+            RuntimeError::UndefinedProperty { .. }
+        ));
+    }
+
+    #[test]
+    fn test_interpret_annotations() {
+        let source = br#"
+            let count: number = 0
+            let name: string
+            count += 2
+            name = "notjs"
+            function repeat(s: string, times: number): string {
+                let result: string = ""
+                for (const i in range(times)) result += s
+                return result
+            }
+            function nothing(): null {}
+            let ab = repeat("ab", count)
+            nothing()
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source).unwrap();
+        assert_eq!(get(&interpreter, "ab").to_string(), "abab");
+
+        for (source, expected) in [
+            (
+                "let flag: bool = 1",
+                "Expected boolean for variable 'flag', found number at line 1",
+            ),
+            (
+                "count = \"2\"",
+                "Expected number for variable 'count', found string at line 1",
+            ),
+            (
+                "count += \"!\"",
+                "Expected number for variable 'count', found string at line 1",
+            ),
+            (
+                "repeat(\"a\", \"2\")",
+                "Expected number for parameter 'times' of 'repeat', found string at line 1",
+            ),
+            (
+                "function bad(): number { return \"1\" }\nbad()",
+                "Expected number for return value of 'bad', found string at line 2",
+            ),
+            (
+                "function implicit(): array {}\nimplicit()",
+                "Expected array for return value of 'implicit', found null at line 2",
+            ),
+            (
+                "function inner(x: number) { x = \"x\" }\ninner(1)",
+                "Expected number for variable 'x', found string at line 1",
+            ),
+        ] {
+            let error = runtime_error(&mut interpreter, source.as_bytes());
+            assert_eq!(error.code(), "R001", "{}", source);
+            assert_eq!(
+                error.to_string(),
+                format!("RuntimeError: {}\n\n", expected),
+                "{}",
+                source
+            );
+        }
+
+        // A failed check leaves the variable as it was.
+        assert_eq!(get(&interpreter, "count"), Value::Number(2.0));
+    }
+
+    #[test]
+    fn test_interpret_function_display() {
+        let mut interpreter = Interpreter::new();
+        let (result, printed) = interpreter
+            .interpret_capture(b"function add(a, b) {\n    return a + b\n}\nprint add\nadd");
+        assert_eq!(printed, "<fn add(a, b)>");
+        let add = result.unwrap();
+        assert_eq!(format!("{:?}", add), "<fn add(a, b)>");
+        assert_eq!(format!("{:#}", add), "<fn add(a, b)>");
+
+        interpreter
+            .interpret(b"let natives = [args, range, format]")
+            .unwrap();
+        assert_eq!(
+            get(&interpreter, "natives").to_string(),
+            "[<native fn args/0>, <native fn range/1..3>, <native fn format/1..>]"
+        );
+    }
+
+    #[test]
+    fn test_interpret_nan_infinity() {
+        let source = br#"
+            let nan = 0 / 0
+            let inf = 1 / 0
+            let checks = [isNaN(nan), isNaN(NaN), isNaN(1), isFinite(inf), isFinite(-Infinity), isFinite(1)]
+            let equality = [NaN == NaN, nan != nan, Infinity == inf, -Infinity < 0, NaN < 1, NaN > 1]
+            println [NaN, Infinity, -Infinity]
+            print "x" + inf
+        "#;
+        let mut interpreter = Interpreter::new();
+        let (result, printed) = interpreter.interpret_capture(source);
+        result.unwrap();
+        assert!(matches!(get(&interpreter, "nan"), Value::Number(num) if num.is_nan()));
+        assert_eq!(get(&interpreter, "inf"), Value::Number(f64::INFINITY));
+        assert_eq!(
+            get(&interpreter, "checks").to_string(),
+            "[true, true, false, false, false, true]"
+        );
+        assert_eq!(
+            get(&interpreter, "equality").to_string(),
+            "[false, true, true, true, false, false]"
+        );
+        assert_eq!(printed, "[NaN, Infinity, -Infinity]\nxInfinity");
+
+        let err = runtime_error(&mut interpreter, b"isNaN(\"a\")");
+        assert_eq!(
+            err,
+            RuntimeError::new("isNaN: expected number, found string".to_string())
+        );
+    }
+
+    #[test]
+    fn test_interpret_strict_equality() {
+        let source = br#"
+            function f() { return 1 }
+            function g() { return 1 }
+            let same = [1 === 1.0, null === null, "a" === "a", [1, [2]] === [1, [2]], f === f]
+            let different = [1 === "1", 1 !== "1", [1] === [2], f === g, NaN === NaN, true !== true]
+            let loose = 1 == 1 & 1 != "1"
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source).unwrap();
+        assert_eq!(
+            get(&interpreter, "same").to_string(),
+            "[true, true, true, true, true]"
+        );
+        assert_eq!(
+            get(&interpreter, "different").to_string(),
+            "[false, true, false, false, false, false]"
+        );
+        assert_eq!(get(&interpreter, "loose"), Value::Boolean(true));
+    }
+
+    #[test]
+    fn test_interpret_short_circuit() {
+        let source = br#"
+            let calls = 0
+            function count(value) {
+                calls += 1
+                return value
+            }
+            let x = 0
+            let guarded = x != 0 & 10 / x > 1
+            let skipped = [false & count(true), true | count(false), 0 & (x = 5)]
+            let evaluated = [true & count(1), false | count(2), 1 & 0, "" | "b"]
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source).unwrap();
+        assert_eq!(get(&interpreter, "guarded"), Value::Boolean(false));
+        assert_eq!(get(&interpreter, "skipped").to_string(), "[false, true, 0]");
+        assert_eq!(get(&interpreter, "evaluated").to_string(), "[1, 2, 0, b]");
+        assert_eq!(get(&interpreter, "x"), Value::Number(0.0));
+        assert_eq!(get(&interpreter, "calls"), Value::Number(2.0));
+    }
+
+    #[test]
+    fn test_interpret_print_flushes() {
+        let buffer = SharedBuffer::default();
+        let mut interpreter = Interpreter::new().with_output(io::BufWriter::new(buffer.clone()));
+        interpreter
+            .interpret(b"print \"Enter a number: \"")
+            .unwrap();
+        assert_eq!(&*buffer.0.borrow(), b"Enter a number: ");
+    }
+
+    #[test]
+    fn test_interpret_long_chain() {
+        // Generated code can chain thousands of operators, each one a level deeper in the tree.
+        let terms = (0..100_000).map(|i| i.to_string()).collect::<Vec<_>>();
+        let source = format!("let n = 1\nn + {}", terms.join(" + "));
+
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter.interpret(source.as_bytes()).unwrap(),
+            Value::Number(4_999_950_001.0)
+        );
+
+        let statements = interpreter.parse(source.as_bytes()).unwrap();
+        assert!(statements[1].node_to_string().ends_with("+ 99998 + 99999"));
+
+        let mut interpreter = Interpreter::new().with_optimizer();
+        let statements = interpreter.parse(source.as_bytes()).unwrap();
+        assert!(statements[1].node_to_string().starts_with("n + 0 + 1"));
+        assert_eq!(
+            interpreter.interpret(source.as_bytes()).unwrap(),
+            Value::Number(4_999_950_001.0)
+        );
+
+        // Short-circuiting still skips the rest of a chain.
+        let source = format!("false & len() | {}", terms.join(" & "));
+        assert_eq!(
+            Interpreter::new().interpret(source.as_bytes()).unwrap(),
+            Value::Number(0.0)
+        );
+    }
+
+    #[test]
+    fn test_interpret_large_array_reads() {
+        // Reading `xs` copied the whole array, making this loop quadratic. It now shares the
+        // elements, so it finishes well within the timeout.
+        let source = br#"
+            let xs = range(100000)
+            let total = 0
+            let i = 0
+            while (i < xs.length) {
+                total += xs[i]
+                i += 1
+            }
+            for (const x in xs) total -= x
+            let ys = xs
+            ys[0] = -1
+        "#;
+        let mut interpreter = Interpreter::new().with_timeout(Duration::from_secs(20));
+        interpreter.interpret(source).unwrap();
+
+        assert_eq!(get(&interpreter, "total"), Value::Number(0.0));
+        assert_eq!(get(&interpreter, "i"), Value::Number(100000.0));
+        assert_eq!(interpreter.interpret(b"xs[0]").unwrap(), Value::Number(0.0));
+        assert_eq!(
+            interpreter.interpret(b"ys[0]").unwrap(),
+            Value::Number(-1.0)
+        );
+    }
+
+    #[test]
+    fn test_interpret_large_object_reads() {
+        // Reading `o` copied all of its properties, it now shares them until a copy is changed.
+        let properties = (0..10000)
+            .map(|i| format!(r#"\"k{}\": {}"#, i, i))
+            .collect::<Vec<String>>()
+            .join(", ");
+        let source = format!(
+            r#"
+            let o = jsonParse("{{{}}}")
+            let total = 0
+            let i = 0
+            while (i < 100000) {{
+                total += o.k1
+                i += 1
+            }}
+            let p = o
+            p.k0 = -1
+            "#,
+            properties
+        );
+        let mut interpreter = Interpreter::new().with_timeout(Duration::from_secs(20));
+        interpreter.interpret(source.as_bytes()).unwrap();
+
+        assert_eq!(get(&interpreter, "total"), Value::Number(100000.0));
+        assert_eq!(interpreter.interpret(b"o.k0").unwrap(), Value::Number(0.0));
+        assert_eq!(interpreter.interpret(b"p.k0").unwrap(), Value::Number(-1.0));
+    }
+
+    #[test]
+    fn test_interpret_large_object_writes() {
+        // Properties were found by a linear search, making this loop quadratic.
+        let source = br#"
+            let o = jsonParse("{}")
+            let i = 0
+            while (i < 100000) {
+                o[i] = i
+                i += 1
+            }
+            delete o[5]
+            let keys = o.keys()
+        "#;
+        let mut interpreter = Interpreter::new().with_timeout(Duration::from_secs(20));
+        interpreter.interpret(source).unwrap();
+
+        assert_eq!(
+            interpreter
+                .interpret(b"[len(keys), keys[4], keys[5], o[6], o[99999]]")
+                .unwrap()
+                .to_string(),
+            "[99999, 4, 6, 6, 99999]"
+        );
+    }
+
+    #[test]
+    fn test_interpret_shared_strings() {
+        // Copies of a string share its buffer, changing one variable must not change the others.
+        let source = br#"
+            let a = "abc"
+            let b = a
+            let xs = [a, a]
+            a += "d"
+            xs[0] += "!"
+            let ys = xs
+            ys[1] = ys[1] + "?"
+            function shout(text) { text += "!" return text }
+            let c = shout(b)
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source).unwrap();
+
+        let strings = |values: &[&str]| {
+            Value::Array(values.iter().map(|&value| Value::from(value)).collect())
+        };
+        assert_eq!(get(&interpreter, "a"), Value::from("abcd"));
+        assert_eq!(get(&interpreter, "b"), Value::from("abc"));
+        assert_eq!(get(&interpreter, "c"), Value::from("abc!"));
+        assert_eq!(get(&interpreter, "xs"), strings(&["abc!", "abc"]));
+        assert_eq!(get(&interpreter, "ys"), strings(&["abc!", "abc?"]));
+    }
+
+    #[test]
+    fn test_interpret_len() {
+        let source = r#"
+            let text = "añ€😀"
+            let lengths = [len(text), text.length, len(""), len([1, [2, 3]]), len(jsonParse("{\"a\": 1, \"b\": 2}"))]
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source.as_bytes()).unwrap();
+        assert_eq!(
+            get(&interpreter, "lengths"),
+            numbers(&[4.0, 4.0, 0.0, 2.0, 2.0])
+        );
+
+        for (value, type_name) in [
+            ("1", "number"),
+            ("true", "boolean"),
+            ("null", "null"),
+            ("len", "function"),
+        ] {
+            assert_eq!(
+                runtime_error(&mut interpreter, format!("len({})", value).as_bytes()).to_string(),
+                format!(
+                    "RuntimeError: len: expected string, array or object, found {}\n\n",
+                    type_name
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn test_interpret_repr() {
+        let source = br#"
+            let number = repr(3)
+            let text = repr("3")
+            let escaped = repr("say \"hi\"\n\tbye")
+            let nested = repr([1, ["a", [true, null]], jsonParse("{\"k\": \"v\"}")])
+            let usable = repr("a") + "!"
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source).unwrap();
+        let get = |name| get(&interpreter, name).to_string();
+        assert_eq!(get("number"), "3");
+        assert_eq!(get("text"), r#""3""#);
+        assert_eq!(get("escaped"), r#""say \"hi\"\n\tbye""#);
+        assert_eq!(get("nested"), r#"[1, ["a", [true, Null]], {k: "v"}]"#);
+        assert_eq!(get("usable"), r#""a"!"#);
+    }
+
+    #[test]
+    fn test_interpret_globals() {
+        let source = br#"
+            let count = 1
+            const name = "notjs"
+            let pending
+            function add(a, b) { return a + b }
+            let reflected = globals()
+            function scoped(x) {
+                let doubled = x * 2
+                return locals()
+            }
+            let inner = scoped(4)
+            let block = null
+            {
+                let hidden = true
+                block = locals()
+            }
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source).unwrap();
+        let Value::Object(reflected) = get(&interpreter, "reflected") else {
+            panic!("globals() should give an object");
+        };
+        let entry = |name: &str| reflected.property(name).cloned();
+        assert_eq!(
+            entry("count").unwrap().to_string(),
+            r#"{mutable: true, type: number, value: 1}"#
+        );
+        assert_eq!(
+            entry("name").unwrap().to_string(),
+            r#"{mutable: false, type: string, value: notjs}"#
+        );
+        assert_eq!(
+            entry("pending").unwrap().to_string(),
+            r#"{mutable: true, type: undefined, value: Null}"#
+        );
+        assert_eq!(
+            entry("add").unwrap().to_string(),
+            r#"{mutable: false, type: function, value: <fn add(a, b)>}"#
+        );
+        // Natives are globals too, the variable being defined isn't one yet.
+        assert_eq!(
+            entry("len").unwrap().to_string(),
+            r#"{mutable: false, type: function, value: <native fn len/1>}"#
+        );
+        assert_eq!(entry("reflected"), None);
+
+        // Only the innermost scope, parameters are in the one around the body.
+        assert_eq!(
+            get(&interpreter, "inner").to_string(),
+            "{doubled: {mutable: true, type: number, value: 8}}"
+        );
+        assert_eq!(
+            get(&interpreter, "block").to_string(),
+            r#"{hidden: {mutable: true, type: boolean, value: true}}"#
+        );
+    }
+
+    #[test]
+    fn test_interpret_checkpoint() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .interpret(b"let count = 1 let xs = [1, 2]")
+            .unwrap();
+
+        let checkpoint = interpreter.checkpoint();
+        interpreter
+            .interpret(b"let later = 2 function f() { return later } count = 5 xs[0] = 9")
+            .unwrap();
+        assert_eq!(get(&interpreter, "later"), Value::Number(2.0));
+        interpreter.rollback(checkpoint);
+
+        // Earlier globals keep the values they had at the checkpoint.
+        assert_eq!(get(&interpreter, "count"), Value::Number(1.0));
+        assert_eq!(get(&interpreter, "xs").to_string(), "[1, 2]");
+
+        // Later ones are gone, both while running and while resolving names.
+        let err = interpreter.interpret(b"later").unwrap_err().to_string();
+        assert!(err.contains("Undeclared variable"), "{}", err);
+        assert!(interpreter.interpret(b"f()").is_err());
+        interpreter.interpret(b"const later = 3").unwrap();
+        assert_eq!(get(&interpreter, "later"), Value::Number(3.0));
+    }
+
+    #[test]
+    fn test_interpret_help() {
+        let mut interpreter = Interpreter::new();
+        interpreter.define_native(
+            NativeFunction::new("shout", Arity::Exact(1), |_, arguments| {
+                Ok(Value::from(arguments[0].to_string().to_uppercase()))
+            })
+            .with_doc("The text in capitals"),
+        );
+        interpreter.bind("hypot", |a: f64, b: f64| (a * a + b * b).sqrt());
+
+        let (result, listing) = interpreter.interpret_capture(b"help()");
+        result.unwrap();
+        let lines = listing.lines().collect::<Vec<&str>>();
+        let mut sorted = lines.clone();
+        sorted.sort();
+        assert_eq!(lines, sorted);
+        assert!(lines
+            .iter()
+            .any(|line| line.starts_with("shout/1 ") && line.ends_with("  The text in capitals")));
+        // Natives registered without a description are listed by their arity alone.
+        assert!(lines.contains(&"hypot/2"));
+        // Functions of the prelude aren't natives.
+        assert!(!listing.contains("map/"));
+
+        let (result, entry) = interpreter.interpret_capture(b"help(\"shout\")");
+        result.unwrap();
+        assert_eq!(entry, "shout/1  The text in capitals\n");
+        let (_, entry) = interpreter.interpret_capture(b"help(\"range\")");
+        assert_eq!(
+            entry,
+            "range/1..3  Numbers from the start up to the end, by the step\n"
+        );
+
+        let mut error = |source: &[u8]| {
+            let (result, _) = interpreter.interpret_capture(source);
+            result.unwrap_err().to_string()
+        };
+        assert!(error(b"help(\"shuot\")")
+            .contains("help: no native named 'shuot', did you mean 'shout'?"));
+        // The closest come first.
+        assert!(error(b"help(\"cr\")")
+            .contains("help: no native named 'cr', did you mean 'chr' or 'ord'?"));
+        let unknown = error(b"help(\"somethingElse\")");
+        assert!(unknown.contains("help: no native named 'somethingElse'"));
+        assert!(!unknown.contains("did you mean"));
+        assert!(error(b"help(1)").contains("help: expected string, found number"));
+    }
+
+    fn numbers(values: &[f64]) -> Value {
+        Value::Array(values.iter().map(|num| Value::Number(*num)).collect())
+    }
+
+    #[test]
+    fn test_interpret_range() {
+        let source = br#"
+            let simple = range(4)
+            let bounded = range(2, 5)
+            let stepped = range(0, 10, 3)
+            let negative = range(5, 0, -2)
+            let fractional = range(0, 1, 0.25)
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source).unwrap();
+        assert_eq!(get(&interpreter, "simple"), numbers(&[0.0, 1.0, 2.0, 3.0]));
+        assert_eq!(get(&interpreter, "bounded"), numbers(&[2.0, 3.0, 4.0]));
+        assert_eq!(get(&interpreter, "stepped"), numbers(&[0.0, 3.0, 6.0, 9.0]));
+        assert_eq!(get(&interpreter, "negative"), numbers(&[5.0, 3.0, 1.0]));
+        assert_eq!(
+            get(&interpreter, "fractional"),
+            numbers(&[0.0, 0.25, 0.5, 0.75])
+        );
+    }
+
+    #[test]
+    fn test_interpret_range_empty() {
+        let source = br#"
+            let zero = range(0)
+            let backwards = range(5, 2)
+            let wrong_direction = range(0, 5, -1)
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source).unwrap();
+        assert_eq!(get(&interpreter, "zero"), numbers(&[]));
+        assert_eq!(get(&interpreter, "backwards"), numbers(&[]));
+        assert_eq!(get(&interpreter, "wrong_direction"), numbers(&[]));
+    }
+
+    #[test]
+    fn test_interpret_range_errors() {
+        let mut interpreter = Interpreter::new().with_range_limit(10);
+        let cases: [(&[u8], &str); 4] = [
+            (b"range(0, 5, 0)", "range: step must not be zero"),
+            (
+                b"range(0, 1 / 0)",
+                "range: bounds must be finite, found Infinity",
+            ),
+            (b"range(\"5\")", "range: expected number, found string"),
+            (b"range(11)", "range: 11 elements exceed the limit of 10"),
+        ];
+        for (source, message) in cases {
+            let err = interpreter.interpret(source).unwrap_err().to_string();
+            assert!(err.contains(message), "{}", err);
+        }
+        assert!(interpreter.interpret(b"range(10)").is_ok());
+    }
+
+    #[test]
+    fn test_interpret_for_in_array() {
+        let source = br#"
+            let sum = 0
+            for (let x in [1, 2, 3]) {
+                sum += x
+            }
+            for (const i in range(4)) sum += i
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source).unwrap();
+        assert_eq!(get(&interpreter, "sum"), Value::Number(12.0));
+    }
+
+    #[test]
+    fn test_interpret_for_in_string() {
+        let source = br#"
+            let reversed = ""
+            for (let chr in "abc") reversed = chr + reversed
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source).unwrap();
+        assert_eq!(get(&interpreter, "reversed"), Value::String("cba".into()));
+    }
+
+    #[test]
+    fn test_interpret_for_in_object_keys_in_insertion_order() {
+        let source = br#"
+            let obj = jsonParse("{\"zeta\": 1, \"alpha\": 2, \"mid\": 3}")
+            let keys = ""
+            let total = 0
+            for (let key in obj) {
+                keys = keys + key + ","
+                total += obj[key]
+            }
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source).unwrap();
+        assert_eq!(
+            get(&interpreter, "keys"),
+            Value::String("zeta,alpha,mid,".into())
+        );
+        assert_eq!(get(&interpreter, "total"), Value::Number(6.0));
+    }
+
+    #[test]
+    fn test_interpret_for_in_scoping() {
+        let mut interpreter = Interpreter::new();
+        let (result, printed) =
+            interpreter.interpret_capture(b"for (let x in [1]) print x print x");
+        // Rejected before running, so nothing is printed.
+        assert!(result.is_err());
+        assert_eq!(printed, "");
+        assert!(interpreter
+            .interpret(b"for (const x in [1]) x = 2")
+            .is_err());
+    }
+
+    #[test]
+    fn test_interpret_for_in_not_iterable() {
+        let mut interpreter = Interpreter::new();
+        let err = runtime_error(&mut interpreter, b"for (let x in 5) print x");
+        assert!(matches!(
+            err,
+            RuntimeError::TypeError { ref expected, line: 1, .. } if expected == "iterable"
+        ));
+    }
+
+    #[test]
+    fn test_interpret_function() {
+        let source = br#"
+            function add(a, b) {
+                return a + b
+            }
+            function fib(n) {
+                if (n < 2) return n
+                return fib(n - 1) + fib(n - 2)
+            }
+            function nothing() {
+                let x = 1
+            }
+            let sum = add(1, 2)
+            let tenth = fib(10)
+            let empty = nothing()
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source).unwrap();
+        assert_eq!(get(&interpreter, "sum"), Value::Number(3.0));
+        assert_eq!(get(&interpreter, "tenth"), Value::Number(55.0));
+        assert_eq!(get(&interpreter, "empty"), Value::Null);
+    }
+
+    #[test]
+    fn test_interpret_function_return_from_loop() {
+        let source = br#"
+            function find(xs, target) {
+                for (let x in xs) {
+                    if (x == target) return true
+                }
+                return false
+            }
+            let found = find([1, 2, 3], 2)
+            let missing = find([1, 2, 3], 4)
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source).unwrap();
+        assert_eq!(get(&interpreter, "found"), Value::Boolean(true));
+        assert_eq!(get(&interpreter, "missing"), Value::Boolean(false));
+    }
+
+    #[test]
+    fn test_interpret_function_lexical_scope() {
+        let source = br#"
+            let x = "global"
+            function show() {
+                return x
+            }
+            function shadow() {
+                let x = "local"
+                return show()
+            }
+            let shown = shadow()
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source).unwrap();
+        assert_eq!(get(&interpreter, "shown"), Value::String("global".into()));
+    }
+
+    #[test]
+    fn test_interpret_runaway_recursion() {
+        let source = br#"
+            function f(n) { f(n + 1) }
+            f(0)
+        "#;
+        let mut interpreter = Interpreter::new();
+        // The recursive calls are shown once.
+        let message = interpreter.interpret(source).unwrap_err().to_string();
+        assert!(
+            message.ends_with(
+                "    at f (line 2)\n    ... previous frame repeated 998 more times\n    \
+                 at f (line 3)\n"
+            ),
+            "{}",
+            message
+        );
+        let err = runtime_error(&mut interpreter, source);
+        assert!(matches!(
+            err,
+            RuntimeError::CallDepthExceeded {
+                max_depth: 1000,
+                ..
+            }
+        ));
+        // The environment is usable again after the error.
+        interpreter.interpret(b"let after = 1").unwrap();
+        assert_eq!(get(&interpreter, "after"), Value::Number(1.0));
+    }
 
     #[test]
-    fn test_interpret_string_index() {
+    fn test_interpret_max_call_depth() {
         let source = br#"
-            let str = "hello";
-            let char = str[1];
+            function down(n) {
+                if (n == 0) return 0
+                return 1 + down(n - 1)
+            }
+            function tail(n) {
+                if (n == 0) return 0
+                return tail(n - 1)
+            }
         "#;
-        let statements = parse(source).unwrap();
-        for statement in statements {
-            statement.execute().unwrap();
+        let mut interpreter = Interpreter::new().with_max_call_depth(10);
+        interpreter.interpret(source).unwrap();
+        assert!(interpreter.interpret(b"down(9)").is_ok());
+        assert_eq!(
+            runtime_error(&mut interpreter, b"down(10)"),
+            RuntimeError::CallDepthExceeded {
+                name: "down".to_string(),
+                max_depth: 10,
+            }
+        );
+        // Tail calls don't nest.
+        assert!(interpreter.interpret(b"tail(100)").is_ok());
+    }
+
+    #[test]
+    fn test_interpret_tail_calls() {
+        let source = br#"
+            function loop(n, acc) {
+                if (n == 0) return acc
+                return loop(n - 1, acc + n)
+            }
+            const sum = loop(1000000, 0)
+
+            let odd = null
+            function isEven(n) {
+                if (n == 0) return true
+                return odd(n - 1)
+            }
+            function isOdd(n) {
+                if (n == 0) return false
+                return isEven(n - 1)
+            }
+            odd = isOdd
+            const even = isEven(10001)
+
+            function count(n: number, acc: number): number {
+                if (n == 0) return acc
+                return count(n - 1, acc + 1)
+            }
+            const counted = count(5000, 0)
+
+            let log = ""
+            function deferring(n) {
+                defer log += n
+                if (n == 0) return 0
+                return deferring(n - 1)
+            }
+            deferring(3)
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source).unwrap();
+        assert_eq!(get(&interpreter, "sum"), Value::Number(500000500000.0));
+        assert_eq!(get(&interpreter, "even"), Value::Boolean(false));
+        assert_eq!(get(&interpreter, "counted"), Value::Number(5000.0));
+        // A call followed by deferred expressions isn't a tail call, so they run innermost first.
+        assert_eq!(get(&interpreter, "log").to_string(), "0123");
+
+        // A function with a return type checks the value of a tail call to another function.
+        let source = br#"
+            function text() { return "text" }
+            function number(): number { return text() }
+            number()
+        "#;
+        assert!(matches!(
+            runtime_error(&mut interpreter, source),
+            RuntimeError::MismatchedType { .. }
+        ));
+
+        // Errors in tail calls are traced from the call that started them.
+        let source = br#"
+            function fail(x) { return x.missing }
+            function down(n) {
+                if (n == 0) return fail(n)
+                return down(n - 1)
+            }
+            down(3)
+        "#;
+        let NotJsError::Runtime(error) = interpreter.interpret(source).unwrap_err() else {
+            panic!("expected a runtime error");
+        };
+        let trace: Vec<(&str, u32)> = error
+            .trace
+            .iter()
+            .map(|frame| (frame.name.as_str(), frame.line))
+            .collect();
+        assert_eq!(trace, [("down", 7), ("fail", 4)]);
+    }
+
+    #[test]
+    fn test_interpret_call_non_function() {
+        let mut interpreter = Interpreter::new();
+        assert!(interpreter.interpret(b"let x = 5 x()").is_err());
+    }
+
+    fn budget_error(interpreter: &mut Interpreter, source: &[u8]) -> Option<usize> {
+        match interpreter.interpret(source).unwrap_err() {
+            NotJsError::Runtime(TracedError {
+                error: RuntimeError::Budget { limit },
+                ..
+            }) => Some(limit),
+            _ => None,
         }
     }
 
     #[test]
-    fn test_interpret_string_length() {
+    fn test_interpret_budget_infinite_loop() {
+        let mut interpreter = Interpreter::new().with_budget(10_000);
+        assert_eq!(
+            budget_error(&mut interpreter, b"while (true) {}"),
+            Some(10_000)
+        );
+    }
+
+    #[test]
+    fn test_interpret_budget_function_body() {
         let source = br#"
-            let str = "hello";
-            let length = str.length;
+            function spin() {
+                let i = 0
+                while (true) i = i + 1
+            }
+            spin()
         "#;
-        let statements = parse(source).unwrap();
-        for statement in statements {
-            statement.execute().unwrap();
+        let mut interpreter = Interpreter::new().with_budget(10_000);
+        assert_eq!(budget_error(&mut interpreter, source), Some(10_000));
+    }
+
+    #[test]
+    fn test_interpret_budget_resets_per_call() {
+        let source = br#"
+            let i = 0
+            while (i < 10) i = i + 1
+        "#;
+        let mut interpreter = Interpreter::new().with_budget(200);
+        interpreter.interpret(source).unwrap();
+        interpreter.interpret(source).unwrap();
+        assert_eq!(get(&interpreter, "i"), Value::Number(10.0));
+        // Other runtime errors are not mistaken for the budget running out.
+        assert_eq!(budget_error(&mut interpreter, b"let x = 5 x()"), None);
+    }
+
+    #[test]
+    fn test_interpret_timeout_infinite_loop() {
+        let mut interpreter = Interpreter::new().with_timeout(Duration::from_millis(50));
+        let err = interpreter.interpret(b"while (true) {}").unwrap_err();
+        assert!(matches!(
+            err,
+            NotJsError::Runtime(TracedError {
+                error: RuntimeError::Timeout { .. },
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_interpret_timeout_not_reached() {
+        let source = br#"
+            let total = 0
+            for (const i in range(1000)) total = total + i
+        "#;
+        let mut interpreter = Interpreter::new().with_timeout(Duration::from_secs(60));
+        interpreter.interpret(source).unwrap();
+        interpreter.interpret(source).unwrap();
+        assert_eq!(get(&interpreter, "total"), Value::Number(499500.0));
+    }
+
+    #[test]
+    fn test_interpret_stack_trace() {
+        let source = br#"
+            function add(a, b) {
+                return a + b
+            }
+            function main() {
+                return add(1, true)
+            }
+            main()
+        "#;
+        let mut interpreter = Interpreter::new();
+        let err = interpreter.interpret(source).unwrap_err();
+        let NotJsError::Runtime(ref traced) = err else {
+            panic!("expected a runtime error, got {:?}", err);
+        };
+        let frames: Vec<_> = traced
+            .trace
+            .iter()
+            .map(|frame| (frame.name.as_str(), frame.line))
+            .collect();
+        assert_eq!(frames, [("main", 8), ("add", 6)]);
+        assert!(err
+            .to_string()
+            .ends_with("    at add (line 6)\n    at main (line 8)\n"));
+
+        // Calls that returned normally leave nothing behind.
+        interpreter.interpret(b"main2()").unwrap_err();
+        interpreter.interpret(b"let x = 1 x()").unwrap_err();
+        let err = interpreter.interpret(b"let y = 1 y()").unwrap_err();
+        assert!(matches!(err, NotJsError::Runtime(traced) if traced.trace.is_empty()));
+
+        let source = b"function down(n) {\n    if (n == 0) return add(1, true)\n    \
+            return 1 + down(n - 1)\n}\ndown(2)";
+        assert!(interpreter
+            .interpret(source)
+            .unwrap_err()
+            .to_string()
+            .ends_with(
+                "    at add (line 2)\n    at down (line 3)\n    \
+                 ... previous frame repeated 1 more time\n    at down (line 5)\n"
+            ));
+    }
+
+    #[test]
+    fn test_interpret_arity_mismatch() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .interpret(b"function add(a, b) {\n    return a + b\n}")
+            .unwrap();
+
+        let err = interpreter
+            .interpret(b"function main() {\n    return add(1, 2, 3)\n}\n\nmain()")
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "RuntimeError: Function 'add(a, b)' expects 2 arguments but was called with 3 at \
+             line 2 (declared at line 1)\n\n    at main (line 5)\n"
+        );
+
+        // The call fails before it is entered, so it leaves no frame or scope behind.
+        let NotJsError::Runtime(traced) = interpreter.interpret(b"\n\nadd(1)").unwrap_err() else {
+            panic!("expected a runtime error");
+        };
+        assert_eq!(traced.error.line(), Some(3));
+        assert!(traced.trace.is_empty());
+        assert_eq!(interpreter.environment.depth(), 0);
+        assert_eq!(
+            interpreter.interpret(b"len()").unwrap_err().to_string(),
+            "RuntimeError: Function 'len' expects 1 argument but was called with 0 at line 1\n\n"
+        );
+    }
+
+    #[test]
+    fn test_interpret_immutable_variable() {
+        // The parser rejects these assignments, so they are made on the environment directly.
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(b"let x = 1\nconst MAX = 10").unwrap();
+
+        let identifier =
+            |name: &str| Token::new(TokenType::Identifier, Value::String(name.into()), 40);
+        let err = interpreter
+            .environment
+            .assign(&identifier("MAX"), Value::Number(1.0))
+            .unwrap_err();
+        assert_eq!(err.line(), Some(40));
+        assert_eq!(
+            err.to_string(),
+            "RuntimeError: Cannot reassign 'MAX' (declared const at line 2) at line 40\n\n"
+        );
+
+        let err = interpreter
+            .environment
+            .assign(&identifier("len"), Value::Null)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "RuntimeError: Cannot reassign global 'len' at line 40\n\n"
+        );
+    }
+
+    fn runtime_error(interpreter: &mut Interpreter, source: &[u8]) -> RuntimeError {
+        match interpreter.interpret(source).unwrap_err() {
+            NotJsError::Runtime(traced) => traced.error,
+            err => panic!("expected a runtime error, got {:?}", err),
+        }
+    }
+
+    fn invalid_operands(operator: &str, left: &str, right: Option<&str>) -> RuntimeError {
+        RuntimeError::InvalidOperands {
+            operator: operator.to_string(),
+            left: left.to_string(),
+            right: right.map(str::to_string),
+            line: 3,
+        }
+    }
+
+    fn type_error(expected: &str, found: &str) -> RuntimeError {
+        RuntimeError::TypeError {
+            expected: expected.to_string(),
+            found: found.to_string(),
+            line: 3,
+        }
+    }
+
+    #[test]
+    fn test_interpret_error_kinds() {
+        let unsupported =
+            |operation: &str, target: &str, type_name: &str| RuntimeError::Unsupported {
+                operation: operation.to_string(),
+                target: target.to_string(),
+                type_name: type_name.to_string(),
+                line: 3,
+            };
+        let undefined_property = RuntimeError::UndefinedProperty {
+            name: "k".to_string(),
+            line: 3,
+        };
+        let cases = [
+            ("1 + true", invalid_operands("+", "number", Some("boolean"))),
+            (
+                "let a = 1 a += true",
+                invalid_operands("+", "number", Some("boolean")),
+            ),
+            ("-true", invalid_operands("-", "boolean", None)),
+            ("\"abc\"[true]", type_error("number", "boolean")),
+            ("[1][\"0\"]", type_error("number", "string")),
+            (
+                "jsonParse(\"{}\")[[1]]",
+                RuntimeError::InvalidKey {
+                    found: "an array".to_string(),
+                    line: 3,
+                },
+            ),
+            ("true[0]", unsupported("index", "true", "boolean")),
+            ("let n = null n[0]", unsupported("index", "n", "null")),
+            ("jsonParse(\"{}\")[\"k\"]", undefined_property.clone()),
+            ("jsonParse(\"{}\").k", undefined_property),
+            (
+                "\"abc\".foo",
+                RuntimeError::UnknownProperty {
+                    name: "foo".to_string(),
+                    available: vec![
+                        "length".to_string(),
+                        "padStart".to_string(),
+                        "padEnd".to_string(),
+                    ],
+                    line: 3,
+                },
+            ),
+            (
+                "[1].foo",
+                RuntimeError::UnknownProperty {
+                    name: "foo".to_string(),
+                    available: vec![
+                        "length".to_string(),
+                        "clone".to_string(),
+                        "flat".to_string(),
+                        "concat".to_string(),
+                        "sort".to_string(),
+                    ],
+                    line: 3,
+                },
+            ),
+            (
+                "true.foo",
+                unsupported("read property 'foo' of", "true", "boolean"),
+            ),
+            (
+                "let n = 5 n.foo",
+                unsupported("read property 'foo' of", "n", "number"),
+            ),
+            ("let x = 5 x(1, 2)", unsupported("call", "x", "number")),
+            (
+                "let a = 1 let b = 2 let c = (a + b)()",
+                unsupported("call", "(a + b)", "number"),
+            ),
+            ("[[1]][0]()", unsupported("call", "[[1]][0]", "array")),
+            (
+                "function f() { return null } f()()",
+                unsupported("call", "f()", "null"),
+            ),
+            ("for (const c in 5) c", type_error("iterable", "number")),
+            (
+                "[1, 2][2]",
+                RuntimeError::IndexOutOfBounds {
+                    index: 2.0,
+                    len: 2,
+                    line: 3,
+                },
+            ),
+            (
+                "\"ab\"[-3]",
+                RuntimeError::IndexOutOfBounds {
+                    index: -3.0,
+                    len: 2,
+                    line: 3,
+                },
+            ),
+        ];
+
+        for (source, expected) in cases {
+            let source = format!("\n\n{}", source);
+            let err = runtime_error(&mut Interpreter::new(), source.as_bytes());
+            assert_eq!(err, expected, "{:?}", source);
+            assert_eq!(err.line(), Some(3));
+        }
+    }
+
+    #[test]
+    fn test_interpret_invalid_operands() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .interpret(b"function f() {} let obj = jsonParse(\"{}\")")
+            .unwrap();
+
+        let operands = [
+            ("true", "boolean"),
+            ("null", "null"),
+            ("[1]", "array"),
+            ("obj", "object"),
+            ("f", "function"),
+        ];
+        let valid = [("1", "number"), ("\"a\"", "string")];
+
+        for operator in ["+", "-", "*", "/"] {
+            for invalid in operands {
+                for &other in valid.iter().chain(&operands) {
+                    for (left, right) in [(invalid, other), (other, invalid)] {
+                        let source = format!("\n\n{} {} {}", left.0, operator, right.0);
+                        assert_eq!(
+                            runtime_error(&mut interpreter, source.as_bytes()),
+                            invalid_operands(operator, left.1, Some(right.1)),
+                            "{}",
+                            source
+                        );
+                    }
+                }
+            }
+        }
+
+        // Strings only support `+`.
+        for operator in ["-", "*", "/"] {
+            let source = format!("\n\n\"a\" {} 1", operator);
+            assert_eq!(
+                runtime_error(&mut interpreter, source.as_bytes()),
+                invalid_operands(operator, "string", Some("number"))
+            );
+        }
+
+        for (operand, operand_type) in valid[1..].iter().chain(&operands) {
+            let source = format!("\n\n-{}", operand);
+            assert_eq!(
+                runtime_error(&mut interpreter, source.as_bytes()),
+                invalid_operands("-", operand_type, None)
+            );
+        }
+
+        // Compound assignments, to variables and to elements, report the same errors.
+        assert_eq!(
+            runtime_error(&mut interpreter, b"let n = null\n\nn -= 2"),
+            invalid_operands("-", "null", Some("number"))
+        );
+        assert_eq!(
+            runtime_error(&mut interpreter, b"let xs = [true]\n\nxs[0] *= 2"),
+            invalid_operands("*", "boolean", Some("number"))
+        );
+        assert_eq!(
+            interpreter.interpret(b"1 + \"a\"").unwrap(),
+            Value::String("1a".into())
+        );
+    }
+
+    #[test]
+    fn test_interpret_error_display() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            runtime_error(&mut interpreter, b"\n1 + true").to_string(),
+            "RuntimeError: Cannot apply '+' to number and boolean at line 2\n\n"
+        );
+        assert_eq!(
+            runtime_error(&mut interpreter, b"-\"abc\"").to_string(),
+            "RuntimeError: Cannot apply '-' to string at line 1\n\n"
+        );
+        assert_eq!(
+            runtime_error(&mut interpreter, b"[1][5]").to_string(),
+            "RuntimeError: Index 5 out of bounds for length 1 at line 1\n\n"
+        );
+    }
+
+    #[test]
+    fn test_eval_expression() {
+        let mut interpreter = Interpreter::sandboxed();
+        interpreter.define("x", Value::Number(20.0));
+        assert_eq!(
+            interpreter.eval_expression(b"x * 2 + 1").unwrap(),
+            Value::Number(41.0)
+        );
+        assert!(interpreter.eval_expression(b"let y = 3").is_err());
+        assert!(interpreter.eval_expression(b"x x").is_err());
+        assert!(interpreter.eval_expression(b"x = 1").is_err());
+        assert!(interpreter.eval_expression(b"z").is_err());
+        let err = interpreter.eval_expression(b"x + true").unwrap_err();
+        assert!(matches!(err, NotJsError::Runtime(traced) if traced.error.line() == Some(1)));
+    }
+
+    #[test]
+    fn test_interpret_in_chunks() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .interpret(b"function double(n) { return n * 2 } let base = 10")
+            .unwrap();
+        assert_eq!(
+            interpreter.interpret(b"double(base)").unwrap(),
+            Value::Number(20.0)
+        );
+
+        // A chunk failing inside nested scopes does not leave them behind for the next one.
+        assert!(interpreter
+            .interpret(b"{ let inner = 1 { double(true) } }")
+            .is_err());
+        assert!(interpreter.interpret(b"inner").is_err());
+        interpreter
+            .interpret(b"function triple(n) { return n * 3 }")
+            .unwrap();
+        assert_eq!(interpreter.environment.depth(), 0);
+        assert_eq!(
+            interpreter.interpret(b"{ triple(double(1)) }").unwrap(),
+            Value::Number(6.0)
+        );
+    }
+
+    #[test]
+    fn test_interpret_function_identity() {
+        let source = br#"
+            function f() { return 1 }
+            function g() { return 1 }
+            const alias = f
+            let same = f == f
+            let different = f == g
+            let aliased = alias == f
+            let listed = [f, g] == [alias, g]
+            let native = range == range
+            let natives = range == eval
+            let truthy = f ? true : false
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source).unwrap();
+        for (name, expected) in [
+            ("same", true),
+            ("different", false),
+            ("aliased", true),
+            ("listed", true),
+            ("native", true),
+            ("natives", false),
+            ("truthy", true),
+        ] {
+            assert_eq!(
+                get(&interpreter, name),
+                Value::Boolean(expected),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_interpret_function_properties() {
+        let source = br#"
+            function add(a, b) { return a + b }
+            const alias = add
+            let name = alias.name
+            let length = add.length
+            let native = range.name
+            let native_length = jsonStringify.length
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source).unwrap();
+        assert_eq!(get(&interpreter, "name"), Value::String("add".into()));
+        assert_eq!(get(&interpreter, "length"), Value::Number(2.0));
+        assert_eq!(get(&interpreter, "native"), Value::String("range".into()));
+        assert_eq!(get(&interpreter, "native_length"), Value::Number(1.0));
+
+        let err = runtime_error(&mut interpreter, b"add.body");
+        assert_eq!(
+            err.to_string(),
+            "RuntimeError: Unknown property: body at line 1, available properties: name, length\n\n"
+        );
+    }
+
+    #[test]
+    fn test_interpret_sum_min_max() {
+        let source = br#"
+            let total = sum([1, 2, 3.5])
+            let nothing = sum([])
+            let smallest = min([3, -1, 2])
+            let largest = max([3, -1, 2])
+            let single = max([4])
+            let smaller = min(3, 7)
+            let larger = max(3, 7)
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source).unwrap();
+        assert_eq!(get(&interpreter, "total"), Value::Number(6.5));
+        assert_eq!(get(&interpreter, "nothing"), Value::Number(0.0));
+        assert_eq!(get(&interpreter, "smallest"), Value::Number(-1.0));
+        assert_eq!(get(&interpreter, "largest"), Value::Number(3.0));
+        assert_eq!(get(&interpreter, "single"), Value::Number(4.0));
+        assert_eq!(get(&interpreter, "smaller"), Value::Number(3.0));
+        assert_eq!(get(&interpreter, "larger"), Value::Number(7.0));
+
+        let mut err = |source: &[u8]| runtime_error(&mut interpreter, source).to_string();
+        assert!(err(b"sum([1, 2, \"3\", null])")
+            .contains("sum: expected number at index 2, found string"));
+        assert!(err(b"min([1, [2]])").contains("min: expected number at index 1, found array"));
+        assert!(err(b"max([null])").contains("max: expected number at index 0, found null"));
+        assert!(err(b"sum(1)").contains("sum: expected array, found number"));
+        assert!(err(b"min(1)").contains("min: expected array, found number"));
+        assert!(err(b"min(\"a\", 1)").contains("min: expected number, found string"));
+        assert!(err(b"max(1, null)").contains("max: expected number, found null"));
+        assert!(err(b"min([])").contains("min: expected a non-empty array"));
+        assert!(err(b"max([])").contains("max: expected a non-empty array"));
+    }
+
+    #[test]
+    fn test_interpret_zip_enumerate() {
+        let source = r#"
+            let pairs = zip([1, 2, 3], ["a", "b"])
+            let longer = zip([1], [true, false])
+            let empty = zip([], [1, 2])
+            let chars = zip("ab", [1, 2, 3])
+            let indexed = enumerate(["x", "y"])
+            let nothing = enumerate([])
+            let letters = enumerate("hé")
+            let total = 0
+            for (const pair in enumerate([10, 20, 30])) {
+                total = total + pair[0] * pair[1]
+            }
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source.as_bytes()).unwrap();
+        let get = |name| get(&interpreter, name).to_string();
+        assert_eq!(get("pairs"), "[[1, a], [2, b]]");
+        assert_eq!(get("longer"), "[[1, true]]");
+        assert_eq!(get("empty"), "[]");
+        assert_eq!(get("chars"), "[[a, 1], [b, 2]]");
+        assert_eq!(get("indexed"), "[[0, x], [1, y]]");
+        assert_eq!(get("nothing"), "[]");
+        assert_eq!(get("letters"), "[[0, h], [1, é]]");
+        assert_eq!(get("total"), "80");
+
+        let mut err = |source: &[u8]| runtime_error(&mut interpreter, source).to_string();
+        assert!(err(b"zip([1], 2)").contains("zip: expected array or string, found number"));
+        assert!(err(b"enumerate(null)").contains("enumerate: expected array or string, found null"));
+    }
+
+    #[test]
+    fn test_interpret_sort() {
+        let source = br#"
+            function descending(a, b) { return b - a }
+            function by_second(a, b) { return a[1] - b[1] }
+            function by_age(a, b) { return a.age - b.age }
+            const numbers = [3, 1, 2, 10]
+            let ascending = numbers.sort()
+            let reversed = numbers.sort(descending)
+            let words = ["pear", "apple", "fig"].sort()
+            let mixed = ["b", 2, "a", 1].sort()
+            let scores = [["ann", 3], ["bob", 1], ["cy", 2]].sort(by_second)
+            const people = jsonParse("[{\"name\": \"x\", \"age\": 40}, {\"name\": \"y\", \"age\": 30}]")
+            let ages = people.sort(by_age)
+            // Equal keys keep the order they had.
+            let stable = [["a", 2], ["b", 1], ["c", 2], ["d", 1], ["e", 2]].sort(by_second)
+            let empty = [].sort(descending)
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source).unwrap();
+        let get = |name| get(&interpreter, name).to_string();
+        assert_eq!(get("ascending"), "[1, 2, 3, 10]");
+        assert_eq!(get("reversed"), "[10, 3, 2, 1]");
+        assert_eq!(get("numbers"), "[3, 1, 2, 10]");
+        assert_eq!(get("words"), "[apple, fig, pear]");
+        assert_eq!(get("mixed"), "[1, 2, a, b]");
+        assert_eq!(get("scores"), "[[bob, 1], [cy, 2], [ann, 3]]");
+        assert_eq!(get("ages"), "[{name: y, age: 30}, {name: x, age: 40}]");
+        assert_eq!(get("stable"), "[[b, 1], [d, 1], [a, 2], [c, 2], [e, 2]]");
+        assert_eq!(get("empty"), "[]");
+
+        let mut err = |source: &[u8]| runtime_error(&mut interpreter, source).to_string();
+        assert!(
+            err(b"function text(a, b) { return \"a\" } [2, 1].sort(text)")
+                .contains("sort: comparator must return a number, found string comparing 1 and 2")
+        );
+        assert!(err(b"[1, len].sort()").contains("sort: can't order <native fn len/1> and 1"));
+        assert!(err(b"[1].sort(2)").contains("sort: expected function, found number"));
+        // Errors of the comparator come out as they were raised, telling what was compared.
+        let source = b"[[1], [2, 3]].sort(by_second)";
+        assert!(interpreter
+            .interpret(source)
+            .unwrap_err()
+            .to_string()
+            .contains("    at sort (line 1), while comparing [2, 3] and [1]\n"));
+        assert!(matches!(
+            runtime_error(&mut interpreter, source),
+            RuntimeError::IndexOutOfBounds { len: 1, .. }
+        ));
+
+        // A comparator that isn't a consistent order still gives every element back.
+        let shuffled = interpreter
+            .interpret(b"function always(a, b) { return 1 } [5, 3, 1, 4, 2].sort(always)")
+            .unwrap();
+        let Value::Array(shuffled) = shuffled else {
+            panic!("sort should give an array");
+        };
+        assert_eq!(shuffled.len(), 5);
+    }
+
+    #[test]
+    fn test_interpret_pad() {
+        let source = r#"
+            let start = "7".padStart(3)
+            let end = "ab".padEnd(5, "-")
+            let zeros = "42".padStart(5, "0")
+            let repeated = "x".padEnd(6, "ab")
+            let multibyte = "é".padStart(4, "→·")
+            let counted = "日本".padEnd(4, "*")
+            let long = "hello".padStart(3, "*")
+            let exact = "hello".padEnd(5)
+            let empty = "".padStart(0)
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source.as_bytes()).unwrap();
+        let get = |name| get(&interpreter, name).to_string();
+        assert_eq!(get("start"), "  7");
+        assert_eq!(get("end"), "ab---");
+        assert_eq!(get("zeros"), "00042");
+        assert_eq!(get("repeated"), "xababa");
+        // Widths count characters, not bytes.
+        assert_eq!(get("multibyte"), "→·→é");
+        assert_eq!(get("counted"), "日本**");
+        assert_eq!(get("long"), "hello");
+        assert_eq!(get("exact"), "hello");
+        assert_eq!(get("empty"), "");
+
+        let mut err = |source: &[u8]| runtime_error(&mut interpreter, source).to_string();
+        assert!(err(b"\"a\".padStart(-1)")
+            .contains("padStart: width must be a non-negative integer, found -1"));
+        assert!(err(b"\"a\".padEnd(2.5)")
+            .contains("padEnd: width must be a non-negative integer, found 2.5"));
+        assert!(err(b"\"a\".padEnd(\"3\")").contains("padEnd: expected number, found string"));
+        assert!(err(b"\"a\".padStart(3, \"\")").contains("padStart: fill must not be empty"));
+        assert!(err(b"\"a\".padStart(3, 0)").contains("padStart: expected string, found number"));
+    }
+
+    #[test]
+    fn test_interpret_flat_concat() {
+        let source = br#"
+            const nested = [[1, [2]], 3]
+            let once = nested.flat()
+            let twice = nested.flat(2)
+            let none = nested.flat(0)
+            let all = [[[[1]]], [2]].flat(1 / 0)
+            let plain = [1, 2]
+            let copy = plain.flat()
+            copy[0] = 5
+            let joined = plain.concat([3], [], [[4]])
+            let alone = plain.concat()
+            alone[0] = 6
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source).unwrap();
+        let get = |name| get(&interpreter, name).to_string();
+        assert_eq!(get("once"), "[1, [2], 3]");
+        assert_eq!(get("twice"), "[1, 2, 3]");
+        assert_eq!(get("none"), "[[1, [2]], 3]");
+        assert_eq!(get("all"), "[1, 2]");
+        assert_eq!(get("nested"), "[[1, [2]], 3]");
+        assert_eq!(get("copy"), "[5, 2]");
+        assert_eq!(get("joined"), "[1, 2, 3, [4]]");
+        assert_eq!(get("alone"), "[6, 2]");
+        assert_eq!(get("plain"), "[1, 2]");
+
+        let mut err = |source: &[u8]| runtime_error(&mut interpreter, source).to_string();
+        // Values that aren't arrays aren't concatenated as elements.
+        assert!(err(b"plain.concat(3)").contains("concat: expected array, found number"));
+        assert!(
+            err(b"plain.flat(-1)").contains("flat: depth must be a non-negative integer, found -1")
+        );
+        assert!(err(b"plain.flat(1.5)")
+            .contains("flat: depth must be a non-negative integer, found 1.5"));
+        assert!(err(b"plain.flat(\"1\")").contains("flat: expected number, found string"));
+    }
+
+    #[test]
+    fn test_interpret_clone() {
+        let source = br#"
+            function f() { return 1 }
+            const nested = [1, [2, [3, f]], jsonParse("{\"a\": [4]}")]
+            const shadowed = jsonParse("{\"clone\": 5}")
+            let copy = nested.clone()
+            let same = copy == nested
+            let shared = copy[1][1][1] == f
+            let inner = nested[1].clone()
+            let object = nested[2].clone()
+            let property = shadowed.clone
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source).unwrap();
+        assert_eq!(get(&interpreter, "same"), Value::Boolean(true));
+        assert_eq!(get(&interpreter, "shared"), Value::Boolean(true));
+        assert_eq!(get(&interpreter, "copy"), get(&interpreter, "nested"));
+        assert_eq!(get(&interpreter, "inner").to_string(), "[2, [3, <fn f()>]]");
+        assert_eq!(get(&interpreter, "object").to_string(), "{a: [4]}");
+        assert_eq!(get(&interpreter, "property"), Value::Number(5.0));
+    }
+
+    #[test]
+    fn test_interpret_clone_depth_limit() {
+        let mut interpreter = Interpreter::new();
+        // 256 levels of arrays, the deepest that can be cloned.
+        interpreter
+            .interpret(b"let deep = [] for (const i in range(255)) deep = [deep]")
+            .unwrap();
+        interpreter.interpret(b"deep.clone()").unwrap();
+        interpreter.interpret(b"deep = [deep]").unwrap();
+        let err = runtime_error(&mut interpreter, b"deep.clone()");
+        assert_eq!(
+            err,
+            RuntimeError::new("clone: nesting exceeds the limit of 256 levels".to_string())
+        );
+    }
+
+    #[test]
+    fn test_interpret_prelude() {
+        let source = br#"
+            let big = max(3, 7)
+            let small = min(3, 7)
+            let distance = abs(2 - 5)
+            function double(x) { return x * 2 }
+            let doubled = map(freeze([1, 2, 3]), double)
+            // Nested deeper than clone copies.
+            let deep = []
+            let i = 0
+            while (i < 300) {
+                deep = [deep]
+                i += 1
+            }
+            let lengths = map([deep, [1, 2]], len)
+        "#;
+        let mut interpreter = Interpreter::sandboxed();
+        interpreter.interpret(source).unwrap();
+        assert_eq!(get(&interpreter, "big"), Value::Number(7.0));
+        assert_eq!(get(&interpreter, "small"), Value::Number(3.0));
+        assert_eq!(get(&interpreter, "distance"), Value::Number(3.0));
+        assert_eq!(get(&interpreter, "doubled").to_string(), "[2, 4, 6]");
+        assert_eq!(get(&interpreter, "lengths").to_string(), "[1, 2]");
+    }
+
+    #[test]
+    fn test_interpret_bare_has_no_prelude() {
+        let err = Interpreter::bare().interpret(b"abs(1)").unwrap_err();
+        assert!(
+            err.to_string().contains("Undeclared variable 'abs'"),
+            "{}",
+            err
+        );
+        // Natives are still there.
+        Interpreter::bare().interpret(b"range(3)").unwrap();
+    }
+
+    // A fresh directory under the system temp directory holding `files`.
+    #[cfg(feature = "host")]
+    fn module_dir(name: &str, files: &[(&str, &str)]) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("notjs-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        for (file, source) in files {
+            std::fs::write(dir.join(file), source).unwrap();
         }
+
+        dir
+    }
+
+    #[cfg(feature = "host")]
+    #[test]
+    fn test_interpret_import() {
+        let dir = module_dir(
+            "import",
+            &[
+                (
+                    "main.notjs",
+                    "import \"utils.notjs\" import \"./utils.notjs\" let result = double(21)",
+                ),
+                (
+                    "utils.notjs",
+                    "import \"constants.notjs\" function double(x) { return x * factor }",
+                ),
+                ("constants.notjs", "const factor = 2 println \"loaded\""),
+            ],
+        );
+
+        let main = dir.join("main.notjs");
+        let mut interpreter = Interpreter::new().with_script(&main);
+        let (result, printed) = interpreter.interpret_capture(&std::fs::read(&main).unwrap());
+        result.unwrap();
+        assert_eq!(get(&interpreter, "result"), Value::Number(42.0));
+        assert_eq!(get(&interpreter, "factor"), Value::Number(2.0));
+        // Imported twice but only run once.
+        assert_eq!(printed, "loaded\n");
+
+        let err = interpreter.parse(b"import \"missing.notjs\"").unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Cannot import missing.notjs at line 1"));
+        let err = interpreter
+            .parse(b"if (true) { import \"utils.notjs\" }")
+            .unwrap_err();
+        assert!(err.to_string().contains("only allowed at the top level"));
+    }
+
+    #[cfg(feature = "host")]
+    #[test]
+    fn test_interpret_import_cycle() {
+        let dir = module_dir(
+            "import-cycle",
+            &[
+                ("a.notjs", "import \"b.notjs\""),
+                ("b.notjs", "\nimport \"a.notjs\""),
+            ],
+        );
+
+        let a = dir.join("a.notjs");
+        let interpreter = Interpreter::new().with_script(&a);
+        let err = interpreter.parse(&std::fs::read(&a).unwrap()).unwrap_err();
+        let a = a.canonicalize().unwrap();
+        let b = dir.join("b.notjs").canonicalize().unwrap();
+        let chain = format!("{} -> {} -> {}", a.display(), b.display(), a.display());
+        assert!(
+            err.to_string().contains("Import cycle at line 2"),
+            "{}",
+            err
+        );
+        assert!(err.to_string().contains(&chain), "{}", err);
+    }
+
+    #[cfg(feature = "host")]
+    #[test]
+    fn test_interpret_sandboxed_rejects_imports() {
+        let dir = module_dir(
+            "import-sandboxed",
+            &[
+                ("main.notjs", "import \"secret.notjs\""),
+                ("secret.notjs", "the secret is 42"),
+            ],
+        );
+
+        let main = dir.join("main.notjs");
+        let mut interpreter = Interpreter::sandboxed().with_script(&main);
+        let err = interpreter
+            .interpret(&std::fs::read(&main).unwrap())
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Cannot import secret.notjs at line 1"));
+        assert!(err.to_string().contains("disabled in sandboxed"), "{}", err);
+        assert!(!err.to_string().contains("secret is"), "{}", err);
+
+        let secret = dir.join("secret.notjs");
+        let source = format!("eval(\"import \\\"{}\\\"\")", secret.display());
+        let err = interpreter.interpret(source.as_bytes()).unwrap_err();
+        assert!(err.to_string().contains("disabled in sandboxed"), "{}", err);
+        assert!(!err.to_string().contains("secret is"), "{}", err);
+    }
+
+    #[cfg(feature = "host")]
+    #[test]
+    fn test_interpret_import_namespace() {
+        let dir = module_dir(
+            "import-namespace",
+            &[
+                (
+                    "utils.notjs",
+                    r#"
+                    const factor = 2
+                    function scale(x) { return x * factor }
+                    export function double(x) { return scale(x) }
+                    export function apply(f, x) { return f(x) }
+                    export const biggest = max(3, 4)
+                    export let pending
+                    println "loaded"
+                    "#,
+                ),
+                (
+                    "main.notjs",
+                    r#"
+                    const factor = 100
+                    const offset = 1
+                    function increment(x) { return x + offset }
+                    import utils from "utils.notjs"
+                    import again from "./utils.notjs"
+                    let doubled = utils.double(21)
+                    let applied = utils.apply(increment, 1)
+                    let biggest = utils.biggest
+                    let same = utils == again
+                    "#,
+                ),
+            ],
+        );
+
+        let main = dir.join("main.notjs");
+        let mut interpreter = Interpreter::new().with_script(&main);
+        let (result, printed) = interpreter.interpret_capture(&std::fs::read(&main).unwrap());
+        result.unwrap();
+        // The module's functions use its own globals, the callback uses the main program's.
+        assert_eq!(get(&interpreter, "doubled"), Value::Number(42.0));
+        assert_eq!(get(&interpreter, "applied"), Value::Number(2.0));
+        assert_eq!(get(&interpreter, "biggest"), Value::Number(4.0));
+        assert_eq!(get(&interpreter, "same"), Value::Boolean(true));
+        assert_eq!(get(&interpreter, "factor"), Value::Number(100.0));
+        assert_eq!(
+            get(&interpreter, "utils").to_string(),
+            "{double: <fn double(x)>, apply: <fn apply(f, x)>, biggest: 4, pending: Null}"
+        );
+        assert_eq!(printed, "loaded\n");
+
+        // Private names are neither globals nor part of the namespace.
+        let err = interpreter.parse(b"scale(1)").unwrap_err();
+        assert!(
+            err.to_string().contains("Undeclared variable 'scale'"),
+            "{}",
+            err
+        );
+        let err = runtime_error(&mut interpreter, b"utils.scale(1)");
+        assert!(
+            matches!(err, RuntimeError::UndefinedProperty { .. }),
+            "{:?}",
+            err
+        );
+
+        let err = interpreter.parse(b"export 1").unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Expected let, const, function or enum after export"));
+        let err = interpreter
+            .parse(b"function f() { export const a = 1 }")
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Exports are only allowed at the top level"));
+    }
+
+    #[test]
+    fn test_interpret_property_assignment() {
+        let source = br#"
+            let xs = [1, [2, 3]]
+            xs[0] = 5
+            xs[1][-1] += 10
+            let obj = jsonParse("{\"a\": {\"b\": 1}}")
+            obj.a.b = 2
+            obj["c"] = xs
+            obj.a.d = "new"
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source).unwrap();
+        assert_eq!(get(&interpreter, "xs").to_string(), "[5, [2, 13]]");
+        assert_eq!(
+            get(&interpreter, "obj").to_string(),
+            "{a: {b: 2, d: new}, c: [5, [2, 13]]}"
+        );
+
+        let err = runtime_error(&mut interpreter, b"xs[5] = 1");
+        assert!(matches!(err, RuntimeError::IndexOutOfBounds { .. }));
+        let err = runtime_error(&mut interpreter, b"obj.x.y = 1");
+        assert!(matches!(err, RuntimeError::UndefinedProperty { .. }));
+
+        let err = interpreter.parse(b"const c = [1] c[0] = 2").unwrap_err();
+        assert!(err.to_string().contains("Cannot reassign 'c'"), "{}", err);
+        let err = interpreter.parse(b"let f = 1 f()[0] = 2").unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Expected identifier, element or property"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_interpret_property_assignment_in_place() {
+        // Copying the array on every write would make this loop quadratic, far past the timeout.
+        let source = br#"
+            let xs = range(50000)
+            let copy = xs
+            for (const i in range(xs.length)) xs[i] = i * 2
+            function double(x) { return x * 2 }
+            let doubled = map(copy, double)
+            let obj = jsonParse("{\"a\": 1}")
+        "#;
+        let mut interpreter = Interpreter::new().with_timeout(Duration::from_secs(20));
+        interpreter.interpret(source).unwrap();
+        let Value::Array(xs) = get(&interpreter, "xs") else {
+            panic!("xs should be an array");
+        };
+        assert_eq!(xs[49999], Value::Number(99998.0));
+        // Other values holding the array keep their elements.
+        let Value::Array(copy) = get(&interpreter, "copy") else {
+            panic!("copy should be an array");
+        };
+        assert_eq!(copy[49999], Value::Number(49999.0));
+        assert_eq!(get(&interpreter, "doubled"), Value::Array(xs));
+
+        // A failed assignment leaves the variable as it was.
+        runtime_error(&mut interpreter, b"obj.b -= 1");
+        assert_eq!(get(&interpreter, "obj").to_string(), "{a: 1}");
+    }
+
+    #[test]
+    fn test_interpret_freeze() {
+        let source = br#"
+            let xs = freeze([1, [2]])
+            let first = xs[0]
+            let frozen = isFrozen(xs)
+            let inner = isFrozen(xs[1])
+            let number = isFrozen(1)
+            xs[1][0] = 3
+            let copy = xs
+            let unfrozen = isFrozen(xs.clone())
+            let obj = freeze(jsonParse("{\"a\": 1}"))
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source).unwrap();
+        assert_eq!(get(&interpreter, "first"), Value::Number(1.0));
+        assert_eq!(get(&interpreter, "frozen"), Value::Boolean(true));
+        assert_eq!(get(&interpreter, "inner"), Value::Boolean(false));
+        assert_eq!(get(&interpreter, "number"), Value::Boolean(true));
+        assert_eq!(get(&interpreter, "unfrozen"), Value::Boolean(false));
+        assert_eq!(get(&interpreter, "xs").to_string(), "[1, [3]]");
+
+        let frozen = |target: &str, type_name: &str| RuntimeError::Frozen {
+            target: target.to_string(),
+            type_name: type_name.to_string(),
+            line: 2,
+        };
+        assert_eq!(
+            runtime_error(&mut interpreter, b"\n xs[0] = 5"),
+            frozen("xs", "array")
+        );
+        assert_eq!(
+            runtime_error(&mut interpreter, b"\n copy[0] += 1"),
+            frozen("copy", "array")
+        );
+        assert_eq!(
+            runtime_error(&mut interpreter, b"\n obj.b = 2"),
+            frozen("obj", "object")
+        );
+        assert_eq!(
+            runtime_error(&mut interpreter, b"let d = deepFreeze([[1]])\n d[0][0] = 2"),
+            frozen("d[0]", "array")
+        );
+        assert_eq!(
+            runtime_error(&mut interpreter, b"\n xs[0] = 5")
+                .to_string()
+                .trim_end(),
+            "RuntimeError: Cannot modify frozen array xs at line 2"
+        );
+
+        // As a statement, freeze would freeze a copy and leave the variable as it was.
+        interpreter.interpret(b"let ys = [1]").unwrap();
+        let err = interpreter
+            .interpret(b"freeze(ys); ys[0] = 5")
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            err,
+            "Failed to parse, errors:\n\n\
+             Parse error: The result of freeze is unused, it freezes a copy and leaves its \
+             argument as it is. Assign it instead, as in `ys = freeze(ys)` at line 1"
+        );
+        assert!(interpreter.interpret(b"deepFreeze([ys])").is_err());
+        assert_eq!(get(&interpreter, "ys").to_string(), "[1]");
+        // Functions of the program are called as any other.
+        interpreter
+            .interpret(b"function freeze(x) { return x }\nfreeze(ys)")
+            .unwrap();
+
+        // The binding is not frozen, only the value.
+        interpreter.interpret(b"xs = [3] xs[0] = 4").unwrap();
+        assert_eq!(get(&interpreter, "xs").to_string(), "[4]");
+    }
+
+    #[test]
+    fn test_trace() {
+        let buffer = SharedBuffer::default();
+        let mut interpreter = Interpreter::bare().with_trace_output(buffer.clone());
+        interpreter.set_trace(true);
+
+        let (result, printed) = interpreter.interpret_capture(
+            b"function double(x) {
+                return x * 2
+            }
+            let n = double(len(\"ab\"))
+            if (n > 3) {
+                println n
+            }",
+        );
+        result.unwrap();
+        assert_eq!(printed, "4\n");
+
+        let trace = String::from_utf8(buffer.0.borrow().clone()).unwrap();
+        assert_eq!(
+            trace,
+            "[line 1, depth 0] function double(x) {
+[line 4, depth 0] let n = double(len(\"ab\"))
+[line 4, depth 0] call len(\"ab\")
+[line 4, depth 0] len returned 2
+[line 4, depth 0] call double(2)
+[line 1, depth 1] {
+[line 2, depth 2] return x * 2
+[line 4, depth 0] double returned 4
+[line 5, depth 0] if (n > 3) {
+[line 5, depth 0] {
+[line 6, depth 1] println n
+"
+        );
+
+        // Nothing is traced once it is turned off.
+        interpreter.set_trace(false);
+        interpreter.interpret(b"double(1)").unwrap();
+        assert_eq!(buffer.0.borrow().len(), trace.len());
+    }
+
+    #[test]
+    fn test_profile() {
+        let mut interpreter = Interpreter::bare();
+        assert_eq!(interpreter.take_profile(), Profile::default());
+
+        interpreter.set_profiling(true);
+        interpreter
+            .interpret(
+                b"function fib(n) { return n < 2 ? n : fib(n - 1) + fib(n - 2) }
+                function inner() { return fib(10) }
+                function outer() { return inner() }
+                fib(15)
+                outer()",
+            )
+            .unwrap();
+
+        let profile = interpreter.take_profile();
+        let get = |name: &str| profile.get(name).unwrap().clone();
+        let (top, fib, inner, outer) = (
+            get(profile::TOP_LEVEL),
+            get("fib"),
+            get("inner"),
+            get("outer"),
+        );
+
+        assert_eq!(top.calls, 1);
+        assert_eq!(fib.calls, 1973 + 177);
+        assert_eq!((inner.calls, outer.calls), (1, 1));
+
+        // Recursive calls run inside the outermost one and are not counted again.
+        assert!(fib.total <= top.total);
+        assert_eq!(fib.self_time, fib.total);
+        // Self times add up to the time of the whole program.
+        let self_times: Duration = profile.functions.iter().map(|f| f.self_time).sum();
+        assert_eq!(self_times, top.total);
+        assert_eq!(outer.self_time + inner.total, outer.total);
+
+        // The profile starts over once taken, and nothing is recorded once profiling is off.
+        interpreter.set_profiling(false);
+        interpreter.interpret(b"fib(2)").unwrap();
+        assert_eq!(interpreter.take_profile(), Profile::default());
+    }
+
+    #[test]
+    fn test_coverage() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_coverage(true);
+        interpreter
+            .interpret(
+                b"function describe(n) {
+                    if (n > 0) {
+                        return \"positive\"
+                    } else {
+                        let message = \"not positive\"
+                        return message
+                    }
+                }
+                describe(1)
+                map([1], abs)
+                eval(\"1\\n2\\n3\\n4\\n5\\n6\\n7\")",
+            )
+            .unwrap();
+
+        let coverage = interpreter.take_coverage();
+        assert_eq!(coverage.files.len(), 1);
+        let file = coverage.file(None).unwrap();
+
+        // The else branch never ran. Neither the prelude nor eval mark lines of the program.
+        assert_eq!(file.uncovered(), [4, 5, 6]);
+        assert_eq!((file.covered(), file.total()), (6, 9));
+
+        interpreter.set_coverage(false);
+        interpreter.interpret(b"describe(0)").unwrap();
+        assert_eq!(interpreter.take_coverage(), Coverage::default());
     }
 }
@@ -0,0 +1,138 @@
+use crate::{
+    common::{
+        convert::ConversionError,
+        function::{Arity, NativeFunction},
+        value::Value,
+    },
+    error::runtime::{RuntimeError, RuntimeResult},
+};
+
+// Rust closures that can be turned into natives, taking their arguments as typed values.
+// `Args` is the tuple of argument types, so closures of different arities get separate impls.
+pub trait Bind<Args> {
+    fn into_native(self, name: &str) -> NativeFunction;
+}
+
+// Convert the argument at `index` to the type the bound closure expects.
+fn argument<T>(native: &str, index: usize, value: Value) -> RuntimeResult<T>
+where
+    T: TryFrom<Value, Error = ConversionError>,
+{
+    T::try_from(value).map_err(|err| {
+        RuntimeError::new(format!(
+            "{}: argument {}: expected {}, got {}",
+            native,
+            index + 1,
+            err.expected,
+            err.found
+        ))
+    })
+}
+
+macro_rules! impl_bind {
+    ($count:literal; $($arg:ident),*) => {
+        impl<F, R, $($arg),*> Bind<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> R + 'static,
+            R: Into<Value>,
+            $($arg: TryFrom<Value, Error = ConversionError>,)*
+        {
+            #[allow(unused_mut, unused_variables)]
+            fn into_native(self, name: &str) -> NativeFunction {
+                let native = name.to_string();
+
+                NativeFunction::new(name, Arity::Exact($count), move |_, arguments| {
+                    // The arity is checked before calling, so every argument is present.
+                    let mut arguments = arguments.into_iter().enumerate();
+
+                    Ok(self($({
+                        let (index, value) = arguments.next().unwrap();
+                        argument::<$arg>(&native, index, value)?
+                    }),*)
+                    .into())
+                })
+            }
+        }
+    };
+}
+
+impl_bind!(0;);
+impl_bind!(1; A);
+impl_bind!(2; A, B);
+impl_bind!(3; A, B, C);
+impl_bind!(4; A, B, C, D);
+
+#[cfg(test)]
+mod tests {
+    use crate::{error::NotJsError, interpreter::Interpreter};
+
+    use super::*;
+
+    fn error(interpreter: &mut Interpreter, source: &[u8]) -> String {
+        match interpreter.interpret(source).unwrap_err() {
+            NotJsError::Runtime(traced) => traced.error.to_string(),
+            err => panic!("expected a runtime error, got {:?}", err),
+        }
+    }
+
+    #[test]
+    fn test_bind() {
+        let mut interpreter = Interpreter::sandboxed();
+        interpreter.bind("answer", || 42.0);
+        interpreter.bind("shout", |text: String| text.to_uppercase() + "!");
+        interpreter.bind("hypot", |a: f64, b: f64| (a * a + b * b).sqrt());
+        interpreter.bind(
+            "pick",
+            |first: bool, a: String, b: String| {
+                if first {
+                    a
+                } else {
+                    b
+                }
+            },
+        );
+
+        assert_eq!(
+            interpreter.interpret(b"answer()").unwrap(),
+            Value::Number(42.0)
+        );
+        assert_eq!(
+            interpreter.interpret(b"shout(\"hi\")").unwrap(),
+            Value::String("HI!".into())
+        );
+        assert_eq!(
+            interpreter.interpret(b"hypot(3, 4)").unwrap(),
+            Value::Number(5.0)
+        );
+        assert_eq!(
+            interpreter.interpret(b"pick(false, \"a\", \"b\")").unwrap(),
+            Value::String("b".into())
+        );
+    }
+
+    #[test]
+    fn test_bind_errors() {
+        let mut interpreter = Interpreter::sandboxed();
+        interpreter.bind("hypot", |a: f64, b: f64| (a * a + b * b).sqrt());
+        interpreter.bind("repeat", |text: String, count: i64| {
+            text.repeat(count.max(0) as usize)
+        });
+
+        assert_eq!(
+            error(&mut interpreter, b"hypot(3, \"4\")"),
+            "RuntimeError: hypot: argument 2: expected number, got string\n\n"
+        );
+        assert_eq!(
+            error(&mut interpreter, b"hypot([], 4)"),
+            "RuntimeError: hypot: argument 1: expected number, got array\n\n"
+        );
+        assert_eq!(
+            error(&mut interpreter, b"repeat(\"a\", 1.5)"),
+            "RuntimeError: repeat: argument 2: expected integer, got number\n\n"
+        );
+        assert_eq!(
+            error(&mut interpreter, b"hypot(3)"),
+            "RuntimeError: Function 'hypot' expects 2 arguments but was called with 1 at line 1\n\n"
+        );
+    }
+}
@@ -0,0 +1,320 @@
+use std::mem;
+
+use crate::{
+    common::{
+        environment::{DeferredBlock, Environment, Scope},
+        generator::{Generator, Items, Resume, State},
+        statements::Statement,
+        token::Token,
+        value::Value,
+    },
+    error::runtime::{RuntimeError, RuntimeResult},
+};
+
+use super::run_deferred;
+
+// What a generator gave when resumed.
+pub(crate) enum Resumed {
+    Yielded(Value),
+    // The body ended, giving the value it returned. Once done, a generator only gives null.
+    Returned(Value),
+}
+
+// How running a statement of the body of a generator ended.
+enum Flow {
+    Normal,
+    Yield(Value),
+    Return(Value),
+}
+
+impl Generator {
+    // Run the body from where it was paused up to the next yield. `line` is where the generator
+    // is resumed from, which is recorded in the call stack. A generator whose body fails is done.
+    pub(crate) fn resume(
+        &self,
+        environment: &mut Environment,
+        line: u32,
+    ) -> RuntimeResult<Resumed> {
+        let state = match self.state.replace(State::Running) {
+            State::Running => {
+                return Err(RuntimeError::new_generator_running(
+                    &self.function.name,
+                    line,
+                ))
+            }
+            State::Done => {
+                self.state.replace(State::Done);
+                return Ok(Resumed::Returned(Value::Null));
+            }
+            state => state,
+        };
+
+        let (state, resumed) = match self.run(state, environment, line) {
+            Ok((Flow::Yield(value), Paused { scopes, deferred }, path)) => (
+                State::Suspended(scopes, deferred, path),
+                Ok(Resumed::Yielded(value)),
+            ),
+            Ok((Flow::Return(value), _, _)) => (State::Done, Ok(Resumed::Returned(value))),
+            Ok((Flow::Normal, _, _)) => (State::Done, Ok(Resumed::Returned(Value::Null))),
+            Err(err) => (State::Done, Err(err)),
+        };
+        self.state.replace(state);
+
+        resumed
+    }
+
+    // Enter the body as a call of the function, giving how it stopped along with what it had
+    // going and the way back to where it stopped.
+    fn run(
+        &self,
+        state: State,
+        environment: &mut Environment,
+        line: u32,
+    ) -> RuntimeResult<(Flow, Paused, Vec<Resume>)> {
+        let function = &self.function;
+
+        environment.enter_call(function.name.name(), line)?;
+        let hidden = match environment.hide_scopes(&function.name, function.depth) {
+            Ok(hidden) => hidden,
+            Err(err) => {
+                environment.exit_call();
+                return Err(err);
+            }
+        };
+        let globals = environment.enter_globals(&function.globals);
+        let caller_file = mem::replace(&mut environment.file, function.file);
+        let deferring = environment.deferred.len();
+        // Returns in the body end the generator, they are never tail calls.
+        let position = environment.enter_tail_position(None);
+
+        let mut path = match state {
+            State::Created(arguments) => {
+                environment.push();
+                for (parameter, argument) in function.parameters.iter().zip(arguments) {
+                    environment.define_annotated(
+                        &parameter.name,
+                        Some(argument),
+                        true,
+                        parameter.annotation,
+                    );
+                }
+                Vec::new()
+            }
+            State::Suspended(scopes, deferred, path) => {
+                environment.environment.extend(scopes);
+                environment.deferred.extend(deferred);
+                path
+            }
+            State::Running | State::Done => unreachable!("only paused generators are resumed"),
+        };
+
+        let flow = run(&function.body, environment, &mut path);
+        if flow.is_err() {
+            environment.record_trace();
+        }
+        let paused = Paused {
+            scopes: environment.environment.split_off(function.depth + 1),
+            deferred: environment.deferred.split_off(deferring),
+        };
+
+        environment.leave_tail_position(position);
+        environment.file = caller_file;
+        environment.leave_globals(globals);
+        environment.restore_scopes(function.depth, hidden);
+        environment.exit_call();
+
+        Ok((flow?, paused, path))
+    }
+}
+
+// What the body had going when it stopped, which is kept if it stopped at a yield.
+struct Paused {
+    scopes: Vec<Scope>,
+    deferred: Vec<DeferredBlock>,
+}
+
+// Run `statement` of the body of a generator. When `path` isn't empty, the statement was paused
+// at a yield inside of it, and it continues from there without running anything twice. On a
+// yield, each statement the yield is inside of adds to `path` where it stopped.
+fn run(
+    statement: &Statement,
+    environment: &mut Environment,
+    path: &mut Vec<Resume>,
+) -> RuntimeResult<Flow> {
+    // Only statements that hold other statements can hold a yield, the rest run as in any
+    // function.
+    if !matches!(
+        statement,
+        Statement::Block(_)
+            | Statement::If(_)
+            | Statement::While(_)
+            | Statement::ForIn(_)
+            | Statement::Yield(_)
+    ) {
+        let value = statement.execute(environment)?;
+
+        return Ok(if environment.is_returning() {
+            Flow::Return(environment.finish_return(value))
+        } else {
+            Flow::Normal
+        });
+    }
+
+    let resume = path.pop();
+    if resume.is_none() {
+        statement.prepare(environment)?;
+        environment.step()?;
+    }
+
+    match statement {
+        Statement::Block(block) => {
+            let start = match resume {
+                Some(Resume::Block(index)) => index,
+                _ => {
+                    environment.push();
+                    environment.start_deferring();
+                    0
+                }
+            };
+
+            let mut flow = Flow::Normal;
+            for (index, statement) in block.statements.iter().enumerate().skip(start) {
+                match run(statement, environment, path)? {
+                    Flow::Normal => {}
+                    Flow::Yield(value) => {
+                        path.push(Resume::Block(index));
+                        return Ok(Flow::Yield(value));
+                    }
+                    returned => {
+                        flow = returned;
+                        break;
+                    }
+                }
+            }
+
+            run_deferred(environment)?;
+            environment.pop();
+            Ok(flow)
+        }
+        Statement::If(statement) => {
+            let then = match resume {
+                Some(Resume::If(then)) => then,
+                _ => statement.condition.evaluate(environment)?.is_truthy(),
+            };
+            let branch = if then {
+                Some(&*statement.then_branch)
+            } else {
+                statement.else_branch.as_deref()
+            };
+
+            let Some(branch) = branch else {
+                return Ok(Flow::Normal);
+            };
+            let flow = run(branch, environment, path)?;
+            if let Flow::Yield(_) = flow {
+                path.push(Resume::If(then));
+            }
+
+            Ok(flow)
+        }
+        Statement::While(statement) => {
+            let mut resuming = matches!(resume, Some(Resume::While));
+
+            while resuming || statement.condition.evaluate(environment)?.is_truthy() {
+                resuming = false;
+
+                match run(&statement.body, environment, path)? {
+                    Flow::Normal => {}
+                    Flow::Yield(value) => {
+                        path.push(Resume::While);
+                        return Ok(Flow::Yield(value));
+                    }
+                    flow => return Ok(flow),
+                }
+
+                environment.check_deadline()?;
+            }
+
+            Ok(Flow::Normal)
+        }
+        Statement::ForIn(statement) => {
+            let (mut items, mut resuming) = match resume {
+                Some(Resume::ForIn(items)) => (items, true),
+                _ => {
+                    let iterable = statement.iterable.evaluate(environment)?;
+                    (Items::new(iterable, &statement.identifier)?, false)
+                }
+            };
+
+            loop {
+                // The scope of the element the loop was paused in is among the saved scopes.
+                if !resuming {
+                    let Some(item) = items.next(environment, statement.identifier.line)? else {
+                        break;
+                    };
+                    environment.push();
+                    environment.define(&statement.identifier, Some(item), statement.mutable);
+                }
+                resuming = false;
+
+                match run(&statement.body, environment, path)? {
+                    Flow::Normal => environment.pop(),
+                    Flow::Yield(value) => {
+                        path.push(Resume::ForIn(items));
+                        return Ok(Flow::Yield(value));
+                    }
+                    flow => {
+                        environment.pop();
+                        return Ok(flow);
+                    }
+                }
+
+                environment.check_deadline()?;
+            }
+
+            Ok(Flow::Normal)
+        }
+        Statement::Yield(statement) => match resume {
+            Some(Resume::Yield) => Ok(Flow::Normal),
+            _ => {
+                let value = match statement.value {
+                    Some(ref value) => value.evaluate(environment)?,
+                    None => Value::Null,
+                };
+                path.push(Resume::Yield);
+
+                Ok(Flow::Yield(value))
+            }
+        },
+        _ => unreachable!("other statements can't hold a yield"),
+    }
+}
+
+impl Items {
+    // The elements of `iterable`, failing at `token` if it can't be looped over.
+    pub(crate) fn new(iterable: Value, token: &Token) -> RuntimeResult<Self> {
+        if let Value::Generator(generator) = iterable {
+            return Ok(Items::Generator(generator));
+        }
+
+        match iterable.iter() {
+            Some(values) => Ok(Items::Values(values.collect::<Vec<Value>>().into_iter())),
+            None => Err(RuntimeError::new_type_error("iterable", &iterable, token)),
+        }
+    }
+
+    // The next element, resuming a generator from `line` to get it.
+    pub(crate) fn next(
+        &mut self,
+        environment: &mut Environment,
+        line: u32,
+    ) -> RuntimeResult<Option<Value>> {
+        match self {
+            Items::Values(values) => Ok(values.next()),
+            Items::Generator(generator) => match generator.resume(environment, line)? {
+                Resumed::Yielded(value) => Ok(Some(value)),
+                Resumed::Returned(_) => Ok(None),
+            },
+        }
+    }
+}
@@ -0,0 +1,843 @@
+#[cfg(feature = "host")]
+use std::env;
+use std::{cmp::Ordering, io::Write, rc::Rc};
+
+use crate::{
+    common::{
+        array::Array,
+        environment::{Environment, Scope, Variable},
+        format,
+        function::{Arity, Function, NativeFunction},
+        generator::Generator,
+        object::Object,
+        resolver::Resolver,
+        value::{number_to_string, Value},
+    },
+    error::runtime::RuntimeError,
+    parser::{self, Imports},
+};
+
+use super::{generator::Resumed, Interpreter};
+
+pub const DEFAULT_RANGE_LIMIT: usize = 1_000_000;
+pub const MAX_CLONE_DEPTH: usize = 256;
+// Natives are suggested for a name given to `help` when they are this many edits away from it.
+const MAX_SUGGESTION_DISTANCE: usize = 2;
+
+// Define the natives every interpreter starts with.
+pub fn register(interpreter: &mut Interpreter) {
+    interpreter.define_native(args(Vec::new()));
+    interpreter.define_native(range(DEFAULT_RANGE_LIMIT));
+    interpreter.define_native(
+        NativeFunction::new("eval", Arity::Exact(1), |environment, arguments| {
+            let source = arguments[0]
+                .as_str()
+                .ok_or_else(|| expected("eval", "string", &arguments[0]))?;
+
+            // The source is resolved against the names defined at the moment of the call, so
+            // declarations made by it are only visible to later calls to eval, not to the
+            // already parsed code around it. Imports are resolved against the working directory.
+            let imports = if environment.sandboxed {
+                Imports::denied()
+            } else {
+                Imports::default()
+            };
+            let statements = parser::parse_with_imports(
+                source.as_bytes(),
+                Resolver::from_environment(environment),
+                imports,
+            )
+            .map_err(|err| RuntimeError::new(format!("eval: {}", err)))?;
+
+            let mut result = Value::Null;
+
+            for statement in statements {
+                result = statement.execute(environment)?;
+
+                if environment.is_returning() {
+                    break;
+                }
+            }
+
+            Ok(result)
+        })
+        .with_doc("Run a string of source code, giving the value of its last statement"),
+    );
+    interpreter.define_native(
+        NativeFunction::new("freeze", Arity::Exact(1), |_, mut arguments| {
+            let mut value = arguments.remove(0);
+            value.freeze(false);
+            Ok(value)
+        })
+        .with_doc("Make an array or object read-only, giving it back"),
+    );
+    interpreter.define_native(
+        NativeFunction::new("deepFreeze", Arity::Exact(1), |_, mut arguments| {
+            let mut value = arguments.remove(0);
+            value.freeze(true);
+            Ok(value)
+        })
+        .with_doc("Make an array or object and everything nested in it read-only"),
+    );
+    interpreter.define_native(
+        NativeFunction::new("isFrozen", Arity::Exact(1), |_, arguments| {
+            Ok(Value::Boolean(arguments[0].is_frozen()))
+        })
+        .with_doc("Whether a value can't be changed"),
+    );
+    interpreter.define_native(
+        NativeFunction::new("len", Arity::Exact(1), |_, arguments| {
+            let length = match &arguments[0] {
+                Value::String(text) => text.chars().count(),
+                Value::Array(array) => array.len(),
+                Value::Object(object) => object.len(),
+                value => return Err(expected("len", "string, array or object", value)),
+            };
+
+            Ok(Value::Number(length as f64))
+        })
+        .with_doc(
+            "Number of characters of a string, elements of an array or properties of an object",
+        ),
+    );
+    interpreter.define_native(
+        NativeFunction::new("ord", Arity::Exact(1), |_, arguments| {
+            let text = match &arguments[0] {
+                Value::String(text) => text,
+                value => return Err(expected("ord", "string", value)),
+            };
+
+            let mut chars = text.chars();
+
+            match (chars.next(), chars.next()) {
+                (Some(chr), None) => Ok(Value::Number(chr as u32 as f64)),
+                _ => Err(RuntimeError::new(format!(
+                    "ord: expected a single character, found \"{}\"",
+                    text
+                ))),
+            }
+        })
+        .with_doc("Code point of a single character"),
+    );
+    interpreter.define_native(
+        NativeFunction::new("chr", Arity::Exact(1), |_, arguments| {
+            let code = match arguments[0] {
+                Value::Number(code) => code,
+                ref value => return Err(expected("chr", "number", value)),
+            };
+
+            if code.fract() != 0.0 || code < 0.0 || code > u32::MAX as f64 {
+                return Err(RuntimeError::new(format!(
+                    "chr: expected a non-negative integer, found {}",
+                    number_to_string(code)
+                )));
+            }
+
+            char::from_u32(code as u32)
+                .map(|chr| Value::String(chr.to_string().into()))
+                .ok_or_else(|| {
+                    RuntimeError::new(format!(
+                        "chr: {} is not a valid code point",
+                        number_to_string(code)
+                    ))
+                })
+        })
+        .with_doc("Character with the given code point"),
+    );
+    interpreter.define_native(
+        NativeFunction::new("format", Arity::AtLeast(1), |_, arguments| {
+            let template = match &arguments[0] {
+                Value::String(template) => template,
+                value => return Err(expected("format", "string", value)),
+            };
+
+            format::format(template, &arguments[1..])
+                .map(Value::from)
+                .map_err(|err| RuntimeError::new(format!("format: {}", err)))
+        })
+        .with_doc("Fill the {} placeholders of a template with the arguments"),
+    );
+    interpreter.define_native(
+        NativeFunction::new("repr", Arity::Exact(1), |_, arguments| {
+            Ok(Value::String(format!("{:?}", arguments[0]).into()))
+        })
+        .with_doc("The value as it is written in source code"),
+    );
+    interpreter.define_native(
+        NativeFunction::new("pprint", Arity::Exact(1), |environment, arguments| {
+            writeln!(environment.output, "{:#}", arguments[0])
+                .map_err(|err| RuntimeError::new(format!("Failed to write output: {}", err)))?;
+
+            Ok(Value::Null)
+        })
+        .with_doc("Print a value across several indented lines"),
+    );
+    interpreter.define_native(
+        NativeFunction::new("isNaN", Arity::Exact(1), |_, arguments| {
+            match arguments[0] {
+                Value::Number(num) => Ok(Value::Boolean(num.is_nan())),
+                ref value => Err(expected("isNaN", "number", value)),
+            }
+        })
+        .with_doc("Whether a number is NaN"),
+    );
+    interpreter.define_native(
+        NativeFunction::new(
+            "isFinite",
+            Arity::Exact(1),
+            |_, arguments| match arguments[0] {
+                Value::Number(num) => Ok(Value::Boolean(num.is_finite())),
+                ref value => Err(expected("isFinite", "number", value)),
+            },
+        )
+        .with_doc("Whether a number is neither infinite nor NaN"),
+    );
+    interpreter.define_native(
+        NativeFunction::new("jsonParse", Arity::Exact(1), |_, arguments| {
+            let source = arguments[0]
+                .as_str()
+                .ok_or_else(|| expected("jsonParse", "string", &arguments[0]))?;
+
+            Value::from_json(source).map_err(|err| RuntimeError::new(format!("jsonParse: {}", err)))
+        })
+        .with_doc("Read a value from JSON"),
+    );
+    interpreter.define_native(
+        NativeFunction::new("jsonStringify", Arity::Range(1, 2), |_, arguments| {
+            let indent = match arguments.get(1) {
+                None => 0,
+                Some(Value::Number(indent)) if *indent >= 0.0 && indent.fract() == 0.0 => {
+                    *indent as usize
+                }
+                Some(Value::Number(indent)) => {
+                    return Err(RuntimeError::new(format!(
+                        "jsonStringify: indent must be a non-negative integer, found {}",
+                        indent
+                    )))
+                }
+                Some(indent) => return Err(expected("jsonStringify", "number", indent)),
+            };
+
+            arguments[0]
+                .to_json(indent)
+                .map(Value::from)
+                .map_err(|err| RuntimeError::new(format!("jsonStringify: {}", err)))
+        })
+        .with_doc("Write a value as JSON, indented by the optional number of spaces"),
+    );
+    interpreter.define_native(
+        NativeFunction::new("sum", Arity::Exact(1), |_, arguments| {
+            numbers("sum", &arguments[0]).map(|numbers| Value::Number(numbers.into_iter().sum()))
+        })
+        .with_doc("Sum of an array of numbers, 0 for an empty one"),
+    );
+    interpreter.define_native(
+        NativeFunction::new("min", Arity::Range(1, 2), |_, arguments| {
+            extreme("min", arguments, |a, b| a < b)
+        })
+        .with_doc("Smallest number of an array, or the smaller of two numbers"),
+    );
+    interpreter.define_native(
+        NativeFunction::new("max", Arity::Range(1, 2), |_, arguments| {
+            extreme("max", arguments, |a, b| a > b)
+        })
+        .with_doc("Largest number of an array, or the larger of two numbers"),
+    );
+    interpreter.define_native(
+        NativeFunction::new("zip", Arity::Exact(2), |_, arguments| {
+            let first = elements("zip", &arguments[0])?;
+            let second = elements("zip", &arguments[1])?;
+
+            Ok(Value::Array(
+                first
+                    .into_iter()
+                    .zip(second)
+                    .map(|(a, b)| Value::Array(vec![a, b].into()))
+                    .collect(),
+            ))
+        })
+        .with_doc("Pairs of the elements at the same index of two arrays, up to the shorter one"),
+    );
+    interpreter.define_native(
+        NativeFunction::new("enumerate", Arity::Exact(1), |_, arguments| {
+            Ok(Value::Array(
+                elements("enumerate", &arguments[0])?
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, value)| {
+                        Value::Array(vec![Value::Number(index as f64), value].into())
+                    })
+                    .collect(),
+            ))
+        })
+        .with_doc("Pairs of the index and the element for each element of an array"),
+    );
+    interpreter.define_native(
+        NativeFunction::new("globals", Arity::Exact(0), |environment, _| {
+            Ok(reflect(&environment.environment[0]))
+        })
+        .with_doc("Describe the global variables"),
+    );
+    interpreter.define_native(
+        NativeFunction::new("locals", Arity::Exact(0), |environment, _| {
+            // Natives don't open a scope, so the innermost one is that of the caller.
+            let scope = environment
+                .environment
+                .last()
+                .expect("the globals are never popped");
+            Ok(reflect(scope))
+        })
+        .with_doc("Describe the variables of the innermost scope"),
+    );
+    interpreter.define_native(
+        NativeFunction::new("help", Arity::Range(0, 1), |environment, arguments| {
+            let text = match arguments.first() {
+                None => help(environment.builtins.values()),
+                Some(Value::String(name)) => help_for(environment, name)?,
+                Some(name) => return Err(expected("help", "string", name)),
+            };
+
+            write!(environment.output, "{}", text)
+                .map_err(|err| RuntimeError::new(format!("Failed to write output: {}", err)))?;
+
+            Ok(Value::Null)
+        })
+        .with_doc("List the natives, or describe the one named"),
+    );
+}
+
+// The natives among `builtins`, sorted by name, each on a line with its arity and description.
+fn help<'a>(builtins: impl Iterator<Item = &'a Variable>) -> String {
+    let mut natives = builtins
+        .filter_map(native)
+        .collect::<Vec<&NativeFunction>>();
+    natives.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let width = natives
+        .iter()
+        .map(|native| native.signature().len())
+        .max()
+        .unwrap_or(0);
+
+    natives
+        .iter()
+        .map(|native| match &native.doc {
+            Some(doc) => format!("{:width$}  {}\n", native.signature(), doc),
+            None => format!("{}\n", native.signature()),
+        })
+        .collect()
+}
+
+// The entry of the native `name`, or an error suggesting the natives named alike.
+fn help_for(environment: &Environment, name: &str) -> Result<String, RuntimeError> {
+    if let Some(variable) = environment
+        .builtins
+        .get(name)
+        .filter(|v| native(v).is_some())
+    {
+        return Ok(help(std::iter::once(variable)));
+    }
+
+    let mut similar = environment
+        .builtins
+        .values()
+        .filter_map(native)
+        .map(|native| (distance(name, &native.name), &native.name))
+        .filter(|(distance, _)| *distance <= MAX_SUGGESTION_DISTANCE)
+        .collect::<Vec<(usize, &String)>>();
+    similar.sort();
+
+    let mut message = format!("help: no native named '{}'", name);
+    if !similar.is_empty() {
+        let names = similar
+            .iter()
+            .take(3)
+            .map(|(_, other)| format!("'{}'", other))
+            .collect::<Vec<String>>();
+        message.push_str(&format!(", did you mean {}?", names.join(" or ")));
+    }
+
+    Err(RuntimeError::new(message))
+}
+
+fn native(variable: &Variable) -> Option<&NativeFunction> {
+    match &variable.value {
+        Some(Value::Function(Function::Native(native))) => Some(native),
+        _ => None,
+    }
+}
+
+// How many characters have to be inserted, removed or replaced to turn `a` into `b`.
+fn distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<char>>();
+    let mut previous = (0..=b.len()).collect::<Vec<usize>>();
+
+    for (i, chr) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, other) in b.iter().enumerate() {
+            let replaced = previous[j] + usize::from(chr != *other);
+            current.push(replaced.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+// Define the natives with access to the host, left out of sandboxed interpreters and of builds
+// without the `host` feature.
+#[cfg(feature = "host")]
+pub fn register_host(interpreter: &mut Interpreter) {
+    interpreter.define_native(
+        NativeFunction::new("env", Arity::Exact(1), |_, arguments| {
+            let name = env_name("env", &arguments[0])?;
+
+            Ok(env::var_os(name)
+                .map(|value| Value::String(value.to_string_lossy().into_owned().into()))
+                .unwrap_or(Value::Null))
+        })
+        .with_doc("Value of an environment variable, null if it isn't set"),
+    );
+    interpreter.define_native(
+        NativeFunction::new("setEnv", Arity::Exact(2), |_, arguments| {
+            let name = env_name("setEnv", &arguments[0])?;
+            let value = match &arguments[1] {
+                Value::String(value) if !value.contains('\0') => value,
+                Value::String(_) => {
+                    return Err(RuntimeError::new(
+                        "setEnv: value must not contain a null character".to_string(),
+                    ))
+                }
+                value => return Err(expected("setEnv", "string", value)),
+            };
+
+            env::set_var(name, &**value);
+
+            Ok(Value::Null)
+        })
+        .with_doc("Set an environment variable"),
+    );
+}
+
+pub fn args(args: Vec<String>) -> NativeFunction {
+    let args = Value::Array(args.into_iter().map(Value::from).collect());
+
+    NativeFunction::new("args", Arity::Exact(0), move |_, _| Ok(args.clone()))
+        .with_doc("The arguments given after the path of the script")
+}
+
+// `range(end)`, `range(start, end)` or `range(start, end, step)`, refusing to build arrays
+// longer than `limit`.
+pub fn range(limit: usize) -> NativeFunction {
+    NativeFunction::new("range", Arity::Range(1, 3), move |_, arguments| {
+        let mut bounds = Vec::with_capacity(arguments.len());
+
+        for argument in &arguments {
+            match argument {
+                Value::Number(num) if num.is_finite() => bounds.push(*num),
+                Value::Number(num) => {
+                    return Err(RuntimeError::new(format!(
+                        "range: bounds must be finite, found {}",
+                        number_to_string(*num)
+                    )))
+                }
+                argument => return Err(expected("range", "number", argument)),
+            }
+        }
+
+        let (start, end, step) = match bounds.as_slice() {
+            [end] => (0.0, *end, 1.0),
+            [start, end] => (*start, *end, 1.0),
+            [start, end, step] => (*start, *end, *step),
+            _ => unreachable!("arity is checked before calling"),
+        };
+
+        if step == 0.0 {
+            return Err(RuntimeError::new(
+                "range: step must not be zero".to_string(),
+            ));
+        }
+
+        let length = ((end - start) / step).ceil().max(0.0);
+
+        if length > limit as f64 {
+            return Err(RuntimeError::new(format!(
+                "range: {} elements exceed the limit of {}",
+                length, limit
+            )));
+        }
+
+        Ok(Value::Array(
+            (0..length as usize)
+                .map(|i| Value::Number(start + i as f64 * step))
+                .collect(),
+        ))
+    })
+    .with_doc("Numbers from the start up to the end, by the step")
+}
+
+// The `clone` method of `value`, giving a deep copy of it.
+pub fn clone_method(value: Value) -> NativeFunction {
+    NativeFunction::new("clone", Arity::Exact(0), move |_, _| {
+        value.deep_copy(MAX_CLONE_DEPTH).ok_or_else(|| {
+            RuntimeError::new(format!(
+                "clone: nesting exceeds the limit of {} levels",
+                MAX_CLONE_DEPTH
+            ))
+        })
+    })
+}
+
+// The `keys`, `values` and `entries` methods of `object`, giving arrays of its properties in
+// insertion order. Keys keep their type, so that each one reads its property back when used as
+// an index.
+pub fn object_method(object: Object, name: &str) -> Option<NativeFunction> {
+    let element: fn(Value, &Value) -> Value = match name {
+        "keys" => |key, _| key,
+        "values" => |_, value| value.clone(),
+        "entries" => |key, value| Value::Array(vec![key, value.clone()].into()),
+        _ => return None,
+    };
+
+    Some(NativeFunction::new(name, Arity::Exact(0), move |_, _| {
+        Ok(Value::Array(
+            object
+                .iter()
+                .map(|(key, value)| element(key.to_value(), value))
+                .collect(),
+        ))
+    }))
+}
+
+// The `padStart` and `padEnd` methods of `string`. `padStart(width, fill)` gives the string
+// after as much of `fill`, repeated, as makes it `width` characters long, a space by default.
+// Strings already as long are given as they are.
+pub fn string_method(string: Rc<str>, name: &str) -> Option<NativeFunction> {
+    let (native, at_start) = match name {
+        "padStart" => ("padStart", true),
+        "padEnd" => ("padEnd", false),
+        _ => return None,
+    };
+
+    Some(NativeFunction::new(
+        native,
+        Arity::Range(1, 2),
+        move |_, arguments| {
+            let width = match arguments[0] {
+                Value::Number(width) if width >= 0.0 && width.fract() == 0.0 => width as usize,
+                Value::Number(width) => {
+                    return Err(RuntimeError::new(format!(
+                        "{}: width must be a non-negative integer, found {}",
+                        native,
+                        number_to_string(width)
+                    )))
+                }
+                ref width => return Err(expected(native, "number", width)),
+            };
+            let fill = match arguments.get(1) {
+                None => " ",
+                Some(Value::String(fill)) if !fill.is_empty() => fill,
+                Some(Value::String(_)) => {
+                    return Err(RuntimeError::new(format!(
+                        "{}: fill must not be empty",
+                        native
+                    )))
+                }
+                Some(fill) => return Err(expected(native, "string", fill)),
+            };
+
+            let length = string.chars().count();
+            if length >= width {
+                return Ok(Value::String(string.clone()));
+            }
+
+            let padding = fill
+                .chars()
+                .cycle()
+                .take(width - length)
+                .collect::<String>();
+            Ok(Value::from(if at_start {
+                padding + &string
+            } else {
+                format!("{}{}", string, padding)
+            }))
+        },
+    ))
+}
+
+// The `flat`, `concat` and `sort` methods of `array`, which give a new array and leave `array` as
+// it is. `line` is where the method is read, which calls to the comparator of `sort` are recorded
+// as made from.
+pub fn array_method(array: Array, name: &str, line: u32) -> Option<NativeFunction> {
+    match name {
+        // `flat(depth)` replaces the arrays nested up to `depth` levels deep, one by default, with
+        // their elements.
+        "flat" => Some(NativeFunction::new(
+            "flat",
+            Arity::Range(0, 1),
+            move |_, arguments| {
+                let depth = match arguments.first() {
+                    None => 1.0,
+                    Some(Value::Number(depth))
+                        if *depth >= 0.0 && (depth.fract() == 0.0 || depth.is_infinite()) =>
+                    {
+                        *depth
+                    }
+                    Some(Value::Number(depth)) => {
+                        return Err(RuntimeError::new(format!(
+                            "flat: depth must be a non-negative integer, found {}",
+                            number_to_string(*depth)
+                        )))
+                    }
+                    Some(depth) => return Err(expected("flat", "number", depth)),
+                };
+
+                // One level at a time, so that deeply nested arrays don't need deep recursion.
+                let mut elements = array.to_vec();
+                let mut level = 0.0;
+                while level < depth
+                    && elements
+                        .iter()
+                        .any(|value| matches!(value, Value::Array(_)))
+                {
+                    elements = elements
+                        .into_iter()
+                        .flat_map(|value| match value {
+                            Value::Array(nested) => nested.into_vec(),
+                            value => vec![value],
+                        })
+                        .collect();
+                    level += 1.0;
+                }
+
+                Ok(Value::Array(elements.into()))
+            },
+        )),
+        // `concat(...arrays)` gives the elements of the array followed by those of each argument,
+        // which must all be arrays.
+        "concat" => Some(NativeFunction::new(
+            "concat",
+            Arity::AtLeast(0),
+            move |_, arguments| {
+                let mut elements = array.to_vec();
+                for argument in &arguments {
+                    match argument {
+                        Value::Array(other) => elements.extend(other.iter().cloned()),
+                        argument => return Err(expected("concat", "array", argument)),
+                    }
+                }
+
+                Ok(Value::Array(elements.into()))
+            },
+        )),
+        // `sort(comparator)` orders the elements by what `comparator(a, b)` gives: a negative
+        // number when `a` goes first, a positive one when `b` does, zero to keep their order.
+        // Without one, elements are ordered as `<` does.
+        "sort" => Some(NativeFunction::new(
+            "sort",
+            Arity::Range(0, 1),
+            move |environment, arguments| {
+                let comparator = match arguments.into_iter().next() {
+                    None => None,
+                    Some(Value::Function(function)) => Some(function),
+                    Some(comparator) => return Err(expected("sort", "function", &comparator)),
+                };
+
+                let mut compare = |a: &Value, b: &Value| match &comparator {
+                    Some(comparator) => compare_with(comparator, environment, a, b, line),
+                    None => a.partial_cmp(b).ok_or_else(|| {
+                        RuntimeError::new(format!("sort: can't order {:?} and {:?}", a, b))
+                    }),
+                };
+
+                merge_sort(array.to_vec(), &mut compare).map(|sorted| Value::Array(sorted.into()))
+            },
+        )),
+        _ => None,
+    }
+}
+
+// Compare `a` and `b` by calling `comparator` on them. Its errors are given as they are raised,
+// and the frame of sort in their trace tells which elements were being compared, except for the
+// errors about the limits of the program.
+fn compare_with(
+    comparator: &Function,
+    environment: &mut Environment,
+    a: &Value,
+    b: &Value,
+    line: u32,
+) -> Result<Ordering, RuntimeError> {
+    match comparator.call(environment, vec![a.clone(), b.clone()], line) {
+        Ok(Value::Number(num)) => Ok(num.partial_cmp(&0.0).unwrap_or(Ordering::Equal)),
+        Err(
+            err @ (RuntimeError::Budget { .. }
+            | RuntimeError::Timeout { .. }
+            | RuntimeError::CallDepthExceeded { .. }),
+        ) => Err(err),
+        Err(err) => {
+            environment.note_trace(format!("while comparing {:?} and {:?}", a, b));
+            Err(err)
+        }
+        Ok(value) => Err(RuntimeError::new(format!(
+            "sort: comparator must return a number, found {} comparing {:?} and {:?}",
+            value.type_name(),
+            a,
+            b
+        ))),
+    }
+}
+
+// Sort `values` keeping equal ones in order, stopping at the first comparison that fails. Unlike
+// `slice::sort_by`, it can't panic when the comparison isn't a consistent order, as one written
+// in NotJS may not be.
+fn merge_sort(
+    mut values: Vec<Value>,
+    compare: &mut impl FnMut(&Value, &Value) -> Result<Ordering, RuntimeError>,
+) -> Result<Vec<Value>, RuntimeError> {
+    if values.len() <= 1 {
+        return Ok(values);
+    }
+
+    let right = values.split_off(values.len() / 2);
+    let left = merge_sort(values, compare)?;
+    let mut right = merge_sort(right, compare)?.into_iter().peekable();
+
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    for value in left {
+        // Elements on the right only go first when they are strictly smaller, for stability.
+        while let Some(next) = right.peek() {
+            if compare(next, &value)? != Ordering::Less {
+                break;
+            }
+            merged.extend(right.next());
+        }
+        merged.push(value);
+    }
+    merged.extend(right);
+
+    Ok(merged)
+}
+
+// The `next` method of `generator`, resuming it from `line`. It gives an object whose `value` is
+// the value yielded, or the value the body returned once `done` is true.
+pub fn next_method(generator: Rc<Generator>, line: u32) -> NativeFunction {
+    NativeFunction::new("next", Arity::Exact(0), move |environment, _| {
+        let (value, done) = match generator.resume(environment, line)? {
+            Resumed::Yielded(value) => (value, false),
+            Resumed::Returned(value) => (value, true),
+        };
+
+        let mut result = Object::new();
+        result.insert("value".into(), value);
+        result.insert("done".into(), Value::Boolean(done));
+        Ok(Value::Object(result))
+    })
+}
+
+// Describe the variables of `scope`, sorted by name, as an object from each name to whether it is
+// mutable, the type of its value and the value itself. Variables declared without a value have
+// the type "undefined" and a null value.
+fn reflect(scope: &Scope) -> Value {
+    let mut names = scope.keys().collect::<Vec<&String>>();
+    names.sort();
+
+    let mut result = Object::new();
+    for name in names {
+        let variable = &scope[name];
+        let (type_name, value) = match &variable.value {
+            Some(value) => (value.type_name(), value.clone()),
+            None => ("undefined", Value::Null),
+        };
+
+        let mut entry = Object::new();
+        entry.insert("mutable".into(), Value::Boolean(variable.mutable));
+        entry.insert("type".into(), Value::from(type_name));
+        entry.insert("value".into(), value);
+        result.insert(name.as_str().into(), Value::Object(entry));
+    }
+
+    Value::Object(result)
+}
+
+// The numbers of an array given to `native`, failing at the first element that isn't one.
+fn numbers(native: &str, value: &Value) -> Result<Vec<f64>, RuntimeError> {
+    let Value::Array(array) = value else {
+        return Err(expected(native, "array", value));
+    };
+
+    array
+        .iter()
+        .enumerate()
+        .map(|(index, element)| match element {
+            Value::Number(num) => Ok(*num),
+            element => Err(RuntimeError::new(format!(
+                "{}: expected number at index {}, found {}",
+                native,
+                index,
+                element.type_name()
+            ))),
+        })
+        .collect()
+}
+
+// The element of an array of numbers that goes `before` the rest, or the one of two numbers that
+// does, the second one if neither does.
+fn extreme(
+    native: &str,
+    arguments: Vec<Value>,
+    before: fn(f64, f64) -> bool,
+) -> Result<Value, RuntimeError> {
+    if let [first, second] = &arguments[..] {
+        let (Value::Number(first), Value::Number(second)) = (first, second) else {
+            let value = if matches!(first, Value::Number(_)) {
+                second
+            } else {
+                first
+            };
+            return Err(expected(native, "number", value));
+        };
+        return Ok(Value::Number(if before(*first, *second) {
+            *first
+        } else {
+            *second
+        }));
+    }
+
+    numbers(native, &arguments[0])?
+        .into_iter()
+        .reduce(|found, num| if before(num, found) { num } else { found })
+        .map(Value::Number)
+        .ok_or_else(|| RuntimeError::new(format!("{}: expected a non-empty array", native)))
+}
+
+// The elements of an array or the characters of a string given to `native`.
+fn elements(native: &str, value: &Value) -> Result<Vec<Value>, RuntimeError> {
+    match value {
+        Value::Array(array) => Ok(array.to_vec()),
+        Value::String(string) => Ok(string
+            .chars()
+            .map(|chr| Value::String(chr.to_string().into()))
+            .collect()),
+        value => Err(expected(native, "array or string", value)),
+    }
+}
+
+fn expected(native: &str, type_name: &str, found: &Value) -> RuntimeError {
+    RuntimeError::new(format!(
+        "{}: expected {}, found {}",
+        native,
+        type_name,
+        found.type_name()
+    ))
+}
+
+#[cfg(feature = "host")]
+fn env_name<'a>(native: &str, name: &'a Value) -> Result<&'a str, RuntimeError> {
+    match name {
+        Value::String(name) if name.is_empty() || name.contains(['=', '\0']) => Err(
+            RuntimeError::new(format!("{}: invalid variable name '{}'", native, name)),
+        ),
+        Value::String(name) => Ok(name),
+        name => Err(expected(native, "string", name)),
+    }
+}
@@ -2,11 +2,16 @@ use crate::common::{
     token::{Token, TokenType, KEYWORDS},
     value::Value,
 };
-use std::{iter::Peekable, slice::Iter};
+use std::{iter::Peekable, ops::Range, slice::Iter};
 
 pub struct Scanner<'a> {
     source_iter: Peekable<Iter<'a, u8>>,
     line: u32,
+    // Whether comments are given as tokens rather than skipped.
+    comments: bool,
+    length: usize,
+    // Offset of the first byte of the token given last.
+    start: usize,
 }
 
 impl<'a> Scanner<'a> {
@@ -14,11 +19,35 @@ impl<'a> Scanner<'a> {
         Scanner {
             source_iter: source.iter().peekable(),
             line: 1,
+            comments: false,
+            length: source.len(),
+            start: 0,
+        }
+    }
+
+    /// The bytes of the source the token given last was read from, e.g. with its quotes for a
+    /// string.
+    pub fn span(&self) -> Range<usize> {
+        self.start..self.offset()
+    }
+
+    fn offset(&self) -> usize {
+        self.length - self.source_iter.len()
+    }
+
+    /// A scanner that gives each comment as a `Comment` token, holding the text of the comment
+    /// with its delimiters and the line it starts at, for tools that keep comments such as
+    /// `notjs fmt`. The parser skips them.
+    pub fn with_comments(source: &'a [u8]) -> Self {
+        Scanner {
+            comments: true,
+            ..Scanner::new(source)
         }
     }
 }
 
-fn skip_characters(scanner: &mut Scanner) {
+// Skip whitespace, semicolons and comments, giving false when the source ends inside a comment.
+fn skip_characters(scanner: &mut Scanner) -> bool {
     loop {
         match scanner.source_iter.peek() {
             // Newline
@@ -34,7 +63,31 @@ fn skip_characters(scanner: &mut Scanner) {
             Some(59) => {
                 scanner.source_iter.next();
             }
-            _ => break,
+            // Comments, skipped here rather than in `next` so that any number of them can follow
+            // each other without recursing.
+            Some(b'/') => {
+                let mut ahead = scanner.source_iter.clone();
+                ahead.next();
+
+                match ahead.peek() {
+                    // Left for `comment` to read.
+                    Some(b'/' | b'*') if scanner.comments => return true,
+                    Some(b'/') => {
+                        scanner.source_iter = ahead;
+                        scanner.source_iter.next();
+                        skip_single_line_comment(scanner);
+                    }
+                    Some(b'*') => {
+                        scanner.source_iter = ahead;
+                        scanner.source_iter.next();
+                        if !skip_multi_line_comment(scanner) {
+                            return false;
+                        }
+                    }
+                    _ => return true,
+                }
+            }
+            _ => return true,
         }
     }
 }
@@ -51,7 +104,8 @@ fn skip_single_line_comment(scanner: &mut Scanner) {
     }
 }
 
-fn skip_multi_line_comment(scanner: &mut Scanner) {
+// Skip a comment up to its end, giving false if it has none.
+fn skip_multi_line_comment(scanner: &mut Scanner) -> bool {
     let mut depth = 1;
     while let Some(chr) = scanner.source_iter.next() {
         match chr {
@@ -66,7 +120,7 @@ fn skip_multi_line_comment(scanner: &mut Scanner) {
                     scanner.source_iter.next();
                     depth -= 1;
                     if depth == 0 {
-                        break;
+                        return true;
                     }
                 }
             }
@@ -74,33 +128,84 @@ fn skip_multi_line_comment(scanner: &mut Scanner) {
             _ => (),
         }
     }
+
+    false
+}
+
+// Read the comment the source continues with, if any.
+fn comment(scanner: &mut Scanner) -> Option<Token> {
+    let start = scanner.source_iter.clone();
+    let line = scanner.line;
+
+    let mut ahead = start.clone();
+    if ahead.next() != Some(&b'/') {
+        return None;
+    }
+    let terminated = match ahead.next() {
+        Some(b'/') => {
+            // The new line is left out of the comment.
+            scanner.source_iter = ahead;
+            while scanner.source_iter.next_if(|&&chr| chr != b'\n').is_some() {}
+            true
+        }
+        Some(b'*') => {
+            scanner.source_iter = ahead;
+            skip_multi_line_comment(scanner)
+        }
+        _ => return None,
+    };
+
+    if !terminated {
+        return Some(Token::new(
+            TokenType::Error,
+            Value::String("unterminated comment".into()),
+            line,
+        ));
+    }
+
+    let length = start.len() - scanner.source_iter.len();
+    let text: Vec<u8> = start.take(length).copied().collect();
+    let text = String::from_utf8_lossy(&text);
+
+    Some(Token::new(
+        TokenType::Comment,
+        Value::String(text.trim_end().into()),
+        line,
+    ))
 }
 
 fn number(scanner: &mut Scanner, first_char: u8) -> Token {
     let mut temp = String::new();
     temp.push(first_char as char);
 
-    while let Some(b'0'..=b'9') = scanner.source_iter.peek() {
-        temp.push(*scanner.source_iter.next().unwrap() as char);
-    }
+    digits(scanner, &mut temp);
     if let Some(b'.') = scanner.source_iter.peek() {
-        temp.push(*scanner.source_iter.next().unwrap() as char);
+        scanner.source_iter.next();
+        temp.push('.');
     }
-    while let Some(b'0'..=b'9') = scanner.source_iter.peek() {
-        temp.push(*scanner.source_iter.next().unwrap() as char);
+    digits(scanner, &mut temp);
+
+    match temp.parse() {
+        Ok(num) => Token::new(TokenType::Number, Value::Number(num), scanner.line),
+        Err(_) => Token::new(TokenType::Error, Value::String(temp.into()), scanner.line),
+    }
+}
+
+fn digits(scanner: &mut Scanner, temp: &mut String) {
+    while let Some(&&digit @ b'0'..=b'9') = scanner.source_iter.peek() {
+        scanner.source_iter.next();
+        temp.push(digit as char);
     }
-    Token::new(
-        TokenType::Number,
-        Value::Number(temp.parse().unwrap()),
-        scanner.line,
-    )
 }
 
 fn string(scanner: &mut Scanner, first_char: u8) -> Token {
-    let mut str_value = String::new();
+    // Bytes are collected and decoded at the end, so characters outside of ASCII stay intact.
+    let mut bytes = Vec::new();
+    let mut closed = false;
 
     while let Some(chr) = scanner.source_iter.next() {
         if *chr == first_char {
+            closed = true;
             break;
         }
 
@@ -111,26 +216,24 @@ fn string(scanner: &mut Scanner, first_char: u8) -> Token {
         // Check for escape characters
         if *chr == b'\\' {
             match scanner.source_iter.next() {
-                Some(b'n') => str_value.push('\n'),
-                Some(b't') => str_value.push('\t'),
-                Some(b'\\') => str_value.push('\\'),
-                Some(b'\'') => str_value.push('\''),
-                Some(b'"') => str_value.push('"'),
-                Some(b'0') => str_value.push('\0'),
-                Some(b'r') => str_value.push('\r'),
+                Some(b'n') => bytes.push(b'\n'),
+                Some(b't') => bytes.push(b'\t'),
+                Some(b'\\') => bytes.push(b'\\'),
+                Some(b'\'') => bytes.push(b'\''),
+                Some(b'"') => bytes.push(b'"'),
+                Some(b'0') => bytes.push(b'\0'),
+                Some(b'r') => bytes.push(b'\r'),
                 Some(c) => {
-                    println!("Error: Invalid escape character: {}", *c as char);
                     return Token::new(
                         TokenType::Error,
-                        Value::String((*c as char).to_string()),
+                        Value::String(format!("invalid escape \\{}", *c as char).into()),
                         scanner.line,
                     );
                 }
                 None => {
-                    println!("Error: Unexpected end of file");
                     return Token::new(
                         TokenType::Error,
-                        Value::String("".to_string()),
+                        Value::String("unterminated string".into()),
                         scanner.line,
                     );
                 }
@@ -138,17 +241,36 @@ fn string(scanner: &mut Scanner, first_char: u8) -> Token {
             continue;
         }
 
-        str_value.push(*chr as char);
+        bytes.push(*chr);
     }
-    Token::new(TokenType::String, Value::String(str_value), scanner.line)
+
+    let str_value = String::from_utf8_lossy(&bytes).into_owned();
+    if !closed {
+        return Token::new(
+            TokenType::Error,
+            Value::String(
+                format!("unterminated string {}{}", first_char as char, str_value).into(),
+            ),
+            scanner.line,
+        );
+    }
+
+    Token::new(
+        TokenType::String,
+        Value::String(str_value.into()),
+        scanner.line,
+    )
 }
 
 fn identifier(scanner: &mut Scanner, first_char: u8) -> Token {
     let mut id = String::new();
     id.push(first_char as char);
 
-    while let Some(b'0'..=b'9' | b'a'..=b'z' | b'A'..=b'Z' | b'_') = scanner.source_iter.peek() {
-        id.push(*scanner.source_iter.next().unwrap() as char);
+    while let Some(&&chr @ (b'0'..=b'9' | b'a'..=b'z' | b'A'..=b'Z' | b'_')) =
+        scanner.source_iter.peek()
+    {
+        scanner.source_iter.next();
+        id.push(chr as char);
     }
 
     match KEYWORDS.get(id.as_str()) {
@@ -156,10 +278,18 @@ fn identifier(scanner: &mut Scanner, first_char: u8) -> Token {
             TokenType::True => Token::new(TokenType::True, Value::Boolean(true), scanner.line),
             TokenType::False => Token::new(TokenType::False, Value::Boolean(false), scanner.line),
             TokenType::Null => Token::new(TokenType::Null, Value::Null, scanner.line),
-            _ => Token::new(*token_type, Value::String(id), scanner.line),
+            TokenType::Number => {
+                let num = if id == "NaN" { f64::NAN } else { f64::INFINITY };
+                Token::new(TokenType::Number, Value::Number(num), scanner.line)
+            }
+            _ => Token::new(*token_type, Value::String(id.into()), scanner.line),
         },
 
-        None => Token::new(TokenType::Identifier, Value::String(id), scanner.line),
+        None => Token::new(
+            TokenType::Identifier,
+            Value::String(id.into()),
+            scanner.line,
+        ),
     }
 }
 
@@ -167,7 +297,21 @@ impl<'a> Iterator for Scanner<'a> {
     type Item = Token;
 
     fn next(&mut self) -> Option<Token> {
-        skip_characters(self);
+        self.start = self.offset();
+        if !skip_characters(self) {
+            return Some(Token::new(
+                TokenType::Error,
+                Value::String("unterminated comment".into()),
+                self.line,
+            ));
+        }
+
+        self.start = self.offset();
+        if self.comments {
+            if let Some(token) = comment(self) {
+                return Some(token);
+            }
+        }
 
         match self.source_iter.next() {
             Some(chr) => match chr {
@@ -185,63 +329,63 @@ impl<'a> Iterator for Scanner<'a> {
                 // # Logical operators
                 b'&' => Some(Token::new(
                     TokenType::And,
-                    Value::String("&".to_string()),
+                    Value::String("&".into()),
                     self.line,
                 )),
                 b'|' => Some(Token::new(
                     TokenType::Or,
-                    Value::String("|".to_string()),
+                    Value::String("|".into()),
                     self.line,
                 )),
                 // ## Punctuation
                 b'(' => Some(Token::new(
                     TokenType::LeftParentheses,
-                    Value::String("(".to_string()),
+                    Value::String("(".into()),
                     self.line,
                 )),
                 b')' => Some(Token::new(
                     TokenType::RightParentheses,
-                    Value::String(")".to_string()),
+                    Value::String(")".into()),
                     self.line,
                 )),
                 b'{' => Some(Token::new(
                     TokenType::LeftBrace,
-                    Value::String("{".to_string()),
+                    Value::String("{".into()),
                     self.line,
                 )),
                 b'}' => Some(Token::new(
                     TokenType::RightBrace,
-                    Value::String("}".to_string()),
+                    Value::String("}".into()),
                     self.line,
                 )),
                 b'[' => Some(Token::new(
                     TokenType::LeftBracket,
-                    Value::String("[".to_string()),
+                    Value::String("[".into()),
                     self.line,
                 )),
                 b']' => Some(Token::new(
                     TokenType::RightBracket,
-                    Value::String("]".to_string()),
+                    Value::String("]".into()),
                     self.line,
                 )),
                 b',' => Some(Token::new(
                     TokenType::Comma,
-                    Value::String(",".to_string()),
+                    Value::String(",".into()),
                     self.line,
                 )),
                 b'.' => Some(Token::new(
                     TokenType::Dot,
-                    Value::String(".".to_string()),
+                    Value::String(".".into()),
                     self.line,
                 )),
                 b'?' => Some(Token::new(
                     TokenType::QuestionMark,
-                    Value::String("?".to_string()),
+                    Value::String("?".into()),
                     self.line,
                 )),
                 b':' => Some(Token::new(
                     TokenType::Colon,
-                    Value::String(":".to_string()),
+                    Value::String(":".into()),
                     self.line,
                 )),
                 // ## One or Two character tokens
@@ -251,13 +395,13 @@ impl<'a> Iterator for Scanner<'a> {
                         self.source_iter.next();
                         Some(Token::new(
                             TokenType::PlusEqual,
-                            Value::String("+=".to_string()),
+                            Value::String("+=".into()),
                             self.line,
                         ))
                     }
                     _ => Some(Token::new(
                         TokenType::Plus,
-                        Value::String("+".to_string()),
+                        Value::String("+".into()),
                         self.line,
                     )),
                 },
@@ -266,13 +410,13 @@ impl<'a> Iterator for Scanner<'a> {
                         self.source_iter.next();
                         Some(Token::new(
                             TokenType::MinusEqual,
-                            Value::String("-=".to_string()),
+                            Value::String("-=".into()),
                             self.line,
                         ))
                     }
                     _ => Some(Token::new(
                         TokenType::Minus,
-                        Value::String("-".to_string()),
+                        Value::String("-".into()),
                         self.line,
                     )),
                 },
@@ -281,39 +425,28 @@ impl<'a> Iterator for Scanner<'a> {
                         self.source_iter.next();
                         Some(Token::new(
                             TokenType::StarEqual,
-                            Value::String("*=".to_string()),
+                            Value::String("*=".into()),
                             self.line,
                         ))
                     }
                     _ => Some(Token::new(
                         TokenType::Star,
-                        Value::String("*".to_string()),
+                        Value::String("*".into()),
                         self.line,
                     )),
                 },
                 b'/' => match self.source_iter.peek() {
-                    // Comments check
-                    Some(b'/') => {
-                        self.source_iter.next();
-                        skip_single_line_comment(self);
-                        self.next()
-                    }
-                    Some(b'*') => {
-                        self.source_iter.next();
-                        skip_multi_line_comment(self);
-                        self.next()
-                    }
                     Some(b'=') => {
                         self.source_iter.next();
                         Some(Token::new(
                             TokenType::SlashEqual,
-                            Value::String("/=".to_string()),
+                            Value::String("/=".into()),
                             self.line,
                         ))
                     }
                     _ => Some(Token::new(
                         TokenType::Slash,
-                        Value::String("/".to_string()),
+                        Value::String("/".into()),
                         self.line,
                     )),
                 },
@@ -321,31 +454,56 @@ impl<'a> Iterator for Scanner<'a> {
                 b'!' => {
                     if let Some(b'=') = self.source_iter.peek() {
                         self.source_iter.next();
-                        Some(Token::new(
-                            TokenType::BangEqual,
-                            Value::String("!=".to_string()),
-                            self.line,
-                        ))
+                        if let Some(b'=') = self.source_iter.peek() {
+                            self.source_iter.next();
+                            Some(Token::new(
+                                TokenType::BangEqualEqual,
+                                Value::String("!==".into()),
+                                self.line,
+                            ))
+                        } else {
+                            Some(Token::new(
+                                TokenType::BangEqual,
+                                Value::String("!=".into()),
+                                self.line,
+                            ))
+                        }
                     } else {
                         Some(Token::new(
                             TokenType::Bang,
-                            Value::String("!".to_string()),
+                            Value::String("!".into()),
                             self.line,
                         ))
                     }
                 }
                 b'=' => {
-                    if let Some(b'=') = self.source_iter.peek() {
+                    if let Some(b'>') = self.source_iter.peek() {
                         self.source_iter.next();
                         Some(Token::new(
-                            TokenType::EqualEqual,
-                            Value::String("==".to_string()),
+                            TokenType::Arrow,
+                            Value::String("=>".into()),
                             self.line,
                         ))
+                    } else if let Some(b'=') = self.source_iter.peek() {
+                        self.source_iter.next();
+                        if let Some(b'=') = self.source_iter.peek() {
+                            self.source_iter.next();
+                            Some(Token::new(
+                                TokenType::EqualEqualEqual,
+                                Value::String("===".into()),
+                                self.line,
+                            ))
+                        } else {
+                            Some(Token::new(
+                                TokenType::EqualEqual,
+                                Value::String("==".into()),
+                                self.line,
+                            ))
+                        }
                     } else {
                         Some(Token::new(
                             TokenType::Equal,
-                            Value::String("=".to_string()),
+                            Value::String("=".into()),
                             self.line,
                         ))
                     }
@@ -355,13 +513,13 @@ impl<'a> Iterator for Scanner<'a> {
                         self.source_iter.next();
                         Some(Token::new(
                             TokenType::GreaterEqual,
-                            Value::String(">=".to_string()),
+                            Value::String(">=".into()),
                             self.line,
                         ))
                     } else {
                         Some(Token::new(
                             TokenType::Greater,
-                            Value::String(">".to_string()),
+                            Value::String(">".into()),
                             self.line,
                         ))
                     }
@@ -371,31 +529,128 @@ impl<'a> Iterator for Scanner<'a> {
                         self.source_iter.next();
                         Some(Token::new(
                             TokenType::LessEqual,
-                            Value::String("<=".to_string()),
+                            Value::String("<=".into()),
                             self.line,
                         ))
                     } else {
                         Some(Token::new(
                             TokenType::Less,
-                            Value::String("<".to_string()),
+                            Value::String("<".into()),
                             self.line,
                         ))
                     }
                 }
-                _ => {
-                    println!("Error: Unexpected character: {}", *chr as char);
-                    Some(Token::new(
-                        TokenType::Error,
-                        Value::String((*chr as char).to_string()),
-                        self.line,
-                    ))
-                }
+                _ => Some(Token::new(
+                    TokenType::Error,
+                    Value::String((*chr as char).to_string().into()),
+                    self.line,
+                )),
             },
             None => None,
         }
     }
 }
 
+/// What a token is, for highlighting.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Category {
+    Keyword,
+    Identifier,
+    Number,
+    String,
+    Operator,
+    Comment,
+    Punctuation,
+    Error,
+}
+
+impl From<TokenType> for Category {
+    fn from(token_type: TokenType) -> Self {
+        match token_type {
+            TokenType::LeftParentheses
+            | TokenType::RightParentheses
+            | TokenType::LeftBrace
+            | TokenType::RightBrace
+            | TokenType::LeftBracket
+            | TokenType::RightBracket
+            | TokenType::Comma
+            | TokenType::Dot => Category::Punctuation,
+            TokenType::QuestionMark
+            | TokenType::Colon
+            | TokenType::Plus
+            | TokenType::PlusEqual
+            | TokenType::Minus
+            | TokenType::MinusEqual
+            | TokenType::Star
+            | TokenType::StarEqual
+            | TokenType::Slash
+            | TokenType::SlashEqual
+            | TokenType::Bang
+            | TokenType::BangEqual
+            | TokenType::BangEqualEqual
+            | TokenType::Equal
+            | TokenType::Arrow
+            | TokenType::EqualEqual
+            | TokenType::EqualEqualEqual
+            | TokenType::Greater
+            | TokenType::GreaterEqual
+            | TokenType::Less
+            | TokenType::LessEqual
+            | TokenType::And
+            | TokenType::Or => Category::Operator,
+            TokenType::Number => Category::Number,
+            TokenType::String => Category::String,
+            TokenType::Identifier => Category::Identifier,
+            TokenType::Function
+            | TokenType::Class
+            | TokenType::Interface
+            | TokenType::Implements
+            | TokenType::If
+            | TokenType::Else
+            | TokenType::Bool
+            | TokenType::True
+            | TokenType::False
+            | TokenType::Null
+            | TokenType::While
+            | TokenType::For
+            | TokenType::In
+            | TokenType::Return
+            | TokenType::Break
+            | TokenType::Continue
+            | TokenType::Print
+            | TokenType::Println
+            | TokenType::SelfTok
+            | TokenType::Let
+            | TokenType::Const
+            | TokenType::Import
+            | TokenType::Export
+            | TokenType::Delete
+            | TokenType::Enum
+            | TokenType::Match
+            | TokenType::Yield
+            | TokenType::Defer
+            | TokenType::Test
+            | TokenType::Assert => Category::Keyword,
+            TokenType::Comment => Category::Comment,
+            TokenType::Error => Category::Error,
+        }
+    }
+}
+
+/// The byte range of each token and comment of `source`, in order, with what it is. Whitespace
+/// and semicolons are left out. A broken token, such as an unterminated string, is an `Error`
+/// and the tokens after it are still classified.
+pub fn highlight(source: &[u8]) -> Vec<(Range<usize>, Category)> {
+    let mut scanner = Scanner::with_comments(source);
+    let mut spans = Vec::new();
+
+    while let Some(token) = scanner.next() {
+        spans.push((scanner.span(), Category::from(token.token_type)));
+    }
+
+    spans
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -406,26 +661,18 @@ mod tests {
         let source = b"+-*/(){}[],.";
         let mut lexer = Scanner::new(source);
         let expected_tokens = vec![
-            Token::new(TokenType::Plus, Value::String("+".to_string()), 1),
-            Token::new(TokenType::Minus, Value::String("-".to_string()), 1),
-            Token::new(TokenType::Star, Value::String("*".to_string()), 1),
-            Token::new(TokenType::Slash, Value::String("/".to_string()), 1),
-            Token::new(
-                TokenType::LeftParentheses,
-                Value::String("(".to_string()),
-                1,
-            ),
-            Token::new(
-                TokenType::RightParentheses,
-                Value::String(")".to_string()),
-                1,
-            ),
-            Token::new(TokenType::LeftBrace, Value::String("{".to_string()), 1),
-            Token::new(TokenType::RightBrace, Value::String("}".to_string()), 1),
-            Token::new(TokenType::LeftBracket, Value::String("[".to_string()), 1),
-            Token::new(TokenType::RightBracket, Value::String("]".to_string()), 1),
-            Token::new(TokenType::Comma, Value::String(",".to_string()), 1),
-            Token::new(TokenType::Dot, Value::String(".".to_string()), 1),
+            Token::new(TokenType::Plus, Value::String("+".into()), 1),
+            Token::new(TokenType::Minus, Value::String("-".into()), 1),
+            Token::new(TokenType::Star, Value::String("*".into()), 1),
+            Token::new(TokenType::Slash, Value::String("/".into()), 1),
+            Token::new(TokenType::LeftParentheses, Value::String("(".into()), 1),
+            Token::new(TokenType::RightParentheses, Value::String(")".into()), 1),
+            Token::new(TokenType::LeftBrace, Value::String("{".into()), 1),
+            Token::new(TokenType::RightBrace, Value::String("}".into()), 1),
+            Token::new(TokenType::LeftBracket, Value::String("[".into()), 1),
+            Token::new(TokenType::RightBracket, Value::String("]".into()), 1),
+            Token::new(TokenType::Comma, Value::String(",".into()), 1),
+            Token::new(TokenType::Dot, Value::String(".".into()), 1),
         ];
         for expected_token in expected_tokens {
             assert_eq!(lexer.next(), Some(expected_token));
@@ -438,6 +685,85 @@ mod tests {
         let source = b"/* This is a multi-line comment */ // This is a single-line comment\n";
         let mut lexer = Scanner::new(source);
         assert_eq!(lexer.next(), None);
+
+        // Comments follow each other without recursing.
+        let source = "// comment\n".repeat(100_000) + "/* a */ 1";
+        let mut lexer = Scanner::new(source.as_bytes());
+        assert_eq!(
+            lexer.next(),
+            Some(Token::new(TokenType::Number, Value::Number(1.0), 100_001))
+        );
+    }
+
+    #[test]
+    fn test_lexing_kept_comments() {
+        let source = b"// first\nlet /* a\nb */ x = 1 / 2 // last";
+        let tokens: Vec<(TokenType, String, u32)> = Scanner::with_comments(source)
+            .map(|token| (token.token_type, token.value.to_string(), token.line))
+            .collect();
+
+        assert_eq!(
+            tokens,
+            [
+                (TokenType::Comment, "// first".to_string(), 1),
+                (TokenType::Let, "let".to_string(), 2),
+                (TokenType::Comment, "/* a\nb */".to_string(), 2),
+                (TokenType::Identifier, "x".to_string(), 3),
+                (TokenType::Equal, "=".to_string(), 3),
+                (TokenType::Number, "1".to_string(), 3),
+                (TokenType::Slash, "/".to_string(), 3),
+                (TokenType::Number, "2".to_string(), 3),
+                (TokenType::Comment, "// last".to_string(), 3),
+            ]
+        );
+
+        let token = Scanner::with_comments(b"/* a /* b */").next().unwrap();
+        assert_eq!(token.token_type, TokenType::Error);
+    }
+
+    #[test]
+    fn test_highlight() {
+        let source = "let s = \"a\\\"b\" // note\nif (x >= 1.5) print s[0]";
+        let spans = highlight(source.as_bytes());
+        let text = |index: usize| (&source[spans[index].0.clone()], spans[index].1);
+
+        assert_eq!(text(0), ("let", Category::Keyword));
+        assert_eq!(text(1), ("s", Category::Identifier));
+        assert_eq!(text(2), ("=", Category::Operator));
+        assert_eq!(text(3), ("\"a\\\"b\"", Category::String));
+        assert_eq!(spans[3].0, 8..14);
+        assert_eq!(text(4), ("// note", Category::Comment));
+        assert_eq!(text(5), ("if", Category::Keyword));
+        assert_eq!(text(6), ("(", Category::Punctuation));
+        assert_eq!(text(8), (">=", Category::Operator));
+        assert_eq!(text(9), ("1.5", Category::Number));
+        assert_eq!(spans.len(), 16);
+
+        let spans = highlight(b"print \"open");
+        assert_eq!(spans, [(0..5, Category::Keyword), (6..11, Category::Error)]);
+        let spans = highlight(b"x /* open");
+        assert_eq!(
+            spans,
+            [(0..1, Category::Identifier), (2..9, Category::Error)]
+        );
+    }
+
+    #[test]
+    fn test_lexing_unterminated() {
+        for source in [&b"/* comment"[..], b"/* a /* b */", b"\"text", b"'text\""] {
+            let token = Scanner::new(source).next().unwrap();
+            assert_eq!(token.token_type, TokenType::Error);
+        }
+    }
+
+    #[test]
+    fn test_lexing_invalid_escape() {
+        let token = Scanner::new(b"\"a\\qb\"").next().unwrap();
+        assert_eq!(token.token_type, TokenType::Error);
+        assert_eq!(token.value, Value::String("invalid escape \\q".into()));
+
+        let token = Scanner::new(b"\"a\\").next().unwrap();
+        assert_eq!(token.value, Value::String("unterminated string".into()));
     }
 
     #[test]
@@ -456,17 +782,20 @@ mod tests {
 
     #[test]
     fn test_lexing_operators() {
-        let source = b"! != = == > >= < <=";
+        let source = b"! != = == > >= < <= === !== =>";
         let mut lexer = Scanner::new(source);
         let expected_tokens = vec![
-            Token::new(TokenType::Bang, Value::String("!".to_string()), 1),
-            Token::new(TokenType::BangEqual, Value::String("!=".to_string()), 1),
-            Token::new(TokenType::Equal, Value::String("=".to_string()), 1),
-            Token::new(TokenType::EqualEqual, Value::String("==".to_string()), 1),
-            Token::new(TokenType::Greater, Value::String(">".to_string()), 1),
-            Token::new(TokenType::GreaterEqual, Value::String(">=".to_string()), 1),
-            Token::new(TokenType::Less, Value::String("<".to_string()), 1),
-            Token::new(TokenType::LessEqual, Value::String("<=".to_string()), 1),
+            Token::new(TokenType::Bang, Value::String("!".into()), 1),
+            Token::new(TokenType::BangEqual, Value::String("!=".into()), 1),
+            Token::new(TokenType::Equal, Value::String("=".into()), 1),
+            Token::new(TokenType::EqualEqual, Value::String("==".into()), 1),
+            Token::new(TokenType::Greater, Value::String(">".into()), 1),
+            Token::new(TokenType::GreaterEqual, Value::String(">=".into()), 1),
+            Token::new(TokenType::Less, Value::String("<".into()), 1),
+            Token::new(TokenType::LessEqual, Value::String("<=".into()), 1),
+            Token::new(TokenType::EqualEqualEqual, Value::String("===".into()), 1),
+            Token::new(TokenType::BangEqualEqual, Value::String("!==".into()), 1),
+            Token::new(TokenType::Arrow, Value::String("=>".into()), 1),
         ];
         for expected_token in expected_tokens {
             assert_eq!(lexer.next(), Some(expected_token));
@@ -479,8 +808,8 @@ mod tests {
         let source = b"& |";
         let mut lexer = Scanner::new(source);
         let expected_tokens = vec![
-            Token::new(TokenType::And, Value::String("&".to_string()), 1),
-            Token::new(TokenType::Or, Value::String("|".to_string()), 1),
+            Token::new(TokenType::And, Value::String("&".into()), 1),
+            Token::new(TokenType::Or, Value::String("|".into()), 1),
         ];
         for expected_token in expected_tokens {
             assert_eq!(lexer.next(), Some(expected_token));
@@ -493,16 +822,8 @@ mod tests {
         let source = b"\"Hello, world!\" 'Hello, world!'";
         let mut lexer = Scanner::new(source);
         let expected_tokens = vec![
-            Token::new(
-                TokenType::String,
-                Value::String(String::from("Hello, world!")),
-                1,
-            ),
-            Token::new(
-                TokenType::String,
-                Value::String(String::from("Hello, world!")),
-                1,
-            ),
+            Token::new(TokenType::String, Value::String("Hello, world!".into()), 1),
+            Token::new(TokenType::String, Value::String("Hello, world!".into()), 1),
         ];
         for expected_token in expected_tokens {
             assert_eq!(lexer.next(), Some(expected_token));
@@ -510,46 +831,46 @@ mod tests {
         assert_eq!(lexer.next(), None);
     }
 
+    #[test]
+    fn test_lexing_non_ascii_strings() {
+        let source = "\"€ ñ 😀\"".as_bytes();
+        let mut lexer = Scanner::new(source);
+        assert_eq!(
+            lexer.next(),
+            Some(Token::new(
+                TokenType::String,
+                Value::String("€ ñ 😀".into()),
+                1,
+            ))
+        );
+        assert_eq!(lexer.next(), None);
+    }
+
     #[test]
     fn test_lexing_keywords() {
-        let source = b"function class interface implements if else bool true false null while for return break continue print self let const";
+        let source = b"function class interface implements if else bool true false null while for in return break continue print self let const";
         let mut lexer = Scanner::new(source);
         let expected_tokens = vec![
-            Token::new(
-                TokenType::Function,
-                Value::String(String::from("function")),
-                1,
-            ),
-            Token::new(TokenType::Class, Value::String(String::from("class")), 1),
-            Token::new(
-                TokenType::Interface,
-                Value::String(String::from("interface")),
-                1,
-            ),
-            Token::new(
-                TokenType::Implements,
-                Value::String(String::from("implements")),
-                1,
-            ),
-            Token::new(TokenType::If, Value::String(String::from("if")), 1),
-            Token::new(TokenType::Else, Value::String(String::from("else")), 1),
-            Token::new(TokenType::Bool, Value::String(String::from("bool")), 1),
+            Token::new(TokenType::Function, Value::String("function".into()), 1),
+            Token::new(TokenType::Class, Value::String("class".into()), 1),
+            Token::new(TokenType::Interface, Value::String("interface".into()), 1),
+            Token::new(TokenType::Implements, Value::String("implements".into()), 1),
+            Token::new(TokenType::If, Value::String("if".into()), 1),
+            Token::new(TokenType::Else, Value::String("else".into()), 1),
+            Token::new(TokenType::Bool, Value::String("bool".into()), 1),
             Token::new(TokenType::True, Value::Boolean(true), 1),
             Token::new(TokenType::False, Value::Boolean(false), 1),
             Token::new(TokenType::Null, Value::Null, 1),
-            Token::new(TokenType::While, Value::String(String::from("while")), 1),
-            Token::new(TokenType::For, Value::String(String::from("for")), 1),
-            Token::new(TokenType::Return, Value::String(String::from("return")), 1),
-            Token::new(TokenType::Break, Value::String(String::from("break")), 1),
-            Token::new(
-                TokenType::Continue,
-                Value::String(String::from("continue")),
-                1,
-            ),
-            Token::new(TokenType::Print, Value::String(String::from("print")), 1),
-            Token::new(TokenType::SelfTok, Value::String(String::from("self")), 1),
-            Token::new(TokenType::Let, Value::String(String::from("let")), 1),
-            Token::new(TokenType::Const, Value::String(String::from("const")), 1),
+            Token::new(TokenType::While, Value::String("while".into()), 1),
+            Token::new(TokenType::For, Value::String("for".into()), 1),
+            Token::new(TokenType::In, Value::String("in".into()), 1),
+            Token::new(TokenType::Return, Value::String("return".into()), 1),
+            Token::new(TokenType::Break, Value::String("break".into()), 1),
+            Token::new(TokenType::Continue, Value::String("continue".into()), 1),
+            Token::new(TokenType::Print, Value::String("print".into()), 1),
+            Token::new(TokenType::SelfTok, Value::String("self".into()), 1),
+            Token::new(TokenType::Let, Value::String("let".into()), 1),
+            Token::new(TokenType::Const, Value::String("const".into()), 1),
         ];
         for expected_token in expected_tokens {
             assert_eq!(lexer.next(), Some(expected_token));
@@ -562,9 +883,9 @@ mod tests {
         let source = b"foo bar baz";
         let mut lexer = Scanner::new(source);
         let expected_tokens = vec![
-            Token::new(TokenType::Identifier, Value::String(String::from("foo")), 1),
-            Token::new(TokenType::Identifier, Value::String(String::from("bar")), 1),
-            Token::new(TokenType::Identifier, Value::String(String::from("baz")), 1),
+            Token::new(TokenType::Identifier, Value::String("foo".into()), 1),
+            Token::new(TokenType::Identifier, Value::String("bar".into()), 1),
+            Token::new(TokenType::Identifier, Value::String("baz".into()), 1),
         ];
         for expected_token in expected_tokens {
             assert_eq!(lexer.next(), Some(expected_token));
@@ -578,12 +899,12 @@ mod tests {
         let mut lexer = Scanner::new(source);
         let expected_tokens = vec![
             Token::new(TokenType::Number, Value::Number(123.0), 1),
-            Token::new(TokenType::Plus, Value::String("+".to_string()), 1),
+            Token::new(TokenType::Plus, Value::String("+".into()), 1),
             Token::new(TokenType::Number, Value::Number(456.789), 1),
-            Token::new(TokenType::Minus, Value::String("-".to_string()), 1),
+            Token::new(TokenType::Minus, Value::String("-".into()), 1),
             Token::new(TokenType::Number, Value::Number(0.1), 1),
-            Token::new(TokenType::Star, Value::String("*".to_string()), 1),
-            Token::new(TokenType::Slash, Value::String("/".to_string()), 1),
+            Token::new(TokenType::Star, Value::String("*".into()), 1),
+            Token::new(TokenType::Slash, Value::String("/".into()), 1),
             Token::new(TokenType::Number, Value::Number(0.2), 1),
         ];
         for expected_token in expected_tokens {
@@ -630,11 +951,7 @@ mod tests {
         }
         assert_eq!(
             lexer.next(),
-            Some(Token::new(
-                TokenType::Error,
-                Value::String("^".to_string()),
-                1
-            ))
+            Some(Token::new(TokenType::Error, Value::String("^".into()), 1))
         );
         assert_eq!(lexer.next(), None);
     }
@@ -0,0 +1,47 @@
+//! NotJS, an interpreted language inspired by JavaScript.
+//!
+//! The [`Interpreter`] is the entry point for running programs from Rust: it parses a source,
+//! runs it, and gives back the [`Value`] of the last statement executed or a [`NotJsError`]
+//! telling whether the program failed to parse or failed while running.
+//!
+//! ```
+//! use notjs::{Interpreter, NotJsError, Value};
+//!
+//! let mut interpreter = Interpreter::new();
+//! interpreter.bind("double", |x: f64| x * 2.0);
+//!
+//! let value = interpreter.interpret(b"let x = double(21) x").unwrap();
+//! assert_eq!(value, Value::Number(42.0));
+//!
+//! // Globals are kept between runs.
+//! assert_eq!(interpreter.interpret(b"x + 1").unwrap(), Value::from(43.0));
+//!
+//! match interpreter.interpret(b"x(1)") {
+//!     Err(NotJsError::Runtime(error)) => assert_eq!(error.error.line(), Some(1)),
+//!     other => panic!("expected a runtime error, got {:?}", other),
+//! }
+//! ```
+//!
+//! The [`lexer`] and [`parser`] can also be used on their own, e.g. by tools that only need the
+//! tokens or the syntax tree of a program.
+//!
+//! # Features
+//!
+//! - `host` (default): the `env` and `setEnv` natives, and imports from the file system.
+//! - `wasm`: a `run` function exported to JavaScript with `wasm-bindgen`, see `wasm::run`.
+//! - `ffi`: functions for C hosts, declared in `include/notjs.h`.
+
+pub mod common;
+pub mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod interpreter;
+pub mod lexer;
+mod optimizer;
+pub mod parser;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use common::value::Value;
+pub use error::{NotJsError, NotJsResult};
+pub use interpreter::Interpreter;
@@ -1,120 +1,2107 @@
 use std::{
-    env,
-    fs::File,
-    io::{stdin, stdout, BufReader, Read, Write},
+    env, fmt,
+    fs::{self, File},
+    io::{self, stderr, stdin, stdout, BufRead, BufReader, IsTerminal, Read, Write},
+    path::PathBuf,
+    process,
+    time::{Duration, SystemTime},
 };
 
-use crate::error::generic::GenericResult;
-use crate::interpreter::interpret;
+use notjs::error::{
+    codes,
+    diagnostic::{Diagnostic, Severity},
+    generic::GenericResult,
+    parse::ParseError,
+    NotJsError, NotJsResult,
+};
+use notjs::{
+    common::{
+        coverage::Coverage,
+        debug::{DebugHook, Pause, Resume},
+        dot,
+        environment::EnvSnapshot,
+        source,
+        statements::Statement,
+        token::TokenType,
+    },
+    interpreter::{ResultKind, TestResult},
+    lexer::Scanner,
+    Interpreter, Value,
+};
 
-mod common;
-mod error;
-mod interpreter;
-mod lexer;
-mod parser;
+mod watch;
+
+use watch::{Changes, Poll};
 
 type Result<T> = GenericResult<T>;
 
-fn cli() -> Result<()> {
+const USAGE: &str = "Usage: notjs [OPTIONS] [FILE...] [SCRIPT ARGS...]
+       notjs [OPTIONS] -e PROGRAM... [SCRIPT ARGS...]
+       notjs [OPTIONS] check [--json] FILE
+       notjs [OPTIONS] fmt [--check] FILE
+       notjs [OPTIONS] test FILE";
+
+const HELP: &str = "Runs NotJS programs, or an interactive prompt without a FILE.
+
+Options:
+  -e, --eval <program>  Run the program given instead of a FILE, can be repeated
+  --timeout <duration>  Stop the program after a time, e.g. 500ms, 5s or 2m
+  --color <when>        Color errors: auto (on a terminal, unless NO_COLOR is set), always or
+                        never
+  --no-color            Same as --color=never
+  --load <file>         Run a file before starting the prompt, keeping its definitions
+  --watch               Run the FILEs again whenever they or the files they import change
+  --no-prelude          Leave out the helpers written in NotJS
+  --typecheck           Check the types of the program before running or checking it
+  --trace               Write each statement and call to stderr as it runs
+  --profile             Write the time spent in each function to stderr
+  --coverage            Write the lines that ran to stderr
+  --ast                 Print the syntax tree of the program without running it
+  --tokens              Print the tokens of the program without running it
+  --dev                 Start a prompt showing the syntax tree of each line, see `:help`
+  --debug               Step through the program, see `s`, `c`, `p`, `vars` and `b`
+  --ast-dot             Print the syntax tree as a Graphviz graph without running
+  -h, --help            Print this help
+  --explain <code>      Describe an error code, such as E010
+  -V, --version         Print the version
+
+Commands:
+  check [--json]  Report the problems in FILE without running it
+  fmt [--check]   Rewrite FILE in its canonical form
+  test            Run FILE, then each of its test blocks, and report which failed
+
+A FILE of `-` reads the program from stdin, as does running without a FILE with stdin not a
+terminal. Several FILEs run in order in one environment, so that each sees what the ones before
+it defined. The arguments after the last FILE are given to the program through args(). Use `--`
+before a FILE starting with a dash.
+
+Exits with 65 if the program doesn't parse, 70 if it fails while running and 66 if a FILE can't
+be found.";
+
+// What to do with the file given, `Run` by default.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum Mode {
+    #[default]
+    Run,
+    Dev,
+    Debug,
+    AstDot,
+}
+
+// When to color errors, given with `--color`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum Color {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+// Command line options given before the file.
+#[derive(Debug, Default, PartialEq)]
+struct Options {
+    timeout: Option<Duration>,
+    no_prelude: bool,
+    // Check the types of the programs before running or checking them.
+    typecheck: bool,
+    trace: bool,
+    profile: bool,
+    coverage: bool,
+    // Print the syntax tree or the tokens instead of running, or as well in the dev prompt.
+    ast: bool,
+    tokens: bool,
+    color: Color,
+    // Run the files again whenever they change.
+    watch: bool,
+    // Files run at the start of the prompt.
+    load: Vec<String>,
+    mode: Mode,
+}
+
+// What the command line asks for.
+#[derive(Debug, PartialEq)]
+enum Command {
+    Help,
+    Version,
+    // The description of an error code.
+    Explain {
+        code: String,
+    },
+    // The interactive prompt, showing the syntax tree of each line in dev mode.
+    Repl,
+    Run {
+        paths: Vec<String>,
+        args: Vec<String>,
+    },
+    Eval {
+        snippets: Vec<String>,
+        args: Vec<String>,
+    },
+    Check {
+        path: String,
+        json: bool,
+    },
+    Fmt {
+        path: String,
+        check: bool,
+    },
+    Test {
+        path: String,
+    },
+}
+
+impl Options {
+    fn interpreter(&self) -> Interpreter {
+        let interpreter = if self.trace {
+            // The trace shows the statements as written.
+            let mut interpreter = self.unoptimized_interpreter();
+            interpreter.set_trace(true);
+            interpreter
+        } else {
+            self.unoptimized_interpreter().with_optimizer()
+        };
+
+        match self.timeout {
+            Some(timeout) => interpreter.with_timeout(timeout),
+            None => interpreter,
+        }
+    }
+
+    // An interpreter whose parsed programs are kept as written, for showing them.
+    fn unoptimized_interpreter(&self) -> Interpreter {
+        if self.no_prelude {
+            Interpreter::bare()
+        } else {
+            Interpreter::new()
+        }
+    }
+}
+
+// Parse durations such as `5s`, `500ms` or `2m`. A bare number is taken as seconds.
+fn parse_duration(text: &str) -> Option<Duration> {
+    let split = text
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(text.len());
+    let (amount, unit) = text.split_at(split);
+    let amount: f64 = amount.parse().ok()?;
+
+    let seconds = match unit {
+        "ms" => amount / 1000.0,
+        "" | "s" => amount,
+        "m" => amount * 60.0,
+        _ => return None,
+    };
+
+    Duration::try_from_secs_f64(seconds).ok()
+}
+
+fn parse_color(value: &str) -> std::result::Result<Color, String> {
+    match value {
+        "auto" => Ok(Color::Auto),
+        "always" => Ok(Color::Always),
+        "never" => Ok(Color::Never),
+        _ => Err(format!(
+            "Invalid color: {}, expected auto, always or never",
+            value
+        )),
+    }
+}
+
+// Set the mode, which can only be given once.
+fn set_mode(options: &mut Options, mode: Mode) -> std::result::Result<(), String> {
+    if options.mode != Mode::Run && options.mode != mode {
+        return Err("Only one of --dev, --debug and --ast-dot can be given".to_string());
+    }
+
+    options.mode = mode;
+    Ok(())
+}
+
+// The options and the file of a subcommand, which takes a single flag.
+fn parse_subcommand<'a>(
+    name: &str,
+    flag: &str,
+    args: &'a [String],
+) -> std::result::Result<(bool, &'a String), String> {
+    match args {
+        [path] if !path.starts_with('-') || path == STDIN => Ok((false, path)),
+        [given, path] if given == flag => Ok((true, path)),
+        [] => Err(format!("{} expects a file", name)),
+        _ => Err(format!("Usage: notjs {} [{}] FILE", name, flag)),
+    }
+}
+
+// Parse the arguments given to the binary, without the name of the binary. Options come before
+// the file, and everything after it is given to the script.
+fn parse_args(mut args: &[String]) -> std::result::Result<(Options, Command), String> {
+    let mut options = Options::default();
+    let mut snippets = Vec::new();
+    // Whether the mode was given as `-dev`, which runs a file after printing its syntax tree.
+    let mut legacy_dev = false;
+
+    while let [flag, rest @ ..] = args {
+        if !flag.starts_with('-') || flag == STDIN {
+            break;
+        }
+        args = rest;
+
+        match flag.as_str() {
+            "-h" | "--help" => return Ok((options, Command::Help)),
+            "-V" | "--version" => return Ok((options, Command::Version)),
+            "--explain" => {
+                let [code, ..] = args else {
+                    return Err("--explain expects an error code, such as E010".to_string());
+                };
+                return Ok((options, Command::Explain { code: code.clone() }));
+            }
+            "--timeout" => {
+                let [value, rest @ ..] = args else {
+                    return Err("--timeout expects a duration".to_string());
+                };
+                options.timeout =
+                    Some(parse_duration(value).ok_or(format!("Invalid duration: {}", value))?);
+                args = rest;
+            }
+            "-e" | "--eval" => {
+                let [snippet, rest @ ..] = args else {
+                    return Err(format!("{} expects a program", flag));
+                };
+                snippets.push(snippet.clone());
+                args = rest;
+            }
+            "--color" => {
+                let [value, rest @ ..] = args else {
+                    return Err("--color expects auto, always or never".to_string());
+                };
+                options.color = parse_color(value)?;
+                args = rest;
+            }
+            _ if flag.starts_with("--color=") => {
+                options.color = parse_color(&flag["--color=".len()..])?;
+            }
+            "--no-color" => options.color = Color::Never,
+            "--watch" => options.watch = true,
+            "--load" => {
+                let [path, rest @ ..] = args else {
+                    return Err("--load expects a file".to_string());
+                };
+                options.load.push(path.clone());
+                args = rest;
+            }
+            "--no-prelude" => options.no_prelude = true,
+            "--typecheck" => options.typecheck = true,
+            "--trace" => options.trace = true,
+            "--profile" => options.profile = true,
+            "--coverage" => options.coverage = true,
+            "--ast" => options.ast = true,
+            "--tokens" => options.tokens = true,
+            "--dev" => set_mode(&mut options, Mode::Dev)?,
+            "-dev" => {
+                legacy_dev = true;
+                set_mode(&mut options, Mode::Dev)?;
+            }
+            "--debug" => set_mode(&mut options, Mode::Debug)?,
+            "--ast-dot" => set_mode(&mut options, Mode::AstDot)?,
+            "--" => break,
+            _ => return Err(format!("Unknown option: {}", flag)),
+        }
+    }
+
+    let dumps = options.ast || options.tokens;
+    if dumps && matches!(options.mode, Mode::Debug | Mode::AstDot) {
+        return Err("--ast and --tokens cannot be used with --debug or --ast-dot".to_string());
+    }
+    if dumps && options.mode != Mode::Dev && (options.trace || options.profile || options.coverage)
+    {
+        return Err(
+            "--ast and --tokens don't run the program, so --trace, --profile and --coverage \
+             cannot be used with them"
+                .to_string(),
+        );
+    }
+
+    if options.watch && (options.mode != Mode::Run || dumps || !snippets.is_empty()) {
+        return Err("--watch only runs files".to_string());
+    }
+
+    if options.typecheck && (dumps || matches!(options.mode, Mode::Dev | Mode::AstDot)) {
+        return Err("--typecheck only applies to running or checking programs".to_string());
+    }
+
+    if !options.load.is_empty()
+        && (!args.is_empty() || !snippets.is_empty() || options.mode != Mode::Run || dumps)
+    {
+        return Err("--load only applies to the prompt, without a FILE".to_string());
+    }
+
+    // The snippets are the program, so everything left is given to it.
+    if !snippets.is_empty() {
+        if options.mode != Mode::Run {
+            return Err("-e cannot be used with --dev, --debug or --ast-dot".to_string());
+        }
+        return Ok((
+            options,
+            Command::Eval {
+                snippets,
+                args: args.to_vec(),
+            },
+        ));
+    }
+
+    let command = match args {
+        [] => match options.mode {
+            Mode::Run if dumps => return Err("--ast and --tokens expect a file".to_string()),
+            Mode::Run if options.typecheck => {
+                return Err("--typecheck expects a file or -e".to_string())
+            }
+            Mode::Run if options.watch => return Err("--watch expects a file".to_string()),
+            Mode::Run | Mode::Dev => Command::Repl,
+            _ => return Err("--debug and --ast-dot expect a file".to_string()),
+        },
+        [command, rest @ ..] if command == "check" => {
+            let (json, path) = parse_subcommand(command, "--json", rest)?;
+            Command::Check {
+                path: path.clone(),
+                json,
+            }
+        }
+        [command, rest @ ..] if command == "test" => match rest {
+            [path] => Command::Test { path: path.clone() },
+            [] => return Err("test expects a file".to_string()),
+            _ => return Err("Usage: notjs test FILE".to_string()),
+        },
+        [command, rest @ ..] if command == "fmt" => {
+            let (check, path) = parse_subcommand(command, "--check", rest)?;
+            Command::Fmt {
+                path: path.clone(),
+                check,
+            }
+        }
+        [path, rest @ ..] => {
+            if path != STDIN && !path.ends_with(".notjs") {
+                return Err(format!("File must have .notjs extension: {}", path));
+            }
+
+            // The files that follow the first one run after it, up to the arguments.
+            let files = rest
+                .iter()
+                .take_while(|arg| arg.ends_with(".notjs"))
+                .count();
+            let (more, rest) = rest.split_at(files);
+            let mut paths = vec![path.clone()];
+            paths.extend_from_slice(more);
+
+            // The mode flags used to come after the file, which still works.
+            let rest = match rest {
+                [flag, rest @ ..] if flag == "-dev" || flag == "--dev" => {
+                    legacy_dev = true;
+                    set_mode(&mut options, Mode::Dev)?;
+                    rest
+                }
+                [flag, rest @ ..] if flag == "--debug" => {
+                    set_mode(&mut options, Mode::Debug)?;
+                    rest
+                }
+                [flag, rest @ ..] if flag == "--ast-dot" => {
+                    set_mode(&mut options, Mode::AstDot)?;
+                    rest
+                }
+                rest => rest,
+            };
+
+            if options.watch && paths.iter().any(|path| path == STDIN) {
+                return Err("--watch cannot read the program from stdin".to_string());
+            }
+            if paths.len() > 1 && options.mode != Mode::Run {
+                return Err("--dev, --debug and --ast-dot take a single file".to_string());
+            }
+            if options.mode == Mode::Dev && !legacy_dev {
+                return Err(
+                    "--dev starts the prompt, use --ast to print the syntax tree of a file"
+                        .to_string(),
+                );
+            }
+
+            Command::Run {
+                paths,
+                args: rest.to_vec(),
+            }
+        }
+    };
+
+    if legacy_dev {
+        eprintln!(
+            "warning: -dev is deprecated and will be removed in the next release, use --dev for \
+             the prompt or --ast to print the syntax tree of a file"
+        );
+    }
+
+    Ok((options, command))
+}
+
+const REPL_HELP: &str = "Commands: :load <file> runs a file and keeps its definitions, \
+                         :type <expr> shows the type of what an expression gives, \
+                         :undo forgets what the last input defined, \
+                         help [name] lists the natives or describes one";
+
+// The interactive prompt, whose lines all run in one interpreter, so that each sees what the
+// ones before it defined.
+struct Repl {
+    interpreter: Interpreter,
+    // The globals from before each input that can still be undone, the latest last.
+    history: Vec<EnvSnapshot>,
+}
+
+// How many inputs can be undone in a row.
+const UNDO_LIMIT: usize = 100;
+
+impl Repl {
+    fn new(options: &Options) -> Self {
+        Self {
+            interpreter: options.interpreter(),
+            history: Vec::new(),
+        }
+    }
+
+    // Remember the globals before running an input, so that it can be undone.
+    fn checkpoint(&mut self) {
+        if self.history.len() == UNDO_LIMIT {
+            self.history.remove(0);
+        }
+        self.history.push(self.interpreter.checkpoint());
+    }
+
+    // Go back to the globals from before the last input, giving whether there was one.
+    fn undo(&mut self) -> bool {
+        match self.history.pop() {
+            Some(checkpoint) => {
+                self.interpreter.rollback(checkpoint);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Run the file at `path` as if its source was entered, giving how many definitions it added
+    // at the top level. Its imports are relative to it, and those of the lines that follow to the
+    // working directory again.
+    fn load_file(&mut self, path: &str) -> NotJsResult<usize> {
+        let source = read_source(path)?;
+        self.checkpoint();
+
+        self.interpreter
+            .set_script((path != STDIN).then_some(path.as_ref()));
+        let result = self.interpreter.parse(&source).map_err(NotJsError::from);
+        let result = result.and_then(|program| {
+            self.interpreter.interpret(&source)?;
+            Ok(definitions(&program))
+        });
+        self.interpreter.set_script(None);
+
+        result
+    }
+
+    // Run a line, giving its value if it is worth showing.
+    fn eval(&mut self, line: &str) -> NotJsResult<Option<Value>> {
+        self.checkpoint();
+        let (value, kind) = match help_call(line) {
+            Some(call) => self.interpreter.interpret_with_kind(call.as_bytes())?,
+            None => self.interpreter.interpret_with_kind(line.as_bytes())?,
+        };
+
+        Ok(echo(&value, kind).then_some(value))
+    }
+
+    // Load `path`, reporting how it went without stopping the prompt.
+    fn load(&mut self, path: &str, color: bool) {
+        match self.load_file(path) {
+            Ok(count) => println!(
+                "Loaded {} {} from {}",
+                count,
+                if count == 1 {
+                    "definition"
+                } else {
+                    "definitions"
+                },
+                display_name(path)
+            ),
+            Err(error) => {
+                let error = FileError {
+                    name: display_name(path).to_string(),
+                    error,
+                };
+                eprintln!("{}", render_error(&error, color));
+            }
+        }
+    }
+}
+
+// Whether the prompt shows `value`, given by a statement of `kind`. Declarations and prints are
+// never shown, and null only when it was typed as such, so that calls giving nothing show
+// nothing.
+fn echo(value: &Value, kind: ResultKind) -> bool {
+    match kind {
+        ResultKind::Declaration | ResultKind::Print => false,
+        ResultKind::Expression { null_literal: true } => true,
+        ResultKind::Expression { .. } | ResultKind::Other => *value != Value::Null,
+    }
+}
+
+// Evaluate the expression `source` in the session, describing what it gives without showing it:
+// the type, along with the length of arrays and the name and arity of functions.
+fn type_of_expression(interpreter: &mut Interpreter, source: &str) -> NotJsResult<String> {
+    Ok(match interpreter.eval_expression(source.as_bytes())? {
+        Value::Array(array) => format!("array[{}]", array.len()),
+        Value::Function(function) => format!("fn {}", function.signature()),
+        value => value.type_name().to_string(),
+    })
+}
+
+// The call to `help()` meant by `help` or `help NAME` typed at the prompt, if the line is one.
+fn help_call(line: &str) -> Option<String> {
+    match line.trim().split_once(' ') {
+        None if line.trim() == "help" => Some("help()".to_string()),
+        Some(("help", name))
+            if name
+                .trim()
+                .chars()
+                .all(|chr| chr.is_alphanumeric() || chr == '_') =>
+        {
+            Some(format!("help(\"{}\")", name.trim()))
+        }
+        _ => None,
+    }
+}
+
+// The names a program declares at the top level, including those shared by the files it imports
+// without a namespace.
+fn definitions(program: &[Statement]) -> usize {
+    program
+        .iter()
+        .map(|statement| match statement {
+            Statement::Variable(_) | Statement::Function(_) | Statement::Enum(_) => 1,
+            Statement::Import(import) if import.namespace.is_some() => 1,
+            Statement::Import(import) => definitions(&import.statements),
+            _ => 0,
+        })
+        .sum()
+}
+
+fn cli(options: &Options) -> Result<()> {
+    let color = use_color(options.color);
+    let mut repl = Repl::new(options);
     let mut buffer = String::new();
 
+    for path in &options.load {
+        repl.load(path, color);
+    }
+
     loop {
         print!("> ");
         stdout().flush()?;
-        match stdin().read_line(&mut buffer)? {
-            0 => {
-                println!("");
-                break;
-            }
-            _ => {
-                interpret(buffer.as_bytes())?;
-                buffer.clear();
-                println!("");
+
+        buffer.clear();
+        if stdin().read_line(&mut buffer)? == 0 {
+            println!();
+            return Ok(());
+        }
+
+        match buffer.trim().split_once(' ') {
+            Some((":load", path)) => repl.load(path.trim(), color),
+            None if buffer.trim() == ":undo" => {
+                if !repl.undo() {
+                    println!("Nothing to undo");
+                }
             }
+            Some((":type", source)) => match type_of_expression(&mut repl.interpreter, source) {
+                Ok(type_name) => println!("{}", type_name),
+                Err(error) => eprintln!("{}", render_error(&error, color)),
+            },
+            _ if buffer.trim_start().starts_with(':') => println!("{}", REPL_HELP),
+            // Errors are shown and the prompt goes on.
+            _ => match repl.eval(&buffer) {
+                Ok(Some(value)) => println!("{:#}", value),
+                Ok(None) => {}
+                Err(error) => eprintln!("{}", render_error(&error, color)),
+            },
+        }
+    }
+}
+
+// The path that stands for the standard input.
+const STDIN: &str = "-";
+
+// Read the whole program at `path`, or the standard input for `-`.
+fn read_source(path: &str) -> io::Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+
+    if path == STDIN {
+        stdin().lock().read_to_end(&mut buffer)?;
+    } else {
+        let fd = File::open(path)?;
+        buffer.reserve(fd.metadata()?.len() as usize);
+        BufReader::new(fd).read_to_end(&mut buffer)?;
+    }
+
+    Ok(buffer)
+}
+
+// How the program at `path` is called in messages.
+fn display_name(path: &str) -> &str {
+    if path == STDIN {
+        "<stdin>"
+    } else {
+        path
+    }
+}
+
+// Resolve the imports of the program at `path` next to it. A program read from the standard input
+// imports from the working directory.
+fn with_script(interpreter: Interpreter, path: &str) -> Interpreter {
+    if path == STDIN {
+        interpreter
+    } else {
+        interpreter.with_script(path)
+    }
+}
+
+// Run the snippets given with `-e` in order against one interpreter, as with several files, and
+// stop at the first one that fails.
+fn eval(snippets: &[String], args: &[String], options: &Options) -> NotJsResult<()> {
+    let mut interpreter = options.interpreter().with_args(args.to_vec());
+
+    for snippet in snippets {
+        if options.typecheck {
+            interpreter.typecheck(snippet.as_bytes())?;
         }
+        interpreter.interpret(snippet.as_bytes())?;
     }
+
     Ok(())
 }
 
-fn run_file(path: &str) -> Result<()> {
-    let fd = File::open(path)?;
-    let mut buffer = Vec::with_capacity(fd.metadata()?.len() as usize);
-    let mut reader = BufReader::new(fd);
+// An error raised while running one of several files, along with the name of the file.
+#[derive(Debug)]
+struct FileError {
+    name: String,
+    error: NotJsError,
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.name, self.error)
+    }
+}
+
+impl std::error::Error for FileError {}
+
+// Run the files in order against one interpreter, so that each sees what the ones before it
+// defined, and stop at the first one that fails.
+fn run_files(paths: &[String], args: &[String], options: &Options) -> Result<()> {
+    let mut interpreter = options.interpreter().with_args(args.to_vec());
+    interpreter.set_profiling(options.profile);
+    interpreter.set_coverage(options.coverage);
+
+    let mut result = Ok(());
+    for path in paths {
+        result = run_file(&mut interpreter, path, options).map_err(|error| {
+            FileError {
+                name: display_name(path).to_string(),
+                error,
+            }
+            .into()
+        });
+        if result.is_err() {
+            break;
+        }
+    }
+
+    // Written apart from the output of the program, even if it failed.
+    if options.profile {
+        eprint!("\n{}", interpreter.take_profile());
+    }
+
+    result
+}
+
+// Run the files as with `run_files`, then again each time one of them or a file they import
+// changes, until `changes` gives no more. Errors are shown without stopping, and each run starts
+// on a cleared screen with the time it started.
+fn watch_files(
+    paths: &[String],
+    args: &[String],
+    options: &Options,
+    changes: &mut impl Changes,
+    output: &mut impl Write,
+) -> Result<()> {
+    let clear = stdout().is_terminal();
+    // The files imported by each file, kept from the last run it parsed in.
+    let mut imports = vec![Vec::new(); paths.len()];
+
+    loop {
+        if clear {
+            write!(output, "\x1b[2J\x1b[H")?;
+        }
+        writeln!(
+            output,
+            "[{}] {}",
+            watch::clock(SystemTime::now()),
+            paths.join(" ")
+        )?;
+        output.flush()?;
+
+        if let Err(error) = run_files(paths, args, options) {
+            let _ = stdout().flush();
+            eprintln!("{}", render_error(error.as_ref(), use_color(options.color)));
+        }
+
+        let mut watched: Vec<PathBuf> = paths.iter().map(PathBuf::from).collect();
+        for (path, imported) in paths.iter().zip(&mut imports) {
+            let program = read_source(path).ok().and_then(|source| {
+                with_script(options.unoptimized_interpreter(), path)
+                    .parse(&source)
+                    .ok()
+            });
+            if let Some(program) = program {
+                *imported = watch::imports(&program);
+            }
+            watched.extend(imported.iter().cloned());
+        }
+
+        if !changes.wait(&watched) {
+            return Ok(());
+        }
+    }
+}
+
+fn run_file(interpreter: &mut Interpreter, path: &str, options: &Options) -> NotJsResult<()> {
+    let buffer = read_source(path)?;
+
+    interpreter.set_script((path != STDIN).then_some(path.as_ref()));
+    if options.typecheck {
+        interpreter.typecheck(&buffer)?;
+    }
+    let result = interpreter.interpret(&buffer);
 
-    reader.read_to_end(&mut buffer)?;
+    // The lines of each file are kept apart by taking them after it ran.
+    if options.coverage {
+        print_coverage(display_name(path), &buffer, &interpreter.take_coverage());
+    }
+    result?;
 
-    Ok(interpreter::interpret(&buffer)?)
+    Ok(())
+}
+
+// A summary of each file followed by its listing, with the lines that never ran marked.
+fn print_coverage(path: &str, source: &[u8], coverage: &Coverage) {
+    for file in &coverage.files {
+        let (name, source) = match file.path {
+            Some(ref module) => (
+                module.display().to_string(),
+                fs::read_to_string(module).unwrap_or_default(),
+            ),
+            None => (
+                path.to_string(),
+                String::from_utf8_lossy(source).into_owned(),
+            ),
+        };
+
+        eprintln!("\n{}", file.summary(&name));
+        eprint!("{}", file.annotate(&source));
+    }
 }
 
-fn debug_file(path: &str) -> Result<()> {
+// What `-dev FILE` did before it was split into `--ast` and `--trace`, kept until it is removed.
+fn debug_file(path: &str, args: &[String], options: &Options) -> Result<()> {
     println!("DEBUG MODE");
-    let fd = File::open(path)?;
-    let mut buffer = Vec::with_capacity(fd.metadata()?.len() as usize);
-    let mut reader = BufReader::new(fd);
+    let buffer = read_source(path)?;
 
-    reader.read_to_end(&mut buffer)?;
+    let mut interpreter = with_script(options.interpreter(), path).with_args(args.to_vec());
 
-    let program = parser::parse(&buffer)?;
+    let program = interpreter.parse(&buffer)?;
 
     println!("{:#?} => ", program);
 
-    interpret(&buffer)?;
+    interpreter.interpret(&buffer)?;
 
     Ok(())
 }
 
-fn debug_cli() -> Result<()> {
-    println!("DEBUG MODE");
+const DEBUG_HELP: &str =
+    "Commands: s (step), c (continue), p <expr> (print), vars, b <line> (breakpoint)";
+
+// Talks to the user whenever the program pauses, reading commands from `input`.
+struct Prompt<R, W> {
+    input: R,
+    output: W,
+    // Once the input is closed, the program runs to the end.
+    closed: bool,
+}
+
+impl<R: BufRead, W: Write> Prompt<R, W> {
+    // Run a command, giving how to resume if it ends the pause.
+    fn command(&mut self, pause: &mut Pause, command: &str) -> std::io::Result<Option<Resume>> {
+        let (name, argument) = command
+            .trim()
+            .split_once(' ')
+            .unwrap_or((command.trim(), ""));
+
+        match (name, argument.trim()) {
+            ("s", "") => return Ok(Some(Resume::Step)),
+            ("c", "") => return Ok(Some(Resume::Continue)),
+            ("p", expression) if !expression.is_empty() => match pause.eval(expression) {
+                Ok(value) => writeln!(self.output, "{:#}", value)?,
+                Err(error) => writeln!(self.output, "{}", error)?,
+            },
+            ("vars", "") => {
+                for (depth, scope) in pause.scopes().iter().enumerate() {
+                    writeln!(self.output, "scope {}:", depth)?;
+                    for (name, value) in scope {
+                        writeln!(self.output, "  {} = {:#}", name, value)?;
+                    }
+                }
+            }
+            ("b", line) => match line.parse() {
+                Ok(line) => {
+                    pause.set_breakpoint(line);
+                    writeln!(self.output, "Breakpoint at line {}", line)?;
+                }
+                Err(_) => writeln!(self.output, "Invalid line: {}", line)?,
+            },
+            ("", "") => {}
+            _ => writeln!(self.output, "{}", DEBUG_HELP)?,
+        }
+
+        Ok(None)
+    }
+
+    fn pause(&mut self, pause: &mut Pause) -> std::io::Result<Resume> {
+        writeln!(self.output, "{:>5}: {}", pause.line(), pause.source())?;
+
+        let mut command = String::new();
+        loop {
+            write!(self.output, "(debug) ")?;
+            self.output.flush()?;
+
+            command.clear();
+            if self.input.read_line(&mut command)? == 0 {
+                writeln!(self.output)?;
+                self.closed = true;
+                return Ok(Resume::Continue);
+            }
+            if let Some(resume) = self.command(pause, &command)? {
+                return Ok(resume);
+            }
+        }
+    }
+}
+
+impl<R: BufRead, W: Write> DebugHook for Prompt<R, W> {
+    fn paused(&mut self, pause: &mut Pause) -> Resume {
+        if self.closed {
+            return Resume::Continue;
+        }
+
+        // The terminal is gone, so there's no one left to ask.
+        self.pause(pause).unwrap_or_else(|_| {
+            self.closed = true;
+            Resume::Continue
+        })
+    }
+}
+
+// Run the file under the debugger, pausing before its first statement.
+fn step_file(path: &str, args: &[String], options: &Options) -> Result<()> {
+    if path == STDIN {
+        return Err("--debug reads its commands from stdin, so the program must be a file".into());
+    }
+    let source = read_source(path)?;
+
+    // Paused statements are shown as written.
+    let mut interpreter =
+        with_script(options.unoptimized_interpreter(), path).with_args(args.to_vec());
+    if let Some(timeout) = options.timeout {
+        interpreter = interpreter.with_timeout(timeout);
+    }
+
+    if options.typecheck {
+        interpreter.typecheck(&source)?;
+    }
+
+    println!("{}", DEBUG_HELP);
+    interpreter.set_debugger(Some(Box::new(Prompt {
+        input: stdin().lock(),
+        output: stdout(),
+        closed: false,
+    })));
+    interpreter.interpret(&source)?;
+
+    Ok(())
+}
+
+// The problems found by parsing and resolving the file, and checking its types with
+// `--typecheck`, without running it, and how many more were left out of them. The types are only
+// checked once the file parses.
+fn check_file(path: &str, options: &Options) -> Result<(Vec<Diagnostic>, usize)> {
+    let source = read_source(path)?;
+    let interpreter = with_script(options.unoptimized_interpreter(), path);
+    let result = if options.typecheck {
+        interpreter.typecheck(&source)
+    } else {
+        interpreter.parse(&source).map(|_| ())
+    };
+
+    Ok(result
+        .err()
+        .map(|err| (err.diagnostics(), err.hidden()))
+        .unwrap_or_default())
+}
+
+// Print the problems in the file, as JSON for tools with `--json`. Exits with 1 if any of them is
+// an error, and with 2 if the file can't be read.
+fn check(path: &str, json: bool, options: &Options) {
+    let (diagnostics, hidden) = match check_file(path, options) {
+        Ok(found) => found,
+        Err(err) => {
+            eprintln!("Cannot check {}: {}", display_name(path), err);
+            process::exit(2);
+        }
+    };
+
+    let name = display_name(path);
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string(&diagnostics).expect("diagnostics should serialize")
+        );
+    } else {
+        for diagnostic in &diagnostics {
+            let severity = match diagnostic.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+            };
+            match diagnostic.line {
+                Some(line) => println!(
+                    "{}:{}: {}[{}]: {}",
+                    name, line, severity, diagnostic.id, diagnostic.message
+                ),
+                None => println!(
+                    "{}: {}[{}]: {}",
+                    name, severity, diagnostic.id, diagnostic.message
+                ),
+            }
+        }
+        match hidden {
+            0 => {}
+            1 => println!("{}: and 1 more error", name),
+            hidden => println!("{}: and {} more errors", name, hidden),
+        }
+    }
+
+    if diagnostics
+        .iter()
+        .any(|diagnostic| diagnostic.severity == Severity::Error)
+    {
+        process::exit(1);
+    }
+}
+
+// The source of the file along with its canonical form.
+fn format_file(path: &str, options: &Options) -> Result<(Vec<u8>, String)> {
+    let source = read_source(path)?;
+    let program = with_script(options.unoptimized_interpreter(), path).parse(&source)?;
+    let formatted = source::format(&source, &program);
+
+    Ok((source, formatted))
+}
+
+// Rewrite the file in its canonical form, or with `check` only tell whether it is in it, exiting
+// with 1 if it isn't. Exits with 2 if the file can't be read or parsed.
+fn fmt(path: &str, check: bool, options: &Options) {
+    let (source, formatted) = match format_file(path, options) {
+        Ok(formatted) => formatted,
+        Err(err) => {
+            eprintln!("Cannot format {}: {}", display_name(path), err);
+            process::exit(2);
+        }
+    };
+
+    // A program read from the standard input is written to the standard output, as there is no
+    // file to write it back to.
+    if path == STDIN && !check {
+        print!("{}", formatted);
+        return;
+    }
+
+    if formatted.as_bytes() == source {
+        return;
+    }
+
+    if check {
+        println!("{} is not formatted", display_name(path));
+        process::exit(1);
+    }
+
+    if let Err(err) = fs::write(path, formatted) {
+        eprintln!("Cannot write {}: {}", path, err);
+        process::exit(2);
+    }
+}
+
+// How the tests went: a line for each of them, with the error of those that failed, followed by
+// how many passed and failed.
+fn test_report(results: &[TestResult]) -> String {
+    let mut report = String::new();
+    let mut failed = 0;
+
+    for result in results {
+        let Some(ref error) = result.error else {
+            report += &format!("PASS {}\n", result.name);
+            continue;
+        };
+
+        failed += 1;
+        report += &format!("FAIL {} (line {})\n", result.name, result.line);
+        for line in error
+            .to_string()
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+        {
+            report += &format!("    {}\n", line.trim());
+        }
+    }
+
+    report + &format!("\n{} passed, {} failed\n", results.len() - failed, failed)
+}
+
+// Run the file, and then its tests.
+fn test_file(path: &str, options: &Options) -> NotJsResult<Vec<TestResult>> {
+    let source = read_source(path)?;
+    let mut interpreter = with_script(options.interpreter(), path);
+
+    if options.typecheck {
+        interpreter.typecheck(&source)?;
+    }
+    interpreter.test(&source)
+}
+
+// Run the file and then its tests, printing how they went after what the program printed. Exits
+// with 1 if any test failed, and fails as a run does if the program itself does.
+fn test(path: &str, options: &Options) -> Result<()> {
+    let results = test_file(path, options).map_err(|error| FileError {
+        name: display_name(path).to_string(),
+        error,
+    })?;
+
+    let _ = stdout().flush();
+    print!("{}", test_report(&results));
+
+    if results.iter().any(|result| result.error.is_some()) {
+        let _ = stdout().flush();
+        process::exit(1);
+    }
+
+    Ok(())
+}
+
+// Print the syntax tree of the file as a Graphviz graph, without running it.
+fn dot_file(path: &str, options: &Options) -> Result<()> {
+    let source = read_source(path)?;
+    let program = with_script(options.unoptimized_interpreter(), path).parse(&source)?;
+
+    print!("{}", dot::to_dot(&program));
+
+    Ok(())
+}
+
+const DEV_HELP: &str = "Commands: :ast, :tokens and :trace turn showing the syntax tree, the \
+     tokens and the trace of each line on or off, :help shows this";
+
+// The prompt of `--dev`, which shows how each line is read before running it. The syntax tree is
+// shown unless only `--tokens` was given.
+fn dev_cli(options: &Options) -> Result<()> {
+    println!("DEV MODE, :help for the commands");
+    dev_prompt(
+        &mut options.interpreter(),
+        stdin().lock(),
+        &mut stdout(),
+        options,
+    )
+}
+
+fn dev_prompt(
+    interpreter: &mut Interpreter,
+    mut input: impl BufRead,
+    output: &mut impl Write,
+    options: &Options,
+) -> Result<()> {
+    let mut ast = options.ast || !options.tokens;
+    let mut tokens = options.tokens;
+    let mut trace = options.trace;
     let mut buffer = String::new();
 
     loop {
-        print!("~> ");
-        stdout().flush()?;
-        match stdin().read_line(&mut buffer)? {
-            0 => {
-                println!("");
-                break;
+        write!(output, "~> ")?;
+        output.flush()?;
+
+        buffer.clear();
+        if input.read_line(&mut buffer)? == 0 {
+            writeln!(output)?;
+            return Ok(());
+        }
+
+        let (name, enabled) = match buffer.trim() {
+            ":ast" => {
+                ast = !ast;
+                ("syntax tree", ast)
+            }
+            ":tokens" => {
+                tokens = !tokens;
+                ("tokens", tokens)
+            }
+            ":trace" => {
+                trace = !trace;
+                interpreter.set_trace(trace);
+                ("trace", trace)
+            }
+            command if command.starts_with(':') => {
+                writeln!(output, "{}", DEV_HELP)?;
+                continue;
             }
             _ => {
-                let expr = parser::parse(buffer.as_bytes())?;
-                print!("{:#?}", expr);
-                print!(" => ");
-                interpret(buffer.as_bytes())?;
-                buffer.clear();
-                println!("");
+                if tokens {
+                    write_tokens(output, buffer.as_bytes())?;
+                }
+                if ast {
+                    write!(output, "{:#?} => ", interpreter.parse(buffer.as_bytes())?)?;
+                }
+                // Written after the output of the line.
+                let value = interpreter.interpret(buffer.as_bytes())?;
+                writeln!(output, "{}", value)?;
+                continue;
             }
+        };
+
+        writeln!(output, "{} {}", name, if enabled { "on" } else { "off" })?;
+    }
+}
+
+// One line for each token of `source`: its line, its type and the text it was read from.
+fn write_tokens(output: &mut impl Write, source: &[u8]) -> std::io::Result<()> {
+    let mut scanner = Scanner::new(source);
+
+    while let Some(token) = scanner.next() {
+        let text = String::from_utf8_lossy(&source[scanner.span()]);
+        write!(
+            output,
+            "{:>4}  {:<10}  {}",
+            token.line,
+            token.token_type.to_string(),
+            text.trim()
+        )?;
+        if token.token_type == TokenType::Error {
+            write!(output, "  ({})", token.value)?;
         }
+        writeln!(output)?;
     }
+
     Ok(())
 }
 
+// Print the tokens or the syntax tree of the files, as asked with `--tokens` and `--ast`, without
+// running them.
+fn dump_files(paths: &[String], options: &Options) -> Result<()> {
+    for path in paths {
+        dump_file(path, options).map_err(|error| FileError {
+            name: display_name(path).to_string(),
+            error,
+        })?;
+    }
+
+    Ok(())
+}
+
+fn dump_file(path: &str, options: &Options) -> NotJsResult<()> {
+    let source = read_source(path)?;
+    let interpreter = with_script(options.interpreter(), path);
+
+    dump(&interpreter, &source, options)
+}
+
+// The syntax tree is the one that would run, folded by the optimizer as with `-dev`.
+fn dump(interpreter: &Interpreter, source: &[u8], options: &Options) -> NotJsResult<()> {
+    if options.tokens {
+        write_tokens(&mut stdout(), source)?;
+    }
+    if options.ast {
+        println!("{:#?}", interpreter.parse(source)?);
+    }
+
+    Ok(())
+}
+
+// Print the name and the description of an error code.
+fn explain(code: &str) -> Result<()> {
+    let Some(code) = codes::explain(code) else {
+        return Err(format!("Unknown error code: {}", code).into());
+    };
+
+    println!("{} {}\n\n{}", code.code, code.name, code.explanation);
+    Ok(())
+}
+
+// Whether errors written to stderr are colored: with `auto`, only on a terminal and unless the
+// `NO_COLOR` environment variable is set to something. `--color=always` wins over `NO_COLOR`.
+fn use_color(color: Color) -> bool {
+    match color {
+        Color::Always => true,
+        Color::Never => false,
+        Color::Auto => {
+            env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()) && stderr().is_terminal()
+        }
+    }
+}
+
+// The message of an error as shown on stderr. The label that starts each error is followed by its
+// code, as in `RuntimeError[R002]:`, in red if `color`, and the message ends with where to read
+// more. Errors quoted in the message of another, such as why an import failed, are left as they
+// are.
+fn render_error(error: &(dyn std::error::Error + 'static), color: bool) -> String {
+    let mut codes = Vec::new();
+    let rendered = render_labeled(error, color, &mut codes);
+    let mut rendered = rendered.trim_end().to_string();
+
+    if let Some(code) = codes.first() {
+        rendered += &format!(
+            "\n\nRun `notjs --explain {}` for more about this error.",
+            code
+        );
+    }
+
+    rendered
+}
+
+// `error` as it is displayed, with the code of each error it is made of after its label. The codes
+// are added to `codes` in the order they appear.
+fn render_labeled(
+    error: &(dyn std::error::Error + 'static),
+    color: bool,
+    codes: &mut Vec<&'static str>,
+) -> String {
+    if let Some(error) = error.downcast_ref::<FileError>() {
+        return format!(
+            "{}: {}",
+            error.name,
+            render_labeled(&error.error, color, codes)
+        );
+    }
+
+    match error.downcast_ref::<NotJsError>() {
+        Some(NotJsError::Parse(error)) => render_parse_error(error, color, codes),
+        Some(NotJsError::Runtime(error)) => {
+            let code = error.error.code();
+            codes.push(code);
+            with_code(&error.to_string(), "RuntimeError", code, color)
+        }
+        // The message of the system says it all.
+        Some(NotJsError::Io(_)) | None => error.to_string(),
+    }
+}
+
+// Laid out as `ParseError` displays itself.
+fn render_parse_error(error: &ParseError, color: bool, codes: &mut Vec<&'static str>) -> String {
+    match error {
+        ParseError::Single(error) => {
+            let code = error.code().id();
+            codes.push(code);
+            with_code(&error.to_string(), "Parse error", code, color)
+        }
+        ParseError::Multiple(multiple) => {
+            let mut rendered = "Failed to parse, errors:\n".to_string();
+            for error in &multiple.errors {
+                rendered += "\n";
+                rendered += &render_parse_error(error, color, codes);
+            }
+            match multiple.hidden {
+                0 => {}
+                1 => rendered += "\nand 1 more error",
+                hidden => rendered += &format!("\nand {} more errors", hidden),
+            }
+            rendered
+        }
+    }
+}
+
+// `message` of a single error, which starts with `label:`, with `code` after the label.
+fn with_code(message: &str, label: &str, code: &str, color: bool) -> String {
+    let Some(rest) = message
+        .strip_prefix(label)
+        .and_then(|rest| rest.strip_prefix(':'))
+    else {
+        return message.to_string();
+    };
+
+    if color {
+        format!("\x1b[31m{}[{}]:\x1b[0m{}", label, code, rest)
+    } else {
+        format!("{}[{}]:{}", label, code, rest)
+    }
+}
+
+// Exit codes from sysexits.h, for scripts to tell the failures apart.
+const EX_DATAERR: i32 = 65;
+const EX_NOINPUT: i32 = 66;
+const EX_SOFTWARE: i32 = 70;
+const EX_IOERR: i32 = 74;
+
+// The exit code for `error`: 65 if the program didn't parse, 70 if it failed while running, 66 if
+// a file couldn't be found, 74 for other failures to read or write, and 1 for anything else.
+fn exit_code(error: &(dyn std::error::Error + 'static)) -> i32 {
+    if let Some(error) = error.downcast_ref::<FileError>() {
+        return exit_code(&error.error);
+    }
+
+    match error.downcast_ref::<NotJsError>() {
+        Some(NotJsError::Parse(_)) => EX_DATAERR,
+        Some(NotJsError::Runtime(_)) => EX_SOFTWARE,
+        Some(NotJsError::Io(error)) => io_exit_code(error),
+        None => error.downcast_ref::<io::Error>().map_or(1, io_exit_code),
+    }
+}
+
+fn io_exit_code(error: &io::Error) -> i32 {
+    if error.kind() == io::ErrorKind::NotFound {
+        EX_NOINPUT
+    } else {
+        EX_IOERR
+    }
+}
+
+// Carry out the command, writing its error to stderr, and give the code to exit with. The output
+// of the program goes to stdout, so that the two can be piped apart.
+fn run_and_report(options: &Options, command: Command) -> i32 {
+    let result = match command {
+        Command::Help => {
+            println!("{}\n\n{}", USAGE, HELP);
+            Ok(())
+        }
+        Command::Version => {
+            println!("notjs {}", env!("CARGO_PKG_VERSION"));
+            Ok(())
+        }
+        Command::Explain { code } => explain(&code),
+        Command::Repl if options.mode == Mode::Dev => dev_cli(options),
+        // A program piped in is run as a whole, as with `notjs -`.
+        Command::Repl if !stdin().is_terminal() && options.load.is_empty() => {
+            run_files(&[STDIN.to_string()], &[], options)
+        }
+        Command::Repl => cli(options),
+        Command::Check { path, json } => {
+            check(&path, json, options);
+            Ok(())
+        }
+        Command::Fmt { path, check } => {
+            fmt(&path, check, options);
+            Ok(())
+        }
+        Command::Test { path } => test(&path, options),
+        Command::Eval { snippets, .. } if options.ast || options.tokens => {
+            let interpreter = options.interpreter();
+            snippets
+                .iter()
+                .try_for_each(|snippet| dump(&interpreter, snippet.as_bytes(), options))
+                .map_err(Into::into)
+        }
+        Command::Eval { snippets, args } => eval(&snippets, &args, options).map_err(Into::into),
+        Command::Run { paths, .. } if options.ast || options.tokens => dump_files(&paths, options),
+        Command::Run { paths, args } if options.watch => {
+            watch_files(&paths, &args, options, &mut Poll::default(), &mut stdout())
+        }
+        Command::Run { paths, args } => match options.mode {
+            Mode::Run => run_files(&paths, &args, options),
+            Mode::Dev => debug_file(&paths[0], &args, options),
+            Mode::Debug => step_file(&paths[0], &args, options),
+            Mode::AstDot => dot_file(&paths[0], options),
+        },
+    };
+
+    let Err(error) = result else {
+        return 0;
+    };
+
+    // The error comes after what the program printed.
+    let _ = stdout().flush();
+    eprintln!("{}", render_error(error.as_ref(), use_color(options.color)));
+
+    exit_code(error.as_ref())
+}
+
 fn main() {
     let args: Vec<String> = env::args().skip(1).collect();
 
-    match args.as_slice() {
-        [] => {
-            println!("\nEjecucion de CLI: ");
-            cli().expect("Error");
+    let (options, command) = match parse_args(&args) {
+        Ok(parsed) => parsed,
+        Err(message) => {
+            eprintln!(
+                "{}\n\n{}\n\nSee notjs --help for the options.",
+                message, USAGE
+            );
+            process::exit(2);
+        }
+    };
+
+    process::exit(run_and_report(&options, command));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("5s"), Some(Duration::from_secs(5)));
+        assert_eq!(parse_duration("500ms"), Some(Duration::from_millis(500)));
+        assert_eq!(parse_duration("2m"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_duration("1.5"), Some(Duration::from_millis(1500)));
+        assert_eq!(parse_duration("5h"), None);
+        assert_eq!(parse_duration("s"), None);
+    }
+
+    fn parse(args: &[&str]) -> std::result::Result<(Options, Command), String> {
+        parse_args(
+            &args
+                .iter()
+                .map(|arg| arg.to_string())
+                .collect::<Vec<String>>(),
+        )
+    }
+
+    fn run(path: &str, args: &[&str]) -> Command {
+        Command::Run {
+            paths: vec![path.to_string()],
+            args: args.iter().map(|arg| arg.to_string()).collect(),
         }
-        [filepath] => {
-            if filepath.ends_with(".notjs") {
-                run_file(filepath).expect("\n\x1b[91mError\x1b[0m");
-            } else {
-                println!("File must have .notjs extension");
-                println!("Usage: notjs [path] [-dev]");
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let (options, command) = parse(&["--timeout", "5s", "main.notjs", "--timeout"]).unwrap();
+        assert_eq!(options.timeout, Some(Duration::from_secs(5)));
+        assert_eq!(command, run("main.notjs", &["--timeout"]));
+        assert!(!options.no_prelude);
+        assert!(parse(&["--timeout"]).is_err());
+
+        // Options in any order, the file last.
+        let (options, command) = parse(&["--coverage", "--debug", "--trace", "a.notjs"]).unwrap();
+        assert!(options.coverage && options.trace && !options.profile);
+        assert_eq!(options.mode, Mode::Debug);
+        assert_eq!(command, run("a.notjs", &[]));
+
+        // The mode flags can still follow the file, and `-dev` before it no longer runs the
+        // prompt.
+        let (options, command) = parse(&["a.notjs", "-dev", "x", "--debug"]).unwrap();
+        assert_eq!(options.mode, Mode::Dev);
+        assert_eq!(command, run("a.notjs", &["x", "--debug"]));
+        assert_eq!(parse(&["-dev", "a.notjs"]).unwrap().0.mode, Mode::Dev);
+        assert_eq!(
+            parse(&["--dev"]).unwrap(),
+            (
+                Options {
+                    mode: Mode::Dev,
+                    ..Options::default()
+                },
+                Command::Repl
+            )
+        );
+        assert_eq!(parse(&[]).unwrap().1, Command::Repl);
+        assert_eq!(parse(&["--", "-a.notjs"]).unwrap().1, run("-a.notjs", &[]));
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!(parse(&["--help"]).unwrap().1, Command::Help);
+        assert_eq!(
+            parse(&["--trace", "-h", "--bogus"]).unwrap().1,
+            Command::Help
+        );
+        assert_eq!(parse(&["-V"]).unwrap().1, Command::Version);
+        assert_eq!(
+            parse(&["--no-prelude", "check", "--json", "a.notjs"])
+                .unwrap()
+                .1,
+            Command::Check {
+                path: "a.notjs".to_string(),
+                json: true
+            }
+        );
+        assert_eq!(
+            parse(&["fmt", "a.notjs"]).unwrap().1,
+            Command::Fmt {
+                path: "a.notjs".to_string(),
+                check: false
+            }
+        );
+        assert_eq!(
+            parse(&["--timeout", "1s", "test", "a.notjs"]).unwrap().1,
+            Command::Test {
+                path: "a.notjs".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_files() {
+        assert_eq!(
+            parse(&["lib.notjs", "main.notjs", "x", "y.notjs"])
+                .unwrap()
+                .1,
+            Command::Run {
+                paths: vec!["lib.notjs".to_string(), "main.notjs".to_string()],
+                args: vec!["x".to_string(), "y.notjs".to_string()],
             }
+        );
+        assert_eq!(parse(&["a.notjs", "-"]).unwrap().1, run("a.notjs", &["-"]));
+        assert_eq!(
+            parse(&["--debug", "a.notjs", "b.notjs"]).unwrap_err(),
+            "--dev, --debug and --ast-dot take a single file"
+        );
+    }
+
+    #[test]
+    fn test_run_files() {
+        let dir = env::temp_dir().join(format!("notjs-run-files-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = |name: &str, source: &str| {
+            let path = dir.join(name);
+            fs::write(&path, source).unwrap();
+            path.to_str().unwrap().to_string()
+        };
+        let lib = file(
+            "lib.notjs",
+            "function double(x) { return x * 2 }
+let base = 20",
+        );
+        let main = file("main.notjs", "if (double(base) != 40) { [][1] }");
+        let broken = file("broken.notjs", "let x = [][0]");
+
+        let options = Options::default();
+        run_files(&[lib.clone(), main.clone()], &[], &options).unwrap();
+        // Without the library, the names it defines are undeclared.
+        assert!(run_files(std::slice::from_ref(&main), &[], &options).is_err());
+
+        let error = run_files(&[lib.clone(), broken.clone(), main], &[], &options).unwrap_err();
+        assert!(error.to_string().starts_with(&format!("{}: ", broken)));
+        let error = run_files(
+            &[lib, dir.join("missing.notjs").display().to_string()],
+            &[],
+            &options,
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("missing.notjs: "));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_dumps() {
+        let (options, command) = parse(&["--tokens", "--ast", "a.notjs", "b.notjs"]).unwrap();
+        assert!(options.tokens && options.ast);
+        assert_eq!(options.mode, Mode::Run);
+        assert!(matches!(command, Command::Run { paths, .. } if paths.len() == 2));
+
+        let (options, command) = parse(&["--dev", "--tokens", "--trace"]).unwrap();
+        assert!(options.tokens && options.trace);
+        assert_eq!(command, Command::Repl);
+
+        for (args, message) in [
+            (&["--ast"][..], "--ast and --tokens expect a file"),
+            (
+                &["--dev", "a.notjs"],
+                "--dev starts the prompt, use --ast to print the syntax tree of a file",
+            ),
+            (
+                &["--ast", "--ast-dot", "a.notjs"],
+                "--ast and --tokens cannot be used with --debug or --ast-dot",
+            ),
+        ] {
+            assert_eq!(parse(args).unwrap_err(), message, "{:?}", args);
+        }
+        assert!(parse(&["--tokens", "--profile", "a.notjs"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_typecheck() {
+        let (options, command) = parse(&["--typecheck", "a.notjs"]).unwrap();
+        assert!(options.typecheck);
+        assert!(matches!(command, Command::Run { .. }));
+        assert!(
+            parse(&["--typecheck", "-e", "print 1"])
+                .unwrap()
+                .0
+                .typecheck
+        );
+        assert!(
+            parse(&["--typecheck", "check", "a.notjs"])
+                .unwrap()
+                .0
+                .typecheck
+        );
+
+        for (args, message) in [
+            (&["--typecheck"][..], "--typecheck expects a file or -e"),
+            (
+                &["--typecheck", "--ast", "a.notjs"],
+                "--typecheck only applies to running or checking programs",
+            ),
+            (
+                &["--typecheck", "--ast-dot", "a.notjs"],
+                "--typecheck only applies to running or checking programs",
+            ),
+        ] {
+            assert_eq!(parse(args).unwrap_err(), message, "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn test_dev_prompt() {
+        let input = "let x = 1\n:ast\n:tokens\nx + 2\n:bogus\n";
+        let mut output = Vec::new();
+        let mut interpreter = Interpreter::new().with_output(std::io::sink());
+        dev_prompt(
+            &mut interpreter,
+            input.as_bytes(),
+            &mut output,
+            &Options::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            format!(
+                "~> [\n    let x = 1,\n] => Null\n\
+                 ~> syntax tree off\n\
+                 ~> tokens on\n\
+                 ~>    1  Identifier  x\n   1  +           +\n   1  Number      2\n3\n\
+                 ~> {}\n\
+                 ~> \n",
+                DEV_HELP
+            )
+        );
+    }
+
+    #[test]
+    fn test_color() {
+        assert_eq!(parse(&["--color=always"]).unwrap().0.color, Color::Always);
+        assert_eq!(parse(&["--color", "never"]).unwrap().0.color, Color::Never);
+        assert_eq!(
+            parse(&["--no-color", "--color=auto"]).unwrap().0.color,
+            Color::Auto
+        );
+        assert_eq!(
+            parse(&["--color=red"]).unwrap_err(),
+            "Invalid color: red, expected auto, always or never"
+        );
+        assert!(use_color(Color::Always) && !use_color(Color::Never));
+
+        // The same error with and without color differs only in the escapes around the labels.
+        let error = Interpreter::new()
+            .interpret(b"let = 1\nconst = 2")
+            .unwrap_err();
+        let plain = render_error(&error, false);
+        let colored = render_error(&error, true);
+        assert_eq!(
+            plain,
+            "Failed to parse, errors:\n\n\
+             Parse error[E002]: Expected: Identifier after let at line 1\n\
+             Parse error[E002]: Expected: Identifier after const at line 2\n\n\
+             Run `notjs --explain E002` for more about this error."
+        );
+        assert!(colored.contains("\n\x1b[31mParse error[E002]:\x1b[0m Expected"));
+        assert_eq!(
+            colored.replace("\x1b[31m", "").replace("\x1b[0m", ""),
+            plain
+        );
+
+        let error = Interpreter::new().interpret(b"[][0]").unwrap_err();
+        assert!(render_error(&error, true).starts_with("\x1b[31mRuntimeError[R002]:\x1b[0m"));
+        let error = NotJsError::from(io::Error::other("no code"));
+        assert_eq!(render_error(&error, true), "no code");
+    }
+
+    #[test]
+    #[cfg(feature = "host")]
+    fn test_render_import_error() {
+        // The error of an imported file is part of the message of the import, it gets no code.
+        let dir = env::temp_dir().join(format!("notjs-render-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let broken = dir.join("broken.notjs");
+        fs::write(&broken, "let = 1").unwrap();
+        let source = format!("import \"{}\"\nconst = 2", broken.display());
+        let error = Interpreter::new().interpret(source.as_bytes()).unwrap_err();
+        assert_eq!(
+            render_error(&error, false),
+            format!(
+                "Failed to parse, errors:\n\n\
+                 Parse error[E022]: Cannot import {} at line 1: Failed to parse, errors:\n\n\
+                 Parse error: Expected: Identifier after let at line 1\n\
+                 Parse error[E002]: Expected: Identifier after const at line 2\n\n\
+                 Run `notjs --explain E022` for more about this error.",
+                broken.display()
+            )
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_exit_code() {
+        let error = Interpreter::new().interpret(b"let").unwrap_err();
+        assert_eq!(exit_code(&error), EX_DATAERR);
+        let error = Interpreter::new().interpret(b"[][0]").unwrap_err();
+        assert_eq!(exit_code(&error), EX_SOFTWARE);
+
+        let error = FileError {
+            name: "main.notjs".to_string(),
+            error: io::Error::from(io::ErrorKind::NotFound).into(),
+        };
+        assert_eq!(exit_code(&error), EX_NOINPUT);
+        let error = NotJsError::from(io::Error::from(io::ErrorKind::PermissionDenied));
+        assert_eq!(exit_code(&error), EX_IOERR);
+        let error: Box<dyn std::error::Error> = "usage".into();
+        assert_eq!(exit_code(error.as_ref()), 1);
+    }
+
+    // Changes given by the test instead of the file system, recording what was watched.
+    #[cfg(feature = "host")]
+    struct Edits<F> {
+        edits: F,
+        watched: Vec<Vec<PathBuf>>,
+    }
+
+    #[cfg(feature = "host")]
+    impl<F: FnMut(usize) -> bool> Changes for Edits<F> {
+        fn wait(&mut self, paths: &[PathBuf]) -> bool {
+            self.watched.push(paths.to_vec());
+            (self.edits)(self.watched.len())
         }
-        [filepath, arg2] => {
-            if filepath.ends_with(".notjs") && arg2 == "-dev" {
-                debug_file(filepath).expect("\n\x1b[91mError\x1b[0m");
-            } else if arg2 == "-dev" {
-                debug_cli().expect("Error");
-            } else {
-                println!("Usage: notjs [path] [-dev]");
+    }
+
+    #[cfg(feature = "host")]
+    #[test]
+    fn test_watch() {
+        let dir = env::temp_dir().join(format!("notjs-watch-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let main = dir.join("main.notjs");
+        let lib = dir.join("lib.notjs");
+        fs::write(&lib, "let base = 1").unwrap();
+        fs::write(&main, "import \"lib.notjs\"\nlet x = base").unwrap();
+
+        let mut edits = Edits {
+            edits: |runs| {
+                match runs {
+                    // The script fails on the next run, which doesn't stop the watch.
+                    1 => fs::write(&main, "import \"lib.notjs\"\nlet x = [][base]").unwrap(),
+                    // It no longer parses, so the imports found before are still watched.
+                    2 => fs::write(&main, "import \"lib.notjs\"\nlet = 1").unwrap(),
+                    _ => return false,
+                }
+                true
+            },
+            watched: Vec::new(),
+        };
+        let mut output = Vec::new();
+        let paths = [main.display().to_string()];
+        watch_files(&paths, &[], &Options::default(), &mut edits, &mut output).unwrap();
+
+        let watched = [main.clone(), lib.canonicalize().unwrap()];
+        assert_eq!(edits.watched, [watched.clone(), watched.clone(), watched]);
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.lines().count(), 3);
+        assert!(output
+            .lines()
+            .all(|line| line.ends_with(&format!("] {}", main.display()))));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_type_of_expression() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .interpret(b"function add(a, b) { return a + b } let xs = [1, 2, 3]")
+            .unwrap();
+        let mut type_of = |source: &str| type_of_expression(&mut interpreter, source);
+
+        assert_eq!(type_of("1 + 2").unwrap(), "number");
+        assert_eq!(type_of("\"a\" + 1").unwrap(), "string");
+        assert_eq!(type_of("xs").unwrap(), "array[3]");
+        assert_eq!(type_of("[]").unwrap(), "array[0]");
+        assert_eq!(type_of("add").unwrap(), "fn add/2");
+        assert_eq!(type_of("range").unwrap(), "fn range/1..3");
+        assert_eq!(type_of("add(1, 2) > 2").unwrap(), "boolean");
+        assert_eq!(type_of("null").unwrap(), "null");
+        assert_eq!(type_of("jsonParse(\"{}\")").unwrap(), "object");
+
+        // Errors leave the session going.
+        let error = type_of("xs[5]").unwrap_err().to_string();
+        assert!(error.contains("Index 5 out of bounds"), "{}", error);
+        assert!(type_of("let y = 1").is_err());
+        assert_eq!(type_of("xs").unwrap(), "array[3]");
+    }
+
+    #[test]
+    fn test_echo() {
+        let expression = ResultKind::Expression {
+            null_literal: false,
+        };
+        let null_literal = ResultKind::Expression { null_literal: true };
+        let one = Value::Number(1.0);
+
+        assert!(!echo(&one, ResultKind::Declaration));
+        assert!(!echo(&Value::Null, ResultKind::Declaration));
+        assert!(!echo(&Value::Null, ResultKind::Print));
+        assert!(echo(&one, expression));
+        assert!(!echo(&Value::Null, expression));
+        assert!(echo(&Value::Null, null_literal));
+        assert!(echo(&one, ResultKind::Other));
+        assert!(!echo(&Value::Null, ResultKind::Other));
+
+        let mut repl = Repl::new(&Options::default());
+        let mut eval = |line: &str| repl.eval(line).unwrap();
+        assert_eq!(eval("let x = 5"), None);
+        assert_eq!(eval("function nothing() {}"), None);
+        assert_eq!(eval("enum Color { Red }"), None);
+        assert_eq!(eval("print \"\""), None);
+        assert_eq!(eval("nothing()"), None);
+        assert_eq!(eval("null"), Some(Value::Null));
+        assert_eq!(eval("x + 1"), Some(Value::Number(6.0)));
+        assert_eq!(eval("if (x > 1) { x = 2 }"), Some(Value::Number(2.0)));
+        assert_eq!(eval("while (false) {}"), None);
+        assert_eq!(eval("nothing() x"), Some(Value::Number(2.0)));
+    }
+
+    #[test]
+    fn test_repl_undo() {
+        let mut repl = Repl::new(&Options::default());
+        assert!(!repl.undo());
+
+        repl.eval("let x = 1").unwrap();
+        repl.eval("let y = 2 x = 10").unwrap();
+        assert!(repl.eval("let z = [][0]").is_err());
+
+        // A failed input is undone too, leaving the ones before it.
+        assert!(repl.undo());
+        assert_eq!(repl.eval("x + y").unwrap(), Some(Value::Number(12.0)));
+        assert!(repl.undo());
+        assert!(repl.undo());
+        assert_eq!(repl.eval("x").unwrap(), Some(Value::Number(1.0)));
+        assert!(repl.eval("y").is_err());
+    }
+
+    #[test]
+    fn test_help_call() {
+        assert_eq!(help_call("help\n").as_deref(), Some("help()"));
+        assert_eq!(help_call(" help len\n").as_deref(), Some("help(\"len\")"));
+        assert_eq!(help_call("help(\"len\")"), None);
+        assert_eq!(help_call("help x + 1"), None);
+        assert_eq!(help_call("helper"), None);
+
+        let mut repl = Repl::new(&Options::default());
+        let error = repl.eval("help lenn").unwrap_err().to_string();
+        assert!(error.contains("did you mean 'len'"), "{}", error);
+    }
+
+    #[cfg(feature = "host")]
+    #[test]
+    fn test_repl_load() {
+        let dir = env::temp_dir().join(format!("notjs-load-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let helpers = dir.join("helpers.notjs");
+        fs::write(dir.join("more.notjs"), "let three = 3").unwrap();
+        fs::write(
+            &helpers,
+            "import \"more.notjs\"\n\
+             function double(x) { return x * 2 }\n\
+             const offset = 1",
+        )
+        .unwrap();
+
+        let mut repl = Repl::new(&Options::default());
+        assert_eq!(repl.load_file(helpers.to_str().unwrap()).unwrap(), 3);
+        assert_eq!(
+            repl.eval("double(three) + offset").unwrap(),
+            Some(Value::Number(7.0))
+        );
+
+        // A failed load leaves the prompt going with what was defined before.
+        assert!(repl.load_file("missing.notjs").is_err());
+        fs::write(&helpers, "let broken = [][0]").unwrap();
+        assert!(repl.load_file(helpers.to_str().unwrap()).is_err());
+        assert_eq!(repl.eval("double(2)").unwrap(), Some(Value::Number(4.0)));
+
+        assert_eq!(
+            parse(&["--load", "a.notjs", "--load", "b.notjs"])
+                .unwrap()
+                .0
+                .load,
+            ["a.notjs", "b.notjs"]
+        );
+        assert!(parse(&["--load", "a.notjs", "b.notjs"]).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_eval() {
+        let (options, command) =
+            parse(&["-e", "let x = 3", "--trace", "--eval", "print x", "a"]).unwrap();
+        assert!(options.trace);
+        assert_eq!(
+            command,
+            Command::Eval {
+                snippets: vec!["let x = 3".to_string(), "print x".to_string()],
+                args: vec!["a".to_string()],
+            }
+        );
+        assert_eq!(parse(&["-e"]).unwrap_err(), "-e expects a program");
+        assert!(parse(&["--dev", "-e", "1"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_stdin() {
+        assert_eq!(parse(&["-", "x"]).unwrap().1, run("-", &["x"]));
+        let (options, command) = parse(&["--trace", "-", "--ast-dot"]).unwrap();
+        assert!(options.trace);
+        assert_eq!(options.mode, Mode::AstDot);
+        assert_eq!(command, run("-", &[]));
+        assert_eq!(
+            parse(&["check", "-"]).unwrap().1,
+            Command::Check {
+                path: "-".to_string(),
+                json: false
             }
+        );
+        assert_eq!(
+            parse(&["fmt", "--check", "-"]).unwrap().1,
+            Command::Fmt {
+                path: "-".to_string(),
+                check: true
+            }
+        );
+        assert_eq!(display_name(STDIN), "<stdin>");
+        assert_eq!(display_name("a.notjs"), "a.notjs");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for (args, message) in [
+            (&["--bogus", "a.notjs"][..], "Unknown option: --bogus"),
+            (&["--timeout", "5h"], "Invalid duration: 5h"),
+            (&["a.txt"], "File must have .notjs extension: a.txt"),
+            (&["--debug"], "--debug and --ast-dot expect a file"),
+            (
+                &["--dev", "--debug", "a.notjs"],
+                "Only one of --dev, --debug and --ast-dot can be given",
+            ),
+            (&["check"], "check expects a file"),
+            (&["test"], "test expects a file"),
+            (&["test", "a.notjs", "b.notjs"], "Usage: notjs test FILE"),
+            (&["--watch"], "--watch expects a file"),
+            (
+                &["--watch", "-"],
+                "--watch cannot read the program from stdin",
+            ),
+            (&["--watch", "--ast", "a.notjs"], "--watch only runs files"),
+            (
+                &["fmt", "--json", "a.notjs"],
+                "Usage: notjs fmt [--check] FILE",
+            ),
+        ] {
+            assert_eq!(parse(args).unwrap_err(), message, "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn test_test_report() {
+        let mut interpreter = Interpreter::new();
+        let results = interpreter
+            .test(b"test \"ok\" { assert true }\ntest \"broken\" {\n    assert 1 > 2\n}")
+            .unwrap();
+
+        assert_eq!(
+            test_report(&results),
+            "PASS ok\n\
+             FAIL broken (line 2)\n\
+             \x20   RuntimeError: Assertion failed: 1 > 2 at line 3\n\
+             \n\
+             1 passed, 1 failed\n"
+        );
+        assert_eq!(test_report(&[]), "\n0 passed, 0 failed\n");
+    }
+
+    #[test]
+    fn test_debug_prompt() {
+        let input = "s\nvars\nb 4\nb x\nfoo\np x + 1\np y\nc\ns\n";
+        let output = debug_output(input);
+        let (before, after) = output.split_once("\n").unwrap();
+
+        assert_eq!(before, "    1: let x = 1");
+        assert_eq!(
+            after,
+            "(debug)     2: x += 1\n\
+             (debug) scope 0:\n\
+             \x20 x = 1\n\
+             (debug) Breakpoint at line 4\n\
+             (debug) Invalid line: x\n\
+             (debug) Commands: s (step), c (continue), p <expr> (print), vars, b <line> (breakpoint)\n\
+             (debug) 2\n\
+             (debug) Parse error: Undeclared variable 'y' at line 1\n\
+             (debug)     4: print x\n\
+             (debug)     5: print x + 1\n\
+             (debug) \n"
+        );
+    }
+
+    // What the prompt writes while `input` drives the debugger over a small program.
+    fn debug_output(input: &'static str) -> String {
+        let buffer = Rc::new(RefCell::new(Vec::new()));
+        let output = SharedOutput(buffer.clone());
+
+        let mut interpreter = Interpreter::new().with_output(std::io::sink());
+        interpreter.set_debugger(Some(Box::new(Prompt {
+            input: input.as_bytes(),
+            output,
+            closed: false,
+        })));
+        interpreter
+            .interpret(b"let x = 1\nx += 1\nx += 1\nprint x\nprint x + 1\nprint x + 2")
+            .unwrap();
+
+        let output = String::from_utf8(buffer.borrow().clone()).unwrap();
+        output
+    }
+
+    #[derive(Clone)]
+    struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
         }
-        _ => {
-            println!("Usage: notjs [path] [-dev]");
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
         }
     }
+
+    #[test]
+    fn test_no_prelude() {
+        let (options, command) = parse(&["--no-prelude", "main.notjs"]).unwrap();
+        assert!(options.no_prelude);
+        assert_eq!(command, run("main.notjs", &[]));
+
+        assert!(options.interpreter().parse(b"abs(1)").is_err());
+        assert!(Options::default().interpreter().parse(b"abs(1)").is_ok());
+    }
 }
@@ -0,0 +1,434 @@
+use std::{mem, rc::Rc};
+
+use crate::common::{
+    expressions::{BinaryExpression, Expression, PostfixOperator, UnaryExpression},
+    statements::{BlockStatement, Statement},
+    value::Value,
+};
+
+// Rewrite a parsed program so that it does less work when run, without changing what it does.
+// Operators whose operands are literals are replaced by their result, computed with the same
+// implementation the interpreter uses. Operators that would fail are left for the run to report.
+// Ifs and whiles whose conditions end up as literals are replaced by the code that would run.
+//
+// The pass runs after the resolver, and keeps the scopes it saw: an if does not open a scope of
+// its own, so its branch can take its place, and removed code only leaves empty blocks behind.
+pub fn optimize(mut statements: Vec<Statement>) -> Vec<Statement> {
+    fold_statements(&mut statements);
+    statements
+}
+
+fn fold_statements(statements: &mut Vec<Statement>) {
+    statements.iter_mut().for_each(fold_statement);
+
+    // Only the value of the last statement is used, empty blocks before it do nothing.
+    let last = statements.len().saturating_sub(1);
+    let mut index = 0;
+    statements.retain(|statement| {
+        let keep = index == last || !is_empty_block(statement);
+        index += 1;
+        keep
+    });
+}
+
+fn is_empty_block(statement: &Statement) -> bool {
+    matches!(statement, Statement::Block(block) if block.statements.is_empty())
+}
+
+fn empty_block(line: u32) -> Statement {
+    Statement::Block(BlockStatement {
+        statements: Vec::new(),
+        line,
+    })
+}
+
+fn fold_statement(statement: &mut Statement) {
+    match statement {
+        Statement::Block(block) => fold_statements(&mut block.statements),
+        Statement::Variable(declaration) => {
+            if let Some(ref mut initializer) = declaration.initializer {
+                fold(initializer);
+            }
+        }
+        Statement::Expression(statement) => fold(&mut statement.expression),
+        Statement::Print(statement) => fold(&mut statement.expression),
+        Statement::If(statement) => {
+            fold(&mut statement.condition);
+            fold_statement(&mut statement.then_branch);
+            if let Some(ref mut else_branch) = statement.else_branch {
+                fold_statement(else_branch);
+            }
+        }
+        Statement::While(statement) => {
+            fold(&mut statement.condition);
+            fold_statement(&mut statement.body);
+        }
+        Statement::ForIn(statement) => {
+            fold(&mut statement.iterable);
+            fold_statement(&mut statement.body);
+        }
+        Statement::Return(statement) => {
+            if let Some(ref mut value) = statement.value {
+                fold(value);
+            }
+        }
+        Statement::Yield(statement) => {
+            if let Some(ref mut value) = statement.value {
+                fold(value);
+            }
+        }
+        Statement::Defer(statement) => {
+            if let Some(expression) = Rc::get_mut(&mut statement.expression) {
+                fold(expression);
+            }
+        }
+        // The body is only shared once the declaration runs, right after parsing it is not.
+        Statement::Function(declaration) => {
+            if let Some(body) = Rc::get_mut(&mut declaration.body) {
+                fold_statement(body);
+            }
+        }
+        Statement::Assert(statement) => fold(&mut statement.condition),
+        Statement::Test(declaration) => {
+            if let Some(body) = Rc::get_mut(&mut declaration.body) {
+                fold_statement(body);
+            }
+        }
+        Statement::Import(import) => fold_statements(&mut import.statements),
+        Statement::Enum(_) => {}
+    }
+
+    if let Some(live) = live_code(statement) {
+        *statement = live;
+    }
+}
+
+// The statement that runs in place of an if or while with a literal condition. Code that can
+// never run is replaced by an empty block, which gives the same null as the skipped statement.
+fn live_code(statement: &mut Statement) -> Option<Statement> {
+    match statement {
+        Statement::If(statement) => match statement.condition {
+            Expression::Literal(ref condition) if condition.is_truthy() => Some(mem::replace(
+                &mut *statement.then_branch,
+                empty_block(statement.line),
+            )),
+            Expression::Literal(_) => Some(match statement.else_branch.take() {
+                Some(else_branch) => *else_branch,
+                None => empty_block(statement.line),
+            }),
+            _ => None,
+        },
+        Statement::While(statement) => match statement.condition {
+            Expression::Literal(ref condition) if !condition.is_truthy() => {
+                Some(empty_block(statement.line))
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn fold(expression: &mut Expression) {
+    match expression {
+        Expression::Assignment(assignment) => fold(&mut assignment.value),
+        Expression::PropertyAssignment(assignment) => {
+            for (operator, _) in &mut assignment.path {
+                if let PostfixOperator::Index(index) = operator {
+                    fold(index);
+                }
+            }
+            fold(&mut assignment.value);
+        }
+        Expression::Conditional(conditional) => {
+            fold(&mut conditional.condition);
+            fold(&mut conditional.then_branch);
+            fold(&mut conditional.else_branch);
+        }
+        Expression::Binary(_) => return fold_chain(expression),
+        Expression::Unary(unary) => fold(&mut unary.right),
+        Expression::Delete(delete) => {
+            for (operator, _) in &mut delete.path {
+                if let PostfixOperator::Index(index) = operator {
+                    fold(index);
+                }
+            }
+        }
+        Expression::Postfix(postfix) => {
+            fold(&mut postfix.left);
+            match postfix.operator {
+                PostfixOperator::Index(ref mut index) => fold(index),
+                PostfixOperator::Call(ref mut arguments) => arguments.iter_mut().for_each(fold),
+                PostfixOperator::Dot(_) => {}
+            }
+        }
+        Expression::Array(array) => array.elements.iter_mut().for_each(fold),
+        Expression::Match(expression) => {
+            fold(&mut expression.value);
+            for arm in &mut expression.arms {
+                fold(&mut arm.body);
+            }
+        }
+        Expression::Identifier(_) | Expression::Literal(_) => {}
+    }
+
+    if let Some(folded) = constant(expression) {
+        *expression = folded;
+    }
+}
+
+// Fold a chain of binary expressions level by level, see `BinaryExpression::chain`. The levels
+// are detached from each other while they are folded.
+fn fold_chain(expression: &mut Expression) {
+    let mut levels = Vec::new();
+    let mut left = mem::replace(expression, Expression::Literal(Value::Null));
+
+    while let Expression::Binary(mut binary) = left {
+        left = mem::replace(&mut *binary.left, Expression::Literal(Value::Null));
+        levels.push(binary);
+    }
+
+    fold(&mut left);
+
+    while let Some(mut binary) = levels.pop() {
+        *binary.left = left;
+        fold(&mut binary.right);
+
+        left = Expression::Binary(binary);
+        if let Some(folded) = constant(&mut left) {
+            left = folded;
+        }
+    }
+
+    *expression = left;
+}
+
+// The expression that can replace `expression` once its operands have been folded, if any.
+fn constant(expression: &mut Expression) -> Option<Expression> {
+    match expression {
+        Expression::Binary(binary) => match (&*binary.left, &*binary.right) {
+            (Expression::Literal(left), Expression::Literal(right)) => {
+                BinaryExpression::apply(&binary.operator, left.clone(), right.clone())
+                    .ok()
+                    .map(Expression::Literal)
+            }
+            // The right side would never run.
+            (Expression::Literal(left), _)
+                if BinaryExpression::short_circuits(&binary.operator, left) =>
+            {
+                Some(Expression::Literal(left.clone()))
+            }
+            _ => None,
+        },
+        Expression::Unary(unary) => match *unary.right {
+            Expression::Literal(ref right) => {
+                UnaryExpression::apply(&unary.operator, right.clone())
+                    .ok()
+                    .map(Expression::Literal)
+            }
+            _ => None,
+        },
+        Expression::Conditional(conditional) => match *conditional.condition {
+            Expression::Literal(ref condition) => {
+                let branch = if condition.is_truthy() {
+                    &mut conditional.then_branch
+                } else {
+                    &mut conditional.else_branch
+                };
+
+                Some(mem::replace(branch, Expression::Literal(Value::Null)))
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interpreter::Interpreter;
+
+    use super::*;
+
+    // Parse with the natives known, so that they can be called.
+    fn folded(source: &[u8]) -> Vec<Statement> {
+        optimize(Interpreter::new().parse(source).unwrap())
+    }
+
+    // The value of an expression statement that was folded into a literal.
+    fn literal(statement: &Statement) -> Option<&Value> {
+        match statement {
+            Statement::Expression(statement) => match statement.expression {
+                Expression::Literal(ref value) => Some(value),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_fold_literals() {
+        let statements = folded(
+            br#"
+            -(2 + 3)
+            60 * 60 * 24
+            "a" + "b" + 1
+            !(1 < 2)
+            1 == 1 ? "yes" : "no"
+            false & len()
+            "#,
+        );
+
+        let expected = [
+            Value::Number(-5.0),
+            Value::Number(86400.0),
+            Value::String("ab1".into()),
+            Value::Boolean(false),
+            Value::String("yes".into()),
+            Value::Boolean(false),
+        ];
+
+        assert_eq!(statements.len(), expected.len());
+        for (statement, expected) in statements.iter().zip(&expected) {
+            assert_eq!(literal(statement), Some(expected), "{:?}", statement);
+        }
+    }
+
+    #[test]
+    fn test_fold_nested() {
+        let statements = folded(
+            br#"
+            let x = 1
+            x * (2 + 3)
+            function f(a) { return a + 60 * 60 }
+            let xs = [1 + 1, x]
+            "#,
+        );
+
+        let source: Vec<String> = statements.iter().map(|s| s.node_to_string()).collect();
+        assert_eq!(
+            source,
+            [
+                "let x = 1",
+                "x * 5",
+                "function f(a) return a + 3600",
+                "let xs = [2, x]",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fold_leaves_errors() {
+        let statements = folded(br#"1 - "a" -true "x" / 2"#);
+
+        assert!(statements
+            .iter()
+            .all(|statement| literal(statement).is_none()));
+
+        // The error is the one the run reports without the optimizer.
+        let source = b"let a = 1 + 1\nlet b = 1 - \"a\"";
+        let plain = Interpreter::new().interpret(source).unwrap_err();
+        let optimized = Interpreter::new()
+            .with_optimizer()
+            .interpret(source)
+            .unwrap_err();
+        assert_eq!(optimized.to_string(), plain.to_string());
+        assert!(optimized.to_string().contains("at line 2"));
+    }
+
+    #[test]
+    fn test_fold_keeps_results() {
+        let source = br#"
+            let seconds = 60 * 60 * 24
+            let greeting = "hello" + ", " + "world"
+            let flag = !false & 1 < 2
+            let picked = seconds > 1000 ? "long" : "short"
+        "#;
+
+        let mut plain = Interpreter::new();
+        let mut optimized = Interpreter::new().with_optimizer();
+        plain.interpret(source).unwrap();
+        optimized.interpret(source).unwrap();
+
+        for name in ["seconds", "greeting", "flag", "picked"] {
+            assert_eq!(
+                plain.interpret(name.as_bytes()).unwrap(),
+                optimized.interpret(name.as_bytes()).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_eliminate_dead_branches() {
+        let statements = folded(
+            br#"
+            const DEBUG = false
+            if (false) { println "debug" }
+            if (1 > 2) println "never" else println "always"
+            while (!true) { println "loop" }
+            if ("yes") { println "taken" } else { println "skipped" }
+            if (DEBUG) println "kept"
+            "#,
+        );
+
+        let source: Vec<String> = statements.iter().map(|s| s.node_to_string()).collect();
+        assert_eq!(
+            source,
+            [
+                "const DEBUG = false",
+                "print \"always\"",
+                "print \"taken\"",
+                "if DEBUG print \"kept\"",
+            ]
+        );
+
+        let mut interpreter = Interpreter::new().with_optimizer();
+        let (result, output) = interpreter.interpret_capture(
+            br#"
+            let count = 0
+            if (false) { count = count + 1 println "dead" }
+            while (1 == 2) count = count + 1
+            if (true) count = count + 10 else { count = count + 100 println "dead" }
+            count
+            "#,
+        );
+        assert_eq!(result.unwrap(), Value::Number(10.0));
+        assert_eq!(output, "");
+    }
+
+    #[test]
+    fn test_eliminate_keeps_results() {
+        // A skipped if still gives null as the value of the program.
+        for source in [
+            &b"1 if (false) 2"[..],
+            b"1 while (false) 2",
+            b"{ 1 if (false) 2 }",
+        ] {
+            assert_eq!(
+                Interpreter::new()
+                    .with_optimizer()
+                    .interpret(source)
+                    .unwrap(),
+                Value::Null
+            );
+        }
+
+        // Declarations only in a dead branch behave as if the branch had been skipped.
+        let source = b"if (false) let x = 1\nx";
+        let plain = Interpreter::new().interpret(source).unwrap_err();
+        let optimized = Interpreter::new()
+            .with_optimizer()
+            .interpret(source)
+            .unwrap_err();
+        assert_eq!(optimized.to_string(), plain.to_string());
+
+        let source = b"if (false) { let y = 1 }\ny";
+        assert!(Interpreter::new().with_optimizer().parse(source).is_err());
+
+        // A live branch that declares a variable defines it in the scope around the if.
+        let mut interpreter = Interpreter::new().with_optimizer();
+        assert_eq!(
+            interpreter.interpret(b"if (true) let z = 3\nz").unwrap(),
+            Value::Number(3.0)
+        );
+    }
+}
@@ -1,92 +1,329 @@
-use std::iter::Peekable;
+use std::{
+    fmt::Display,
+    io,
+    iter::Peekable,
+    ops::Range,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 
 use crate::{
     common::{
         expressions::{
             ArrayLiteral, AssignmentExpression, BinaryExpression, ConditionalExpression,
-            Expression, Identifier, PostfixExpression, PostfixOperator, UnaryExpression,
+            DeleteExpression, Expression, Identifier, MatchArm, MatchExpression, Pattern,
+            PostfixExpression, PostfixOperator, PropertyAssignment, UnaryExpression,
         },
         resolver::Resolver,
+        serialize,
         statements::{
-            BlockStatement, ExpressionStatement, IfStatement, PrintStatement, ReturnStatement,
-            Statement, VariableDeclaration, WhileStatement,
+            AssertStatement, BlockStatement, DeferStatement, EnumDeclaration, ExpressionStatement,
+            ForInStatement, FunctionDeclaration, IfStatement, ImportStatement, Parameter,
+            PrintStatement, ReturnStatement, Statement, TestDeclaration, VariableDeclaration,
+            WhileStatement, YieldStatement,
         },
         token::{Token, TokenType},
+        value::{Value, ValueType},
     },
-    error::parse::{ParseError, ParseResult},
+    error::parse::{ErrorCode, ParseError, ParseResult, Single},
     lexer::Scanner,
 };
 
+// Statements and expressions nested deeper than this are rejected, as parsing them would overflow
+// the stack. A parenthesized expression goes through every level of precedence, and unoptimized
+// builds run out of the 2 MiB stack of a spawned thread past about 70 of them. Chains of binary
+// operators don't nest, see `BinaryExpression::chain`.
+const MAX_NESTING: usize = 64;
+
+// Errors reported for a program at most, the rest are only counted. Past the first few, errors
+// are mostly caused by the earlier ones.
+const MAX_ERRORS: usize = 20;
+
+// The tokens of a source along with the bytes each was read from.
+struct Spanned<'a>(Scanner<'a>);
+
+impl Iterator for Spanned<'_> {
+    type Item = (Token, Range<usize>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let token = self.0.next()?;
+        Some((token, self.0.span()))
+    }
+}
+
 struct Parser<'a> {
+    source: &'a [u8],
     actual: Option<Token>,
-    _scanner: Peekable<Scanner<'a>>,
+    // Bytes of the token read last.
+    span: Range<usize>,
+    _scanner: Peekable<Spanned<'a>>,
     resolver: Resolver,
+    imports: Imports,
+    // Names marked with `export`, in the order they were declared.
+    exports: Vec<String>,
+    // Statements and expressions being parsed, one inside the other.
+    nesting: usize,
+    // Whether the body of a generator function is being parsed, the only place yields can be.
+    generator: bool,
+}
+
+// Where the file being parsed imports from.
+#[derive(Clone, Default)]
+pub(crate) struct Imports {
+    // Directory that import paths are relative to, the working directory when empty.
+    directory: PathBuf,
+    // Canonical paths of the files being imported, outermost first, to detect cycles.
+    chain: Vec<PathBuf>,
+    // Names that files imported with a namespace can use without declaring them.
+    builtins: Resolver,
+    // Whether imports are refused before reading anything, as in sandboxed interpreters.
+    denied: bool,
+}
+
+impl Imports {
+    // Imports for the file at `path`, relative to its directory.
+    pub fn for_file(path: &Path) -> Self {
+        let path = canonical_module(path).unwrap_or_else(|_| path.to_path_buf());
+
+        Imports {
+            directory: path.parent().map(Path::to_path_buf).unwrap_or_default(),
+            chain: vec![path],
+            builtins: Resolver::new(),
+            denied: false,
+        }
+    }
+
+    // Imports that all fail, without looking at the file system.
+    pub fn denied() -> Self {
+        Imports {
+            denied: true,
+            ..Imports::default()
+        }
+    }
+
+    pub fn with_builtins(mut self, builtins: Resolver) -> Self {
+        self.builtins = builtins;
+        self
+    }
+}
+
+// Imports read files, which needs the `host` feature. Builds without it, such as the WASM one,
+// have no file system to import from.
+#[cfg(feature = "host")]
+fn canonical_module(path: &Path) -> io::Result<PathBuf> {
+    path.canonicalize()
+}
+
+#[cfg(feature = "host")]
+fn read_module(path: &Path) -> io::Result<Vec<u8>> {
+    std::fs::read(path)
+}
+
+#[cfg(not(feature = "host"))]
+fn canonical_module(_: &Path) -> io::Result<PathBuf> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "imports need the host feature",
+    ))
+}
+
+#[cfg(not(feature = "host"))]
+fn read_module(_: &Path) -> io::Result<Vec<u8>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "imports need the host feature",
+    ))
 }
 
 impl<'a> Parser<'a> {
-    pub fn new(source: &'a [u8]) -> Self {
+    pub fn new(source: &'a [u8], resolver: Resolver) -> Self {
         Parser {
+            source,
             actual: None,
-            _scanner: Scanner::new(source).peekable(),
-            resolver: Resolver::new(),
+            span: 0..0,
+            _scanner: Spanned(Scanner::new(source)).peekable(),
+            resolver,
+            imports: Imports::default(),
+            exports: Vec::new(),
+            nesting: 0,
+            generator: false,
         }
     }
 
-    fn parse(&mut self) -> ParseResult<Vec<Box<dyn Statement>>> {
+    fn parse(&mut self) -> ParseResult<Vec<Statement>> {
         self.program()
     }
 
+    // Parse a single expression that must make up the whole source.
+    fn parse_expression(&mut self) -> ParseResult<Expression> {
+        let expression = self.expression()?;
+
+        match self._scanner.peek().cloned() {
+            Some((token, span)) => Err(self.unexpected(token, span)),
+            None => Ok(expression),
+        }
+    }
+
     fn next(&mut self) -> Option<Token> {
-        self.actual = self._scanner.next();
+        self.actual = self._scanner.next().map(|(token, span)| {
+            self.span = span;
+            token
+        });
         self.actual.clone()
     }
 
+    // The next token, which must exist.
+    fn advance(&mut self) -> ParseResult<Token> {
+        self.next().ok_or_else(|| self.unexpected_eof())
+    }
+
     fn peek(&mut self) -> Option<&Token> {
-        self._scanner.peek()
+        self._scanner.peek().map(|(token, _)| token)
+    }
+
+    // Bytes of the next token, or the end of the source.
+    fn peek_span(&mut self) -> Range<usize> {
+        match self._scanner.peek() {
+            Some((_, span)) => span.clone(),
+            None => self.source.len()..self.source.len(),
+        }
+    }
+
+    // The text of the source at `span`.
+    fn lexeme(&self, span: &Range<usize>) -> String {
+        String::from_utf8_lossy(&self.source[span.clone()]).into_owned()
+    }
+
+    // Place `error` at `span` of the source, see `ParseError::with_span`.
+    fn located(&self, error: ParseError, span: Range<usize>) -> ParseError {
+        let line = &self.source[..span.start];
+        let start = line
+            .iter()
+            .rposition(|&byte| byte == b'\n')
+            .map_or(0, |index| index + 1);
+        let column = String::from_utf8_lossy(&line[start..]).chars().count() as u32 + 1;
+
+        error.with_span(column, span)
+    }
+
+    // The file ended where more was expected, placed right after the last token. Its line is
+    // counted from the source, as no token is read at the end.
+    fn unexpected_eof(&self) -> ParseError {
+        let end = self.span.end;
+        let line = self.source[..end]
+            .iter()
+            .filter(|&&byte| byte == b'\n')
+            .count() as u32
+            + 1;
+        self.located(ParseError::new_unexpected_eof(line), end..end)
+    }
+
+    // `token` at `span` is not allowed where it was found.
+    fn unexpected(&self, token: Token, span: Range<usize>) -> ParseError {
+        let found = self.lexeme(&span);
+        self.located(ParseError::new_unexpected_token(token), span)
+            .with_found(found)
+    }
+
+    // Line of the token read last, such as the keyword of the statement being parsed.
+    fn line(&self) -> u32 {
+        self.actual.as_ref().map_or(1, |token| token.line)
     }
 
     fn consume(&mut self, ttype: TokenType) -> Result<Token, ParseError> {
-        match self.peek() {
-            Some(Token { token_type, .. }) => {
-                if *token_type == ttype {
-                    Ok(self.next().unwrap())
-                } else {
-                    Err(ParseError::new_missing_token(
-                        ttype,
-                        self.actual.clone().unwrap(),
-                    ))
-                }
-            }
-            None => Err(ParseError::new_unexpected_eof()),
+        let (token, span) = match self._scanner.peek() {
+            Some((token, _)) if token.token_type == ttype => return self.advance(),
+            Some((token, span)) => (token.clone(), span.clone()),
+            None => return Err(self.unexpected_eof().with_expected(vec![ttype])),
+        };
+
+        let error = match self.actual {
+            // Placed after the token read last, where the missing one should be.
+            Some(ref actual) => self
+                .located(
+                    ParseError::new_missing_token(ttype, actual.clone()),
+                    self.span.clone(),
+                )
+                .with_found(self.lexeme(&span)),
+            // Nothing came before, the first token of the source is the wrong one.
+            None => self.unexpected(token, span).with_expected(vec![ttype]),
+        };
+
+        Err(error)
+    }
+
+    // Parse something that may contain itself, failing instead of going deeper than
+    // `MAX_NESTING`. The rest of the source is skipped then, as each enclosing level would only
+    // fail again while recovering.
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> ParseResult<T>) -> ParseResult<T> {
+        if self.nesting >= MAX_NESTING {
+            let line = self
+                ._scanner
+                .peek()
+                .map(|(token, _)| token)
+                .or(self.actual.as_ref())
+                .map_or(0, |token| token.line);
+            let span = self.peek_span();
+            let error = self.located(
+                ParseError::new_single(
+                    ErrorCode::TooDeeplyNested,
+                    Some(line),
+                    format!("Nesting deeper than {} levels", MAX_NESTING),
+                ),
+                span,
+            );
+            while self.next().is_some() {}
+
+            return Err(error);
         }
+
+        self.nesting += 1;
+        let result = parse(self);
+        self.nesting -= 1;
+
+        result
     }
 
+    // Skip to where the next statement likely starts after an error: a keyword that starts one,
+    // or the `}` ending the enclosing block. A declaration whose keyword was already read still
+    // declares its name, so that its uses don't fail in turn.
     fn synchronize(&mut self) {
-        while let Some(token) = self._scanner.peek() {
+        while let Some((token, _)) = self._scanner.peek() {
             match token.token_type {
-                // TokenType::Semicolon | TokenType::Newline => {
-                //     println!("stop at {}", token.token_type);
-                //     self.next();
-                //     return;
-                // }
-                _ => {
+                TokenType::Class
+                | TokenType::Function
+                | TokenType::Enum
+                | TokenType::Let
+                | TokenType::Const
+                | TokenType::If
+                | TokenType::While
+                | TokenType::For
+                | TokenType::Print
+                | TokenType::Println
+                | TokenType::Return
+                | TokenType::Yield
+                | TokenType::Defer
+                | TokenType::Assert
+                | TokenType::Test
+                | TokenType::Import
+                | TokenType::Export
+                | TokenType::LeftBrace => return,
+                TokenType::RightBrace if self.nesting > 0 => return,
+                TokenType::Identifier => {
                     if let Some(Token {
                         token_type:
-                            TokenType::Class
-                            | TokenType::Function
-                            | TokenType::Let
+                            keyword @ (TokenType::Let
                             | TokenType::Const
-                            | TokenType::If
-                            | TokenType::While
-                            | TokenType::Print
-                            | TokenType::Return
-                            | TokenType::LeftBrace,
+                            | TokenType::Function
+                            | TokenType::Enum),
                         ..
-                    }) = self._scanner.peek()
+                    }) = self.actual
                     {
-                        return;
+                        let identifier = token.clone();
+                        self.resolver.declare(identifier, keyword == TokenType::Let);
                     }
                 }
+                _ => {}
             }
 
             self.next();
@@ -94,6 +331,61 @@ impl<'a> Parser<'a> {
     }
 }
 
+// Gathers the errors of the statements of a program into what is reported. An error often
+// causes others after it, so:
+// - names used after a syntax error, up to the next statement that parses, may have been
+//   declared by what was skipped, so they aren't reported as undeclared or immutable.
+// - the same error on consecutive lines is reported once.
+// - past `MAX_ERRORS`, errors are only counted.
+#[derive(Default)]
+struct Report {
+    errors: Vec<ParseError>,
+    hidden: usize,
+    // Whether a statement failed with anything but a resolver error since one last parsed.
+    covered: bool,
+    // The last error found, reported or not, to collapse repeats.
+    last: Option<Single>,
+}
+
+impl Report {
+    fn parsed(&mut self) {
+        self.covered = false;
+    }
+
+    fn failed(&mut self, error: ParseError) {
+        for error in error.errors() {
+            self.add(error.clone());
+        }
+    }
+
+    fn add(&mut self, error: Single) {
+        match error.code() {
+            ErrorCode::UndeclaredVariable | ErrorCode::ImmutableVariable if self.covered => return,
+            ErrorCode::UndeclaredVariable | ErrorCode::ImmutableVariable => {}
+            _ => self.covered = true,
+        }
+
+        let last = self.last.replace(error.clone());
+        if last.is_some_and(|last| error.repeats(&last)) {
+            return;
+        }
+
+        if self.errors.len() < MAX_ERRORS {
+            self.errors.push(ParseError::Single(Box::new(error)));
+        } else {
+            self.hidden += 1;
+        }
+    }
+
+    fn finish(self) -> ParseResult<()> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ParseError::new_truncated(self.errors, self.hidden))
+        }
+    }
+}
+
 /*
 program = { statement } ;
 (* Statement *)
@@ -103,14 +395,28 @@ statement = block
             | print_statement
             | if_statement
             | while_statement
-            | return_statement ;
+            | for_statement
+            | return_statement
+            | function_declaration
+            | enum_declaration
+            | import_statement
+            | export_declaration ;
 block = "{" , { statement } , "}" ;
-variable_declaration = ( "let" | "const" ) , identifier , [ "=" , expression ] , ";" ;
+variable_declaration = ( "let" | "const" ) , identifier , [ annotation ] , [ "=" , expression ] , ";" ;
 expression_statement = expression , ";" ;
 print_statement = "print" , expression , ";" ;
 if_statement = "if" , "(" , expression , ")" , statement , [ "else" , statement ] ;
 while_statement = "while" , "(" , expression , ")" , statement ;
+for_statement = "for" , "(" , ( "let" | "const" ) , identifier , "in" , expression , ")" , statement ;
 return_statement = "return" , [ expression ] , ";" ;
+function_declaration = "function" , identifier , "(" , [ parameter_list ] , ")" , [ annotation ] , block ;
+parameter_list = parameter , { "," , parameter } ;
+parameter = identifier , [ annotation ] ;
+annotation = ":" , type ;
+type = "number" | "string" | "boolean" | "bool" | "null" | "array" | "object" | "function" | "enum" ;
+enum_declaration = "enum" , identifier , "{" , [ identifier , { "," , identifier } , [ "," ] ] , "}" ;
+import_statement = "import" , [ identifier , "from" ] , STRING ;
+export_declaration = "export" , ( variable_declaration | function_declaration | enum_declaration ) ;
 
 (* Expression *)
 expression = assignment_expression ;
@@ -120,18 +426,22 @@ conditional_expression = logical_or_expression , [ "?" , expression , ":" , cond
 (* BinaryExpression *)
 logical_or_expression = logical_and_expression , { "|" , logical_and_expression } ;
 logical_and_expression = equality_expression , { "&" , equality_expression } ;
-equality_expression = relational_expression , { ( "==" | "!=" ) , relational_expression } ;
+equality_expression = relational_expression , { ( "==" | "!=" | "===" | "!==" ) , relational_expression } ;
 relational_expression = additive_expression , { ( "<" | "<=" | ">" | ">=" ) , additive_expression } ;
 additive_expression = multiplicative_expression , { ( "+" | "-" ) , multiplicative_expression } ;
 multiplicative_expression = unary_expression , { ( "*" | "/" ) , unary_expression } ;
 
 (* UnaryExpression *)
-unary_expression = postfix_expression | ( (  "-" | "!" ) , unary_expression ) ;
+unary_expression = postfix_expression | ( (  "-" | "!" ) , unary_expression ) | "delete" , postfix_expression ; (* the target must be an element or property *)
 
 (* PostfixExpression *)
 postfix_expression = primary_expression , { "[" , expression , "]" | "." , identifier | "(" , [ argument_list ] , ")" } ;
 
-primary_expression = identifier | literal | "(" , expression , ")" ;
+primary_expression = identifier | literal | "(" , expression , ")" | match_expression ;
+match_expression = "match" , expression , "{" , match_arm , { "," , match_arm } , [ "," ] , "}" ; (* an arm must match every value *)
+match_arm = pattern , "=>" , expression ;
+pattern = single_pattern , { "|" , single_pattern } ;
+single_pattern = "_" | identifier , { "." , identifier } | [ "-" ] , literal | "[" , [ pattern , { "," , pattern } ] , "]" ;
 argument_list = expression , { "," , expression } ;
 assignment_operator = "=" | "+=" | "-=" | "*=" | "/=" ;
 identifier = letter , { letter | digit | "_" } ;
@@ -139,34 +449,31 @@ literal = NUMBER | STRING | BOOLEAN | NULL ;
 */
 
 impl<'a> Parser<'a> {
-    fn program(&mut self) -> ParseResult<Vec<Box<dyn Statement>>> {
+    fn program(&mut self) -> ParseResult<Vec<Statement>> {
         let mut statements = Vec::new();
-        let mut errors = Vec::new();
+        let mut report = Report::default();
 
-        while let Some(_) = self.peek() {
+        while self.peek().is_some() {
             match self.statement() {
                 Ok(statement) => {
                     statements.push(statement);
+                    report.parsed();
                 }
-                Err(ParseError::Single(err)) => {
-                    errors.push(ParseError::Single(err));
-                    self.synchronize();
-                }
-                Err(ParseError::Multiple(err)) => {
-                    errors.extend(err.errors);
+                Err(err) => {
+                    report.failed(err);
                     self.synchronize();
                 }
             }
         }
 
-        if !errors.is_empty() {
-            Err(ParseError::new_multiple(errors))
-        } else {
-            Ok(statements)
-        }
+        report.finish().map(|()| statements)
     }
 
-    fn statement(&mut self) -> ParseResult<Box<dyn Statement>> {
+    fn statement(&mut self) -> ParseResult<Statement> {
+        self.nested(Self::nested_statement)
+    }
+
+    fn nested_statement(&mut self) -> ParseResult<Statement> {
         if let Some(token) = self.peek() {
             match token.token_type {
                 TokenType::LeftBrace => {
@@ -183,28 +490,73 @@ impl<'a> Parser<'a> {
                     self.next();
                     self.while_statement()
                 }
+                TokenType::For => {
+                    self.next();
+                    self.for_statement()
+                }
                 TokenType::Return => {
                     self.next();
                     self.return_statement()
                 }
+                TokenType::Yield => self.yield_statement(),
+                TokenType::Defer => {
+                    self.next();
+                    self.defer_statement()
+                }
+                TokenType::Assert => {
+                    self.next();
+                    self.assert_statement()
+                }
+                TokenType::Test => self.test_declaration(),
+                TokenType::Function => {
+                    self.next();
+                    self.function_declaration()
+                }
+                TokenType::Enum => {
+                    self.next();
+                    self.enum_declaration()
+                }
+                TokenType::Import => {
+                    self.next();
+                    self.import_statement()
+                }
+                TokenType::Export => self.export_declaration(),
                 _ => self.expression_statement(),
             }
         } else {
-            Err(ParseError::new_unexpected_eof())
+            Err(self.unexpected_eof())
         }
     }
 
-    fn block(&mut self) -> ParseResult<Box<dyn Statement>> {
+    fn block(&mut self) -> ParseResult<Statement> {
+        // The `{` opening the block, which the error of a block left open is placed at.
+        let brace = self.span.clone();
+        let line = self.line();
         let mut statements = Vec::new();
         let mut errors = Vec::new();
 
         self.resolver.push();
 
-        while let Some(token) = self.peek() {
-            if token.token_type == TokenType::RightBrace {
-                self.next();
-                self.resolver.pop();
-                break;
+        loop {
+            match self.peek() {
+                Some(Token {
+                    token_type: TokenType::RightBrace,
+                    ..
+                }) => {
+                    self.next();
+                    break;
+                }
+                Some(_) => {}
+                None => {
+                    let error = ParseError::new_single(
+                        ErrorCode::UnexpectedEof,
+                        Some(line),
+                        "Unexpected end of file in the block".to_string(),
+                    )
+                    .with_expected(vec![TokenType::RightBrace]);
+                    errors.push(self.located(error, brace));
+                    break;
+                }
             }
 
             match self.statement() {
@@ -222,14 +574,16 @@ impl<'a> Parser<'a> {
             }
         }
 
+        self.resolver.pop();
+
         if !errors.is_empty() {
             Err(ParseError::new_multiple(errors))
         } else {
-            Ok(Box::new(BlockStatement { statements }))
+            Ok(Statement::Block(BlockStatement { statements, line }))
         }
     }
 
-    fn variable_declaration(&mut self) -> ParseResult<Box<dyn Statement>> {
+    fn variable_declaration(&mut self) -> ParseResult<Statement> {
         let mutable = if let Some(Token {
             token_type: TokenType::Let,
             ..
@@ -243,6 +597,7 @@ impl<'a> Parser<'a> {
         };
 
         let identifier = self.consume(TokenType::Identifier)?;
+        let annotation = self.annotation()?;
 
         let scope = self.resolver.declare(identifier.clone(), mutable);
 
@@ -257,21 +612,74 @@ impl<'a> Parser<'a> {
             None
         };
 
-        Ok(Box::new(VariableDeclaration {
+        Ok(Statement::Variable(VariableDeclaration {
             mutable,
             identifier,
+            annotation,
             initializer,
             scope,
         }))
     }
 
-    fn expression_statement(&mut self) -> ParseResult<Box<dyn Statement>> {
+    fn expression_statement(&mut self) -> ParseResult<Statement> {
+        let line = match self.peek() {
+            Some(token) => token.line,
+            None => self.line(),
+        };
+        let start = self.peek_span().start;
         let expression = self.expression()?;
 
-        Ok(Box::new(ExpressionStatement { expression }))
+        if let Some(name) = self.frozen_copy(&expression) {
+            let variable = match &expression {
+                Expression::Postfix(PostfixExpression {
+                    operator: PostfixOperator::Call(arguments),
+                    ..
+                }) => arguments.first().and_then(Expression::is_identifier),
+                _ => None,
+            };
+            let hint = match variable {
+                Some(variable) => format!("{} = {}({})", variable.value, name, variable.value),
+                None => format!("xs = {}(xs)", name),
+            };
+            let error = ParseError::new_single(
+                ErrorCode::UnusedResult,
+                Some(line),
+                format!(
+                    "The result of {} is unused, it freezes a copy and leaves its argument as it \
+                     is. Assign it instead, as in `{}`",
+                    name, hint
+                ),
+            );
+            return Err(self.located(error, start..self.span.end));
+        }
+
+        Ok(Statement::Expression(ExpressionStatement {
+            expression,
+            line,
+        }))
     }
 
-    fn print_statement(&mut self) -> ParseResult<Box<dyn Statement>> {
+    // The name of `freeze` or `deepFreeze` if `expression` calls one of the builtins, which give
+    // a frozen copy of their argument.
+    fn frozen_copy(&self, expression: &Expression) -> Option<&'static str> {
+        let Expression::Postfix(PostfixExpression {
+            left,
+            operator: PostfixOperator::Call(_),
+            ..
+        }) = expression
+        else {
+            return None;
+        };
+
+        let name = match left.is_identifier()?.name() {
+            "freeze" => "freeze",
+            "deepFreeze" => "deepFreeze",
+            _ => return None,
+        };
+        self.resolver.is_builtin(name).then_some(name)
+    }
+
+    fn print_statement(&mut self) -> ParseResult<Statement> {
         let new_line = if let Some(Token {
             token_type: TokenType::Println,
             ..
@@ -284,15 +692,18 @@ impl<'a> Parser<'a> {
             false
         };
 
+        let line = self.line();
         let expression = self.expression()?;
 
-        Ok(Box::new(PrintStatement {
+        Ok(Statement::Print(PrintStatement {
             new_line,
             expression,
+            line,
         }))
     }
 
-    fn if_statement(&mut self) -> ParseResult<Box<dyn Statement>> {
+    fn if_statement(&mut self) -> ParseResult<Statement> {
+        let line = self.line();
         let condition = self.expression()?;
 
         let then_branch = self.statement()?;
@@ -303,46 +714,502 @@ impl<'a> Parser<'a> {
         }) = self.peek()
         {
             self.next();
-            Some(self.statement()?)
+            Some(Box::new(self.statement()?))
         } else {
             None
         };
 
-        Ok(Box::new(IfStatement {
+        Ok(Statement::If(IfStatement {
             condition,
-            then_branch,
+            then_branch: Box::new(then_branch),
             else_branch,
+            line,
         }))
     }
 
-    fn while_statement(&mut self) -> ParseResult<Box<dyn Statement>> {
+    fn while_statement(&mut self) -> ParseResult<Statement> {
+        let line = self.line();
         let condition = self.expression()?;
 
         let body = self.statement()?;
 
-        Ok(Box::new(WhileStatement { condition, body }))
+        Ok(Statement::While(WhileStatement {
+            condition,
+            body: Box::new(body),
+            line,
+        }))
     }
 
-    fn return_statement(&mut self) -> ParseResult<Box<dyn Statement>> {
-        let value = if let Some(Token {
-            token_type: TokenType::Null,
+    fn for_statement(&mut self) -> ParseResult<Statement> {
+        self.consume(TokenType::LeftParentheses)?;
+
+        let mutable = match self.next() {
+            Some(Token {
+                token_type: TokenType::Let,
+                ..
+            }) => true,
+            Some(Token {
+                token_type: TokenType::Const,
+                ..
+            }) => false,
+            Some(Token { value, line, .. }) => {
+                let error = ParseError::new_single(
+                    ErrorCode::UnexpectedToken,
+                    Some(line),
+                    format!("Expected let or const after for, found: {}", value),
+                )
+                .with_expected(vec![TokenType::Let, TokenType::Const])
+                .with_found(self.lexeme(&self.span));
+
+                return Err(self.located(error, self.span.clone()));
+            }
+            None => return Err(self.unexpected_eof()),
+        };
+
+        let identifier = self.consume(TokenType::Identifier)?;
+
+        self.consume(TokenType::In)?;
+
+        let iterable = self.expression()?;
+
+        self.consume(TokenType::RightParentheses)?;
+
+        // The loop variable lives in its own scope around the body.
+        self.resolver.push();
+        self.resolver.declare(identifier.clone(), mutable);
+        let body = self.statement();
+        self.resolver.pop();
+
+        Ok(Statement::ForIn(ForInStatement {
+            mutable,
+            identifier,
+            iterable,
+            body: Box::new(body?),
+        }))
+    }
+
+    fn return_statement(&mut self) -> ParseResult<Statement> {
+        let line = self.line();
+        let value = self.optional_value()?;
+
+        Ok(Statement::Return(ReturnStatement { value, line }))
+    }
+
+    fn yield_statement(&mut self) -> ParseResult<Statement> {
+        let keyword = self.advance()?;
+
+        if !self.generator {
+            let error = ParseError::new_single(
+                ErrorCode::MisplacedYield,
+                Some(keyword.line),
+                "Yields are only allowed in generator functions, declared with function*"
+                    .to_string(),
+            );
+
+            return Err(self.located(error, self.span.clone()));
+        }
+
+        let value = self.optional_value()?;
+
+        Ok(Statement::Yield(YieldStatement {
+            value,
+            line: keyword.line,
+        }))
+    }
+
+    fn defer_statement(&mut self) -> ParseResult<Statement> {
+        let line = self.line();
+        let expression = self.expression()?;
+
+        Ok(Statement::Defer(DeferStatement {
+            expression: Rc::new(expression),
+            line,
+        }))
+    }
+
+    fn assert_statement(&mut self) -> ParseResult<Statement> {
+        let line = self.line();
+        let condition = self.expression()?;
+
+        Ok(Statement::Assert(AssertStatement { condition, line }))
+    }
+
+    fn test_declaration(&mut self) -> ParseResult<Statement> {
+        let keyword = self.advance()?;
+
+        if !self.resolver.is_global() {
+            let error = ParseError::new_single(
+                ErrorCode::MisplacedTest,
+                Some(keyword.line),
+                "Tests are only allowed at the top level, found one".to_string(),
+            );
+
+            return Err(self.located(error, self.span.clone()));
+        }
+
+        let name = self.consume(TokenType::String)?;
+        self.consume(TokenType::LeftBrace)?;
+        let body = self.block()?;
+
+        Ok(Statement::Test(TestDeclaration {
+            name,
+            body: Rc::new(body),
+        }))
+    }
+
+    // The value of a return or yield. There is none at the end of a block, or when it is `null`,
+    // which is how a statement without a value is written before another statement.
+    fn optional_value(&mut self) -> ParseResult<Option<Expression>> {
+        let value = match self.peek() {
+            Some(Token {
+                token_type: TokenType::Null,
+                ..
+            }) => {
+                self.next();
+                None
+            }
+            Some(Token {
+                token_type: TokenType::RightBrace,
+                ..
+            })
+            | None => None,
+            _ => Some(self.expression()?),
+        };
+
+        Ok(value)
+    }
+
+    fn import_statement(&mut self) -> ParseResult<Statement> {
+        let namespace = match self.peek() {
+            Some(Token {
+                token_type: TokenType::Identifier,
+                ..
+            }) => {
+                let namespace = self.advance()?;
+
+                // `from` is only a keyword here, so it can still be used as a name.
+                match self.next() {
+                    Some(token)
+                        if token.token_type == TokenType::Identifier
+                            && token.value.to_string() == "from" => {}
+                    Some(token) => return Err(self.unexpected(token, self.span.clone())),
+                    None => return Err(self.unexpected_eof()),
+                }
+
+                Some(namespace)
+            }
+            _ => None,
+        };
+
+        let path = self.consume(TokenType::String)?;
+        let line = path.line;
+        // Errors are placed at the path.
+        let at = |error| self.located(error, self.span.clone());
+        let failed = |err: &dyn Display| {
+            at(ParseError::new_single(
+                ErrorCode::ImportFailed,
+                Some(line),
+                format!("Cannot import {}", path.value),
+            )
+            .with_cause(err))
+        };
+
+        if !self.resolver.is_global() {
+            return Err(at(ParseError::new_single(
+                ErrorCode::MisplacedImport,
+                Some(line),
+                "Imports are only allowed at the top level, found one".to_string(),
+            )));
+        }
+
+        if self.imports.denied {
+            return Err(failed(&"imports are disabled in sandboxed interpreters"));
+        }
+
+        let module = canonical_module(&self.imports.directory.join(path.value.to_string()))
+            .map_err(|err| failed(&err))?;
+
+        if self.imports.chain.contains(&module) {
+            let chain = self
+                .imports
+                .chain
+                .iter()
+                .chain([&module])
+                .map(|path| path.display().to_string())
+                .collect::<Vec<String>>()
+                .join(" -> ");
+
+            return Err(at(ParseError::new_single(
+                ErrorCode::ImportCycle,
+                Some(line),
+                "Import cycle".to_string(),
+            )
+            .with_cause(chain)));
+        }
+
+        let source = read_module(&module).map_err(|err| failed(&err))?;
+
+        // A file imported with a namespace only sees the builtins. Otherwise it sees the globals
+        // declared so far, and its own globals are declared in the importing file.
+        let resolver = match namespace {
+            Some(_) => self.imports.builtins.clone(),
+            None => self.resolver.globals(),
+        };
+
+        let mut parser = Parser::new(&source, resolver);
+        parser.imports = Imports {
+            directory: module.parent().map(Path::to_path_buf).unwrap_or_default(),
+            chain: [self.imports.chain.clone(), vec![module.clone()]].concat(),
+            builtins: self.imports.builtins.clone(),
+            denied: false,
+        };
+
+        let statements = parser.parse().map_err(|err| failed(&err))?;
+
+        match namespace {
+            Some(ref namespace) => {
+                self.resolver.declare(namespace.clone(), false);
+            }
+            None => self.resolver.extend_globals(parser.resolver),
+        }
+
+        Ok(Statement::Import(ImportStatement {
+            path,
+            module,
+            statements,
+            namespace,
+            exports: parser.exports,
+        }))
+    }
+
+    fn export_declaration(&mut self) -> ParseResult<Statement> {
+        let export = self.advance()?;
+        let span = self.span.clone();
+
+        if !self.resolver.is_global() {
+            return Err(self.located(
+                ParseError::new_single(
+                    ErrorCode::MisplacedExport,
+                    Some(export.line),
+                    "Exports are only allowed at the top level, found one".to_string(),
+                ),
+                span,
+            ));
+        }
+
+        let declaration = match self.peek() {
+            Some(Token {
+                token_type: TokenType::Let | TokenType::Const,
+                ..
+            }) => self.variable_declaration()?,
+            Some(Token {
+                token_type: TokenType::Function,
+                ..
+            }) => {
+                self.next();
+                self.function_declaration()?
+            }
+            Some(Token {
+                token_type: TokenType::Enum,
+                ..
+            }) => {
+                self.next();
+                self.enum_declaration()?
+            }
+            _ => {
+                let error = ParseError::new_single(
+                    ErrorCode::MisplacedExport,
+                    Some(export.line),
+                    "Expected let, const, function or enum after export".to_string(),
+                )
+                .with_expected(vec![
+                    TokenType::Let,
+                    TokenType::Const,
+                    TokenType::Function,
+                    TokenType::Enum,
+                ]);
+
+                return Err(self.located(error, span));
+            }
+        };
+
+        if let Some(name) = declaration.declared_name() {
+            self.exports.push(name.value.to_string());
+        }
+
+        Ok(declaration)
+    }
+
+    fn function_declaration(&mut self) -> ParseResult<Statement> {
+        let generator = matches!(
+            self.peek(),
+            Some(Token {
+                token_type: TokenType::Star,
+                ..
+            })
+        );
+        if generator {
+            self.next();
+        }
+
+        let name = self.consume(TokenType::Identifier)?;
+
+        // Declared before the body so the function can call itself.
+        self.resolver.declare(name.clone(), false);
+
+        self.consume(TokenType::LeftParentheses)?;
+
+        let mut parameters = Vec::new();
+
+        if let Some(Token {
+            token_type: TokenType::RightParentheses,
             ..
         }) = self.peek()
         {
-            self.next();
-            None
         } else {
-            Some(self.expression()?)
-        };
+            loop {
+                let name = self.consume(TokenType::Identifier)?;
+                let annotation = self.annotation()?;
+                parameters.push(Parameter { name, annotation });
+
+                if let Some(Token {
+                    token_type: TokenType::Comma,
+                    ..
+                }) = self.peek()
+                {
+                    self.next();
+                } else {
+                    break;
+                }
+            }
+        }
+
+        self.consume(TokenType::RightParentheses)?;
+        let return_type = self.annotation()?;
+        self.consume(TokenType::LeftBrace)?;
+
+        self.resolver.push();
+        for parameter in &parameters {
+            self.resolver.declare(parameter.name.clone(), true);
+        }
+        let enclosing = std::mem::replace(&mut self.generator, generator);
+        let body = self.block();
+        self.generator = enclosing;
+        self.resolver.pop();
+
+        Ok(Statement::Function(FunctionDeclaration {
+            name,
+            generator,
+            parameters,
+            return_type,
+            body: Rc::from(body?),
+        }))
+    }
 
-        Ok(Box::new(ReturnStatement { value }))
+    fn enum_declaration(&mut self) -> ParseResult<Statement> {
+        let name = self.consume(TokenType::Identifier)?;
+        self.resolver.declare(name.clone(), false);
+
+        self.consume(TokenType::LeftBrace)?;
+
+        let mut variants: Vec<Token> = Vec::new();
+
+        while !matches!(
+            self.peek(),
+            Some(Token {
+                token_type: TokenType::RightBrace,
+                ..
+            })
+        ) {
+            let variant = self.consume(TokenType::Identifier)?;
+
+            if variants
+                .iter()
+                .any(|declared| declared.name() == variant.name())
+            {
+                let error = ParseError::new_single(
+                    ErrorCode::DuplicateVariant,
+                    Some(variant.line),
+                    format!(
+                        "Duplicate variant '{}' in enum '{}'",
+                        variant.name(),
+                        name.name()
+                    ),
+                );
+
+                return Err(self.located(error, self.span.clone()));
+            }
+            variants.push(variant);
+
+            // A comma may follow the last variant.
+            if let Some(Token {
+                token_type: TokenType::Comma,
+                ..
+            }) = self.peek()
+            {
+                self.next();
+            } else {
+                break;
+            }
+        }
+
+        self.consume(TokenType::RightBrace)?;
+
+        Ok(Statement::Enum(EnumDeclaration { name, variants }))
     }
 
-    fn expression(&mut self) -> ParseResult<Box<dyn Expression>> {
+    // The type after a `:`, if there is one. Most types are named by identifiers, but `bool`,
+    // `null`, `function` and `enum` are keywords.
+    fn annotation(&mut self) -> ParseResult<Option<ValueType>> {
+        let Some(Token {
+            token_type: TokenType::Colon,
+            ..
+        }) = self.peek()
+        else {
+            return Ok(None);
+        };
+        self.next();
+
+        let token = match self.peek() {
+            Some(Token {
+                token_type:
+                    TokenType::Bool | TokenType::Null | TokenType::Function | TokenType::Enum,
+                ..
+            }) => self.advance()?,
+            _ => self.consume(TokenType::Identifier)?,
+        };
+        let name = self.lexeme(&self.span.clone());
+
+        match ValueType::from_name(&name) {
+            Some(value_type) => Ok(Some(value_type)),
+            None => {
+                let types: Vec<&str> = ValueType::ALL.iter().map(ValueType::name).collect();
+                let error = ParseError::new_single(
+                    ErrorCode::UnknownType,
+                    Some(token.line),
+                    format!(
+                        "Unknown type '{}', expected one of {}",
+                        name,
+                        types.join(", ")
+                    ),
+                );
+
+                Err(self.located(error, self.span.clone()))
+            }
+        }
+    }
+
+    fn expression(&mut self) -> ParseResult<Expression> {
         self.assignment_expression()
     }
 
-    fn assignment_expression(&mut self) -> ParseResult<Box<dyn Expression>> {
+    fn assignment_expression(&mut self) -> ParseResult<Expression> {
+        self.nested(Self::nested_assignment_expression)
+    }
+
+    fn nested_assignment_expression(&mut self) -> ParseResult<Expression> {
+        // Where the target starts, for the errors of assigning to it.
+        let target = self.peek_span();
         let mut expression = self.conditional_expression()?;
 
         if let Some(Token {
@@ -356,31 +1223,54 @@ impl<'a> Parser<'a> {
         }) = self.peek()
         {
             if let Some(identifier) = expression.is_identifier() {
-                let scope = self.resolver.define(identifier.clone())?;
-                let operator = self.next().unwrap().token_type;
+                let scope = self
+                    .resolver
+                    .define(identifier.clone())
+                    .map_err(|error| self.located(error, target))?;
+                let operator = self.advance()?.token_type;
                 let value = self.assignment_expression()?;
 
-                expression = Box::new(AssignmentExpression {
+                expression = Expression::Assignment(AssignmentExpression {
                     identifier,
                     operator,
-                    value,
+                    value: Box::new(value),
                     scope,
                 })
+            } else if let Some((identifier, path)) = expression.into_target() {
+                self.resolver
+                    .define(identifier.clone())
+                    .map_err(|error| self.located(error, target))?;
+                let operator = self.advance()?;
+                let value = self.assignment_expression()?;
+
+                expression = Expression::PropertyAssignment(PropertyAssignment {
+                    identifier,
+                    path,
+                    operator,
+                    value: Box::new(value),
+                })
             } else {
                 let Token {
                     token_type, line, ..
-                } = self.next().unwrap();
-                return Err(ParseError::new_single(format!(
-                    "Expected identifier before {} at line {}",
-                    token_type, line
-                )));
+                } = self.advance()?;
+                let error = ParseError::new_single(
+                    ErrorCode::InvalidAssignmentTarget,
+                    Some(line),
+                    format!(
+                        "Expected identifier, element or property before {}",
+                        token_type
+                    ),
+                )
+                .with_found(self.lexeme(&self.span));
+
+                return Err(self.located(error, self.span.clone()));
             }
         }
 
         Ok(expression)
     }
 
-    fn conditional_expression(&mut self) -> ParseResult<Box<dyn Expression>> {
+    fn conditional_expression(&mut self) -> ParseResult<Expression> {
         let mut expression = self.logical_or_expression()?;
 
         if let Some(Token {
@@ -394,19 +1284,19 @@ impl<'a> Parser<'a> {
 
             self.consume(TokenType::Colon)?;
 
-            let else_branch = self.conditional_expression()?;
+            let else_branch = self.nested(Self::conditional_expression)?;
 
-            expression = Box::new(ConditionalExpression {
-                condition: expression,
-                then_branch,
-                else_branch,
+            expression = Expression::Conditional(ConditionalExpression {
+                condition: Box::new(expression),
+                then_branch: Box::new(then_branch),
+                else_branch: Box::new(else_branch),
             });
         }
 
         Ok(expression)
     }
 
-    fn logical_or_expression(&mut self) -> ParseResult<Box<dyn Expression>> {
+    fn logical_or_expression(&mut self) -> ParseResult<Expression> {
         let mut expression = self.logical_and_expression()?;
 
         while let Some(Token {
@@ -414,20 +1304,20 @@ impl<'a> Parser<'a> {
             ..
         }) = self.peek()
         {
-            let operator = self.next().unwrap();
+            let operator = self.advance()?;
             let right = self.logical_and_expression()?;
 
-            expression = Box::new(BinaryExpression {
-                left: expression,
+            expression = Expression::Binary(BinaryExpression {
+                left: Box::new(expression),
                 operator,
-                right,
+                right: Box::new(right),
             });
         }
 
         Ok(expression)
     }
 
-    fn logical_and_expression(&mut self) -> ParseResult<Box<dyn Expression>> {
+    fn logical_and_expression(&mut self) -> ParseResult<Expression> {
         let mut expression = self.equality_expression()?;
 
         while let Some(Token {
@@ -435,41 +1325,45 @@ impl<'a> Parser<'a> {
             ..
         }) = self.peek()
         {
-            let operator = self.next().unwrap();
+            let operator = self.advance()?;
             let right = self.equality_expression()?;
 
-            expression = Box::new(BinaryExpression {
-                left: expression,
+            expression = Expression::Binary(BinaryExpression {
+                left: Box::new(expression),
                 operator,
-                right,
+                right: Box::new(right),
             });
         }
 
         Ok(expression)
     }
 
-    fn equality_expression(&mut self) -> ParseResult<Box<dyn Expression>> {
+    fn equality_expression(&mut self) -> ParseResult<Expression> {
         let mut expression = self.relational_expression()?;
 
         while let Some(Token {
-            token_type: TokenType::EqualEqual | TokenType::BangEqual,
+            token_type:
+                TokenType::EqualEqual
+                | TokenType::BangEqual
+                | TokenType::EqualEqualEqual
+                | TokenType::BangEqualEqual,
             ..
         }) = self.peek()
         {
-            let operator = self.next().unwrap();
+            let operator = self.advance()?;
             let right = self.relational_expression()?;
 
-            expression = Box::new(BinaryExpression {
-                left: expression,
+            expression = Expression::Binary(BinaryExpression {
+                left: Box::new(expression),
                 operator,
-                right,
+                right: Box::new(right),
             });
         }
 
         Ok(expression)
     }
 
-    fn relational_expression(&mut self) -> ParseResult<Box<dyn Expression>> {
+    fn relational_expression(&mut self) -> ParseResult<Expression> {
         let mut expression = self.additive_expression()?;
 
         while let Some(Token {
@@ -478,20 +1372,20 @@ impl<'a> Parser<'a> {
             ..
         }) = self.peek()
         {
-            let operator = self.next().unwrap();
+            let operator = self.advance()?;
             let right = self.additive_expression()?;
 
-            expression = Box::new(BinaryExpression {
-                left: expression,
+            expression = Expression::Binary(BinaryExpression {
+                left: Box::new(expression),
                 operator,
-                right,
+                right: Box::new(right),
             });
         }
 
         Ok(expression)
     }
 
-    fn additive_expression(&mut self) -> ParseResult<Box<dyn Expression>> {
+    fn additive_expression(&mut self) -> ParseResult<Expression> {
         let mut expression = self.multiplicative_expression()?;
 
         while let Some(Token {
@@ -499,20 +1393,20 @@ impl<'a> Parser<'a> {
             ..
         }) = self.peek()
         {
-            let operator = self.next().unwrap();
+            let operator = self.advance()?;
             let right = self.multiplicative_expression()?;
 
-            expression = Box::new(BinaryExpression {
-                left: expression,
+            expression = Expression::Binary(BinaryExpression {
+                left: Box::new(expression),
                 operator,
-                right,
+                right: Box::new(right),
             });
         }
 
         Ok(expression)
     }
 
-    fn multiplicative_expression(&mut self) -> ParseResult<Box<dyn Expression>> {
+    fn multiplicative_expression(&mut self) -> ParseResult<Expression> {
         let mut expression = self.unary_expression()?;
 
         while let Some(Token {
@@ -520,63 +1414,108 @@ impl<'a> Parser<'a> {
             ..
         }) = self.peek()
         {
-            let operator = self.next().unwrap();
+            let operator = self.advance()?;
             let right = self.unary_expression()?;
 
-            expression = Box::new(BinaryExpression {
-                left: expression,
+            expression = Expression::Binary(BinaryExpression {
+                left: Box::new(expression),
                 operator,
-                right,
+                right: Box::new(right),
             });
         }
 
         Ok(expression)
     }
 
-    fn unary_expression(&mut self) -> ParseResult<Box<dyn Expression>> {
+    fn unary_expression(&mut self) -> ParseResult<Expression> {
+        if let Some(Token {
+            token_type: TokenType::Delete,
+            ..
+        }) = self.peek()
+        {
+            return self.delete_expression();
+        }
+
         if let Some(Token {
             token_type: TokenType::Minus | TokenType::Bang,
             ..
         }) = self.peek()
         {
-            let operator = self.next().unwrap();
-            let right = self.unary_expression()?;
+            let operator = self.advance()?;
+            let right = self.nested(Self::unary_expression)?;
 
-            Ok(Box::new(UnaryExpression { operator, right }))
+            Ok(Expression::Unary(UnaryExpression {
+                operator,
+                right: Box::new(right),
+            }))
         } else {
             self.postfix_expression()
         }
     }
 
-    fn postfix_expression(&mut self) -> ParseResult<Box<dyn Expression>> {
+    fn delete_expression(&mut self) -> ParseResult<Expression> {
+        let token = self.advance()?;
+        // Where the target starts, for the errors of deleting it.
+        let target = self.peek_span();
+        let expression = self.postfix_expression()?;
+
+        match expression.into_target() {
+            Some((identifier, path)) if !path.is_empty() => {
+                self.resolver
+                    .define(identifier.clone())
+                    .map_err(|error| self.located(error, target))?;
+
+                Ok(Expression::Delete(DeleteExpression {
+                    token,
+                    identifier,
+                    path,
+                }))
+            }
+            _ => {
+                let span = target.start..self.span.end;
+                let error = ParseError::new_single(
+                    ErrorCode::InvalidAssignmentTarget,
+                    Some(token.line),
+                    "Expected element or property after delete".to_string(),
+                )
+                .with_found(self.lexeme(&span));
+
+                Err(self.located(error, span))
+            }
+        }
+    }
+
+    fn postfix_expression(&mut self) -> ParseResult<Expression> {
         let mut expression = self.primary_expression()?;
 
         while let Some(Token { token_type, .. }) = self.peek() {
             match token_type {
                 TokenType::LeftBracket => {
-                    self.next();
+                    let token = self.advance()?;
 
                     let index = self.expression()?;
 
                     self.consume(TokenType::RightBracket)?;
 
-                    expression = Box::new(PostfixExpression {
-                        left: expression,
-                        operator: PostfixOperator::Index(index),
+                    expression = Expression::Postfix(PostfixExpression {
+                        left: Box::new(expression),
+                        operator: PostfixOperator::Index(Box::new(index)),
+                        token,
                     });
                 }
                 TokenType::Dot => {
-                    self.next();
+                    let token = self.advance()?;
 
                     let name = self.consume(TokenType::Identifier)?;
 
-                    expression = Box::new(PostfixExpression {
-                        left: expression,
+                    expression = Expression::Postfix(PostfixExpression {
+                        left: Box::new(expression),
                         operator: PostfixOperator::Dot(name.value.to_string()),
+                        token,
                     });
                 }
                 TokenType::LeftParentheses => {
-                    self.next();
+                    let token = self.advance()?;
 
                     let arguments = if let Some(token) = self.peek() {
                         if token.token_type == TokenType::RightParentheses {
@@ -586,7 +1525,6 @@ impl<'a> Parser<'a> {
 
                             loop {
                                 arguments.push(self.expression()?);
-                                println!("{}", self.peek().unwrap().value.to_string());
 
                                 match self.peek() {
                                     Some(Token {
@@ -602,10 +1540,23 @@ impl<'a> Parser<'a> {
                                         self.next();
                                     }
                                     Some(token) => {
-                                        return Err(ParseError::new_single(format!(
-                                            "Expected ')' or ',' after argument, found: {}",
-                                            token.value
-                                        )))
+                                        let error = ParseError::new_single(
+                                            ErrorCode::MissingToken,
+                                            Some(token.line),
+                                            format!(
+                                                "Expected ')' or ',' after argument, found: {}",
+                                                token.value
+                                            ),
+                                        )
+                                        .with_expected(vec![
+                                            TokenType::RightParentheses,
+                                            TokenType::Comma,
+                                        ]);
+                                        let span = self.peek_span();
+
+                                        return Err(self
+                                            .located(error, span.clone())
+                                            .with_found(self.lexeme(&span)));
                                     }
                                     None => break,
                                 }
@@ -619,9 +1570,10 @@ impl<'a> Parser<'a> {
 
                     self.consume(TokenType::RightParentheses)?;
 
-                    expression = Box::new(PostfixExpression {
-                        left: expression,
+                    expression = Expression::Postfix(PostfixExpression {
+                        left: Box::new(expression),
                         operator: PostfixOperator::Call(arguments.unwrap_or(Vec::new())),
+                        token,
                     });
                 }
                 _ => {
@@ -633,7 +1585,7 @@ impl<'a> Parser<'a> {
         Ok(expression)
     }
 
-    fn primary_expression(&mut self) -> ParseResult<Box<dyn Expression>> {
+    fn primary_expression(&mut self) -> ParseResult<Expression> {
         if let Some(Token {
             token_type,
             value,
@@ -642,13 +1594,15 @@ impl<'a> Parser<'a> {
         {
             match token_type {
                 TokenType::Identifier => {
-                    self.resolver.resolve(Token {
-                        token_type,
-                        value: value.clone(),
-                        line,
-                    })?;
+                    self.resolver
+                        .resolve(Token {
+                            token_type,
+                            value: value.clone(),
+                            line,
+                        })
+                        .map_err(|error| self.located(error, self.span.clone()))?;
 
-                    Ok(Box::new(Identifier {
+                    Ok(Expression::Identifier(Identifier {
                         identifier: Token {
                             token_type,
                             value,
@@ -656,9 +1610,11 @@ impl<'a> Parser<'a> {
                         },
                     }))
                 }
-                TokenType::Number | TokenType::String | TokenType::True | TokenType::False => {
-                    Ok(Box::new(value))
-                }
+                TokenType::Number
+                | TokenType::String
+                | TokenType::True
+                | TokenType::False
+                | TokenType::Null => Ok(Expression::Literal(value)),
                 TokenType::LeftParentheses => {
                     let expression = self.expression()?;
 
@@ -666,6 +1622,11 @@ impl<'a> Parser<'a> {
 
                     Ok(expression)
                 }
+                TokenType::Match => self.match_expression(Token {
+                    token_type,
+                    value,
+                    line,
+                }),
                 TokenType::LeftBracket => {
                     let mut elements = Vec::new();
 
@@ -693,10 +1654,20 @@ impl<'a> Parser<'a> {
                                     self.next();
                                 }
                                 Some(token) => {
-                                    return Err(ParseError::new_single(format!(
-                                        "Expected ']' or ',' after element, found: {}",
-                                        token.value
-                                    )))
+                                    let error = ParseError::new_single(
+                                        ErrorCode::MissingToken,
+                                        Some(token.line),
+                                        format!(
+                                            "Expected ']' or ',' after element, found: {}",
+                                            token.value
+                                        ),
+                                    )
+                                    .with_expected(vec![TokenType::RightBracket, TokenType::Comma]);
+                                    let span = self.peek_span();
+
+                                    return Err(self
+                                        .located(error, span.clone())
+                                        .with_found(self.lexeme(&span)));
                                 }
                                 None => break,
                             }
@@ -705,28 +1676,370 @@ impl<'a> Parser<'a> {
                         self.consume(TokenType::RightBracket)?;
                     }
 
-                    Ok(Box::new(ArrayLiteral { elements }))
+                    Ok(Expression::Array(ArrayLiteral { elements }))
+                }
+                _ => {
+                    let error = ParseError::new_single(
+                        ErrorCode::ExpectedExpression,
+                        Some(line),
+                        format!(
+                            "Expected identifier, number, string, true, false or '(' after: {}",
+                            value
+                        ),
+                    )
+                    .with_expected(vec![
+                        TokenType::Identifier,
+                        TokenType::Number,
+                        TokenType::String,
+                        TokenType::True,
+                        TokenType::False,
+                        TokenType::LeftParentheses,
+                    ])
+                    .with_found(self.lexeme(&self.span));
+
+                    Err(self.located(error, self.span.clone()))
                 }
-                _ => Err(ParseError::new_single(format!(
-                    "Expected identifier, number, string, true, false or '(' after: {} at line {}",
-                    value, line
-                ))),
             }
         } else {
-            Err(ParseError::new_unexpected_eof())
+            Err(self.unexpected_eof())
+        }
+    }
+}
+
+impl<'a> Parser<'a> {
+    fn match_expression(&mut self, token: Token) -> ParseResult<Expression> {
+        let keyword = self.span.clone();
+        let value = self.expression()?;
+        self.consume(TokenType::LeftBrace)?;
+
+        let mut arms = Vec::new();
+
+        while !matches!(
+            self.peek(),
+            Some(Token {
+                token_type: TokenType::RightBrace,
+                ..
+            })
+        ) {
+            let start = self.peek_span().start;
+            let pattern = self.pattern()?;
+
+            let bindings = pattern.bindings();
+            if let Some((index, name)) = bindings.iter().enumerate().find(|(index, name)| {
+                bindings[..*index]
+                    .iter()
+                    .any(|declared| declared.name() == name.name())
+            }) {
+                let error = ParseError::new_single(
+                    ErrorCode::InvalidPattern,
+                    Some(bindings[index].line),
+                    format!(
+                        "'{}' is declared more than once in the pattern",
+                        name.name()
+                    ),
+                );
+                return Err(self.located(error, start..self.span.end));
+            }
+
+            self.consume(TokenType::Arrow)?;
+
+            // The names the pattern declares are only visible in its arm.
+            self.resolver.push();
+            for name in bindings {
+                self.resolver.declare(name.clone(), false);
+            }
+            let body = self.expression();
+            self.resolver.pop();
+
+            arms.push(MatchArm {
+                pattern,
+                body: body?,
+            });
+
+            // A comma may follow the last arm.
+            if let Some(Token {
+                token_type: TokenType::Comma,
+                ..
+            }) = self.peek()
+            {
+                self.next();
+            } else {
+                break;
+            }
+        }
+
+        self.consume(TokenType::RightBrace)?;
+
+        let expression = MatchExpression {
+            token,
+            value: Box::new(value),
+            arms,
+        };
+
+        if !expression.is_exhaustive() {
+            let error = ParseError::new_single(
+                ErrorCode::NonExhaustiveMatch,
+                Some(expression.token.line),
+                "Match is not exhaustive, add a '_' arm for the values no other arm matches"
+                    .to_string(),
+            );
+            return Err(self.located(error, keyword));
+        }
+
+        Ok(Expression::Match(expression))
+    }
+
+    fn pattern(&mut self) -> ParseResult<Pattern> {
+        let start = self.peek_span().start;
+        let first = self.single_pattern()?;
+
+        if !matches!(
+            self.peek(),
+            Some(Token {
+                token_type: TokenType::Or,
+                ..
+            })
+        ) {
+            return Ok(first);
+        }
+
+        let mut alternatives = vec![first];
+        while let Some(Token {
+            token_type: TokenType::Or,
+            ..
+        }) = self.peek()
+        {
+            self.next();
+            alternatives.push(self.single_pattern()?);
+        }
+
+        let pattern = Pattern::Alternatives(alternatives);
+        // Each alternative would have to declare the same names.
+        if let Some(name) = pattern.bindings().first() {
+            let error = ParseError::new_single(
+                ErrorCode::InvalidPattern,
+                Some(name.line),
+                format!(
+                    "Patterns joined with '|' cannot declare names, found '{}'",
+                    name.name()
+                ),
+            );
+            return Err(self.located(error, start..self.span.end));
+        }
+
+        Ok(pattern)
+    }
+
+    fn single_pattern(&mut self) -> ParseResult<Pattern> {
+        let token = self.advance()?;
+
+        match token.token_type {
+            TokenType::Identifier if token.name() == "_" => Ok(Pattern::Wildcard),
+            TokenType::Identifier => {
+                if !matches!(
+                    self.peek(),
+                    Some(Token {
+                        token_type: TokenType::Dot,
+                        ..
+                    })
+                ) {
+                    return Ok(Pattern::Binding(token));
+                }
+
+                self.resolver
+                    .resolve(token.clone())
+                    .map_err(|error| self.located(error, self.span.clone()))?;
+
+                let mut constant = Expression::Identifier(Identifier { identifier: token });
+                while let Some(Token {
+                    token_type: TokenType::Dot,
+                    ..
+                }) = self.peek()
+                {
+                    let dot = self.advance()?;
+                    let name = self.consume(TokenType::Identifier)?;
+                    constant = Expression::Postfix(PostfixExpression {
+                        left: Box::new(constant),
+                        operator: PostfixOperator::Dot(name.name().to_string()),
+                        token: dot,
+                    });
+                }
+
+                Ok(Pattern::Constant(Box::new(constant)))
+            }
+            TokenType::Number
+            | TokenType::String
+            | TokenType::True
+            | TokenType::False
+            | TokenType::Null => Ok(Pattern::Literal(token.value)),
+            TokenType::Minus
+                if matches!(
+                    self.peek(),
+                    Some(Token {
+                        token_type: TokenType::Number,
+                        ..
+                    })
+                ) =>
+            {
+                let number = self.advance()?;
+                Ok(Pattern::Literal(Value::Number(
+                    -number.value.as_number().unwrap_or_default(),
+                )))
+            }
+            TokenType::LeftBracket => {
+                let mut elements = Vec::new();
+
+                if let Some(Token {
+                    token_type: TokenType::RightBracket,
+                    ..
+                }) = self.peek()
+                {
+                } else {
+                    loop {
+                        elements.push(self.pattern()?);
+
+                        if let Some(Token {
+                            token_type: TokenType::Comma,
+                            ..
+                        }) = self.peek()
+                        {
+                            self.next();
+                        } else {
+                            break;
+                        }
+                    }
+                }
+
+                self.consume(TokenType::RightBracket)?;
+
+                Ok(Pattern::Array(elements))
+            }
+            _ => {
+                let error = ParseError::new_single(
+                    ErrorCode::InvalidPattern,
+                    Some(token.line),
+                    format!("Expected a pattern, found: {}", token.value),
+                )
+                .with_expected(vec![
+                    TokenType::Identifier,
+                    TokenType::Number,
+                    TokenType::String,
+                    TokenType::True,
+                    TokenType::False,
+                    TokenType::Null,
+                    TokenType::LeftBracket,
+                ])
+                .with_found(self.lexeme(&self.span));
+
+                Err(self.located(error, self.span.clone()))
+            }
         }
     }
 }
 
-pub fn parse(source: &[u8]) -> ParseResult<Vec<Box<dyn Statement>>> {
-    let mut parser = Parser::new(source);
+/// Parse a program that only uses the names it declares itself.
+pub fn parse(source: &[u8]) -> ParseResult<Vec<Statement>> {
+    parse_with_resolver(source, Resolver::new())
+}
+
+// Parse with a resolver that may already know some names, e.g. the globals of an interpreter.
+pub(crate) fn parse_with_resolver(
+    source: &[u8],
+    resolver: Resolver,
+) -> ParseResult<Vec<Statement>> {
+    let mut parser = Parser::new(source, resolver);
+
+    parser.parse()
+}
+
+// Parse a file, resolving its imports relative to the file's directory.
+pub(crate) fn parse_with_imports(
+    source: &[u8],
+    resolver: Resolver,
+    imports: Imports,
+) -> ParseResult<Vec<Statement>> {
+    let mut parser = Parser::new(source, resolver);
+    parser.imports = imports;
 
     parser.parse()
 }
 
+/// Version of the JSON written by [`parse_to_json`], raised whenever programs written by an older
+/// version can no longer be read.
+pub const AST_VERSION: u32 = serialize::VERSION;
+
+/// Parse a program into JSON, for tools that work with its syntax tree.
+///
+/// The program is `{ "version": AST_VERSION, "statements": [...] }`. Statements and expressions
+/// are objects with a `kind`, the name of their variant in [`Statement`] or [`Expression`], and
+/// the fields of the node it holds, e.g. `{ "kind": "Print", "expression": ..., "new_line": true }`.
+/// Literals are `{ "kind": "Literal", "value": ... }`, and postfix operators have a kind of
+/// `Index`, `Dot` or `Call` and their operand as the value. Tokens are
+/// `{ "type": ..., "value": ..., "line": ... }`, with the name of their [`TokenType`] variant as
+/// the type.
+///
+/// The names are those of the AST types, so renaming a variant or a field changes the format. The
+/// files in tests/ast hold the JSON of a few programs to catch that.
+pub fn parse_to_json(source: &[u8]) -> ParseResult<String> {
+    let statements = parse(source)?;
+    let program = serialize::Program {
+        version: AST_VERSION,
+        statements: &statements,
+    };
+
+    serde_json::to_string_pretty(&program).map_err(|err| {
+        ParseError::new_single(
+            ErrorCode::InvalidAst,
+            None,
+            format!("Cannot serialize the program: {}", err),
+        )
+    })
+}
+
+/// Read the statements of a program written by [`parse_to_json`]. Programs of another version
+/// are rejected. The statements are not checked, run them with `Interpreter::run_ast` to have
+/// their names resolved as in a parsed program.
+///
+/// Nesting deeper than 128 levels, e.g. a chain of that many `+`, is rejected by the JSON reader.
+pub fn program_from_json(json: &str) -> ParseResult<Vec<Statement>> {
+    let invalid = |err: serde_json::Error| {
+        ParseError::new_single(ErrorCode::InvalidAst, None, format!("Invalid AST: {}", err))
+    };
+
+    let header: serialize::Header = serde_json::from_str(json).map_err(invalid)?;
+    if header.version != AST_VERSION {
+        return Err(ParseError::new_single(
+            ErrorCode::InvalidAst,
+            None,
+            format!(
+                "Unsupported AST version {}, expected {}",
+                header.version, AST_VERSION
+            ),
+        ));
+    }
+
+    let program: serialize::OwnedProgram = serde_json::from_str(json).map_err(invalid)?;
+
+    Ok(program.statements)
+}
+
+/// Parse a single expression that only uses the names it declares itself.
+pub fn parse_expression(source: &[u8]) -> ParseResult<Expression> {
+    parse_expression_with_resolver(source, Resolver::new())
+}
+
+pub(crate) fn parse_expression_with_resolver(
+    source: &[u8],
+    resolver: Resolver,
+) -> ParseResult<Expression> {
+    let mut parser = Parser::new(source, resolver);
+
+    parser.parse_expression()
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::common::value::Value;
+    use crate::common::{environment::Environment, value::Value};
 
     use super::*;
 
@@ -734,7 +2047,7 @@ mod tests {
     fn test_parse_number() {
         let source = b"42";
         let statements = parse(source).unwrap();
-        let expected = vec![Box::new(Value::Number(42.0))];
+        let expected = [Expression::Literal(Value::Number(42.0))];
 
         for (i, statement) in statements.iter().enumerate() {
             assert_eq!(*statement.node_to_string(), expected[i].node_to_string());
@@ -745,7 +2058,7 @@ mod tests {
     fn test_parse_string() {
         let source = br#""hello, world!""#;
         let statements = parse(source).unwrap();
-        let expected = vec![Box::new(Value::String(String::from("hello, world!")))];
+        let expected = [Expression::Literal(Value::String("hello, world!".into()))];
 
         for (i, statement) in statements.iter().enumerate() {
             assert_eq!(*statement.node_to_string(), expected[i].node_to_string());
@@ -756,7 +2069,7 @@ mod tests {
     fn test_parse_true() {
         let source = b"true";
         let statements = parse(source).unwrap();
-        let expected = vec![Box::new(Value::Boolean(true))];
+        let expected = [Expression::Literal(Value::Boolean(true))];
 
         for (i, statement) in statements.iter().enumerate() {
             assert_eq!(*statement.node_to_string(), expected[i].node_to_string());
@@ -767,7 +2080,7 @@ mod tests {
     fn test_parse_false() {
         let source = b"false";
         let statements = parse(source).unwrap();
-        let expected = vec![Box::new(Value::Boolean(false))];
+        let expected = [Expression::Literal(Value::Boolean(false))];
 
         for (i, statement) in statements.iter().enumerate() {
             assert_eq!(*statement.node_to_string(), expected[i].node_to_string());
@@ -778,7 +2091,7 @@ mod tests {
     fn test_parse_parentheses() {
         let source = b"(42)";
         let statements = parse(source).unwrap();
-        let expected = vec![Box::new(Value::Number(42.0))];
+        let expected = [Expression::Literal(Value::Number(42.0))];
 
         for (i, statement) in statements.iter().enumerate() {
             assert_eq!(*statement.node_to_string(), expected[i].node_to_string());
@@ -789,25 +2102,27 @@ mod tests {
     fn test_parse_index() {
         let source = br#"let foo = "012345" foo[4]"#;
         let statements = parse(source).unwrap();
-        let expected_declaration = Box::new(VariableDeclaration {
+        let expected_declaration = Statement::Variable(VariableDeclaration {
             mutable: true,
             identifier: Token {
                 token_type: TokenType::Identifier,
-                value: Value::String(String::from("foo")),
+                value: Value::String("foo".into()),
                 line: 1,
             },
-            initializer: Some(Box::new(Value::String(String::from("012345")))),
+            annotation: None,
+            initializer: Some(Expression::Literal(Value::String("012345".into()))),
             scope: 0,
         });
-        let expected_index = Box::new(PostfixExpression {
-            left: Box::new(Identifier {
+        let expected_index = Expression::Postfix(PostfixExpression {
+            left: Box::new(Expression::Identifier(Identifier {
                 identifier: Token {
                     token_type: TokenType::Identifier,
-                    value: Value::String(String::from("foo")),
+                    value: Value::String("foo".into()),
                     line: 1,
                 },
-            }),
-            operator: PostfixOperator::Index(Box::new(Value::Number(4.0))),
+            })),
+            operator: PostfixOperator::Index(Box::new(Expression::Literal(Value::Number(4.0)))),
+            token: Token::new(TokenType::LeftBracket, Value::String("[".into()), 1),
         });
 
         assert_eq!(
@@ -824,25 +2139,27 @@ mod tests {
     fn test_parse_dot() {
         let source = br#"let foo = "foo" foo.length"#;
         let statements = parse(source).unwrap();
-        let expected_declaration = Box::new(VariableDeclaration {
+        let expected_declaration = Statement::Variable(VariableDeclaration {
             mutable: true,
             identifier: Token {
                 token_type: TokenType::Identifier,
-                value: Value::String(String::from("foo")),
+                value: Value::String("foo".into()),
                 line: 1,
             },
-            initializer: Some(Box::new(Value::String(String::from("foo")))),
+            annotation: None,
+            initializer: Some(Expression::Literal(Value::String("foo".into()))),
             scope: 0,
         });
-        let expected_dot = Box::new(PostfixExpression {
-            left: Box::new(Identifier {
+        let expected_dot = Expression::Postfix(PostfixExpression {
+            left: Box::new(Expression::Identifier(Identifier {
                 identifier: Token {
                     token_type: TokenType::Identifier,
-                    value: Value::String(String::from("foo")),
+                    value: Value::String("foo".into()),
                     line: 1,
                 },
-            }),
+            })),
             operator: PostfixOperator::Dot(String::from("length")),
+            token: Token::new(TokenType::Dot, Value::String(".".into()), 1),
         });
 
         assert_eq!(
@@ -859,13 +2176,13 @@ mod tests {
     fn test_parse_unary_minus() {
         let source = b"-42";
         let statements = parse(source).unwrap();
-        let expected = vec![Box::new(UnaryExpression {
+        let expected = [Expression::Unary(UnaryExpression {
             operator: Token {
                 token_type: TokenType::Minus,
-                value: Value::String(String::from("-")),
+                value: Value::String("-".into()),
                 line: 1,
             },
-            right: Box::new(Value::Number(42.0)),
+            right: Box::new(Expression::Literal(Value::Number(42.0))),
         })];
 
         for (i, statement) in statements.iter().enumerate() {
@@ -877,17 +2194,410 @@ mod tests {
     fn test_parse_unary_not() {
         let source = b"!true";
         let statements = parse(source).unwrap();
-        let expected = vec![Box::new(UnaryExpression {
+        let expected = [Expression::Unary(UnaryExpression {
             operator: Token {
                 token_type: TokenType::Bang,
-                value: Value::String(String::from("!")),
+                value: Value::String("!".into()),
                 line: 1,
             },
-            right: Box::new(Value::Boolean(true)),
+            right: Box::new(Expression::Literal(Value::Boolean(true))),
         })];
 
         for (i, statement) in statements.iter().enumerate() {
             assert_eq!(*statement.node_to_string(), expected[i].node_to_string())
         }
     }
+
+    #[test]
+    fn test_parse_expression() {
+        let expression = parse_expression(b"1 + 2 * 3").unwrap();
+        assert_eq!(
+            expression.node_to_string(),
+            parse(b"1 + 2 * 3").unwrap()[0].node_to_string()
+        );
+        assert!(parse_expression(b"1 + 2 3").is_err());
+        assert!(parse_expression(b"let y = 3").is_err());
+        assert!(parse_expression(b"").is_err());
+    }
+
+    #[test]
+    fn test_parse_nesting() {
+        let nested = |depth: usize| format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+
+        assert!(parse(nested(50).as_bytes()).is_ok());
+        let err = parse(nested(100_000).as_bytes()).unwrap_err();
+        assert!(err.to_string().contains("Nesting deeper than"), "{}", err);
+
+        let blocks = format!("{}{}", "{".repeat(100_000), "}".repeat(100_000));
+        assert!(parse(blocks.as_bytes()).is_err());
+        assert!(parse(&[b'-'; 100_000]).is_err());
+    }
+
+    #[test]
+    fn test_parse_cascading_errors() {
+        let lines = |source: &[u8]| -> Vec<Option<u32>> {
+            let err = parse(source).unwrap_err();
+            err.errors().iter().map(|error| error.line()).collect()
+        };
+
+        // Names in the block after a broken header, or declared by a skipped `let`, aren't
+        // reported as undeclared.
+        assert_eq!(
+            lines(b"function f(a {\n    print a\n}\nlet x = (1 +\nlet y = 2\nprint y"),
+            [Some(1), Some(5)]
+        );
+        // Until a statement parses.
+        assert_eq!(lines(b"let = 1\nprint 2\nprint a"), [Some(1), Some(3)]);
+        // Repeats on consecutive lines are collapsed, others are kept.
+        assert_eq!(
+            lines(b"print a\nprint a\nprint a\nprint b\n\nprint b"),
+            [Some(1), Some(4), Some(6)]
+        );
+        // The unclosed block is reported, rather than the file ending inside it.
+        assert_eq!(lines(b"if (true) {\n    print 1\n"), [Some(1)]);
+
+        let source: String = (0..25).map(|n| format!("print a{}\n", n)).collect();
+        let err = parse(source.as_bytes()).unwrap_err();
+        assert_eq!((err.errors().len(), err.hidden()), (MAX_ERRORS, 5));
+        assert!(err.to_string().ends_with("\nand 5 more errors"), "{}", err);
+    }
+
+    #[test]
+    fn test_parse_error_fields() {
+        let first = |source: &[u8]| parse(source).unwrap_err().errors()[0].clone();
+
+        let error = first(b"let x = 1\nprint  y");
+        assert_eq!(error.code(), ErrorCode::UndeclaredVariable);
+        assert_eq!(error.message(), "Undeclared variable 'y'");
+        assert_eq!((error.line(), error.column()), (Some(2), Some(8)));
+        assert_eq!(error.span(), Some(17..18));
+        assert_eq!(error.found(), Some("y"));
+        assert!(error.expected().is_empty());
+        assert_eq!(
+            error.to_string(),
+            "Parse error: Undeclared variable 'y' at line 2"
+        );
+
+        // Placed after the token the missing one should follow.
+        let error = first(b"let = 1");
+        assert_eq!(error.message(), "Expected: Identifier after let");
+        assert_eq!((error.column(), error.span()), (Some(1), Some(0..3)));
+        assert_eq!(error.expected(), [TokenType::Identifier]);
+        assert_eq!(error.found(), Some("="));
+
+        let error = first("print [\"é\" 2]".as_bytes());
+        assert_eq!(error.code(), ErrorCode::MissingToken);
+        assert_eq!((error.column(), error.span()), (Some(12), Some(12..13)));
+        assert_eq!(
+            error.expected(),
+            [TokenType::RightBracket, TokenType::Comma]
+        );
+
+        let error = first(b"print (1 + 2");
+        assert_eq!(error.code(), ErrorCode::UnexpectedEof);
+        // Placed right after the last token.
+        assert_eq!(
+            (error.line(), error.column(), error.span()),
+            (Some(1), Some(13), Some(12..12))
+        );
+        assert_eq!(error.expected(), [TokenType::RightParentheses]);
+        assert_eq!(
+            error.to_string(),
+            "Parse error: Unexpected end of file at line 1"
+        );
+
+        // Placed at the brace of the block left open.
+        let error = first(b"print 1\nif (true) {\n    print 1\n");
+        assert_eq!(error.code(), ErrorCode::UnexpectedEof);
+        assert_eq!((error.line(), error.column()), (Some(2), Some(11)));
+
+        let error = first(b"import \"missing.notjs\"");
+        assert_eq!(error.message(), "Cannot import missing.notjs");
+        assert!(error.cause().is_some());
+        assert_eq!(error.span(), Some(7..22));
+        assert!(error
+            .to_string()
+            .starts_with("Parse error: Cannot import missing.notjs at line 1: "));
+
+        let ParseError::Multiple(multiple) = parse(b"print a\n\nprint (").unwrap_err() else {
+            panic!("expected several errors");
+        };
+        let lines: Vec<Option<u32>> = multiple.iter().map(Single::line).collect();
+        assert_eq!(lines, [Some(1), Some(3)]);
+    }
+
+    #[test]
+    fn test_parse_annotations() {
+        let program =
+            parse(b"let count: number = 0 let later: bool function f(x: string, y): array {}")
+                .unwrap();
+
+        let Statement::Variable(ref count) = program[0] else {
+            panic!("expected a variable declaration");
+        };
+        assert_eq!(count.annotation, Some(ValueType::Number));
+        let Statement::Variable(ref later) = program[1] else {
+            panic!("expected a variable declaration");
+        };
+        assert_eq!(later.annotation, Some(ValueType::Boolean));
+        assert!(later.initializer.is_none());
+
+        let Statement::Function(ref function) = program[2] else {
+            panic!("expected a function declaration");
+        };
+        let annotations: Vec<Option<ValueType>> = function
+            .parameters
+            .iter()
+            .map(|parameter| parameter.annotation)
+            .collect();
+        assert_eq!(annotations, [Some(ValueType::String), None]);
+        assert_eq!(function.return_type, Some(ValueType::Array));
+
+        let error = parse(b"let x: numbr = 1").unwrap_err().errors()[0].clone();
+        assert_eq!(error.code(), ErrorCode::UnknownType);
+        assert_eq!(
+            error.message(),
+            "Unknown type 'numbr', expected one of null, number, string, boolean, array, \
+             object, function, enum, generator"
+        );
+        let error = parse(b"function f(x:) {}").unwrap_err().errors()[0].clone();
+        assert_eq!(error.code(), ErrorCode::MissingToken);
+    }
+
+    #[test]
+    fn test_parse_delete() {
+        let program = parse(b"let xs = [1]\nprint !delete xs[0].a + 1").unwrap();
+        let Statement::Print(ref print) = program[1] else {
+            panic!("expected a print statement");
+        };
+        // `delete` binds as tightly as the other unary operators.
+        let Expression::Binary(ref binary) = print.expression else {
+            panic!("expected a binary expression");
+        };
+        let Expression::Unary(ref unary) = *binary.left else {
+            panic!("expected a unary expression");
+        };
+        let Expression::Delete(ref delete) = *unary.right else {
+            panic!("expected a delete expression");
+        };
+        assert_eq!(delete.identifier.name(), "xs");
+        assert_eq!(delete.path.len(), 2);
+
+        for source in [
+            "let x = 1 delete x",
+            "delete 1",
+            "function f() {} delete f()",
+        ] {
+            let error = parse(source.as_bytes()).unwrap_err().errors()[0].clone();
+            assert_eq!(
+                error.code(),
+                ErrorCode::InvalidAssignmentTarget,
+                "{}",
+                source
+            );
+            assert_eq!(error.message(), "Expected element or property after delete");
+        }
+        let error = parse(b"const xs = [1] delete xs[0]").unwrap_err();
+        assert_eq!(error.errors()[0].code(), ErrorCode::ImmutableVariable);
+    }
+
+    #[test]
+    fn test_parse_enum() {
+        let program =
+            parse(b"enum State { Idle, Done, }\nenum Empty {}\nprint State.Idle").unwrap();
+        let Statement::Enum(ref declaration) = program[0] else {
+            panic!("expected an enum declaration");
+        };
+        assert_eq!(declaration.name.name(), "State");
+        let variants: Vec<&str> = declaration.variants.iter().map(Token::name).collect();
+        assert_eq!(variants, ["Idle", "Done"]);
+        assert_eq!(declaration.to_string(), "enum State { Idle, Done }");
+        let Statement::Enum(ref empty) = program[1] else {
+            panic!("expected an enum declaration");
+        };
+        assert_eq!(empty.to_string(), "enum Empty {}");
+
+        let error = parse(b"enum State {\n    Idle,\n    Idle\n}")
+            .unwrap_err()
+            .errors()[0]
+            .clone();
+        assert_eq!(error.code(), ErrorCode::DuplicateVariant);
+        assert_eq!(error.message(), "Duplicate variant 'Idle' in enum 'State'");
+        assert_eq!((error.line(), error.column()), (Some(3), Some(5)));
+
+        // The enum is a constant.
+        let error = parse(b"enum State { Idle }\nState = 1").unwrap_err();
+        assert_eq!(error.errors()[0].code(), ErrorCode::ImmutableVariable);
+        assert!(parse(b"enum State { Idle Done }").is_err());
+    }
+
+    #[test]
+    fn test_parse_match() {
+        let program = parse(b"enum State { Idle }\nlet x = 1\nprint match x { 1 | -2 => x, [a, [_]] => a, State.Idle => 0, other => other, }").unwrap();
+        let Statement::Print(ref print) = program[2] else {
+            panic!("expected a print statement");
+        };
+        let Expression::Match(ref expression) = print.expression else {
+            panic!("expected a match expression");
+        };
+        assert_eq!(expression.arms.len(), 4);
+        assert!(matches!(
+            expression.arms[0].pattern,
+            Pattern::Alternatives(ref alternatives)
+                if matches!(alternatives[..], [Pattern::Literal(Value::Number(1.0)), Pattern::Literal(Value::Number(-2.0))])
+        ));
+        let names: Vec<&str> = expression.arms[1]
+            .pattern
+            .bindings()
+            .into_iter()
+            .map(Token::name)
+            .collect();
+        assert_eq!(names, ["a"]);
+        assert!(matches!(expression.arms[2].pattern, Pattern::Constant(_)));
+        assert!(matches!(expression.arms[3].pattern, Pattern::Binding(_)));
+
+        // Arms for both booleans are enough.
+        assert!(parse(b"print match 1 == 1 { true => 1, false | null => 0 }").is_ok());
+
+        for (source, code, message) in [
+            (
+                "print match 1 { 1 => 2 }",
+                ErrorCode::NonExhaustiveMatch,
+                "Match is not exhaustive, add a '_' arm for the values no other arm matches",
+            ),
+            (
+                "print match 1 {}",
+                ErrorCode::NonExhaustiveMatch,
+                "Match is not exhaustive, add a '_' arm for the values no other arm matches",
+            ),
+            (
+                "print match 1 { [a, [a]] => a, _ => 0 }",
+                ErrorCode::InvalidPattern,
+                "'a' is declared more than once in the pattern",
+            ),
+            (
+                "print match 1 { 1 | a => a }",
+                ErrorCode::InvalidPattern,
+                "Patterns joined with '|' cannot declare names, found 'a'",
+            ),
+            (
+                "print match 1 { (1) => 1, _ => 0 }",
+                ErrorCode::InvalidPattern,
+                "Expected a pattern, found: (",
+            ),
+            (
+                "print match 1 { a => a, _ => a }",
+                ErrorCode::UndeclaredVariable,
+                "Undeclared variable 'a'",
+            ),
+            (
+                "print match 1 { Missing.x => 1, _ => 0 }",
+                ErrorCode::UndeclaredVariable,
+                "Undeclared variable 'Missing'",
+            ),
+        ] {
+            let error = parse(source.as_bytes()).unwrap_err().errors()[0].clone();
+            assert_eq!(error.code(), code, "{}", source);
+            assert_eq!(error.message(), message, "{}", source);
+        }
+
+        let error = parse(b"let x = 1\nprint match x {\n    1 => 2\n}")
+            .unwrap_err()
+            .errors()[0]
+            .clone();
+        // Placed at the keyword.
+        assert_eq!((error.line(), error.column()), (Some(2), Some(7)));
+        assert!(parse(b"print match 1 { 1 2 }").is_err());
+    }
+
+    #[test]
+    fn test_parse_generator() {
+        let program = parse(b"function* count(n) { yield n yield null while (n) yield\n}").unwrap();
+        let Statement::Function(ref function) = program[0] else {
+            panic!("expected a function declaration");
+        };
+        assert!(function.generator);
+        let Statement::Block(ref body) = *function.body else {
+            panic!("expected a block");
+        };
+        assert!(matches!(
+            body.statements[..],
+            [
+                Statement::Yield(YieldStatement { value: Some(_), .. }),
+                Statement::Yield(YieldStatement { value: None, .. }),
+                Statement::While(_),
+            ]
+        ));
+        assert!(matches!(
+            parse(b"function plain() {}").unwrap()[0],
+            Statement::Function(FunctionDeclaration {
+                generator: false,
+                ..
+            })
+        ));
+
+        let message = "Yields are only allowed in generator functions, declared with function*";
+        for source in [
+            "yield 1",
+            "function f() { yield 1 }",
+            // Functions declared in a generator are not generators themselves.
+            "function* outer() { function inner() { yield 1 } }",
+        ] {
+            let error = parse(source.as_bytes()).unwrap_err();
+            assert!(
+                error
+                    .errors()
+                    .iter()
+                    .any(|error| error.code() == ErrorCode::MisplacedYield
+                        && error.message() == message),
+                "{}",
+                source
+            );
+        }
+
+        // A yield is a statement, so it can't be used as a value.
+        assert_eq!(
+            parse(b"function* f() { print yield }")
+                .unwrap_err()
+                .errors()[0]
+                .code(),
+            ErrorCode::ExpectedExpression
+        );
+        // Yields after a nested function are still in the generator.
+        assert!(parse(b"function* f() { function g() {} yield g }").is_ok());
+    }
+
+    #[test]
+    fn test_parse_immutable_assignment() {
+        for assignment in ["MAX = 1", "MAX += 1", "MAX -= 1", "MAX *= 2", "MAX /= 2"] {
+            let source = format!("let x = 1\nconst MAX = 10\n\n{}", assignment);
+            assert_eq!(
+                parse(source.as_bytes()).unwrap_err().errors()[0].to_string(),
+                "Parse error: Cannot reassign 'MAX' (declared const at line 2) at line 4",
+                "{}",
+                assignment
+            );
+        }
+
+        // Through an element or property, and for functions, which are constant too.
+        let error = parse(b"const xs = [1]\nxs[0] = 2").unwrap_err();
+        assert_eq!(
+            error.errors()[0].message(),
+            "Cannot reassign 'xs' (declared const at line 1)"
+        );
+        let error = parse(b"function f() {}\n{\n    f = 1\n}").unwrap_err();
+        assert_eq!(
+            error.errors()[0].to_string(),
+            "Parse error: Cannot reassign 'f' (declared const at line 1) at line 3"
+        );
+
+        let mut environment = Environment::new();
+        environment.define_global("len", Value::Null);
+        let resolver = Resolver::from_environment(&environment);
+        let error = parse_with_resolver(b"len = 1", resolver).unwrap_err();
+        assert_eq!(
+            error.errors()[0].to_string(),
+            "Parse error: Cannot reassign global 'len' at line 1"
+        );
+    }
 }
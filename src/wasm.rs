@@ -0,0 +1,122 @@
+use std::{
+    cell::RefCell,
+    panic::{self, AssertUnwindSafe},
+    sync::Once,
+};
+
+use js_sys::{Object, Reflect};
+use wasm_bindgen::prelude::*;
+
+use crate::Interpreter;
+
+/// Run `source` in a fresh sandboxed interpreter, giving `{ output, error }`: everything the
+/// program printed, and the message of the error that stopped it or `null` if it ran to the end.
+///
+/// A panic of the interpreter is given as an internal error too. Where panics unwind, it is the
+/// error of the call that panicked. Builds that abort on panic, the default for
+/// wasm32-unknown-unknown, can't return from that call, so every later call gives the panic as
+/// its error: the instance is poisoned and has to be created again.
+#[wasm_bindgen]
+pub fn run(source: &str) -> JsValue {
+    let (output, error) = capture(source);
+
+    let result = Object::new();
+    let error = error.map_or(JsValue::NULL, JsValue::from);
+    // Setting a property of a plain object can't fail.
+    Reflect::set(&result, &"output".into(), &output.into()).unwrap();
+    Reflect::set(&result, &"error".into(), &error).unwrap();
+
+    result.into()
+}
+
+thread_local! {
+    // Message of the last panic of the thread, recorded by the panic hook before the panic
+    // unwinds or aborts.
+    static PANIC: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+// Record panics, then let the hook that was there before report them as usual.
+fn record_panics() {
+    static HOOK: Once = Once::new();
+
+    HOOK.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            let payload = info.payload();
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            PANIC.set(Some(message));
+
+            previous(info);
+        }));
+    });
+}
+
+fn capture(source: &str) -> (String, Option<String>) {
+    guarded(|| {
+        let (result, output) = Interpreter::sandboxed().interpret_capture(source.as_bytes());
+        (output, result.err().map(|err| err.to_string()))
+    })
+}
+
+// Run `f`, giving a panic in it, or one that aborted an earlier call, as an internal error.
+fn guarded(f: impl FnOnce() -> (String, Option<String>)) -> (String, Option<String>) {
+    record_panics();
+
+    // A recorded panic that is still there aborted its call, which left the instance in an
+    // unknown state.
+    if let Some(message) = PANIC.with_borrow(Clone::clone) {
+        let error = format!(
+            "Internal error: {} (the instance is poisoned by this panic and has to be created \
+             again)",
+            message
+        );
+        return (String::new(), Some(error));
+    }
+
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(result) => result,
+        // The panic unwound, so the instance is fine and the panic is only this call's error.
+        Err(_) => {
+            let message = PANIC.take().unwrap_or_default();
+            (String::new(), Some(format!("Internal error: {}", message)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capture() {
+        assert_eq!(
+            capture("println \"hi\" print 1 + 1"),
+            ("hi\n2".to_string(), None)
+        );
+
+        let (output, error) = capture("println 1\nlet x = 1 x()");
+        assert_eq!(output, "1\n");
+        assert!(error.unwrap().contains("line 2"));
+
+        // There is no host to reach from the browser.
+        assert!(capture("env(\"HOME\")").1.is_some());
+    }
+
+    #[test]
+    fn test_capture_panic() {
+        let (output, error) = guarded(|| panic!("boom"));
+        assert_eq!(output, "");
+        assert_eq!(error.unwrap(), "Internal error: boom");
+        // The panic unwound, so the next run goes ahead.
+        assert_eq!(capture("print 1"), ("1".to_string(), None));
+
+        // As left by a panic that aborted its call.
+        PANIC.set(Some("boom".to_string()));
+        let error = capture("print 1").1.unwrap();
+        assert!(error.starts_with("Internal error: boom (the instance is poisoned"));
+    }
+}
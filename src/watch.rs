@@ -0,0 +1,142 @@
+// `notjs --watch`: run the files again each time they, or the files they import, change.
+
+use std::{
+    collections::HashSet,
+    fs,
+    path::PathBuf,
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use notjs::common::statements::Statement;
+
+// Tells when the files being watched change.
+pub trait Changes {
+    // Wait until one of `paths` changes, giving false when no more changes will come.
+    fn wait(&mut self, paths: &[PathBuf]) -> bool;
+}
+
+// Checks the modification times of the files every `interval`. Editors often write a file in
+// several steps, so a change is only given once the times have stayed the same for `debounce`.
+pub struct Poll {
+    pub interval: Duration,
+    pub debounce: Duration,
+}
+
+impl Default for Poll {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_millis(200),
+            debounce: Duration::from_millis(100),
+        }
+    }
+}
+
+// A file that can't be read, e.g. while an editor replaces it, has no time, so that it changes
+// again when it is back.
+fn modified(paths: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    paths
+        .iter()
+        .map(|path| {
+            fs::metadata(path)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+        })
+        .collect()
+}
+
+impl Changes for Poll {
+    fn wait(&mut self, paths: &[PathBuf]) -> bool {
+        let before = modified(paths);
+
+        let mut last = loop {
+            thread::sleep(self.interval);
+            let now = modified(paths);
+            if now != before {
+                break now;
+            }
+        };
+
+        loop {
+            thread::sleep(self.debounce);
+            let now = modified(paths);
+            if now == last {
+                return true;
+            }
+            last = now;
+        }
+    }
+}
+
+// The canonical paths of the files imported by `statements`, and by the files they import.
+pub fn imports(statements: &[Statement]) -> Vec<PathBuf> {
+    let mut seen = HashSet::new();
+    let mut paths = Vec::new();
+    add_imports(statements, &mut seen, &mut paths);
+    paths
+}
+
+fn add_imports(statements: &[Statement], seen: &mut HashSet<PathBuf>, paths: &mut Vec<PathBuf>) {
+    for statement in statements {
+        if let Statement::Import(import) = statement {
+            if seen.insert(import.module.clone()) {
+                paths.push(import.module.clone());
+                add_imports(&import.statements, seen, paths);
+            }
+        }
+    }
+}
+
+// The time of day in UTC, as `HH:MM:SS`.
+pub fn clock(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600 % 24,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clock() {
+        let time = UNIX_EPOCH + Duration::from_secs(3 * 86400 + 13 * 3600 + 5 * 60 + 9);
+        assert_eq!(clock(time), "13:05:09");
+    }
+
+    #[test]
+    fn test_poll() {
+        let path = std::env::temp_dir().join(format!("notjs-poll-{}.notjs", std::process::id()));
+        fs::write(&path, "print 1").unwrap();
+
+        let writer = {
+            let path = path.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(50));
+                // Rapid writes are given as one change.
+                for n in 0..3 {
+                    fs::write(&path, format!("print {}", n)).unwrap();
+                    thread::sleep(Duration::from_millis(5));
+                }
+            })
+        };
+
+        let mut poll = Poll {
+            interval: Duration::from_millis(10),
+            debounce: Duration::from_millis(100),
+        };
+        assert!(poll.wait(std::slice::from_ref(&path)));
+        writer.join().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "print 2");
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,151 @@
+// Uses the library the way an embedding application would, only through its public items.
+
+mod common;
+
+use std::{cell::RefCell, io, io::Write, rc::Rc};
+
+use notjs::{
+    common::{
+        function::{Arity, NativeFunction},
+        token::TokenType,
+    },
+    error::runtime::RuntimeError,
+    lexer::Scanner,
+    parser, Interpreter, NotJsError, Value,
+};
+
+// Output that stays readable after it is handed to the interpreter.
+#[derive(Clone, Default)]
+struct Output(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Output {
+    fn text(&self) -> String {
+        String::from_utf8(self.0.borrow().clone()).unwrap()
+    }
+}
+
+#[test]
+fn test_interpret() {
+    let mut interpreter = Interpreter::new();
+
+    assert_eq!(interpreter.interpret(b"").unwrap(), Value::Null);
+    assert_eq!(
+        interpreter.interpret(b"let xs = [1, 2, 1 + 2] xs").unwrap(),
+        Value::from(vec![1.0.into(), 2.0.into(), 3.0.into()])
+    );
+    assert_eq!(interpreter.interpret(b"len(xs)").unwrap(), Value::from(3.0));
+    assert_eq!(
+        notjs::interpreter::interpret_value(b"\"a\" + 1").unwrap(),
+        Value::from("a1")
+    );
+}
+
+#[test]
+fn test_errors() {
+    let mut interpreter = Interpreter::new();
+
+    let err = interpreter.interpret(b"let = 1").unwrap_err();
+    assert!(matches!(err, NotJsError::Parse(_)), "{:?}", err);
+
+    let err = interpreter.interpret(b"let x = 1\nx - true").unwrap_err();
+    let NotJsError::Runtime(ref traced) = err else {
+        panic!("expected a runtime error, got {:?}", err);
+    };
+    assert!(matches!(traced.error, RuntimeError::InvalidOperands { .. }));
+    assert_eq!(traced.error.line(), Some(2));
+    assert!(std::error::Error::source(&err).is_some());
+
+    // A failed run leaves the interpreter usable.
+    assert_eq!(interpreter.interpret(b"x").unwrap(), Value::from(1.0));
+}
+
+#[cfg(feature = "host")]
+#[test]
+fn test_run_file() {
+    let file = common::TempFile::new("api", "let x = 2\nx * 21");
+    let path = file.path();
+    let mut interpreter = Interpreter::new();
+
+    assert_eq!(interpreter.run_file(path).unwrap(), Value::from(42.0));
+
+    std::fs::write(path, "x +").unwrap();
+    let err = interpreter.run_file(path).unwrap_err();
+    assert!(matches!(err, NotJsError::Parse(_)), "{:?}", err);
+
+    std::fs::write(path, "x()").unwrap();
+    let err = interpreter.run_file(path).unwrap_err();
+    assert!(matches!(err, NotJsError::Runtime(_)), "{:?}", err);
+
+    std::fs::remove_file(path).unwrap();
+    let err = interpreter.run_file(path).unwrap_err();
+    let NotJsError::Io(ref io) = err else {
+        panic!("expected an I/O error, got {:?}", err);
+    };
+    assert_eq!(io.kind(), io::ErrorKind::NotFound);
+    assert_eq!(err.code(), "I001");
+}
+
+#[test]
+fn test_host_functions() {
+    let output = Output::default();
+    let mut interpreter = Interpreter::sandboxed().with_output(output.clone());
+
+    interpreter.bind("hypot", |a: f64, b: f64| (a * a + b * b).sqrt());
+    interpreter.define("GREETING", Value::from("hello"));
+    interpreter.define_native(NativeFunction::new(
+        "shout",
+        Arity::Exact(1),
+        |environment, arguments| {
+            let text = String::try_from(arguments[0].clone())
+                .map_err(|error| RuntimeError::new(format!("shout: {}", error)))?;
+            environment
+                .print(&Value::from(text.to_uppercase()), true)
+                .map_err(|error| RuntimeError::new(error.to_string()))?;
+            Ok(Value::Null)
+        },
+    ));
+
+    assert_eq!(
+        interpreter.interpret(b"hypot(3, 4)").unwrap(),
+        Value::from(5.0)
+    );
+    interpreter.interpret(b"shout(GREETING)").unwrap();
+    assert_eq!(output.text(), "HELLO\n");
+
+    let err = interpreter.interpret(b"shout(1)").unwrap_err();
+    assert!(
+        err.to_string().contains("shout: expected string"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_lexer_and_parser() {
+    let types: Vec<TokenType> = Scanner::new(b"let x = 1").map(|t| t.token_type).collect();
+    assert_eq!(
+        types,
+        [
+            TokenType::Let,
+            TokenType::Identifier,
+            TokenType::Equal,
+            TokenType::Number
+        ]
+    );
+
+    let program = parser::parse(b"let x = 1 + 2 print x").unwrap();
+    let source: Vec<String> = program.iter().map(|s| s.node_to_string()).collect();
+    assert_eq!(source, ["let x = 1 + 2", "print x"]);
+
+    assert!(parser::parse(b"print y").is_err());
+}
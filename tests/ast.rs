@@ -0,0 +1,151 @@
+// Compares the JSON of the programs in tests/ast with the expected one next to them. Run with
+// `NOTJS_BLESS=1` to write the current JSON instead, after checking the change is wanted.
+
+use std::{cell::RefCell, env, fs, io, io::Write, path::Path, rc::Rc};
+
+use notjs::{parser, Interpreter, NotJsError, NotJsResult, Value};
+
+#[derive(Clone, Default)]
+struct Output(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Run a program read back from its JSON, giving its result and what it printed.
+fn run_json(json: &str) -> (NotJsResult<Value>, String) {
+    let output = Output::default();
+    let mut interpreter = Interpreter::sandboxed().with_output(output.clone());
+
+    let result = parser::program_from_json(json)
+        .map_err(NotJsError::from)
+        .and_then(|program| interpreter.run_ast(program));
+    let printed = String::from_utf8(output.0.borrow().clone()).unwrap();
+
+    (result, printed)
+}
+
+#[test]
+fn test_ast_json() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/ast");
+    let bless = env::var_os("NOTJS_BLESS").is_some();
+    let mut programs = 0;
+
+    for entry in fs::read_dir(&dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension() != Some("notjs".as_ref()) {
+            continue;
+        }
+        programs += 1;
+
+        let json = parser::parse_to_json(&fs::read(&path).unwrap()).unwrap() + "\n";
+        let expected_path = path.with_extension("json");
+
+        if bless {
+            fs::write(&expected_path, json).unwrap();
+            continue;
+        }
+
+        let expected = fs::read_to_string(&expected_path).unwrap_or_default();
+        assert!(
+            json == expected,
+            "the JSON of {} changed, run with NOTJS_BLESS=1 to update it:\n{}",
+            path.display(),
+            json
+        );
+    }
+
+    assert!(programs > 0);
+}
+
+#[test]
+fn test_run_from_json() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/ast");
+    let mut sources: Vec<Vec<u8>> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension() == Some("notjs".as_ref()))
+        .map(|path| fs::read(path).unwrap())
+        .collect();
+    sources.push(
+        b"function fib(n) { return n < 2 ? n : fib(n - 1) + fib(n - 2) } print fib(10)".to_vec(),
+    );
+
+    for source in sources {
+        let json = parser::parse_to_json(&source).unwrap();
+        let (expected, expected_output) = Interpreter::sandboxed().interpret_capture(&source);
+        let (result, output) = run_json(&json);
+
+        assert_eq!(result.unwrap(), expected.unwrap());
+        assert_eq!(output, expected_output);
+    }
+}
+
+// The JSON of `source` after `change` is applied to its statements.
+fn edit(source: &[u8], change: impl FnOnce(&mut serde_json::Value)) -> String {
+    let mut program: serde_json::Value =
+        serde_json::from_str(&parser::parse_to_json(source).unwrap()).unwrap();
+    change(&mut program["statements"]);
+    program.to_string()
+}
+
+fn parse_error(json: &str) -> String {
+    let (result, output) = run_json(json);
+    assert_eq!(output, "");
+    match result {
+        Err(NotJsError::Parse(err)) => err.to_string(),
+        other => panic!("expected a parse error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_invalid_json() {
+    let newer = edit(b"print 1", |_| ()).replacen(
+        &format!("\"version\":{}", parser::AST_VERSION),
+        &format!("\"version\":{}", parser::AST_VERSION + 1),
+        1,
+    );
+    assert!(parse_error(&newer).contains("Unsupported AST version"));
+    let statements_missing = format!("{{\"version\":{}}}", parser::AST_VERSION);
+    assert!(parse_error(&statements_missing).contains("Invalid AST"));
+    assert!(parse_error("[]").contains("Invalid AST"));
+
+    // Names are resolved again, nothing in the JSON is trusted to be declared.
+    let undeclared = edit(b"let x = 1 print x", |statements| {
+        statements[0]["identifier"]["value"] = "y".into();
+    });
+    assert!(parse_error(&undeclared).contains("x"));
+
+    let constant = edit(b"let x = 1 x = 2", |statements| {
+        statements[0]["mutable"] = false.into();
+    });
+    assert!(parse_error(&constant).contains("x"));
+
+    let no_property = edit(b"let xs = [1] xs[0] = 2", |statements| {
+        statements[1]["expression"]["path"] = serde_json::json!([]);
+    });
+    assert!(parse_error(&no_property).contains("without a property"));
+
+    let no_element = edit(b"let xs = [1] delete xs[0]", |statements| {
+        statements[1]["expression"]["path"] = serde_json::json!([]);
+    });
+    assert!(parse_error(&no_element).contains("without an element or property"));
+
+    let nested_import = edit(b"{ print 1 }", |statements| {
+        statements[0]["statements"][0] = serde_json::json!({
+            "kind": "Import",
+            "path": { "type": "String", "value": "m", "line": 1 },
+            "module": "m.notjs",
+            "statements": [],
+            "namespace": null,
+            "exports": []
+        });
+    });
+    assert!(parse_error(&nested_import).contains("top level"));
+}
@@ -0,0 +1,128 @@
+// `notjs check --json` is read by editor plugins, so the fields of its diagnostics are checked
+// here as the plugins see them.
+
+mod common;
+
+use std::{fs, path::Path};
+
+use common::{notjs, TempFile};
+use serde_json::{json, Value as Json};
+
+fn check(source: &str, name: &str) -> (Json, Option<i32>) {
+    check_with(&[], source, name)
+}
+
+fn check_with(options: &[&str], source: &str, name: &str) -> (Json, Option<i32>) {
+    let file = TempFile::new(&format!("check-{}", name), source);
+    let args = [options, &["check", "--json", file.arg()]].concat();
+    let (status, stdout, _) = notjs(&args, "");
+
+    let json = serde_json::from_str(&stdout)
+        .unwrap_or_else(|err| panic!("invalid JSON: {}\n{}", err, stdout));
+    (json, status)
+}
+
+#[test]
+fn test_two_errors() {
+    let (diagnostics, status) = check("let = 1\nprint 2\nconst x = 1\nx = 2\n", "errors");
+
+    assert_eq!(status, Some(1));
+    assert_eq!(
+        diagnostics,
+        json!([
+            {
+                "severity": "error",
+                "message": "Expected: Identifier after let",
+                "line": 1,
+                "column": 1,
+                "code": "missing-token",
+                "id": "E002",
+            },
+            {
+                "severity": "error",
+                "message": "Cannot reassign 'x' (declared const at line 3)",
+                "line": 4,
+                "column": 1,
+                "code": "immutable-variable",
+                "id": "E011",
+            },
+        ])
+    );
+}
+
+#[test]
+fn test_no_errors() {
+    let (diagnostics, status) = check("let x = 1\nprint x\n", "clean");
+
+    assert_eq!(status, Some(0));
+    assert_eq!(diagnostics, json!([]));
+}
+
+#[test]
+fn test_mangled() {
+    let source = fs::read_to_string(
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs/mangled.notjs"),
+    )
+    .unwrap();
+    let (diagnostics, status) = check(&source, "mangled");
+
+    // 24 errors are left once those caused by others are dropped, and only the first 20 are
+    // reported.
+    assert_eq!(status, Some(1));
+    let lines: Vec<&Json> = diagnostics
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|diagnostic| &diagnostic["line"])
+        .collect();
+    assert_eq!(lines.len(), 20);
+    assert_eq!(lines[..4], [&json!(5), &json!(12), &json!(16), &json!(21)]);
+}
+
+#[test]
+fn test_unexpected_eof() {
+    // Placed right after the last token, the file may end with blank lines.
+    let (diagnostics, status) = check("print 1\n\nprint (\n\n", "eof");
+
+    assert_eq!(status, Some(1));
+    assert_eq!(
+        diagnostics,
+        json!([
+            {
+                "severity": "error",
+                "message": "Unexpected end of file",
+                "line": 3,
+                "column": 8,
+                "code": "unexpected-eof",
+                "id": "E003",
+            },
+        ])
+    );
+}
+
+#[test]
+fn test_typecheck() {
+    let source = "let x: number = \"1\"\nprint x\n";
+    let (diagnostics, status) = check_with(&["--typecheck"], source, "typecheck");
+
+    assert_eq!(status, Some(1));
+    // The syntax tree only keeps lines, so the checker cannot give a column.
+    assert_eq!(
+        diagnostics,
+        json!([
+            {
+                "severity": "error",
+                "message": "Expected number for variable 'x', found string",
+                "line": 1,
+                "column": null,
+                "code": "type-mismatch",
+                "id": "E040",
+            },
+        ])
+    );
+
+    // Without the flag only the syntax is checked.
+    let (diagnostics, status) = check(source, "untyped");
+    assert_eq!(status, Some(0));
+    assert_eq!(diagnostics, json!([]));
+}
@@ -0,0 +1,114 @@
+// The messages of errors are matched by tools, so their wording is kept in tests/errors.snap, along
+// with their stable codes. Each broken program below is run and its code and error written without
+// color after its name and source. Run with `NOTJS_BLESS=1` to write the current messages instead,
+// after checking the change is wanted.
+
+use std::{env, fs, path::Path, time::Duration};
+
+use notjs::Interpreter;
+
+const CASES: &[(&str, &str)] = &[
+    // Parse errors, one for each way they are made.
+    ("unexpected token", "import x to \"other.notjs\""),
+    ("missing token", "let = 1"),
+    ("missing operand", "let x = )"),
+    ("unexpected end of file", "print (1 + 2"),
+    ("several errors", "let = 1\nconst = 2"),
+    ("undeclared variable", "print y"),
+    ("immutable variable", "const x = 1\nx = 2"),
+    ("invalid assignment target", "1 = 2"),
+    ("nested import", "if (true) { import \"other.notjs\" }"),
+    ("too deeply nested", "let x = ((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((1))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))"),
+    ("unterminated string", "print \"abc"),
+    ("invalid escape", "print \"a\\qb\""),
+    ("unterminated comment", "print 1 /* never closed"),
+    ("non-exhaustive match", "print match 1 {\n    0 => \"zero\",\n}"),
+    ("invalid pattern", "print match [1, 2] { [a, a] => a, _ => 0 }"),
+    ("misplaced yield", "function f() {\n    yield 1\n}"),
+    ("misplaced test", "function f() {\n    test \"inside\" { assert true }\n}"),
+    ("unused result", "let xs = [1]\nfreeze(xs)"),
+    // Runtime errors.
+    ("undefined variable", "let x\nprint x"),
+    ("type error", "for (const x in 1) {}"),
+    ("index type error", "print [1][\"a\"]"),
+    ("invalid operator", "let x = 1\nx()"),
+    ("invalid operands", "print [1] - 2"),
+    ("invalid unary operand", "print -\"a\""),
+    ("index out of bounds", "let xs = [1, 2]\nprint xs[2]"),
+    ("unknown property", "print [1].size"),
+    ("undefined property", "let o = jsonParse(\"{}\")\nprint o.missing"),
+    ("frozen", "let xs = freeze([1])\nxs[0] = 2"),
+    ("no match", "print match \"yes\" { true => 1, false => 0 }"),
+    ("arity mismatch", "function f(a, b) { return a }\nf(1)"),
+    ("native arity mismatch", "len(1, 2)"),
+    ("native type error", "chr(\"a\")"),
+    ("out of scope call", "function outer() {\n    function inner() {}\n    return inner\n}\nouter()()"),
+    ("call depth", "function f(n) { f(n + 1) }\nf(0)"),
+    ("generator running", "let g = null\nfunction* f() { g.next() yield 1 }\ng = f()\nfor (const x in g) {}"),
+    ("assertion failed", "const x = 2\nassert x * x == 5"),
+    ("trace", "function inner(x) { return x.length }\nfunction outer() { return inner(1) }\nouter()"),
+    ("budget", "while (true) {}"),
+];
+
+fn interpreter() -> Interpreter {
+    Interpreter::sandboxed()
+        .with_max_call_depth(4)
+        .with_budget(10_000)
+        .with_timeout(Duration::from_secs(10))
+}
+
+fn snapshot() -> String {
+    let mut snapshot = String::new();
+
+    for (name, source) in CASES {
+        let error = match interpreter().interpret_capture(source.as_bytes()).0 {
+            Ok(value) => panic!("{} should fail, got {}", name, value),
+            Err(error) => error,
+        };
+
+        snapshot += &format!(
+            "== {}\n{}\n-- {}\n{}\n\n",
+            name,
+            source,
+            error.code(),
+            error.to_string().trim_end()
+        );
+    }
+
+    snapshot
+}
+
+#[test]
+fn test_error_messages() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/errors.snap");
+    let snapshot = snapshot();
+
+    if env::var_os("NOTJS_BLESS").is_some() {
+        fs::write(&path, snapshot).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&path).unwrap_or_default();
+    for (actual, expected) in snapshot.split("\n\n== ").zip(expected.split("\n\n== ")) {
+        assert!(
+            actual == expected,
+            "an error message changed, run with NOTJS_BLESS=1 to update tests/errors.snap:\n\
+            expected:\n{}\n\nfound:\n{}",
+            expected,
+            actual
+        );
+    }
+    assert!(snapshot == expected, "tests/errors.snap has other cases");
+}
+
+#[test]
+fn test_no_colors() {
+    for (_, source) in CASES {
+        let error = interpreter().interpret_capture(source.as_bytes()).0;
+        assert!(!error.unwrap_err().to_string().contains('\x1b'));
+    }
+
+    // Colors are left to the program showing the error.
+    let error = interpreter().interpret(b"let = 1").unwrap_err();
+    assert!(!format!("{:#} {:?}", error, error).contains('\x1b'));
+}
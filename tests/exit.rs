@@ -0,0 +1,52 @@
+// Scripts around the binary tell its failures apart by the exit code, following sysexits.h, and
+// read the error from stderr without a panic message.
+
+mod common;
+
+use common::{notjs, TempFile};
+
+// Run `source` from a file, `name` telling the file apart.
+fn run(name: &str, source: &str) -> (Option<i32>, String, String) {
+    let file = TempFile::new(&format!("exit-{}", name), source);
+    notjs(&[file.arg()], "")
+}
+
+#[test]
+fn test_success() {
+    assert_eq!(
+        run("success", "print 1"),
+        (Some(0), "1".to_string(), String::new())
+    );
+}
+
+#[test]
+fn test_parse_error() {
+    let (status, stdout, stderr) = run("parse", "print 1\nprint y");
+
+    assert_eq!(status, Some(65));
+    assert_eq!(stdout, "");
+    assert!(
+        stderr.contains("Undeclared variable 'y' at line 2"),
+        "{}",
+        stderr
+    );
+    assert!(!stderr.contains("panicked"));
+}
+
+#[test]
+fn test_runtime_error() {
+    let (status, stdout, stderr) = run("runtime", "print 1\nprint [][0]");
+
+    assert_eq!(status, Some(70));
+    assert_eq!(stdout, "1");
+    assert!(stderr.contains("Index 0 out of bounds"), "{}", stderr);
+    assert!(!stderr.contains("panicked") && !stderr.contains('\x1b'));
+}
+
+#[test]
+fn test_missing_file() {
+    let (status, _, stderr) = notjs(&["missing-file.notjs"], "");
+
+    assert_eq!(status, Some(66));
+    assert!(stderr.starts_with("missing-file.notjs: "), "{}", stderr);
+}
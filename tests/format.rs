@@ -0,0 +1,193 @@
+// `notjs fmt` rewrites files in place, so formatting must not change what a program does, must
+// keep every comment, and must leave formatted files as they are.
+
+mod common;
+
+use std::{fs, path::Path};
+
+use common::{notjs, TempFile};
+use notjs::{common::source, Interpreter};
+
+fn format(source: &str) -> String {
+    let program = Interpreter::new().parse(source.as_bytes()).unwrap();
+    source::format(source.as_bytes(), &program)
+}
+
+// The comments of `source`, in order.
+fn comments(source: &str) -> Vec<String> {
+    notjs::lexer::Scanner::with_comments(source.as_bytes())
+        .filter(|token| token.token_type == notjs::common::token::TokenType::Comment)
+        .map(|token| token.value.to_string())
+        .collect()
+}
+
+fn check_format(path: &Path, source: &str) {
+    let interpreter = Interpreter::new().with_script(path);
+    let program = interpreter.parse(source.as_bytes()).unwrap();
+    let formatted = source::format(source.as_bytes(), &program);
+
+    let reparsed = interpreter
+        .parse(formatted.as_bytes())
+        .unwrap_or_else(|err| panic!("{}\n{}\nformatted as:\n{}", err, source, formatted));
+    assert_eq!(
+        source::to_source(&reparsed),
+        source::to_source(&program),
+        "formatting changed {}",
+        path.display()
+    );
+    assert_eq!(comments(&formatted), comments(source), "{}", path.display());
+    assert_eq!(
+        source::format(formatted.as_bytes(), &reparsed),
+        formatted,
+        "formatting {} twice changed it",
+        path.display()
+    );
+}
+
+#[test]
+fn test_idempotent() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut programs = 0;
+
+    for dir_name in [".", "tests/ast", "tests/programs", "src/interpreter"] {
+        for entry in fs::read_dir(dir.join(dir_name)).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension() != Some("notjs".as_ref()) {
+                continue;
+            }
+
+            let source = fs::read_to_string(&path).unwrap();
+            if Interpreter::new()
+                .with_script(&path)
+                .parse(source.as_bytes())
+                .is_ok()
+            {
+                check_format(&path, &source);
+                programs += 1;
+            }
+        }
+    }
+
+    assert!(programs > 0);
+}
+
+#[test]
+fn test_layout() {
+    assert_eq!(
+        format("let a=1;let b='it\\'s'\nfunction f(x){if(x>a){return x*2}else print b}\n\n\n\nprint f( a+1 )"),
+        "let a = 1
+let b = \"it's\"
+function f(x) {
+    if (x > a) {
+        return x * 2
+    } else print b
+}
+
+print f(a + 1)
+"
+    );
+
+    assert_eq!(
+        format("export const x = 1\nexport function f() {}\nconst y = 2"),
+        "export const x = 1\nexport function f() {}\nconst y = 2\n"
+    );
+}
+
+#[test]
+fn test_comments() {
+    let source = "// header
+
+/* about a */ let a = 1 // one
+let b = 2 /* two */ // still two
+
+function f(x) { // inside f
+    // before the return
+    return x /* after x */ + 1
+
+    // at the end of f
+}
+while (false) {
+    // only a comment
+}
+if (a) print 1 // trailing an if
+else {
+}
+// last";
+
+    let expected = "// header
+
+/* about a */
+let a = 1 // one
+let b = 2 /* two */ // still two
+
+function f(x) {
+    // inside f
+    // before the return
+    return x + 1 /* after x */
+
+    // at the end of f
+}
+while (false) {
+    // only a comment
+}
+if (a) print 1 else {} // trailing an if
+// last
+";
+
+    let formatted = format(source);
+    assert_eq!(formatted, expected);
+    assert_eq!(comments(&formatted), comments(source));
+    assert_eq!(format(&formatted), formatted);
+}
+
+#[test]
+fn test_fmt_command() {
+    let file = TempFile::new("fmt", "let x=1 // keep\nprint x");
+    let path = file.path();
+
+    let fmt = |check: bool| {
+        let mut args = vec!["fmt"];
+        if check {
+            args.push("--check");
+        }
+        args.push(file.arg());
+        notjs(&args, "").0
+    };
+
+    assert_eq!(fmt(true), Some(1));
+    assert_eq!(
+        fs::read_to_string(path).unwrap(),
+        "let x=1 // keep\nprint x"
+    );
+    assert_eq!(fmt(false), Some(0));
+    assert_eq!(
+        fs::read_to_string(path).unwrap(),
+        "let x = 1 // keep\nprint x\n"
+    );
+    assert_eq!(fmt(true), Some(0));
+
+    fs::write(path, "let = 1").unwrap();
+    assert_eq!(fmt(true), Some(2));
+}
+
+#[test]
+fn test_comments_moved() {
+    // Comments in the middle of statements can't stay where they are, but are still kept in
+    // order, and where they end up is stable.
+    let source = "let a = [1, // one
+  2 /* two */, 3]
+if (a) // cond
+  print 1 // body
+else // else
+{ print 2 } // after
+function h() {
+  return /* nothing */ null
+} /* end h */ // really
+while (a.length > 3) { a = [] /* reset */
+}";
+
+    let formatted = format(source);
+    assert_eq!(comments(&formatted), comments(source));
+    assert_eq!(format(&formatted), formatted);
+    assert!(formatted.contains("} /* end h */ // really\n"));
+}
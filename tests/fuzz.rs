@@ -0,0 +1,191 @@
+// Malformed programs must be reported as errors, never panic. A fixed corpus covers inputs that
+// used to panic, and a deterministic generator covers random programs built from tokens and
+// mutations of valid ones.
+
+mod common;
+
+use std::{
+    fs,
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+    time::Duration,
+};
+
+use common::Random;
+use notjs::{parser, Interpreter};
+
+const CORPUS: &[&[u8]] = &[
+    b")",
+    b"]",
+    b"}",
+    b"let",
+    b"let = 1",
+    b"let x =",
+    b"const",
+    b"print",
+    b"if",
+    b"if (",
+    b"while (true",
+    b"for",
+    b"for (let",
+    b"for (let x in",
+    b"function",
+    b"function f(",
+    b"function f(a, ",
+    b"function* f() { yield",
+    b"yield yield",
+    b"defer",
+    b"defer defer {",
+    b"return return",
+    b"import",
+    b"import x from",
+    b"export",
+    b"export 1",
+    b"1 +",
+    b"1 ? 2",
+    b"1 ? 2 :",
+    b"f(1, 2",
+    b"[1, 2",
+    b"x.",
+    b"[1][",
+    b"1 = 2",
+    b"let x = 1 x.a = 2",
+    b"let s = \"\xc3\xa9\xe2\x82\xac\" s[1] + s[2]",
+    b"\"\xff\xfe\"[1] + 1()",
+    b"\xff\xfe\xfd",
+    b"\x00",
+    b"\"unterminated",
+    b"\"bad \\q escape\"",
+    b"/* unterminated comment",
+    b"1.2.3",
+    b"99999999999999999999999999999999999999999999 + x",
+    b"[1, 2][5]",
+    b"[1, 2][-3]",
+    b"[1, 2][NaN]",
+    b"\"abc\"[3]",
+    b"\"\"[0]",
+    b"let xs = [] xs[0] = 1",
+    b"let o = {} o.a.b = 1",
+    b"let xs = [[1]] xs[0][1][2] = 3",
+    b"1 / 0 + undefined",
+    b"null.length",
+    b"let f = 1 f()",
+    b"function f() { f() } f()",
+    b"range(1, 2, 0) + x",
+    b"chr(-1) chr(1114112) chr(NaN) x",
+    b"format(\"{}{}\", 1)()",
+    b"jsonParse(\"[[[[\")",
+    b"jsonParse(\"1e999999\") x",
+    b"eval(\"let = 1\")",
+    b"eval(\")\")",
+];
+
+// Declared before the random tokens, so that programs get past the resolver and run.
+const DECLARATIONS: &str =
+    "let x = [1, \"a\"] let y = \"\u{e9}t\u{e9}\" let xs = [[1], 2] function f(a) { return a }\n";
+
+// Separated by whitespace, and joined with a space, a new line or nothing.
+const FRAGMENTS: &str = "let const x y xs f f( x[ y[ xs[0][ = += -= ( ) [ ] { } , . ? : + - * / ! \
+    < <= == != === & | 0 1 -1 1.5 NaN Infinity \"s\" '\u{e9}' true null if else while for in \
+    return function function* yield defer test assert print println delete enum match => _ number bool len length clone \
+    range str next";
+
+// Unoptimized builds can't reach the default call depth on the stack of a test thread.
+fn interpreter() -> Interpreter {
+    Interpreter::sandboxed()
+        .with_timeout(Duration::from_millis(100))
+        .with_max_call_depth(64)
+}
+
+// Run the program, failing with the input when it panics.
+fn check(source: &[u8]) {
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let _ = parser::parse(source);
+        interpreter().interpret_capture(source).0.is_err()
+    }));
+
+    assert!(
+        result.is_ok(),
+        "panicked on {:?}",
+        String::from_utf8_lossy(source)
+    );
+}
+
+#[test]
+fn test_corpus() {
+    for source in CORPUS {
+        check(source);
+
+        let result = interpreter().interpret_capture(source).0;
+        assert!(
+            result.is_err(),
+            "{:?} should fail",
+            String::from_utf8_lossy(source)
+        );
+    }
+}
+
+#[test]
+fn test_deep_nesting() {
+    for (open, close) in [
+        ("(", ")"),
+        ("[", "]"),
+        ("{", "}"),
+        ("-", ""),
+        ("if (1) ", ""),
+    ] {
+        let source = format!("{}1{}", open.repeat(100_000), close.repeat(100_000));
+        check(source.as_bytes());
+    }
+}
+
+#[test]
+fn test_random_tokens() {
+    let fragments: Vec<&str> = FRAGMENTS.split_whitespace().collect();
+    let mut random = Random(0x5eed);
+
+    for _ in 0..2000 {
+        let length = random.below(24) + 1;
+        let mut source = String::from(DECLARATIONS);
+        for _ in 0..length {
+            source += fragments[random.below(fragments.len())];
+            source += ["", " ", "\n"][random.below(3)];
+        }
+        check(source.as_bytes());
+    }
+}
+
+#[test]
+fn test_mutations() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let programs: Vec<Vec<u8>> = ["tests/ast", "tests/programs", "."]
+        .iter()
+        .flat_map(|dir_name| fs::read_dir(dir.join(dir_name)).unwrap())
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension() == Some("notjs".as_ref()))
+        .map(|path| fs::read(path).unwrap())
+        .collect();
+    assert!(!programs.is_empty());
+
+    let mut random = Random(0xf00d);
+
+    for _ in 0..1000 {
+        let mut source = programs[random.below(programs.len())].clone();
+
+        for _ in 0..random.below(4) + 1 {
+            let at = random.below(source.len() + 1);
+            match random.below(3) {
+                0 if at < source.len() => {
+                    source.remove(at);
+                }
+                1 => source.insert(at, b"()[]{}.,;=+-!?:\"'\\/*0a\xc3"[random.below(22)]),
+                _ => {
+                    let end = (at + random.below(16)).min(source.len());
+                    source.drain(at..end);
+                }
+            }
+        }
+
+        check(&source);
+    }
+}
@@ -0,0 +1,60 @@
+// Runs the programs in tests/programs and compares what they print with the `.expected` file next
+// to them. A program that fails also has a `.stderr` file with its error. Run with
+// `NOTJS_BLESS=1` to write the current output instead, after checking the change is wanted.
+
+use std::{env, fs, path::Path};
+
+use notjs::Interpreter;
+
+// Errors are compared without trailing spaces, so that the files stay readable.
+fn render(error: &str) -> String {
+    let lines: Vec<&str> = error.trim().lines().map(str::trim_end).collect();
+    lines.join("\n") + "\n"
+}
+
+fn check(path: &Path, contents: Option<String>, bless: bool) {
+    if bless {
+        match contents {
+            Some(contents) => fs::write(path, contents).unwrap(),
+            None => {
+                if path.exists() {
+                    fs::remove_file(path).unwrap();
+                }
+            }
+        }
+        return;
+    }
+
+    let expected = fs::read_to_string(path).ok();
+    assert!(
+        contents == expected,
+        "{} changed, run with NOTJS_BLESS=1 to update it:\n{}",
+        path.display(),
+        contents.as_deref().unwrap_or("(nothing)")
+    );
+}
+
+#[test]
+fn test_programs() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs");
+    let bless = env::var_os("NOTJS_BLESS").is_some();
+    let mut programs = 0;
+
+    for entry in fs::read_dir(&dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension() != Some("notjs".as_ref()) {
+            continue;
+        }
+        programs += 1;
+
+        let source = fs::read(&path).unwrap();
+        let mut interpreter = Interpreter::new().with_script(&path);
+        let (result, printed) = interpreter.interpret_capture(&source);
+        let error = result.err().map(|error| render(&error.to_string()));
+
+        check(&path.with_extension("expected"), Some(printed), bless);
+        check(&path.with_extension("stderr"), error, bless);
+    }
+
+    assert!(programs > 0);
+}
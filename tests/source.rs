@@ -0,0 +1,370 @@
+// Programs printed with `to_source` must parse back into the syntax tree they were printed from.
+// Random programs are generated from the grammar with fixed seeds, so failures are reproducible,
+// and the programs in the repository are checked as well.
+
+mod common;
+
+use std::{fs, path::Path};
+
+use common::Random;
+use notjs::{common::source::to_source, parser};
+use serde_json::Value as Json;
+
+const MAX_DEPTH: usize = 4;
+
+const NUMBERS: &[&str] = &[
+    "0",
+    "1",
+    "2.5",
+    "10",
+    "0.125",
+    "100000000000000000000000",
+    "123456789.75",
+    "NaN",
+    "Infinity",
+];
+const STRING_PARTS: &[&str] = &[
+    "a", " ", "\\\"", "'", "\\\\", "\\n", "\\t", "\\r", "\\0", "\u{e9}", "{}",
+];
+const BINARY: &[&str] = &[
+    "|", "&", "==", "!=", "===", "!==", "<", "<=", ">", ">=", "+", "-", "*", "/",
+];
+const ASSIGNMENT: &[&str] = &["=", "+=", "-=", "*=", "/="];
+
+// Source of random programs. Every name used is declared, and only names declared with `let` are
+// assigned, so that the programs get past the resolver.
+struct Generator {
+    random: Random,
+    source: String,
+    // The names in scope, with whether they can be assigned.
+    scopes: Vec<Vec<(String, bool)>>,
+    names: usize,
+}
+
+impl Generator {
+    fn new(seed: u64) -> Self {
+        Self {
+            random: Random(seed),
+            source: String::from(
+                "let a = 1 let xs = [1, [2]] const s = \"s\" function f(p) { return p }\n",
+            ),
+            scopes: vec![vec![
+                ("a".to_string(), true),
+                ("xs".to_string(), true),
+                ("s".to_string(), false),
+                ("f".to_string(), false),
+            ]],
+            names: 0,
+        }
+    }
+
+    fn name(&mut self) -> String {
+        self.names += 1;
+        format!("v{}", self.names)
+    }
+
+    fn variable(&mut self, mutable: bool) -> String {
+        let names: Vec<&String> = self
+            .scopes
+            .iter()
+            .flatten()
+            .filter(|(_, assignable)| *assignable || !mutable)
+            .map(|(name, _)| name)
+            .collect();
+        names[self.random.below(names.len())].clone()
+    }
+
+    fn declare(&mut self, name: &str, mutable: bool) {
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .push((name.to_string(), mutable));
+    }
+
+    fn push(&mut self, text: &str) {
+        self.source.push_str(text);
+    }
+
+    // Push one of `items`, giving the one pushed.
+    fn push_any(&mut self, items: &[&'static str]) -> &'static str {
+        let item = self.random.pick(items);
+        self.push(item);
+        item
+    }
+
+    fn program(mut self, statements: usize) -> String {
+        for _ in 0..statements {
+            self.statement(0, false);
+            self.push("\n");
+        }
+        self.source
+    }
+
+    fn statement(&mut self, depth: usize, in_function: bool) {
+        let choices = if depth < MAX_DEPTH { 10 } else { 5 };
+
+        match self.random.below(choices) {
+            0 | 1 => {
+                let keyword = self.push_any(&["let ", "const "]);
+                let name = self.name();
+                self.push(&name);
+                self.push(" = ");
+                self.expression(depth);
+                self.declare(&name, keyword == "let ");
+            }
+            2 => {
+                self.push_any(&["print ", "println "]);
+                self.expression(depth);
+            }
+            3 => self.assignment(depth),
+            4 => {
+                self.push("f(");
+                self.expression(depth);
+                self.push(")");
+            }
+            5 => {
+                self.push("if (");
+                self.expression(depth);
+                self.push(") ");
+                self.body(depth, in_function);
+                if self.random.below(2) == 0 {
+                    self.push(" else ");
+                    self.body(depth, in_function);
+                }
+            }
+            6 => {
+                self.push("while (");
+                self.expression(depth);
+                self.push(") ");
+                self.body(depth, in_function);
+            }
+            7 => {
+                self.push("for (");
+                let keyword = self.push_any(&["let", "const"]);
+                self.push(" ");
+                let name = self.name();
+                self.push(&name);
+                self.push(" in ");
+                self.expression(depth);
+                self.push(") ");
+                self.scopes.push(vec![(name, keyword == "let")]);
+                self.body(depth, in_function);
+                self.scopes.pop();
+            }
+            8 => {
+                let name = self.name();
+                let parameter = self.name();
+                self.declare(&name, false);
+                self.push(&format!("function {}({}) ", name, parameter));
+                self.scopes.push(vec![(parameter, true)]);
+                self.block(depth, true);
+                self.scopes.pop();
+            }
+            _ => self.block(depth, in_function),
+        }
+    }
+
+    // A block or a single statement, as the branch of an if or the body of a loop.
+    fn body(&mut self, depth: usize, in_function: bool) {
+        if self.random.below(2) == 0 {
+            self.block(depth, in_function);
+        } else {
+            self.scopes.push(Vec::new());
+            self.statement(depth + 1, in_function);
+            self.scopes.pop();
+        }
+    }
+
+    fn block(&mut self, depth: usize, in_function: bool) {
+        self.scopes.push(Vec::new());
+        self.push("{ ");
+        for _ in 0..self.random.below(3) {
+            self.statement(depth + 1, in_function);
+            self.push("\n");
+        }
+        if in_function && self.random.below(2) == 0 {
+            // Without a value, a return is only allowed at the end of a block.
+            match self.random.below(3) {
+                0 => self.push("return"),
+                1 => self.push("return null"),
+                // In parentheses, as a value starting with null would be taken for no value.
+                _ => {
+                    self.push("return (");
+                    self.expression(depth + 1);
+                    self.push(")");
+                }
+            }
+        }
+        self.push(" }");
+        self.scopes.pop();
+    }
+
+    fn assignment(&mut self, depth: usize) {
+        let target = self.variable(true);
+        self.push(&target);
+        match self.random.below(3) {
+            0 => {
+                self.push("[");
+                self.expression(depth + 1);
+                self.push("]");
+            }
+            1 => self.push(".x"),
+            _ => {}
+        }
+        self.push(" ");
+        self.push_any(ASSIGNMENT);
+        self.push(" ");
+        self.expression(depth + 1);
+    }
+
+    fn expression(&mut self, depth: usize) {
+        let choices = if depth < MAX_DEPTH { 13 } else { 4 };
+
+        match self.random.below(choices) {
+            0 => {
+                self.push_any(NUMBERS);
+            }
+            1 => {
+                let quote = self.push_any(&["\"", "'"]);
+                for _ in 0..self.random.below(4) {
+                    let part = match self.random.pick(STRING_PARTS) {
+                        "'" if quote == "'" => "\\'",
+                        part => part,
+                    };
+                    self.push(part);
+                }
+                self.push(quote);
+            }
+            2 => {
+                let name = self.variable(false);
+                self.push(&name);
+            }
+            3 => {
+                self.push_any(&["true", "false", "null"]);
+            }
+            4 | 5 => {
+                self.expression(depth + 1);
+                self.push(" ");
+                self.push_any(BINARY);
+                self.push(" ");
+                self.expression(depth + 1);
+            }
+            6 => {
+                self.push_any(&["-", "!", "- "]);
+                self.expression(depth + 1);
+            }
+            7 => {
+                self.expression(depth + 1);
+                self.push(" ? ");
+                self.expression(depth + 1);
+                self.push(" : ");
+                self.expression(depth + 1);
+            }
+            8 => {
+                self.push("(");
+                self.expression(depth + 1);
+                self.push(")");
+            }
+            9 => {
+                self.push("[");
+                for index in 0..self.random.below(3) {
+                    if index > 0 {
+                        self.push(", ");
+                    }
+                    self.expression(depth + 1);
+                }
+                self.push("]");
+            }
+            10 => {
+                self.push("(");
+                self.expression(depth + 1);
+                self.push(")");
+                match self.random.below(3) {
+                    0 => {
+                        self.push("[");
+                        self.expression(depth + 1);
+                        self.push("]");
+                    }
+                    1 => self.push(".length"),
+                    _ => {
+                        self.push("(");
+                        self.expression(depth + 1);
+                        self.push(")");
+                    }
+                }
+            }
+            11 => {
+                self.push("f(");
+                self.expression(depth + 1);
+                self.push(")");
+            }
+            _ => {
+                self.push("(");
+                self.assignment(depth);
+                self.push(")");
+            }
+        }
+    }
+}
+
+// The JSON of the program, without the line numbers, which printing doesn't keep.
+fn tree(source: &str) -> Json {
+    let json = parser::parse_to_json(source.as_bytes())
+        .unwrap_or_else(|err| panic!("{}\nin:\n{}", err, source));
+    let mut tree = serde_json::from_str(&json).unwrap();
+    strip_lines(&mut tree);
+    tree
+}
+
+fn strip_lines(json: &mut Json) {
+    match json {
+        Json::Object(object) => {
+            object.remove("line");
+            object.values_mut().for_each(strip_lines);
+        }
+        Json::Array(array) => array.iter_mut().for_each(strip_lines),
+        _ => {}
+    }
+}
+
+fn check_round_trip(source: &str) {
+    let tree_before = tree(source);
+    let printed = to_source(&parser::parse(source.as_bytes()).unwrap());
+    assert!(
+        tree_before == tree(&printed),
+        "printing changed the program\n{}\nprinted as:\n{}",
+        source,
+        printed
+    );
+
+    // Printing is stable once the source is in its printed form.
+    assert_eq!(
+        to_source(&parser::parse(printed.as_bytes()).unwrap()),
+        printed
+    );
+}
+
+#[test]
+fn test_round_trip_random() {
+    for seed in 1..=500 {
+        let program = Generator::new(seed * 0x9e37_79b9).program(8);
+        check_round_trip(&program);
+    }
+}
+
+#[test]
+fn test_round_trip_programs() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let programs: Vec<String> = ["tests/ast", "."]
+        .iter()
+        .flat_map(|dir_name| fs::read_dir(dir.join(dir_name)).unwrap())
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension() == Some("notjs".as_ref()))
+        .map(|path| fs::read_to_string(path).unwrap())
+        .collect();
+    assert!(!programs.is_empty());
+
+    for program in programs {
+        check_round_trip(&program);
+    }
+}
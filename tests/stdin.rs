@@ -0,0 +1,86 @@
+// Programs given to the binary without a file: piped with `-` as the file or with no file at
+// all, or inline with `-e`.
+
+mod common;
+
+use common::notjs;
+
+#[test]
+fn test_run() {
+    let program = "let x = 40\nprint x + len(args())\n";
+
+    assert_eq!(
+        notjs(&["-", "a", "b"], program),
+        (Some(0), "42".to_string(), String::new())
+    );
+    assert_eq!(
+        notjs(&[], program),
+        (Some(0), "40".to_string(), String::new())
+    );
+    assert_eq!(
+        notjs(&["--", "-"], program),
+        (Some(0), "40".to_string(), String::new())
+    );
+}
+
+#[test]
+fn test_check() {
+    assert_eq!(
+        notjs(&["check", "-"], "print 1\nprint y\n"),
+        (
+            Some(1),
+            "<stdin>:2: error[E010]: Undeclared variable 'y'\n".to_string(),
+            String::new()
+        )
+    );
+    assert_eq!(
+        notjs(&["check", "-"], "print 1\n"),
+        (Some(0), String::new(), String::new())
+    );
+}
+
+#[test]
+fn test_fmt() {
+    assert_eq!(
+        notjs(&["fmt", "-"], "let x=1\nif(x){print x}"),
+        (
+            Some(0),
+            "let x = 1\nif (x) {\n    print x\n}\n".to_string(),
+            String::new()
+        )
+    );
+    assert_eq!(
+        notjs(&["fmt", "--check", "-"], "let x=1"),
+        (
+            Some(1),
+            "<stdin> is not formatted\n".to_string(),
+            String::new()
+        )
+    );
+}
+
+#[test]
+fn test_eval() {
+    assert_eq!(
+        notjs(&["-e", "let x = 3", "-e", "print x * x"], ""),
+        (Some(0), "9".to_string(), String::new())
+    );
+    // What ran before the error is still printed.
+    let (status, stdout, stderr) = notjs(&["-e", "print 1", "-e", "print y", "-e", "print 2"], "");
+    assert_eq!((status, stdout.as_str()), (Some(65), "1"));
+    assert!(stderr.contains("Undeclared variable 'y'"), "{}", stderr);
+}
+
+#[test]
+fn test_ast() {
+    // The tree is shown folded, as it runs.
+    let folded = "[\n    print 86400,\n]\n".to_string();
+    assert_eq!(
+        notjs(&["--ast", "-"], "print 60 * 60 * 24"),
+        (Some(0), folded.clone(), String::new())
+    );
+    assert_eq!(
+        notjs(&["--ast", "-e", "print 60 * 60 * 24"], ""),
+        (Some(0), folded, String::new())
+    );
+}
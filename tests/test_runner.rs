@@ -0,0 +1,50 @@
+// `notjs test` runs the test blocks of a file after the file itself, reporting each of them, and
+// exits with 1 when any fails so that it can gate CI.
+
+mod common;
+
+use std::path::Path;
+
+use common::{notjs, TempFile};
+
+#[test]
+fn test_passing_and_failing() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs/unit_tests.notjs");
+    let (status, stdout, stderr) = notjs(&["test", path.to_str().unwrap()], "");
+
+    assert_eq!(status, Some(1));
+    assert_eq!(
+        stdout,
+        "add(2, 3) = 5
+PASS adds numbers
+FAIL adds strings (line 15)
+    RuntimeError: Assertion failed: joined == \"ba\" at line 17
+
+1 passed, 1 failed
+"
+    );
+    assert_eq!(stderr, "");
+}
+
+#[test]
+fn test_all_passing() {
+    let file = TempFile::new("test-pass", "test \"truth\" {\n    assert true\n}\n");
+    let (status, stdout, _) = notjs(&["test", file.arg()], "");
+
+    assert_eq!(status, Some(0));
+    assert_eq!(stdout, "PASS truth\n\n1 passed, 0 failed\n");
+}
+
+#[test]
+fn test_program_failing() {
+    let file = TempFile::new(
+        "test-fail",
+        "test \"never run\" {\n    assert true\n}\nprint [][0]\n",
+    );
+    let (status, stdout, stderr) = notjs(&["test", file.arg()], "");
+
+    // The program failing before its tests run is reported as any run.
+    assert_eq!(status, Some(70));
+    assert_eq!(stdout, "");
+    assert!(stderr.contains("Index 0 out of bounds"), "{}", stderr);
+}
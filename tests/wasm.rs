@@ -0,0 +1,26 @@
+// Runs in Node with `cargo test --target wasm32-unknown-unknown --no-default-features
+// --features wasm`, which needs `wasm-bindgen-test-runner` from wasm-bindgen-cli on the path.
+#![cfg(all(target_arch = "wasm32", feature = "wasm"))]
+
+use js_sys::Reflect;
+use wasm_bindgen::JsValue;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+fn field(result: &JsValue, name: &str) -> JsValue {
+    Reflect::get(result, &name.into()).unwrap()
+}
+
+#[wasm_bindgen_test]
+fn test_run() {
+    let result = notjs::wasm::run("let x = 20 println x + 1");
+    assert_eq!(field(&result, "output").as_string().unwrap(), "21\n");
+    assert!(field(&result, "error").is_null());
+}
+
+#[wasm_bindgen_test]
+fn test_run_error() {
+    let result = notjs::wasm::run("print \"before\"\nlet x = 1 x()");
+    assert_eq!(field(&result, "output").as_string().unwrap(), "before");
+    let error = field(&result, "error").as_string().unwrap();
+    assert!(error.contains("line 2"), "{}", error);
+}